@@ -0,0 +1,88 @@
+//! Uses the world from `config_local.rs`, but only a small region of it unless noted otherwise.
+//! Run with `cargo bench`.
+
+use std::{fs, hint::black_box, path::PathBuf};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use mc_gen::{config::*, pathfind::pathfind, sim::sim, *};
+
+/// Side length of the region used for the cheaper benchmarks
+const REGION_SIZE: i32 = 64;
+
+fn region() -> Rect {
+    Rect::new_centered(ivec2(AREA[0], AREA[1]), IVec2::splat(REGION_SIZE))
+}
+
+fn full_area() -> Rect {
+    Rect::new_centered(ivec2(AREA[0], AREA[1]), ivec2(AREA[2], AREA[3]))
+}
+
+/// Loading without a separate write path skips copying the save
+fn load(area: Rect) -> Level {
+    Level::new(SAVE_READ_PATH, SAVE_READ_PATH, area)
+}
+
+fn chunk_load(c: &mut Criterion) {
+    c.bench_function("chunk load", |b| b.iter(|| black_box(load(region()))));
+}
+
+/// Unpacking happens when loading, packing when saving. Saves into a scratch folder so that
+/// the world itself is left untouched.
+fn palette_pack(c: &mut Criterion) {
+    let scratch = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("bench-save");
+    fs::create_dir_all(scratch.join("region")).unwrap();
+    fs::copy(
+        PathBuf::from(SAVE_READ_PATH).join("level.dat"),
+        scratch.join("level.dat"),
+    )
+    .unwrap();
+
+    let mut level = load(region());
+    level.path = scratch;
+    // Only modified chunks get saved
+    let area = level.area();
+    for column in area {
+        let pos = level.ground(column);
+        level(pos, Full(Cobble));
+    }
+    c.bench_function("palette pack", |b| b.iter(|| level.debug_save()));
+}
+
+fn heightmap(c: &mut Criterion) {
+    let mut level = load(region());
+    c.bench_function("heightmap", |b| b.iter(|| level.recompute_all_heights()));
+}
+
+fn pathfinding(c: &mut Criterion) {
+    let level = load(region());
+    let area = level.area().shrink(4);
+    let start = level.ground(area.min) + IVec3::Z;
+    let end = level.ground(area.max) + IVec3::Z;
+    c.bench_function("pathfinding", |b| {
+        b.iter(|| black_box(pathfind(&level, start, end, 1)))
+    });
+}
+
+/// Complete run with one settlement, including writing the replay
+fn village(c: &mut Criterion) {
+    let mut group = c.benchmark_group("village");
+    group.sample_size(10);
+    group.bench_function("small village", |b| {
+        b.iter_batched(
+            || Level::new(SAVE_READ_PATH, SAVE_WRITE_PATH, full_area()),
+            |level| sim(level, 1, false, None, None),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    chunk_load,
+    palette_pack,
+    heightmap,
+    pathfinding,
+    village
+);
+criterion_main!(benches);
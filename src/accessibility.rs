@@ -0,0 +1,186 @@
+//! Checks that every door of the finished buildings can be walked to from the plazas. Houses
+//! built on steep terrain, or ones the terrain got regenerated around, can end up with their
+//! door above a drop or against a slope.
+//!
+//! Stranded doors get reported with their coordinates, or connected to the nearest reachable
+//! spot with a staircase.
+
+use bevy_ecs::prelude::*;
+
+use crate::{
+    pathfind::reachability_from,
+    sim::{
+        blacksmith::Blacksmith, building_plan::House, lumberjack::Lumberjack, quarry::Quarry,
+        Built, CityCenter, Pos,
+    },
+    *,
+};
+
+/// How far from a door to look for a reachable spot to connect it to
+const MAX_FIX_DISTANCE: i32 = 12;
+/// Headroom to clear above the steps
+const HEADROOM: i32 = 3;
+/// How far below the roof to look for doors
+const MAX_BUILDING_HEIGHT: i32 = 24;
+
+/// Pass for [`sim::passes::Phase::Finish`]. With `fix`, stranded doors get a staircase where
+/// possible; the ones left are reported either way.
+pub fn validate_doors(fix: bool) -> impl FnMut(&mut Level, &mut World) {
+    move |level: &mut Level, world: &mut World| {
+        let buildings = building_areas(world);
+        let centers: Vec<IVec2> = world
+            .query_filtered::<&Pos, With<CityCenter>>()
+            .iter(world)
+            .map(|pos| pos.block().truncate())
+            .collect();
+        let mut reachable: HashMap<IVec2, Vec<i32>> = default();
+        for center in centers {
+            for pos in reachability_from(level, level.ground(center) + IVec3::Z).into_keys() {
+                reachable.entry(pos.truncate()).or_default().push(pos.z);
+            }
+        }
+
+        let mut stranded = Vec::new();
+        let mut fixed = 0;
+        for &area in &buildings {
+            for (door, outward) in doors(level, area) {
+                if reachable
+                    .get(&door.truncate())
+                    .is_some_and(|zs| zs.contains(&door.z))
+                {
+                    continue;
+                }
+                if fix && connect(level, &reachable, &buildings, door + IVec3::from(outward)) {
+                    fixed += 1;
+                } else {
+                    stranded.push(door);
+                }
+            }
+        }
+
+        if fix {
+            println!("Connected {fixed} stranded doors");
+        }
+        for door in &stranded {
+            println!("Door at {} {} {} can't be reached", door.x, door.z, door.y);
+        }
+    }
+}
+
+fn building_areas(world: &mut World) -> Vec<Rect> {
+    let mut query = world.query_filtered::<(
+        Option<&House>,
+        Option<&Lumberjack>,
+        Option<&Quarry>,
+        Option<&Blacksmith>,
+    ), With<Built>>();
+    query
+        .iter(world)
+        .filter_map(|(house, lumberjack, quarry, blacksmith)| {
+            house
+                .map(|house| house.area)
+                .or(lumberjack.map(|lumberjack| lumberjack.area))
+                .or(quarry.map(|quarry| quarry.area))
+                .or(blacksmith.map(|blacksmith| blacksmith.area))
+        })
+        .collect()
+}
+
+/// Lower halves of the doors in and around the area, with the side facing away from it
+fn doors(level: &Level, area: Rect) -> Vec<(IVec3, HDir)> {
+    let mut doors = Vec::new();
+    for column in area.grow(1) {
+        let top = level.height(column);
+        for z in top - MAX_BUILDING_HEIGHT..top {
+            let pos = column.extend(z);
+            let Door(_, dir, meta) = level(pos) else {
+                continue;
+            };
+            if meta.contains(DoorMeta::TOP) {
+                continue;
+            }
+            let outward = if area.contains(column + dir) {
+                dir.rotated(2)
+            } else {
+                dir
+            };
+            doors.push((pos, outward));
+        }
+    }
+    doors
+}
+
+/// Builds a staircase from in front of a door (feet position) to the closest reachable spot.
+/// Returns whether there was one.
+fn connect(
+    level: &mut Level,
+    reachable: &HashMap<IVec2, Vec<i32>>,
+    buildings: &[Rect],
+    start: IVec3,
+) -> bool {
+    let search = Rect::new_centered(start.truncate(), IVec2::splat(MAX_FIX_DISTANCE * 2 + 1))
+        .overlap(level.area());
+    let target = search
+        .into_iter()
+        .flat_map(|column| {
+            reachable
+                .get(&column)
+                .into_iter()
+                .flatten()
+                .map(move |&z| column.extend(z))
+        })
+        .filter(|target| {
+            let len = run_length(start.truncate(), target.truncate());
+            (len >= (target.z - start.z).abs())
+                && run_columns(start.truncate(), target.truncate())
+                    .iter()
+                    .all(|&column| {
+                        !buildings.iter().any(|area| area.contains(column))
+                            && level.water_level(column).is_none()
+                    })
+        })
+        .min_by_key(|target| run_length(start.truncate(), target.truncate()));
+    let Some(target) = target else {
+        return false;
+    };
+
+    // The steps replace the floor, see `stair_run`
+    let (from, to) = (start - IVec3::Z, target - IVec3::Z);
+    let columns = run_columns(from.truncate(), to.truncate());
+    let len = columns.len() as i32 - 1;
+    for (i, column) in columns.into_iter().enumerate() {
+        let i = i as i32;
+        let z = if to.z >= from.z {
+            (from.z + i).min(to.z)
+        } else {
+            (to.z + len - i).min(from.z)
+        };
+        for z in z + 1..=z + HEADROOM {
+            if level(column.extend(z)).solid() {
+                level(column.extend(z), Air);
+            }
+        }
+    }
+    stair_run(level, from, to, Cobble);
+    true
+}
+
+/// Horizontal steps of a [`stair_run`]
+fn run_length(from: IVec2, to: IVec2) -> i32 {
+    (to.x - from.x).abs() + (to.y - from.y).abs()
+}
+
+/// Columns [`stair_run`] goes through: first along x, then along y
+fn run_columns(from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let mut columns = vec![from];
+    let mut column = from;
+    while column.x != to.x {
+        column.x += (to.x - column.x).signum();
+        columns.push(column);
+    }
+    while column.y != to.y {
+        column.y += (to.y - column.y).signum();
+        columns.push(column);
+    }
+    columns
+}
@@ -0,0 +1,277 @@
+use bevy_ecs::prelude::*;
+
+use crate::{
+    hydrology::{Hydrology, WaterKind},
+    sim::*,
+    terraform::flood,
+    *,
+};
+
+/// How far from the settlement center a river may be to get tapped
+const MAX_RIVER_DISTANCE: i32 = 64;
+/// How far from the settlement center fields get supplied
+const FIELD_RANGE: i32 = 32;
+/// How far from the settlement center the cistern may be
+const CISTERN_SEARCH_DISTANCE: i32 = 20;
+/// Inside of the cistern, the walls come on top
+const CISTERN_SIZE: i32 = 5;
+const CISTERN_DEPTH: i32 = 3;
+/// Deepest cut a canal is dug through higher ground
+const MAX_CUT: i32 = 4;
+/// Highest a channel gets carried above the valley floor
+const MAX_AQUEDUCT_HEIGHT: i32 = 20;
+/// Columns between the piers of the aqueduct
+const ARCH_SPAN: i32 = 5;
+/// With less room beneath it, the aqueduct stands on a solid wall instead of arches
+const MIN_ARCH_HEIGHT: i32 = 4;
+
+/// Pass for [`sim::passes::Phase::Finish`] that taps the closest river of each settlement: A
+/// canal runs to its fields and an aqueduct to a cistern near the center. Channels that would
+/// run through buildings or not hold water aren't built.
+pub fn supply_water(level: &mut Level, world: &mut World) {
+    let centers: Vec<IVec2> = world
+        .query_filtered::<&Pos, With<CityCenter>>()
+        .iter(world)
+        .map(|pos| pos.block().truncate())
+        .collect();
+    let hydrology = Hydrology::new(level);
+    for center in centers {
+        let fields: Vec<IVec2> = Rect::new_centered(center, IVec2::splat(FIELD_RANGE * 2 + 1))
+            .into_iter()
+            .filter(|column| level.area().contains(*column))
+            .filter(|column| level(level.ground(*column)) == Farmland)
+            .collect();
+        if let Some(&first) = fields.first() {
+            let bounds = fields.iter().fold(
+                Rect {
+                    min: first,
+                    max: first,
+                },
+                |bounds, &column| Rect {
+                    min: bounds.min.min(column),
+                    max: bounds.max.max(column),
+                },
+            );
+            if canal(level, &hydrology, bounds).is_none() {
+                eprintln!("No canal to the fields at {bounds:?}");
+            }
+        }
+        if let Some(area) = level.free_spot_near(
+            center,
+            IVec2::splat(CISTERN_SIZE + 2),
+            CISTERN_SEARCH_DISTANCE,
+        ) && aqueduct(level, &hydrology, area.center()).is_none()
+        {
+            eprintln!("No aqueduct to {center}");
+        }
+    }
+}
+
+/// Canal from the closest river up to the `fields`.
+/// Returns the columns of the channel, or `None` without building anything if there's no
+/// channel that holds water.
+pub fn canal(level: &mut Level, hydrology: &Hydrology, fields: Rect) -> Option<Vec<IVec2>> {
+    let source = closest_river(level, hydrology, fields.center())?;
+    let end = source.truncate().clamp(fields.min, fields.max);
+    let route: Vec<IVec2> = route(level, source.truncate(), end)
+        .into_iter()
+        .take_while(|column| !fields.contains(*column))
+        .collect();
+    if !buildable(level, &route, source.z) {
+        return None;
+    }
+    let cursor = level.recording_cursor();
+    build_channel(level, &route, source.z);
+    if fill(level, &route, source.z, 0).is_none() {
+        undo(level, cursor);
+        return None;
+    }
+    level.set_blocked(route.iter().copied());
+    Some(route)
+}
+
+/// Cistern centered on `cistern`, fed by a channel from the closest river which gets carried
+/// across valleys on arches.
+/// Returns the columns of the channel, or `None` without building anything if there's no
+/// channel that holds water.
+pub fn aqueduct(level: &mut Level, hydrology: &Hydrology, cistern: IVec2) -> Option<Vec<IVec2>> {
+    let source = closest_river(level, hydrology, cistern)?;
+    let inside = Rect::new_centered(cistern, IVec2::splat(CISTERN_SIZE));
+    let mut channel = route(level, source.truncate(), cistern);
+    // The channel leads through the wall of the cistern
+    let entrance = channel
+        .iter()
+        .position(|column| inside.grow(1).contains(*column))?;
+    channel.truncate(entrance + 1);
+    if !buildable(level, &channel[..entrance], source.z) {
+        return None;
+    }
+    let cursor = level.recording_cursor();
+    build_channel(level, &channel, source.z);
+    build_cistern(level, inside, source.z, channel[entrance]);
+    let volume = (inside.total() * CISTERN_DEPTH) as usize;
+    if fill(level, &channel, source.z, volume).is_none() {
+        undo(level, cursor);
+        return None;
+    }
+    level.set_blocked(inside.grow(1));
+    level.set_blocked(channel.iter().copied());
+    Some(channel)
+}
+
+/// Water surface of the river column closest to `target`
+fn closest_river(level: &Level, hydrology: &Hydrology, target: IVec2) -> Option<IVec3> {
+    hydrology
+        .bodies
+        .iter()
+        .filter(|body| body.kind == WaterKind::River)
+        .flat_map(|body| &body.columns)
+        .filter(|column| column.distance_squared(target) <= MAX_RIVER_DISTANCE.pow(2))
+        .min_by_key(|column| column.distance_squared(target))
+        .map(|&column| column.extend(level.water_level(column).unwrap()))
+}
+
+/// Columns from the river to `end` through which water can flow, leaving out the river itself
+fn route(level: &Level, source: IVec2, end: IVec2) -> Vec<IVec2> {
+    ColumnLineIter::new(source, end, LineStyle::Thick)
+        .chain([end])
+        .skip_while(|column| level.water_level(*column).is_some())
+        .collect()
+}
+
+/// Whether the channel stays clear of buildings and roads and neither has to cut too deep nor
+/// stand too high
+fn buildable(level: &Level, route: &[IVec2], surface: i32) -> bool {
+    !route.is_empty()
+        && route.iter().all(|&column| {
+            level.area().contains(column)
+                && level.unblocked(Rect {
+                    min: column,
+                    max: column,
+                })
+                && level.water_level(column).is_none()
+                && (level.height(column) - surface <= MAX_CUT)
+                && (surface - level.height(column) <= MAX_AQUEDUCT_HEIGHT)
+        })
+}
+
+/// Channel with the water surface at `surface`: Dug into the ground as a canal lined with
+/// cobble where it's high enough, otherwise carried on an aqueduct of stone bricks
+fn build_channel(level: &mut Level, channel: &[IVec2], surface: i32) {
+    let in_channel: HashSet<IVec2> = channel.iter().copied().collect();
+    // Distance along the aqueduct, for spacing the piers
+    let mut span = 0;
+    for (i, &column) in channel.iter().enumerate() {
+        let ground = level.height(column);
+        let raised = ground < surface - 1;
+        let material = if raised { StoneBrick } else { Cobble };
+        for z in surface..=ground.max(surface + 1) {
+            level(column.extend(z), Air);
+        }
+        level(column.extend(surface - 1), Full(material));
+        for off in NEIGHBORS_2D {
+            let side = column + off;
+            if in_channel.contains(&side) {
+                continue;
+            }
+            let block = level(side.extend(surface));
+            if raised || !block.solid() || (block.soil() && block != Farmland) {
+                level(side.extend(surface), Full(material));
+            }
+            if raised {
+                level(side.extend(surface - 1), Full(material));
+            }
+        }
+        if !raised {
+            span = 0;
+            continue;
+        }
+
+        let clearance = surface - 2 - ground;
+        if clearance < MIN_ARCH_HEIGHT {
+            for z in ground + 1..surface - 1 {
+                level(column.extend(z), Full(StoneBrick));
+            }
+            span = 0;
+            continue;
+        }
+        // Arches between piers, each a slab between upside-down stairs
+        let along = channel
+            .get(i + 1)
+            .map_or(column - channel[i.saturating_sub(1)], |next| *next - column);
+        let forward = HDir::ALL
+            .into_iter()
+            .find(|dir| IVec2::from(*dir) == along)
+            .unwrap_or(YPos);
+        let pos = column.extend(surface - 2);
+        match span % ARCH_SPAN {
+            0 => {
+                for z in ground + 1..surface - 1 {
+                    level(column.extend(z), Full(StoneBrick));
+                }
+            }
+            1 => level(
+                pos,
+                Stair(StoneBrick, forward.rotated(2), Top, StairShape::Straight),
+            ),
+            offset if offset == ARCH_SPAN - 1 => {
+                level(pos, Stair(StoneBrick, forward, Top, StairShape::Straight))
+            }
+            _ => level(pos, Slab(StoneBrick, Top)),
+        }
+        span += 1;
+    }
+}
+
+/// Basin dug into the ground or built up on walls, with the water surface at `surface`. The
+/// wall is left open at the `entrance` of the channel.
+fn build_cistern(level: &mut Level, inside: Rect, surface: i32, entrance: IVec2) {
+    let floor = surface - CISTERN_DEPTH + 1;
+    for column in inside {
+        let ground = level.height(column);
+        for z in floor..=ground.max(surface + 1) {
+            level(column.extend(z), Air);
+        }
+        for z in ground + 1..floor {
+            level(column.extend(z), Full(StoneBrick));
+        }
+        level(column.extend(floor - 1), Full(StoneBrick));
+    }
+    for column in inside.grow(1).border() {
+        let ground = level.height(column);
+        let top = if column == entrance {
+            surface - 1
+        } else {
+            surface
+        };
+        for z in (ground + 1).min(floor - 1)..=top {
+            level(column.extend(z), Full(StoneBrick));
+        }
+    }
+}
+
+/// Lets the water flow into the channel from the river end. `None` if it would leak out, the
+/// channel then stays dry.
+fn fill(level: &mut Level, channel: &[IVec2], surface: i32, extra_volume: usize) -> Option<usize> {
+    let source = channel.first()?.extend(surface);
+    level(source, Water);
+    let filled = flood(level, source, channel.len() * 2 + extra_volume);
+    if filled.is_none() {
+        level(source, Air);
+    } else {
+        level.recompute_heights(channel.iter().copied());
+    }
+    filled
+}
+
+/// Puts back the blocks changed since `cursor`, for channels that don't hold water. Neither
+/// the changes nor their undoing stay in the recording.
+fn undo(level: &mut Level, cursor: RecordingCursor) {
+    let changes: Vec<SetBlock> = level.pop_recording(cursor).collect();
+    let cursor = level.recording_cursor();
+    for set in changes.iter().rev() {
+        level(set.pos, set.previous);
+    }
+    level.pop_recording(cursor).for_each(drop);
+    level.recompute_heights(changes.iter().map(|set| set.pos.truncate()));
+}
@@ -0,0 +1,218 @@
+use nbt::{CompoundTag, Tag};
+
+use crate::{replay::Replay, *};
+
+/// Body parts that can be posed, as named in the `Pose` tag
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Limb {
+    Head,
+    Body,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+}
+
+impl Limb {
+    const ALL: [Limb; 6] = [
+        Self::Head,
+        Self::Body,
+        Self::LeftArm,
+        Self::RightArm,
+        Self::LeftLeg,
+        Self::RightLeg,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Head => "Head",
+            Self::Body => "Body",
+            Self::LeftArm => "LeftArm",
+            Self::RightArm => "RightArm",
+            Self::LeftLeg => "LeftLeg",
+            Self::RightLeg => "RightLeg",
+        }
+    }
+}
+
+/// Equipment slots, in the order Minecraft stores them
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Slot {
+    Feet,
+    Legs,
+    Chest,
+    Head,
+    MainHand,
+    OffHand,
+}
+
+/// Armor stand used as a statue, scarecrow, …
+/// Items are given by their id without namespace, e.g. `"iron_helmet"`.
+#[derive(Debug, Clone)]
+pub struct ArmorStand {
+    /// Bottom center
+    pub pos: Vec3,
+    /// Degrees clockwise, starting facing south
+    pub yaw: f32,
+    /// Rotation in degrees around the x, y & z axes (Minecraft coordinates)
+    pose: [Option<Vec3>; 6],
+    equipment: [Option<&'static str>; 6],
+    marker: bool,
+    invisible: bool,
+    small: bool,
+    show_arms: bool,
+    base_plate: bool,
+}
+
+impl ArmorStand {
+    /// Standing in the middle of the block, facing `facing`
+    pub fn new(pos: IVec3, facing: HDir) -> Self {
+        Self {
+            pos: pos.as_vec3() + vec3(0.5, 0.5, 0.),
+            yaw: facing as i32 as f32 * 90.,
+            pose: default(),
+            equipment: default(),
+            marker: false,
+            invisible: false,
+            small: false,
+            show_arms: false,
+            base_plate: true,
+        }
+    }
+
+    pub fn pose(mut self, limb: Limb, angles: Vec3) -> Self {
+        self.pose[limb as usize] = Some(angles);
+        self
+    }
+
+    /// Also shows the arms when holding something
+    pub fn equip(mut self, slot: Slot, item: &'static str) -> Self {
+        self.equipment[slot as usize] = Some(item);
+        if matches!(slot, Slot::MainHand | Slot::OffHand) {
+            self.show_arms = true;
+        }
+        self
+    }
+
+    /// No hitbox
+    pub fn marker(mut self) -> Self {
+        self.marker = true;
+        self
+    }
+
+    /// Only the equipment is visible
+    pub fn invisible(mut self) -> Self {
+        self.invisible = true;
+        self
+    }
+
+    pub fn small(mut self) -> Self {
+        self.small = true;
+        self
+    }
+
+    pub fn show_arms(mut self) -> Self {
+        self.show_arms = true;
+        self
+    }
+
+    pub fn no_base_plate(mut self) -> Self {
+        self.base_plate = false;
+        self
+    }
+
+    /// Entity data without the position
+    pub fn nbt(&self) -> CompoundTag {
+        let mut nbt = CompoundTag::new();
+        nbt.insert_str("id", "minecraft:armor_stand");
+        nbt.insert(
+            "Rotation",
+            Tag::List(vec![Tag::Float(self.yaw), Tag::Float(0.)]),
+        );
+        let items = |slots: &[Option<&str>]| {
+            slots
+                .iter()
+                .map(|item| {
+                    let mut nbt = CompoundTag::new();
+                    if let Some(item) = item {
+                        nbt.insert_str("id", format!("minecraft:{item}"));
+                        nbt.insert_i8("Count", 1);
+                    }
+                    nbt
+                })
+                .collect::<Vec<_>>()
+        };
+        nbt.insert_compound_tag_vec("ArmorItems", items(&self.equipment[..4]));
+        nbt.insert_compound_tag_vec("HandItems", items(&self.equipment[4..]));
+        let mut pose = CompoundTag::new();
+        for (limb, angles) in Limb::ALL.into_iter().zip(self.pose) {
+            if let Some(angles) = angles {
+                pose.insert(
+                    limb.name(),
+                    Tag::List(angles.to_array().map(Tag::Float).to_vec()),
+                );
+            }
+        }
+        nbt.insert_compound_tag("Pose", pose);
+        for (flag, set) in [
+            ("Marker", self.marker),
+            ("Invisible", self.invisible),
+            ("Small", self.small),
+            ("ShowArms", self.show_arms),
+            ("NoBasePlate", !self.base_plate),
+        ] {
+            nbt.insert_i8(flag, set as i8);
+        }
+        nbt.insert_i8("NoGravity", 1);
+        nbt.insert_i8("Invulnerable", 1);
+        nbt
+    }
+
+    /// Gets written to the entities region when saving
+    pub fn place(&self, level: &mut Level) {
+        level.add_entity(self.pos, self.nbt());
+    }
+
+    pub fn summon(&self, replay: &mut Replay) {
+        replay.command(format!(
+            "summon armor_stand {} {} {} {}",
+            self.pos.x,
+            self.pos.z,
+            self.pos.y,
+            replay::snbt(&self.nbt())
+        ));
+    }
+}
+
+/// Knight in armor holding up a sword, on a pedestal
+pub fn statue(level: &mut Level, pos: IVec3, facing: HDir) -> ArmorStand {
+    level(pos, Full(SmoothStone));
+    ArmorStand::new(pos + IVec3::Z, facing)
+        .equip(Slot::Head, "iron_helmet")
+        .equip(Slot::Chest, "iron_chestplate")
+        .equip(Slot::Legs, "iron_leggings")
+        .equip(Slot::Feet, "iron_boots")
+        .equip(Slot::MainHand, "iron_sword")
+        .equip(Slot::OffHand, "shield")
+        .pose(Limb::RightArm, vec3(-120., 0., 10.))
+        .pose(Limb::LeftArm, vec3(-40., 20., 0.))
+        .pose(Limb::Head, vec3(-10., 0., 0.))
+        .no_base_plate()
+}
+
+/// Pumpkin head and outstretched arms on a fence post
+pub fn scarecrow(level: &mut Level, pos: IVec3) -> ArmorStand {
+    level(pos, Fence(Wood(Oak)));
+    ArmorStand::new(pos, *HDir::ALL.choose())
+        .equip(Slot::Head, "carved_pumpkin")
+        .equip(Slot::Chest, "leather_chestplate")
+        .equip(Slot::MainHand, "wheat")
+        .pose(Limb::RightArm, vec3(0., 0., 90.))
+        .pose(Limb::LeftArm, vec3(0., 0., -90.))
+        .pose(
+            Limb::Head,
+            vec3(0., 0., rand_range::<i32, _>(-15..=15) as f32),
+        )
+        .invisible()
+        .no_base_plate()
+}
@@ -0,0 +1,61 @@
+//! Backups of a save before it gets written to, so a generation run can be undone
+
+use std::{
+    fs::{copy, create_dir_all, remove_dir_all, remove_file},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+
+const BACKUP_DIR: &str = "mcgen-backups";
+/// Everything the generator writes to. Datapacks and the metadata are included so that a
+/// restored save doesn't try to run a replay whose data is gone.
+const BACKED_UP: &[&str] = &["region", "data", "datapacks", "level.dat", "mcgen-meta"];
+
+/// Copies the parts of the save the generator modifies into a timestamped folder inside the
+/// save. Returns the path of the backup.
+pub fn backup(save: &Path) -> Result<PathBuf> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let backup = save.join(BACKUP_DIR).join(timestamp.to_string());
+    create_dir_all(&backup)?;
+    for name in BACKED_UP {
+        let path = save.join(name);
+        if path.is_dir() {
+            copy_dir::copy_dir(&path, backup.join(name))?;
+        } else if path.exists() {
+            copy(&path, backup.join(name))?;
+        }
+    }
+    Ok(backup)
+}
+
+/// Restores the most recent backup, returns its path. The backup is kept.
+pub fn restore(save: &Path) -> Result<PathBuf> {
+    let backup = save
+        .join(BACKUP_DIR)
+        .read_dir()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .max_by_key(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str()?.parse::<u64>().ok())
+        })
+        .ok_or_else(|| anyhow!("No backups in {}", save.display()))?;
+    for name in BACKED_UP {
+        let path = save.join(name);
+        if path.is_dir() {
+            remove_dir_all(&path)?;
+        } else if path.exists() {
+            remove_file(&path)?;
+        }
+        let saved = backup.join(name);
+        if saved.is_dir() {
+            copy_dir::copy_dir(&saved, &path)?;
+        } else if saved.exists() {
+            copy(&saved, &path)?;
+        }
+    }
+    Ok(backup)
+}
@@ -0,0 +1,285 @@
+//! Export of the finished buildings for Bedrock Edition, as one `.mcstructure` file per
+//! building. These can be loaded with a structure block or `/structure load`.
+//!
+//! Block names get converted for the handful that differ between the editions. Of the block
+//! states, those that matter for the looks of a building get converted: the facing and half of
+//! stairs, doors and trapdoors, slab halves, pillar axes, hanging lanterns and persistent
+//! leaves. Other states are left at Bedrock's default. Block entities and entities aren't
+//! exported.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use itertools::Itertools;
+
+use crate::{
+    sim::{
+        blacksmith::Blacksmith, building_plan::House, lumberjack::Lumberjack, quarry::Quarry, Built,
+    },
+    *,
+};
+
+/// Directory in the save the structures get written to
+pub const EXPORT_DIR: &str = "mcgen-bedrock";
+/// Blocks above the roof that still belong to the building, for chimneys, lanterns etc.
+const MAX_OVERHANG: i32 = 8;
+/// Version of the block palette entries, 1.20.80
+const BLOCK_VERSION: i32 = 18108416;
+
+/// Java names of blocks that are called differently in Bedrock Edition
+const RENAMED: &[(&str, &str)] = &[
+    ("grass_block", "grass"),
+    ("dirt_path", "grass_path"),
+    ("stone_bricks", "stonebrick"),
+    ("mossy_stone_bricks", "stonebrick"),
+    ("cracked_stone_bricks", "stonebrick"),
+    ("bricks", "brick_block"),
+    ("terracotta", "hardened_clay"),
+    ("snow", "snow_layer"),
+    ("snow_block", "snow"),
+    ("cobweb", "web"),
+    ("lily_pad", "waterlily"),
+    ("sugar_cane", "reeds"),
+    ("oak_sign", "standing_sign"),
+    ("oak_wall_sign", "wall_sign"),
+    ("wall_torch", "torch"),
+    ("cave_air", "air"),
+    ("void_air", "air"),
+];
+
+/// Pass for [`sim::passes::Phase::Finish`] that exports every finished building
+pub fn export_buildings(level: &mut Level, world: &mut World) {
+    let mut buildings = Vec::new();
+    let mut collect = |kind, areas: Vec<Rect>| {
+        for (i, area) in areas.into_iter().enumerate() {
+            buildings.push((format!("{kind}-{i}"), area));
+        }
+    };
+    collect("house", areas::<House>(world, |house| house.area));
+    collect("lumberjack", areas::<Lumberjack>(world, |lj| lj.area));
+    collect("quarry", areas::<Quarry>(world, |quarry| quarry.area));
+    collect("blacksmith", areas::<Blacksmith>(world, |smith| smith.area));
+
+    let dir = level.path.join(EXPORT_DIR);
+    let result = fs::create_dir_all(&dir).and_then(|()| {
+        for (name, area) in &buildings {
+            let structure = structure(level, bounds(level, *area));
+            write(&dir.join(format!("{name}.mcstructure")), &structure)?;
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => println!(
+            "Exported {} buildings to {}",
+            buildings.len(),
+            dir.display()
+        ),
+        Err(err) => eprintln!("Failed to export for Bedrock: {err}"),
+    }
+}
+
+fn areas<T: Component>(world: &mut World, area: impl Fn(&T) -> Rect) -> Vec<Rect> {
+    world
+        .query_filtered::<&T, With<Built>>()
+        .iter(world)
+        .map(area)
+        .collect()
+}
+
+/// From below the lowest ground level to the last layer with blocks above the roof
+fn bounds(level: &Level, area: Rect) -> Cuboid {
+    let heights = area.into_iter().map(|column| level.height(column));
+    let (bottom, roof) = heights.minmax().into_option().unwrap();
+    let occupied = |z| {
+        area.into_iter()
+            .any(|column| level(column.extend(z)) != Air)
+    };
+    let mut top = roof;
+    while top < roof + MAX_OVERHANG && occupied(top + 1) {
+        top += 1;
+    }
+    Cuboid::new(area.min.extend(bottom - 1), area.max.extend(top))
+}
+
+fn structure(level: &Level, bounds: Cuboid) -> Tag {
+    let size = bounds.size();
+    let unknown = UNKNOWN_BLOCKS.read().unwrap();
+    // Keyed by the Java blockstate
+    let mut palette: Vec<(String, Tag)> = Vec::new();
+    let mut indices = Vec::new();
+    // Bedrock orders the blocks by x, then y, then z in its own axes, which are x, z, y here
+    for x in bounds.min.x..=bounds.max.x {
+        for z in bounds.min.z..=bounds.max.z {
+            for y in bounds.min.y..=bounds.max.y {
+                let state = level(ivec3(x, y, z)).blockstate(&unknown);
+                let key = state.to_string();
+                let index = match palette.iter().position(|(entry, _)| *entry == key) {
+                    Some(index) => index,
+                    None => {
+                        palette.push((key, palette_entry(&state)));
+                        palette.len() - 1
+                    }
+                };
+                indices.push(Tag::Int(index as i32));
+            }
+        }
+    }
+    // The second layer is for waterlogging, which doesn't get exported
+    let empty_layer = vec![Tag::Int(-1); indices.len()];
+    let palette = palette.into_iter().map(|(_, entry)| entry).collect();
+    let xyz = |v: IVec3| Tag::List(vec![Tag::Int(v.x), Tag::Int(v.z), Tag::Int(v.y)]);
+    Tag::Compound(vec![
+        ("format_version", Tag::Int(1)),
+        ("size", xyz(size)),
+        ("structure_world_origin", xyz(bounds.min)),
+        (
+            "structure",
+            Tag::Compound(vec![
+                (
+                    "block_indices",
+                    Tag::List(vec![Tag::List(indices), Tag::List(empty_layer)]),
+                ),
+                ("entities", Tag::List(Vec::new())),
+                (
+                    "palette",
+                    Tag::Compound(vec![(
+                        "default",
+                        Tag::Compound(vec![
+                            ("block_palette", Tag::List(palette)),
+                            ("block_position_data", Tag::Compound(Vec::new())),
+                        ]),
+                    )]),
+                ),
+            ]),
+        ),
+    ])
+}
+
+fn palette_entry(java: &Blockstate) -> Tag {
+    Tag::Compound(vec![
+        ("name", Tag::String(bedrock_name(&java.0))),
+        ("states", Tag::Compound(bedrock_states(java))),
+        ("version", Tag::Int(BLOCK_VERSION)),
+    ])
+}
+
+fn bedrock_states(java: &Blockstate) -> Vec<(&'static str, Tag)> {
+    let name = &*java.0;
+    let property = |key: &str| {
+        java.1
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| &**value)
+    };
+    let bit = |set: bool| Tag::Byte(set as i8);
+    let mut states = Vec::new();
+    // Stairs and trapdoors count east, west, south, north; doors go around clockwise from east
+    let facing = |order: [&str; 4]| {
+        let index = order
+            .iter()
+            .position(|dir| Some(*dir) == property("facing"));
+        Tag::Int(index.unwrap_or_default() as i32)
+    };
+    if name.ends_with("_stairs") {
+        states.push((
+            "weirdo_direction",
+            facing(["east", "west", "south", "north"]),
+        ));
+        states.push(("upside_down_bit", bit(property("half") == Some("top"))));
+    } else if name.ends_with("_trapdoor") {
+        states.push(("direction", facing(["east", "west", "south", "north"])));
+        states.push(("upside_down_bit", bit(property("half") == Some("top"))));
+        states.push(("open_bit", bit(property("open") == Some("true"))));
+    } else if name.ends_with("_door") {
+        states.push(("direction", facing(["east", "south", "west", "north"])));
+        states.push(("upper_block_bit", bit(property("half") == Some("upper"))));
+        states.push(("open_bit", bit(property("open") == Some("true"))));
+        states.push(("door_hinge_bit", bit(property("hinge") == Some("right"))));
+    } else if name.ends_with("_slab") {
+        let half = if property("type") == Some("top") {
+            "top"
+        } else {
+            "bottom"
+        };
+        states.push(("minecraft:vertical_half", Tag::String(half.into())));
+    } else if matches!(name, "lantern" | "soul_lantern") {
+        states.push(("hanging", bit(property("hanging") == Some("true"))));
+    }
+    if let Some(axis) = property("axis") {
+        states.push(("pillar_axis", Tag::String(axis.into())));
+    }
+    if let Some(persistent) = property("persistent") {
+        states.push(("persistent_bit", bit(persistent == "true")));
+    }
+    states
+}
+
+fn bedrock_name(java: &str) -> String {
+    let name = RENAMED
+        .iter()
+        .find(|(from, _)| *from == java)
+        .map_or(java, |(_, to)| to);
+    format!("minecraft:{name}")
+}
+
+fn write(path: &Path, structure: &Tag) -> std::io::Result<()> {
+    let mut data = Vec::new();
+    // The root is an unnamed compound
+    data.push(structure.id());
+    write_string(&mut data, "");
+    structure.write_payload(&mut data);
+    fs::File::create(path)?.write_all(&data)
+}
+
+/// The subset of NBT needed here. Bedrock uses little endian, unlike the `nbt` crate.
+#[derive(Clone)]
+enum Tag {
+    Byte(i8),
+    Int(i32),
+    String(String),
+    List(Vec<Tag>),
+    Compound(Vec<(&'static str, Tag)>),
+}
+
+impl Tag {
+    fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => 1,
+            Tag::Int(_) => 3,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+        }
+    }
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        match self {
+            Tag::Byte(value) => out.extend(value.to_le_bytes()),
+            Tag::Int(value) => out.extend(value.to_le_bytes()),
+            Tag::String(value) => write_string(out, value),
+            Tag::List(items) => {
+                // Empty lists are written as lists of end tags
+                out.push(items.first().map_or(0, Tag::id));
+                out.extend((items.len() as i32).to_le_bytes());
+                for item in items {
+                    item.write_payload(out);
+                }
+            }
+            Tag::Compound(entries) => {
+                for (name, tag) in entries {
+                    out.push(tag.id());
+                    write_string(out, name);
+                    tag.write_payload(out);
+                }
+                out.push(0);
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    out.extend((value.len() as u16).to_le_bytes());
+    out.extend(value.as_bytes());
+}
@@ -1,26 +1,200 @@
 #![allow(dead_code)]
-use std::fs::File;
+use std::{fs::File, path::Path};
 
 use config::*;
-use mc_gen::sim::sim;
+use mc_gen::sim::{
+    checkpoint::Checkpoint,
+    passes::Phase,
+    schedule::StopCondition,
+    settlement::{Blueprint, BLUEPRINT},
+    Generator, Rerun, DEFAULT_TICKS,
+};
 use mc_gen::*;
 use nanorand::*;
 use nbt::decode::read_gzip_compound_tag;
 
 fn main() {
-    let seed = match std::env::args().nth(1) {
-        Some(seed) if seed == "random" => tls_rng().generate::<u16>() as u64,
-        Some(seed) => seed.parse().expect("Invalid seed"),
-        None => get_seed(SAVE_READ_PATH),
+    let (flags, args): (Vec<String>, Vec<String>) =
+        std::env::args().partition(|arg| arg.starts_with("--"));
+    if args.get(1).is_some_and(|arg| arg == "restore") {
+        match backup::restore(Path::new(SAVE_WRITE_PATH)) {
+            Ok(backup) => println!("Restored {}", backup.display()),
+            Err(err) => eprintln!("Failed to restore backup: {err}"),
+        }
+        return;
+    }
+    let resume = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--resume="))
+        .map(|path| Checkpoint::load(Path::new(path)).expect("Failed to load checkpoint"));
+    let seed = match (&resume, args.get(1)) {
+        (Some(checkpoint), _) => checkpoint.seed,
+        (_, Some(seed)) if seed == "random" => tls_rng().generate::<u16>() as u64,
+        (_, Some(seed)) => seed.parse().expect("Invalid seed"),
+        (_, None) => get_seed(SAVE_READ_PATH),
     };
     println!("Seed: {seed}");
     RNG.set(WyRand::new_seed(seed));
 
     let area = Rect::new_centered(ivec2(AREA[0], AREA[1]), ivec2(AREA[2], AREA[3]));
 
-    let level = Level::new(SAVE_READ_PATH, SAVE_WRITE_PATH, area);
+    let settlements = match args.get(2) {
+        Some(count) => count.parse().expect("Invalid settlement count"),
+        None => 1,
+    };
+    let use_vanilla_villages = flags.iter().any(|flag| flag == "--vanilla-villages");
+    let activation_range = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--activation-range="))
+        .map(|range| range.parse().expect("Invalid activation range"));
+    let stats_json = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--stats="))
+        .map(Path::new);
+    let stream_changes = flags.iter().any(|flag| flag == "--stream-changes");
+    let dry_run = flags.iter().any(|flag| flag == "--dry-run");
+    let headless = flags.iter().any(|flag| flag == "--headless");
+    let count_flag = |name: &str| {
+        flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix(name))
+            .map(|count| count.parse().expect("Invalid count"))
+    };
+    let stop = if let Some(buildings) = count_flag("--until-buildings=") {
+        StopCondition::Buildings(buildings)
+    } else if let Some(population) = count_flag("--until-population=") {
+        StopCondition::Population(population)
+    } else {
+        StopCondition::Ticks(count_flag("--ticks=").unwrap_or(DEFAULT_TICKS))
+    };
+    let quests = flags.iter().any(|flag| flag == "--quests");
+    let checkpoint = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--checkpoint="))
+        .map(Path::new);
+    let extend = flags.iter().any(|flag| flag == "--extend").then(|| {
+        Blueprint::load(&Path::new(SAVE_READ_PATH).join(BLUEPRINT))
+            .expect("Failed to load settlement blueprint")
+    });
+
+    // Otherwise the save gets copied, so there's nothing to lose. When resuming, the backup
+    // from before the crashed run is the one to keep.
+    if !dry_run && resume.is_none() && SAVE_READ_PATH == SAVE_WRITE_PATH {
+        let backup = backup::backup(Path::new(SAVE_WRITE_PATH)).expect("Failed to back up save");
+        println!("Backed up save to {}", backup.display());
+    }
+    // Dry runs don't write to the save. They still get a copy of it to work in, so that
+    // nothing can end up in the original by accident.
+    let dry_run_path = std::env::temp_dir().join("mcgen-dry-run");
+    let write_path = if dry_run {
+        dry_run_path.to_str().expect("Temp dir isn't valid UTF-8")
+    } else {
+        SAVE_WRITE_PATH
+    };
+    let level = Level::new(SAVE_READ_PATH, write_path, area);
 
-    sim(level);
+    let mut generator = Generator::new(settlements)
+        .use_vanilla_villages(use_vanilla_villages)
+        .activation_range(activation_range)
+        .stats_json(stats_json)
+        .stream_changes(stream_changes)
+        .dry_run(dry_run)
+        .headless(headless)
+        .stop_when(stop)
+        .quests(quests)
+        .checkpoint(checkpoint, seed)
+        .resume(resume)
+        .world_border(
+            flags
+                .iter()
+                .find_map(|flag| flag.strip_prefix("--world-border="))
+                .map(|margin| margin.parse().expect("Invalid world border margin")),
+        )
+        .game_mode(
+            flags
+                .iter()
+                .find_map(|flag| flag.strip_prefix("--gamemode="))
+                .map(|mode| mode.parse().expect("Invalid game mode")),
+        )
+        .spawn_at_plaza(flags.iter().any(|flag| flag == "--spawn-at-plaza"))
+        .extend(extend)
+        .town_biome(
+            flags
+                .iter()
+                .find_map(|flag| flag.strip_prefix("--town-biome="))
+                .map(|biome| {
+                    if biome.contains(':') {
+                        biome.to_owned()
+                    } else {
+                        format!("minecraft:{biome}")
+                    }
+                }),
+        )
+        .rerun(if flags.iter().any(|flag| flag == "--overwrite") {
+            Rerun::Overwrite
+        } else {
+            Rerun::Extend
+        });
+    if flags.iter().any(|flag| flag == "--aqueducts") {
+        generator = generator.add_pass(Phase::Finish, aqueduct::supply_water);
+    }
+    for rule in flags
+        .iter()
+        .filter_map(|flag| flag.strip_prefix("--gamerule="))
+    {
+        let (rule, value) = rule.split_once('=').expect("Invalid game rule");
+        generator = generator.game_rule(rule, value);
+    }
+    // Writes to the nether directly
+    if !dry_run {
+        generator = generator.add_pass(Phase::Finish, portal::link_nether);
+        generator = generator.add_pass(
+            Phase::Finish,
+            players::relocate_players(flags.iter().any(|flag| flag == "--town-book")),
+        );
+    }
+    let fix_doors = flags.iter().any(|flag| flag == "--fix-doors");
+    if fix_doors || flags.iter().any(|flag| flag == "--check-doors") {
+        generator = generator.add_pass(Phase::Finish, accessibility::validate_doors(fix_doors));
+    }
+    if let Some(mode) = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--mob-proofing="))
+    {
+        let mode = mode.parse().expect("Invalid mob proofing mode");
+        generator = generator.add_pass(Phase::Finish, mob_proofing::audit(mode));
+    }
+    #[cfg(feature = "preview")]
+    {
+        let port = flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix("--preview="))
+            .map(|port| port.parse().expect("Invalid preview port"));
+        generator = generator.preview(port);
+    }
+    // Added last, so the changes of the other passes get sent too
+    if let Some(address) = flags.iter().find_map(|flag| flag.strip_prefix("--rcon=")) {
+        let password = std::env::var("MCGEN_RCON_PASSWORD").expect("MCGEN_RCON_PASSWORD not set");
+        let commands_per_second = flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix("--rcon-rate="))
+            .map_or(200., |rate| rate.parse().expect("Invalid RCON rate"));
+        generator = generator.add_pass(
+            Phase::Finish,
+            rcon::apply(rcon::RconConfig {
+                address: address.into(),
+                password,
+                commands_per_second,
+                read_path: SAVE_READ_PATH.into(),
+            }),
+        );
+    }
+    // Writes into the save
+    #[cfg(feature = "bedrock")]
+    if !dry_run && flags.iter().any(|flag| flag == "--bedrock") {
+        generator = generator.add_pass(Phase::Finish, bedrock::export_buildings);
+    }
+    generator.run(level);
 }
 
 fn get_seed(path: &str) -> u64 {
@@ -0,0 +1,149 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use nbt::CompoundTag;
+
+use crate::{sim::PlaceList, *};
+
+/// Wraps a [`WorldView`] and records the changes made through it, including block entity
+/// data, so that they can be replayed block by block (e.g. for animated construction).
+pub struct BuildRecorder<'a, W: WorldView + ?Sized> {
+    level: &'a mut W,
+    recording: BuildRecording,
+}
+
+#[derive(Default, Clone)]
+pub struct BuildRecording {
+    pub blocks: PlaceList,
+    /// Block entity data to apply when the block at that position is placed
+    pub tile_entities: HashMap<IVec3, CompoundTag>,
+}
+
+impl<'a, W: WorldView + ?Sized> BuildRecorder<'a, W> {
+    pub fn new(level: &'a mut W) -> Self {
+        Self {
+            level,
+            recording: default(),
+        }
+    }
+
+    pub fn finish(self) -> BuildRecording {
+        self.recording
+    }
+}
+
+impl<W: WorldView + ?Sized> WorldView for BuildRecorder<'_, W> {
+    fn get(&self, pos: IVec3) -> Block {
+        self.level.get(pos)
+    }
+
+    fn set(&mut self, pos: IVec3, block: Block) {
+        let previous = self.level.get(pos);
+        self.level.set(pos, block);
+        if previous != block {
+            self.recording.tile_entities.remove(&pos);
+            self.recording.blocks.push_back(SetBlock {
+                pos,
+                block,
+                previous,
+            });
+        }
+    }
+
+    fn tile_entity(&self, pos: IVec3) -> Option<CompoundTag> {
+        self.level.tile_entity(pos)
+    }
+
+    fn set_tile_entity(&mut self, pos: IVec3, nbt: CompoundTag) {
+        self.recording.tile_entities.insert(pos, nbt.clone());
+        self.level.set_tile_entity(pos, nbt)
+    }
+
+    fn height(&self, column: IVec2) -> i32 {
+        self.level.height(column)
+    }
+
+    fn set_height(&mut self, column: IVec2, height: i32) {
+        self.level.set_height(column, height)
+    }
+
+    fn water_level(&self, column: IVec2) -> Option<i32> {
+        self.level.water_level(column)
+    }
+
+    fn set_water_level(&mut self, column: IVec2, level: Option<i32>) {
+        self.level.set_water_level(column, level)
+    }
+
+    fn biome(&self, column: IVec2) -> Biome {
+        self.level.biome(column)
+    }
+
+    fn area(&self) -> Rect {
+        self.level.area()
+    }
+
+    fn recording_cursor(&self) -> RecordingCursor {
+        self.level.recording_cursor()
+    }
+
+    fn pop_recording(&mut self, cursor: RecordingCursor) -> Vec<SetBlock> {
+        self.level.pop_recording(cursor)
+    }
+}
+
+/// Reorders placements so that blocks attached to another one (torches, doors, ladders, rails,
+/// …) are placed after and removed before the block supporting them, instead of briefly
+/// floating. Otherwise keeps the recorded order.
+pub fn order_by_support(blocks: PlaceList) -> PlaceList {
+    let mut changes_at: HashMap<IVec3, Vec<usize>> = default();
+    for (i, set) in blocks.iter().enumerate() {
+        changes_at.entry(set.pos).or_default().push(i);
+    }
+    let mut unlocks: Vec<Vec<usize>> = vec![Vec::new(); blocks.len()];
+    let mut waiting_on = vec![0; blocks.len()];
+    let mut require = |before: usize, after: usize| {
+        unlocks[before].push(after);
+        waiting_on[after] += 1;
+    };
+    for (i, set) in blocks.iter().enumerate() {
+        if let Some(offset) = set.block.support()
+            && let Some(&support) = changes_at.get(&(set.pos + offset)).and_then(|changes| {
+                changes
+                    .iter()
+                    .rev()
+                    .find(|&&j| (j > i) & (blocks[j].block != Air))
+            })
+        {
+            require(support, i);
+        }
+        if let Some(offset) = set.previous.support() {
+            for &j in changes_at.get(&(set.pos + offset)).into_iter().flatten() {
+                if j < i {
+                    require(i, j);
+                }
+            }
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..blocks.len())
+        .filter(|&i| waiting_on[i] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(blocks.len());
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(i);
+        for &j in &unlocks[i] {
+            waiting_on[j] -= 1;
+            if waiting_on[j] == 0 {
+                ready.push(Reverse(j));
+            }
+        }
+    }
+    // Circular dependencies can't be satisfied, those stay in recorded order
+    let mut ordered = vec![false; blocks.len()];
+    for &i in &order {
+        ordered[i] = true;
+    }
+    order.extend((0..blocks.len()).filter(|&i| !ordered[i]));
+    order.into_iter().map(|i| blocks[i]).collect()
+}
@@ -0,0 +1,304 @@
+use crate::{
+    compound_layout::{layout, Requirement},
+    heraldry::TownCrest,
+    house::shack,
+    moat::moat,
+    remove_foliage::remove_trees,
+    sim::PlaceList,
+    *,
+};
+
+const MATERIAL: BlockMaterial = StoneBrick;
+const WALL_HEIGHT: i32 = 7;
+const TOWER_RADIUS: i32 = 3;
+const KEEP_SIZE: i32 = 11;
+const KEEP_HEIGHT: i32 = 15;
+const MOAT_WIDTH: i32 = 3;
+const MOAT_DEPTH: i32 = 3;
+/// Smallest side length of the curtain wall that leaves room for the keep and outbuildings
+pub const MIN_SIZE: i32 = 25;
+const OUTBUILDINGS: [Requirement; 4] = [
+    Requirement {
+        size: ivec2(5, 7),
+        rotatable: true,
+    },
+    Requirement {
+        size: ivec2(5, 7),
+        rotatable: true,
+    },
+    Requirement {
+        size: ivec2(5, 5),
+        rotatable: false,
+    },
+    Requirement {
+        size: ivec2(5, 5),
+        rotatable: false,
+    },
+];
+
+/// Castle with curtain walls, round corner towers, a gatehouse, a keep and outbuildings in the
+/// courtyard, surrounded by a moat. `area` is the outline of the curtain wall. The walls, gate
+/// and keep show the `crest`. Returns `None` if `area` is smaller than [`MIN_SIZE`].
+pub fn castle(level: &mut impl WorldView, area: Rect, crest: &TownCrest) -> Option<PlaceList> {
+    if area.size().min_element() < MIN_SIZE {
+        return None;
+    }
+    let floor = level.average_height(area.border()).round() as i32;
+    // Direction the gate faces
+    let gate_dir = *HDir::ALL.choose();
+
+    let cursor = level.recording_cursor();
+    remove_trees(level, area.grow(TOWER_RADIUS + 1));
+    courtyard(level, area, floor);
+    curtain_wall(level, area, floor, gate_dir, crest);
+    for corner in area.corners() {
+        tower(level, corner, floor, area.center());
+    }
+    let gate = gatehouse(level, area, floor, gate_dir, crest);
+    let keep_area = keep(level, area, floor, gate_dir, crest);
+    let mut rec: PlaceList = level.pop_recording(cursor).into();
+
+    for building in outbuildings(area, gate_dir, keep_area) {
+        rec.extend(shack(level, building));
+    }
+
+    let cursor = level.recording_cursor();
+    // The towers stick out of the wall
+    let outline = Polygon(area.grow(TOWER_RADIUS).corners().collect());
+    moat(
+        level,
+        &outline,
+        gate + IVec2::from(gate_dir) * TOWER_RADIUS,
+        MOAT_WIDTH,
+        MOAT_DEPTH,
+    );
+    rec.extend(level.pop_recording(cursor));
+
+    Some(rec)
+}
+
+/// Everything a castle with the curtain wall at `area` touches, including towers and moat
+pub fn footprint(area: Rect) -> Rect {
+    // The moat keeps a gap of two blocks to the thick outline of the towers
+    area.grow(TOWER_RADIUS + 1 + 2 + MOAT_WIDTH)
+}
+
+fn courtyard(level: &mut impl WorldView, area: Rect, floor: i32) {
+    for column in area {
+        let ground = level.height(column);
+        for z in (floor + 1..=ground).rev() {
+            level.set(column.extend(z), Air);
+        }
+        let soil = level.biome(column).default_topsoil();
+        for z in ground + 1..=floor {
+            level.set(column.extend(z), soil);
+        }
+        if 0.3 > rand() {
+            level.set(column.extend(floor), Path);
+        }
+        level.set_height(column, floor);
+    }
+}
+
+/// Makes sure a structure standing on the column doesn't float
+fn foundation(level: &mut impl WorldView, column: IVec2, floor: i32) {
+    let mut z = floor;
+    while !level.get(column.extend(z)).solid() | level.get(column.extend(z)).soil() {
+        level.set(column.extend(z), Full(Cobble));
+        z -= 1;
+    }
+}
+
+fn crenellation(level: &mut impl WorldView, column: IVec2, z: i32) {
+    if (column.x + column.y) % 2 == 0 {
+        level.set(column.extend(z), Full(MATERIAL));
+    }
+}
+
+fn curtain_wall(level: &mut impl WorldView, area: Rect, floor: i32, gate: HDir, crest: &TownCrest) {
+    let top = floor + WALL_HEIGHT;
+    for column in area.border() {
+        foundation(level, column, floor);
+        for z in floor + 1..=top {
+            level.set(column.extend(z), Full(MATERIAL));
+        }
+        crenellation(level, column, top + 1);
+        level.set_height(column, top);
+    }
+    // Wall walk
+    for column in area.shrink(1).border() {
+        level.set(column.extend(top), Slab(MATERIAL, Top));
+    }
+    // Banners in the middle of the outside of the walls, the gatehouse has its own
+    for dir in HDir::ALL.into_iter().filter(|dir| *dir != gate) {
+        let outside = area.side_center(dir) + IVec2::from(dir);
+        crest.place_on_wall(level, outside.extend(top - 1), dir);
+    }
+}
+
+fn tower(level: &mut impl WorldView, center: IVec2, floor: i32, courtyard: IVec2) {
+    let top = floor + WALL_HEIGHT + 4;
+    let radius = TOWER_RADIUS as f32 + 0.5;
+    for column in disc(center.as_vec2(), radius) {
+        let distance = column.as_vec2().distance(center.as_vec2());
+        foundation(level, column, floor);
+        if distance > radius - 1. {
+            for z in floor + 1..=top {
+                level.set(column.extend(z), Full(MATERIAL));
+            }
+            crenellation(level, column, top + 1);
+        } else {
+            for z in floor + 1..top {
+                level.set(column.extend(z), Air);
+            }
+            // Floor at the height of the wall walk
+            level.set(column.extend(floor + WALL_HEIGHT), Full(Wood(Spruce)));
+            level.set(column.extend(top), Full(Wood(Spruce)));
+        }
+        level.set_height(column, top);
+    }
+    // Entrance from the courtyard & the wall walks
+    let towards_courtyard = (courtyard - center).signum();
+    for step in [ivec2(towards_courtyard.x, 0), ivec2(0, towards_courtyard.y)] {
+        let column = center + step * TOWER_RADIUS;
+        for z in [
+            floor + 1,
+            floor + 2,
+            floor + WALL_HEIGHT + 1,
+            floor + WALL_HEIGHT + 2,
+        ] {
+            level.set(column.extend(z), Air);
+        }
+    }
+    // Ladders up to the top
+    let ladder = center - towards_courtyard.x * IVec2::X * (TOWER_RADIUS - 1);
+    let facing = if towards_courtyard.x > 0 { XPos } else { XNeg };
+    for z in floor + 1..top {
+        level.set(ladder.extend(z), Ladder(facing));
+    }
+}
+
+/// Returns the gate column
+fn gatehouse(
+    level: &mut impl WorldView,
+    area: Rect,
+    floor: i32,
+    facing: HDir,
+    crest: &TownCrest,
+) -> IVec2 {
+    let gate = area.side_center(facing);
+    let outwards = IVec2::from(facing);
+    let side = IVec2::from(facing.rotated(1));
+    let top = floor + WALL_HEIGHT + 3;
+
+    // Flanking towers
+    for offset in [-3, 3] {
+        let tower = Rect::new_centered(gate + side * offset + outwards, IVec2::splat(3));
+        for column in tower {
+            foundation(level, column, floor);
+            for z in floor + 1..=top {
+                level.set(column.extend(z), Full(MATERIAL));
+            }
+            level.set_height(column, top);
+        }
+        for column in tower.border() {
+            crenellation(level, column, top + 1);
+        }
+        let front = gate + side * offset + outwards * 3;
+        crest.place_on_wall(level, front.extend(top - 1), facing);
+    }
+
+    // Passage with a raised portcullis
+    for offset in -1..=1 {
+        for depth in [-1, 0, 1] {
+            let column = gate + side * offset + outwards * depth;
+            level.set(column.extend(floor), Full(Cobble));
+            for z in floor + 1..=floor + 4 {
+                level.set(column.extend(z), Air);
+            }
+            level.set(column.extend(floor + 5), Full(MATERIAL));
+        }
+        let column = gate + side * offset;
+        level.set(column.extend(floor + 4), Fence(Wood(Spruce)));
+        level.set_height(column, floor);
+    }
+
+    gate
+}
+
+/// Returns the area of the keep
+fn keep(level: &mut impl WorldView, area: Rect, floor: i32, gate: HDir, crest: &TownCrest) -> Rect {
+    let towards_gate = IVec2::from(gate);
+    let extent = (area.size() * towards_gate.abs()).max_element();
+    let keep = Rect::new_centered(
+        area.center() - towards_gate * (extent / 2 - KEEP_SIZE / 2 - 3),
+        IVec2::splat(KEEP_SIZE),
+    );
+    let top = floor + KEEP_HEIGHT;
+
+    for column in keep {
+        foundation(level, column, floor);
+        level.set(column.extend(floor), Full(Cobble));
+        level.set_height(column, top);
+    }
+    for column in keep.border() {
+        for z in floor + 1..=top {
+            level.set(column.extend(z), Full(MATERIAL));
+        }
+        crenellation(level, column, top + 1);
+    }
+    let ladder = keep.min + IVec2::ONE;
+    for column in keep.shrink(1) {
+        for z in floor + 1..top {
+            level.set(column.extend(z), Air);
+        }
+        for z in [floor + 5, floor + 10, top] {
+            if column != ladder {
+                level.set(column.extend(z), Full(Wood(Spruce)));
+            }
+        }
+    }
+    for z in floor + 1..=top {
+        level.set(ladder.extend(z), Ladder(XPos));
+    }
+
+    // Windows on the upper floors
+    for dir in HDir::ALL {
+        let column = keep.center() + IVec2::from(dir) * (KEEP_SIZE / 2);
+        for z in [floor + 7, floor + 8, floor + 12, floor + 13] {
+            level.set(column.extend(z), GlassPane(None));
+        }
+    }
+
+    // Door facing the gate
+    let door = keep.center() + towards_gate * (KEEP_SIZE / 2);
+    place_door(level, door.extend(floor + 1), gate, Spruce);
+    crest.place_on_wall(level, (door + towards_gate).extend(floor + 4), gate);
+
+    keep
+}
+
+/// Areas for shacks along the walls, mirrored around the axis through the gate if possible.
+/// Fewer of them if they don't fit.
+fn outbuildings(area: Rect, gate: HDir, keep: Rect) -> Vec<Rect> {
+    let wall = Polygon(area.corners().collect());
+    let obstacles: Vec<Rect> = area
+        .corners()
+        .map(|corner| Rect::new_centered(corner, IVec2::splat(TOWER_RADIUS * 2 + 1)))
+        .chain([keep])
+        .collect();
+    (0..=OUTBUILDINGS.len())
+        .rev()
+        .flat_map(|count| [(count, true), (count, false)])
+        .find_map(|(count, symmetric)| {
+            layout(
+                &wall,
+                area.side_center(gate),
+                gate,
+                &obstacles,
+                &OUTBUILDINGS[..count],
+                symmetric,
+            )
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,266 @@
+use bevy_ecs::prelude::*;
+
+use crate::{
+    sim::{
+        building_plan::{CliffDwelling, House},
+        Built, CityCenter, PlaceList, Pos,
+    },
+    *,
+};
+
+/// Solid blocks required between the surface and any tunnel ceiling
+const MIN_COVER: i32 = 3;
+/// How far below the lowest building the cellar floors lie
+const CELLAR_DEPTH: i32 = 6;
+const CELLAR_HEIGHT: i32 = 3;
+const TUNNEL_HEIGHT: i32 = 3;
+const LIGHT_SPACING: i32 = 6;
+/// Tunnels longer than this get a storage room halfway along
+const STORAGE_ROOM_DISTANCE: i32 = 16;
+const CATACOMB_SPACING: i32 = 4;
+/// Catacombs go under the middle of the plaza, there are no graveyards yet
+const CATACOMB_SIZE: i32 = 17;
+
+/// Pass for [`sim::passes::Phase::Finish`] that digs [`cellars`] under the houses of each
+/// settlement, with catacombs under its plaza
+pub fn dig_cellars(level: &mut Level, world: &mut World) {
+    let centers: Vec<IVec2> = world
+        .query_filtered::<&Pos, With<CityCenter>>()
+        .iter(world)
+        .map(|pos| pos.truncate().block())
+        .collect();
+    // Cliff dwellings are already underground
+    let houses: Vec<Rect> = world
+        .query_filtered::<&House, (With<Built>, Without<CliffDwelling>)>()
+        .iter(world)
+        .map(|house| house.area)
+        .collect();
+    for &center in &centers {
+        let buildings: Vec<Rect> = houses
+            .iter()
+            .filter(|house| {
+                let nearest = centers
+                    .iter()
+                    .min_by_key(|other| other.distance_squared(house.center()));
+                nearest == Some(&center)
+            })
+            .copied()
+            .collect();
+        let plaza = Rect::new_centered(center, IVec2::splat(CATACOMB_SIZE));
+        cellars(level, &buildings, Some(plaza));
+    }
+}
+
+/// Cellars under `buildings`, connected by lit tunnels, with storage rooms along longer
+/// tunnels and catacombs under the `graveyard`. Parts that would break into caves or come too
+/// close to the surface are left out.
+pub fn cellars(
+    level: &mut impl WorldView,
+    buildings: &[Rect],
+    graveyard: Option<Rect>,
+) -> PlaceList {
+    let Some(floor) = buildings
+        .iter()
+        .chain(&graveyard)
+        .map(|&area| level.average_height(area).round() as i32)
+        .min()
+    else {
+        return PlaceList::default();
+    };
+    let z = floor - CELLAR_DEPTH;
+
+    let cursor = level.recording_cursor();
+    let mut carved = HashSet::default();
+
+    let mut rooms = Vec::new();
+    for &building in buildings {
+        let room = building.shrink(1);
+        let volume = cuboid(room, z + 1, z + CELLAR_HEIGHT);
+        if try_carve(level, &mut carved, &volume) {
+            let building_floor = level.average_height(building).round() as i32;
+            hatch(level, room.min, z, building_floor);
+            for column in room.border() {
+                if (column != room.min) & (0.3 > rand()) {
+                    level.set(column.extend(z + 1), Barrel);
+                }
+            }
+            rooms.push(room.center());
+        }
+    }
+    if let Some(graveyard) = graveyard
+        && let Some(center) = catacomb(level, &mut carved, graveyard.shrink(1), z)
+    {
+        rooms.push(center);
+    }
+
+    // Minimum spanning tree, so every cellar is reachable
+    let mut connected: Vec<IVec2> = rooms.first().copied().into_iter().collect();
+    let mut remaining: Vec<IVec2> = rooms.iter().skip(1).copied().collect();
+    while !remaining.is_empty() {
+        let (i, from) = remaining
+            .iter()
+            .enumerate()
+            .flat_map(|(i, to)| connected.iter().map(move |from| (i, *from, *to)))
+            .min_by_key(|(_, from, to)| from.distance_squared(*to))
+            .map(|(i, from, _)| (i, from))
+            .unwrap();
+        let to = remaining.swap_remove(i);
+        tunnel(level, &mut carved, from, to, z);
+        connected.push(to);
+    }
+
+    level.pop_recording(cursor).into()
+}
+
+/// L-shaped tunnel between two columns, trying both corners
+fn tunnel(level: &mut impl WorldView, carved: &mut HashSet<IVec3>, from: IVec2, to: IVec2, z: i32) {
+    for corner in [ivec2(to.x, from.y), ivec2(from.x, to.y)] {
+        // Without the corner twice, neighboring columns of the path always differ
+        let path: Vec<IVec2> = straight(from, corner)
+            .chain(straight(corner, to).skip(1))
+            .collect();
+        let volume: Vec<IVec3> = path
+            .iter()
+            .flat_map(|column| (z + 1..=z + TUNNEL_HEIGHT).map(|z| column.extend(z)))
+            .collect();
+        if !try_carve(level, carved, &volume) {
+            continue;
+        }
+        for (i, column) in path.iter().enumerate() {
+            if i as i32 % LIGHT_SPACING == LIGHT_SPACING / 2 {
+                level.set(column.extend(z + TUNNEL_HEIGHT), hanging_lantern());
+            }
+        }
+        if path.len() as i32 > STORAGE_ROOM_DISTANCE {
+            storage_room(level, carved, &path, z);
+        }
+        return;
+    }
+}
+
+/// Room with barrels to the side of the middle of the tunnel
+fn storage_room(level: &mut impl WorldView, carved: &mut HashSet<IVec3>, path: &[IVec2], z: i32) {
+    let middle = path[path.len() / 2];
+    let along = path[path.len() / 2 + 1] - middle;
+    let side = along.perp();
+    let center = middle + side * 4;
+    let room = Rect {
+        min: center - IVec2::splat(2),
+        max: center + IVec2::splat(2),
+    };
+    let doorway = [(middle + side).extend(z + 1), (middle + side).extend(z + 2)];
+    let volume = cuboid(room, z + 1, z + CELLAR_HEIGHT);
+    if !try_carve(level, carved, &[&volume[..], &doorway].concat()) {
+        return;
+    }
+    for column in room.border() {
+        if column != middle + side * 2 {
+            level.set(column.extend(z + 1), Barrel);
+        }
+    }
+    level.set(room.center().extend(z + CELLAR_HEIGHT), hanging_lantern());
+}
+
+/// Grid of corridors lined with bone niches, returns the center column
+fn catacomb(
+    level: &mut impl WorldView,
+    carved: &mut HashSet<IVec3>,
+    area: Rect,
+    z: i32,
+) -> Option<IVec2> {
+    let mut corridors = Vec::new();
+    for column in area {
+        let offset = column - area.min;
+        if (offset.x % CATACOMB_SPACING == 0) | (offset.y % CATACOMB_SPACING == 0) {
+            corridors.push(column);
+        }
+    }
+    let volume: Vec<IVec3> = corridors
+        .iter()
+        .flat_map(|column| (z + 1..=z + TUNNEL_HEIGHT).map(|z| column.extend(z)))
+        .collect();
+    if !try_carve(level, carved, &volume) {
+        return None;
+    }
+    let bones = Block::from_name("bone_block", &[("axis", "y")]);
+    let skull = Block::from_name("skeleton_skull", &[("rotation", "0")]);
+    for column in &corridors {
+        for dir in HDir::ALL {
+            let niche = *column + dir;
+            if corridors.contains(&niche) | !area.contains(niche) {
+                continue;
+            }
+            level.set(niche.extend(z + 1), bones);
+            if 0.2 > rand() {
+                level.set(niche.extend(z + 2), skull);
+            }
+        }
+        let offset = *column - area.min;
+        if (offset.x % CATACOMB_SPACING == 0) & (offset.y % CATACOMB_SPACING == 0) {
+            level.set(column.extend(z + TUNNEL_HEIGHT), hanging_lantern());
+        }
+    }
+    corridors
+        .into_iter()
+        .min_by_key(|column| column.distance_squared(area.center()))
+}
+
+/// Ladder from the cellar up through the building floor, closed with a trapdoor
+fn hatch(level: &mut impl WorldView, column: IVec2, cellar: i32, floor: i32) {
+    for z in cellar + 1..floor {
+        level.set(column.extend(z), Ladder(YPos));
+    }
+    level.set(
+        column.extend(floor),
+        Trapdoor(Spruce, YPos, DoorMeta::empty()),
+    );
+}
+
+/// Carves out the volume unless that would open up a cave or come close to the surface.
+/// Blocks carved earlier don't count as breaches.
+fn try_carve(level: &mut impl WorldView, carved: &mut HashSet<IVec3>, volume: &[IVec3]) -> bool {
+    let new: HashSet<IVec3> = volume.iter().copied().collect();
+    for &pos in volume {
+        if level.height(pos.truncate()) < pos.z + MIN_COVER {
+            return false;
+        }
+        for offset in [
+            IVec3::X,
+            IVec3::Y,
+            IVec3::Z,
+            -IVec3::X,
+            -IVec3::Y,
+            -IVec3::Z,
+        ] {
+            let neighbor = pos + offset;
+            if !new.contains(&neighbor)
+                && !carved.contains(&neighbor)
+                && !level.get(neighbor).solid()
+            {
+                return false;
+            }
+        }
+    }
+    for &pos in volume {
+        level.set(pos, Air);
+    }
+    carved.extend(new);
+    true
+}
+
+fn cuboid(area: Rect, min_z: i32, max_z: i32) -> Vec<IVec3> {
+    area.into_iter()
+        .flat_map(|column| (min_z..=max_z).map(move |z| column.extend(z)))
+        .collect()
+}
+
+/// Axis aligned line, including both ends
+fn straight(from: IVec2, to: IVec2) -> impl Iterator<Item = IVec2> {
+    let step = (to - from).signum();
+    let length = (to - from).abs().max_element();
+    (0..=length).map(move |i| from + step * i)
+}
+
+fn hanging_lantern() -> Block {
+    Block::from_name("lantern", &[("hanging", "true")])
+}
@@ -0,0 +1,177 @@
+use crate::{remove_foliage::remove_trees, sim::PlaceList, terraform::slope, *};
+
+const STOREY_HEIGHT: i32 = 4;
+const MAX_STOREYS: i32 = 4;
+/// Rooms span laterals -ROOM_HALF_WIDTH..=ROOM_HALF_WIDTH
+const ROOM_HALF_WIDTH: i32 = 2;
+const ROOM_DEPTH: i32 = 4;
+const BALCONY_DEPTH: i32 = 3;
+/// How far in front of the foot the face may start
+const MAX_FACE_DISTANCE: i32 = 6;
+/// How uneven the face may be before it's no longer worth flattening
+const MAX_FACE_UNEVENNESS: i32 = 2;
+const FACADE: Block = Terracotta(Some(Orange));
+const TRIM: BlockMaterial = Wood(Spruce);
+
+/// Steep faces at least `min_height` tall, as the column at the foot of the face and the
+/// direction towards the rock, tallest first
+pub fn find_cliffs(level: &Level, area: Rect, min_height: i32) -> Vec<(IVec2, HDir)> {
+    // Dwellings are a dozen columns wide, no need to look at every column
+    let step = 4;
+    let mut candidates = Vec::new();
+    for x in (area.min.x + 8..=area.max.x - 8).step_by(step) {
+        for y in (area.min.y + 8..=area.max.y - 8).step_by(step) {
+            let column = ivec2(x, y);
+            let slope = slope(level, column);
+            let dir = match (slope.x.abs() > slope.y.abs(), slope.x > 0, slope.y > 0) {
+                (true, true, _) => XPos,
+                (true, false, _) => XNeg,
+                (false, _, true) => YPos,
+                (false, _, false) => YNeg,
+            };
+            let height = (level.height)(column);
+            let rise = (level.height)(column + IVec2::from(dir) * 4) - height;
+            // There needs to be some room in front of the face
+            let front = (level.height)(column - IVec2::from(dir) * 2);
+            if (rise >= min_height) & ((front - height).abs() <= 1) {
+                candidates.push((column, dir, rise));
+            }
+        }
+    }
+    candidates.sort_by_key(|(.., rise)| -rise);
+
+    let mut cliffs: Vec<(IVec2, HDir)> = Vec::new();
+    for (column, dir, _) in candidates {
+        if cliffs
+            .iter()
+            .all(|(other, _)| other.distance_squared(column) > 16 * 16)
+        {
+            cliffs.push((column, dir));
+        }
+    }
+    cliffs
+}
+
+/// Columns a dwelling at `foot` may touch, from the balconies to the back of the rooms
+pub fn footprint(foot: IVec2, dir: HDir) -> Rect {
+    let forward = IVec2::from(dir);
+    let side = IVec2::from(dir.rotated(1));
+    let a = foot - forward * BALCONY_DEPTH - side * (ROOM_HALF_WIDTH + 2);
+    let b = foot + forward * (MAX_FACE_DISTANCE + ROOM_DEPTH + 1) + side * (ROOM_HALF_WIDTH + 2);
+    Rect {
+        min: a.min(b),
+        max: a.max(b),
+    }
+}
+
+/// Rooms carved into the cliff behind `foot`, stacked on top of each other, in the style of
+/// the mesa dwellings: a flush terracotta facade with glass windows, balconies on the upper
+/// storeys and a ladder connecting them.
+pub fn cliff_dwelling(level: &mut impl WorldView, foot: IVec2, dir: HDir) -> PlaceList {
+    let forward = IVec2::from(dir);
+    let side = IVec2::from(dir.rotated(1));
+    let at = |depth: i32, lateral: i32, z: i32| (foot + forward * depth + side * lateral).extend(z);
+    let floor = level.height(foot);
+
+    // Stack storeys as long as the face stays reasonably flat
+    let mut storeys = 0;
+    let mut face_min = i32::MAX;
+    let mut face_max = i32::MIN;
+    while storeys < MAX_STOREYS {
+        let z = floor + storeys * STOREY_HEIGHT + 2;
+        let Some(depths) = (-ROOM_HALF_WIDTH - 1..=ROOM_HALF_WIDTH + 1)
+            .map(|lateral| {
+                (0..=MAX_FACE_DISTANCE).find(|&depth| level.get(at(depth, lateral, z)).solid())
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            break;
+        };
+        let min = depths.iter().copied().fold(face_min, i32::min);
+        let max = depths.iter().copied().fold(face_max, i32::max);
+        if max - min > MAX_FACE_UNEVENNESS {
+            break;
+        }
+        // The rock needs to reach over the room
+        let ceiling = floor + (storeys + 1) * STOREY_HEIGHT;
+        if level.height(at(max + ROOM_DEPTH, 0, 0).truncate()) <= ceiling {
+            break;
+        }
+        face_min = min;
+        face_max = max;
+        storeys += 1;
+    }
+    if storeys == 0 {
+        return PlaceList::default();
+    }
+    // The facade sits at the outermost rock, anything recessed behind it gets filled
+    let face = face_min;
+
+    let cursor = level.recording_cursor();
+    remove_trees(
+        level,
+        Rect::new_centered(foot, IVec2::splat(ROOM_HALF_WIDTH * 2 + 5)),
+    );
+
+    for storey in 0..storeys {
+        let z = floor + storey * STOREY_HEIGHT;
+
+        // Shell: rock where there is rock, terracotta where there isn't
+        for depth in face..=face + ROOM_DEPTH + 1 {
+            for lateral in -ROOM_HALF_WIDTH - 1..=ROOM_HALF_WIDTH + 1 {
+                for dz in 0..=STOREY_HEIGHT {
+                    fill_if_open(level, at(depth, lateral, z + dz), FACADE);
+                }
+            }
+        }
+        // Room
+        for depth in face + 1..=face + ROOM_DEPTH {
+            for lateral in -ROOM_HALF_WIDTH..=ROOM_HALF_WIDTH {
+                for dz in 1..STOREY_HEIGHT {
+                    level.set(at(depth, lateral, z + dz), Air);
+                }
+            }
+        }
+        // Door & windows, flush with the face
+        level.set(at(face, 0, z + 1), Air);
+        level.set(at(face, 0, z + 2), Air);
+        for lateral in [-ROOM_HALF_WIDTH, ROOM_HALF_WIDTH] {
+            level.set(at(face, lateral, z + 2), GlassPane(None));
+        }
+        level.set(
+            at(face + ROOM_DEPTH, -ROOM_HALF_WIDTH, z + 1),
+            if storey == 0 { CraftingTable } else { Barrel },
+        );
+
+        // Balcony in front of the door, flush with the floor. The side towards the ladder
+        // stays open.
+        if storey > 0 {
+            for depth in face - BALCONY_DEPTH..face {
+                for lateral in -ROOM_HALF_WIDTH..=ROOM_HALF_WIDTH {
+                    fill_if_open(level, at(depth, lateral, z), Slab(TRIM, Top));
+                }
+                fill_if_open(level, at(depth, -ROOM_HALF_WIDTH - 1, z + 1), Fence(TRIM));
+            }
+            for lateral in -ROOM_HALF_WIDTH..=ROOM_HALF_WIDTH {
+                fill_if_open(level, at(face - BALCONY_DEPTH, lateral, z + 1), Fence(TRIM));
+            }
+        }
+    }
+
+    // Ladder up the facade, next to the balconies
+    let ladder_top = floor + (storeys - 1) * STOREY_HEIGHT + 1;
+    for z in floor + 1..=ladder_top {
+        let pos = at(face - 1, ROOM_HALF_WIDTH + 1, z);
+        if !level.get(pos).solid() {
+            level.set(pos, Ladder(dir.rotated(2)));
+        }
+    }
+
+    level.pop_recording(cursor).into()
+}
+
+fn fill_if_open(level: &mut impl WorldView, pos: IVec3, block: Block) {
+    if !level.get(pos).solid() {
+        level.set(pos, block)
+    }
+}
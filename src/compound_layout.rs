@@ -0,0 +1,244 @@
+use bevy_math::Vec2Swizzles;
+
+use crate::*;
+
+/// Columns kept free around buildings, so that they can be walked around
+const SPACING: i32 = 1;
+/// Length of the passage in from the gate that stays free
+const GATE_PASSAGE: i32 = 5;
+/// Positions tried per building, best first
+const CANDIDATES: usize = 12;
+/// Layouts checked for access before giving up
+const SEARCH_BUDGET: u32 = 2000;
+
+/// Building that needs a place in the compound
+#[derive(Debug, Copy, Clone)]
+pub struct Requirement {
+    pub size: IVec2,
+    /// Whether it may be turned by 90°
+    pub rotatable: bool,
+}
+
+/// Buildings placed together, either alone or as a mirrored pair
+#[derive(Copy, Clone)]
+enum Group {
+    Single(usize),
+    Pair(usize, usize),
+}
+
+struct Search<'a> {
+    buildings: &'a [Requirement],
+    buildable: HashSet<IVec2>,
+    inside: HashSet<IVec2>,
+    obstacles: &'a [Rect],
+    /// Way in from the gate, can't be built on but walked through
+    passage: Rect,
+    entrance: IVec2,
+    bounds: Rect,
+    /// Point on the mirror axis and the direction across it
+    mirror: Option<(IVec2, IVec2)>,
+    placed: Vec<Option<Rect>>,
+    budget: u32,
+}
+
+/// Places the buildings within the wall so that they don't overlap each other or the
+/// `obstacles`, leave the way in from the gate free and can all be reached from it through
+/// the courtyard. Buildings are placed as far out as possible, keeping the middle open.
+/// With `symmetric`, the layout is mirrored along the axis through the gate: buildings of the
+/// same size are placed as pairs, the rest on the axis.
+/// Returns the areas in the order of `buildings`, `None` if they don't fit.
+pub fn layout(
+    wall: &Polygon,
+    gate: IVec2,
+    gate_dir: HDir,
+    obstacles: &[Rect],
+    buildings: &[Requirement],
+    symmetric: bool,
+) -> Option<Vec<Rect>> {
+    let inside: HashSet<IVec2> = wall.iter().collect();
+    let buildable = inside
+        .iter()
+        .copied()
+        .filter(|&column| {
+            Rect::new_centered(column, IVec2::splat(SPACING * 2 + 1))
+                .into_iter()
+                .all(|near| inside.contains(&near))
+        })
+        .collect();
+    let inwards = -IVec2::from(gate_dir);
+    let side = IVec2::from(gate_dir.rotated(1)).abs();
+    let passage = Rect {
+        min: (gate - side).min(gate - side + inwards * GATE_PASSAGE),
+        max: (gate + side).max(gate + side + inwards * GATE_PASSAGE),
+    };
+
+    let mut groups = Vec::new();
+    let mut unpaired: Vec<usize> = (0..buildings.len()).collect();
+    while let Some(i) = unpaired.pop() {
+        let partner = symmetric
+            .then(|| {
+                unpaired
+                    .iter()
+                    .position(|&j| same_shape(buildings[i], buildings[j]))
+            })
+            .flatten();
+        match partner {
+            Some(index) => groups.push(Group::Pair(i, unpaired.remove(index))),
+            None => groups.push(Group::Single(i)),
+        }
+    }
+    // The big ones are hardest to fit
+    groups.sort_by_key(|group| {
+        let (Group::Single(i) | Group::Pair(i, _)) = *group;
+        -buildings[i].size.x * buildings[i].size.y
+    });
+
+    let mut search = Search {
+        buildings,
+        buildable,
+        inside,
+        obstacles,
+        passage,
+        entrance: gate + inwards,
+        bounds: wall.bounds(),
+        mirror: symmetric.then_some((gate, side)),
+        placed: vec![None; buildings.len()],
+        budget: SEARCH_BUDGET,
+    };
+    search
+        .place(&groups)
+        .then(|| search.placed.into_iter().map(Option::unwrap).collect())
+}
+
+fn same_shape(a: Requirement, b: Requirement) -> bool {
+    (a.size == b.size) | (a.rotatable & b.rotatable & (a.size == b.size.yx()))
+}
+
+impl Search<'_> {
+    /// Depth first search over the positions of the groups
+    fn place(&mut self, groups: &[Group]) -> bool {
+        let Some((group, rest)) = groups.split_first() else {
+            self.budget = self.budget.saturating_sub(1);
+            return self.accessible();
+        };
+        for placement in self.candidates(group) {
+            if self.budget == 0 {
+                return false;
+            }
+            for &(i, area) in &placement {
+                self.placed[i] = Some(area);
+            }
+            if self.place(rest) {
+                return true;
+            }
+            for &(i, _) in &placement {
+                self.placed[i] = None;
+            }
+        }
+        false
+    }
+
+    fn candidates(&self, group: &Group) -> Vec<Vec<(usize, Rect)>> {
+        let (Group::Single(i) | Group::Pair(i, _)) = *group;
+        let requirement = self.buildings[i];
+        let sizes = if requirement.rotatable {
+            vec![requirement.size, requirement.size.yx()]
+        } else {
+            vec![requirement.size]
+        };
+        let bounds = self.bounds;
+        let mut candidates: Vec<(f32, Vec<(usize, Rect)>)> = Vec::new();
+        for size in sizes {
+            for min in (Rect {
+                min: bounds.min,
+                max: bounds.max - size + IVec2::ONE,
+            }) {
+                let area = Rect {
+                    min,
+                    max: min + size - IVec2::ONE,
+                };
+                if !self.fits(area, &[]) {
+                    continue;
+                }
+                let placement = match (*group, self.mirrored(area)) {
+                    (Group::Single(i), None) => vec![(i, area)],
+                    (Group::Single(i), Some(image)) => {
+                        if image != area {
+                            continue;
+                        }
+                        vec![(i, area)]
+                    }
+                    (Group::Pair(i, j), Some(image)) => {
+                        // Each pair only needs to be considered from one side
+                        if (image.min.to_array() <= area.min.to_array())
+                            | !self.fits(image, &[area])
+                        {
+                            continue;
+                        }
+                        vec![(i, area), (j, image)]
+                    }
+                    (Group::Pair(..), None) => unreachable!(),
+                };
+                let distance = area.center_vec2().distance(bounds.center_vec2());
+                candidates.push((distance, placement));
+            }
+        }
+        candidates.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        candidates
+            .into_iter()
+            .take(CANDIDATES)
+            .map(|(_, placement)| placement)
+            .collect()
+    }
+
+    /// `None` without symmetry
+    fn mirrored(&self, area: Rect) -> Option<Rect> {
+        let (axis, across) = self.mirror?;
+        let mirror = |column: IVec2| column - 2 * (column - axis) * across;
+        let (a, b) = (mirror(area.min), mirror(area.max));
+        Some(Rect {
+            min: a.min(b),
+            max: a.max(b),
+        })
+    }
+
+    /// Whether the area is buildable and keeps its distance to everything placed so far
+    fn fits(&self, area: Rect, also_placed: &[Rect]) -> bool {
+        let grown = area.grow(SPACING);
+        area.into_iter()
+            .all(|column| self.buildable.contains(&column))
+            && !self
+                .obstacles
+                .iter()
+                .chain([&self.passage])
+                .chain(self.placed.iter().flatten())
+                .chain(also_placed)
+                .any(|other| other.overlapps(grown))
+    }
+
+    /// Whether each building can be reached from the gate through the courtyard
+    fn accessible(&self) -> bool {
+        let blocked = |column: IVec2| {
+            !self.inside.contains(&column)
+                || self
+                    .placed
+                    .iter()
+                    .flatten()
+                    .any(|area| area.contains(column))
+                || self.obstacles.iter().any(|area| area.contains(column))
+        };
+        let mut reached = HashSet::default();
+        let mut queue = vec![self.entrance];
+        while let Some(column) = queue.pop() {
+            if blocked(column) || !reached.insert(column) {
+                continue;
+            }
+            queue.extend(NEIGHBORS_2D.iter().map(|off| column + *off));
+        }
+        self.placed.iter().flatten().all(|area| {
+            area.grow(1)
+                .border()
+                .any(|column| reached.contains(&column))
+        })
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{
+    remove_foliage::remove_trees,
+    roof::roof,
+    sim::PlaceList,
+    terraform::{make_retaining_wall, WallCrest},
+    *,
+};
+
+const TERRACES: i32 = 3;
+const TERRACE_STEP: i32 = 3;
+const BASE_SIZE: i32 = 27;
+const WALL_HEIGHT: i32 = 12;
+/// Height difference per block the path down may have
+const MAX_GRADE: f32 = 0.5;
+const FLAG_COLORS: [Color; 5] = [Blue, White, Red, Green, Yellow];
+
+/// Local maxima of the heightmap that rise at least `min_prominence` above the average height
+/// at distance `radius`, most prominent first
+pub fn find_peaks(level: &Level, area: Rect, radius: i32, min_prominence: f32) -> Vec<IVec2> {
+    // Sampling every few columns is plenty for finding mountain tops
+    let step = 4;
+    let mut peaks = Vec::new();
+    for x in (area.min.x + radius..=area.max.x - radius).step_by(step) {
+        for y in (area.min.y + radius..=area.max.y - radius).step_by(step) {
+            let column = ivec2(x, y);
+            let height = (level.height)(column);
+            let surroundings = Rect::new_centered(column, IVec2::splat(radius * 2));
+            if surroundings.border().any(|c| (level.height)(c) > height)
+                || Rect::new_centered(column, IVec2::splat(step as i32 * 2))
+                    .into_iter()
+                    .any(|c| (level.height)(c) > height)
+            {
+                continue;
+            }
+            let prominence = height as f32 - level.average_height(surroundings.border());
+            if prominence >= min_prominence {
+                peaks.push((column, prominence));
+            }
+        }
+    }
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    peaks.into_iter().map(|(column, _)| column).collect()
+}
+
+/// Terraced monastery fortress on top of `peak`, with a path down to `village`
+pub fn dzong(level: &mut impl WorldView, peak: IVec2, village: IVec2) -> PlaceList {
+    let top = level.height(peak);
+    let base = top - (TERRACES - 1) * TERRACE_STEP;
+
+    let cursor = level.recording_cursor();
+    remove_trees(level, footprint(peak));
+
+    // Terraces, from the bottom up
+    let mut terrace = Rect::new_centered(peak, IVec2::splat(BASE_SIZE));
+    let mut floor = base;
+    for i in 0..TERRACES {
+        for column in terrace {
+            for z in (floor + 1..=level.height(column)).rev() {
+                level.set(column.extend(z), Air);
+            }
+            if level.height(column) > floor {
+                level.set_height(column, floor);
+            }
+        }
+        make_retaining_wall(
+            level,
+            &Polygon(terrace.corners().collect()),
+            floor,
+            WallCrest::Wall,
+        );
+        if i < TERRACES - 1 {
+            terrace = terrace.shrink(3);
+            floor += TERRACE_STEP;
+        }
+    }
+    let gate = gate(
+        level,
+        Rect::new_centered(peak, IVec2::splat(BASE_SIZE)),
+        base,
+        village,
+    );
+    let mut rec: PlaceList = level.pop_recording(cursor).into();
+
+    // Main building on the topmost terrace
+    let building = terrace.shrink(1);
+    let cursor = level.recording_cursor();
+    battered_walls(level, building, floor);
+    rec.extend(level.pop_recording(cursor));
+    rec.extend(roof(
+        level,
+        building.grow(1),
+        floor + WALL_HEIGHT + 1,
+        Wood(DarkOak),
+    ));
+
+    let cursor = level.recording_cursor();
+    for (i, corner) in building.corners().enumerate() {
+        let terrace_corner = corner + (corner - peak).signum() * 4;
+        prayer_flags(
+            level,
+            corner.extend(floor + WALL_HEIGHT - 2),
+            terrace_corner,
+            i,
+        );
+    }
+    switchback_path(level, gate, village);
+    rec.extend(level.pop_recording(cursor));
+
+    rec
+}
+
+/// Area taken up by a dzong on `peak`, not counting the path down
+pub fn footprint(peak: IVec2) -> Rect {
+    Rect::new_centered(peak, IVec2::splat(BASE_SIZE + 4))
+}
+
+/// Massive walls that get thinner towards the top, white with a red band below the roof
+fn battered_walls(level: &mut impl WorldView, area: Rect, floor: i32) {
+    for z in floor + 1..=floor + WALL_HEIGHT {
+        let inset = (z - floor - 1) / 4;
+        let block = if z > floor + WALL_HEIGHT - 2 {
+            Terracotta(Some(Red))
+        } else {
+            SmoothQuartz
+        };
+        // Thicker at the bottom, sloping inwards
+        for depth in 0..(3 - inset).max(1) {
+            for column in area.shrink(depth).border() {
+                level.set(column.extend(z), block);
+            }
+        }
+        // Small windows on the upper stories
+        if (z - floor) % 4 == 2 && z > floor + 4 {
+            for dir in HDir::ALL {
+                let center = area.center();
+                let column = match dir {
+                    YPos => ivec2(center.x, area.max.y),
+                    XNeg => ivec2(area.min.x, center.y),
+                    YNeg => ivec2(center.x, area.min.y),
+                    XPos => ivec2(area.max.x, center.y),
+                };
+                for depth in 0..=2 {
+                    level.set((column - IVec2::from(dir) * depth).extend(z), Air);
+                }
+                level.set(column.extend(z), GlassPane(None));
+            }
+        }
+    }
+    // Entrance
+    let door = ivec2(area.center().x, area.min.y);
+    for depth in 0..=2 {
+        for z in floor + 1..=floor + 2 {
+            level.set((door + IVec2::Y * depth).extend(z), Air);
+        }
+    }
+    place_door(level, door.extend(floor + 1), YNeg, DarkOak);
+}
+
+/// Line of banners hanging on the side of fences between `start` and the ground at `end`
+fn prayer_flags(level: &mut impl WorldView, start: IVec3, end: IVec2, color_offset: usize) {
+    let end = level.ground(end) + IVec3::Z * 3;
+    for z in end.z - 2..end.z {
+        level.set(end.truncate().extend(z), Fence(Wood(DarkOak)));
+    }
+    let facing = if (end.x - start.x).abs() > (end.y - start.y).abs() {
+        YNeg
+    } else {
+        XPos
+    };
+    let steps = (end - start).abs().max_element();
+    let mut fences = Vec::new();
+    for step in 0..=steps {
+        let pos = start
+            + ((end - start).as_vec3() * step as f32 / steps as f32)
+                .round()
+                .as_ivec3();
+        if level.get(pos).solid() {
+            continue;
+        }
+        level.set(pos, Fence(Wood(DarkOak)));
+        fences.push((step, pos));
+    }
+    // Only once the line is done, so that the banners don't end up where a fence goes
+    for (step, fence) in fences {
+        let banner = fence + IVec3::from(facing);
+        if level.get(banner) == Air {
+            let color = FLAG_COLORS[(step as usize + color_offset) % FLAG_COLORS.len()];
+            level.set(banner, WallBanner(facing, color));
+        }
+    }
+}
+
+/// Opening in the lowest terrace wall facing the village
+fn gate(level: &mut impl WorldView, terrace: Rect, floor: i32, village: IVec2) -> IVec2 {
+    let gate = terrace
+        .border()
+        .min_by_key(|column| (*column - village).length_squared())
+        .unwrap();
+    for column in Rect::new_centered(gate, IVec2::splat(3)) {
+        for z in floor + 1..=floor + 3 {
+            level.set(column.extend(z), Air);
+        }
+    }
+    gate
+}
+
+/// Path down to `end`. Where the slope is steeper than [`MAX_GRADE`], it goes back and forth
+/// across it instead of straight down.
+fn switchback_path(level: &mut impl WorldView, start: IVec2, end: IVec2) {
+    let area = level.area();
+    let mut pos = start.as_vec2();
+    let mut side = 1.;
+    let segment_length = 8;
+    'segments: for _ in 0..100 {
+        let downhill = (end.as_vec2() - pos).normalize_or_zero();
+        let ahead = (pos + downhill * segment_length as f32).round().as_ivec2();
+        let grade = if area.contains(ahead) {
+            (level.height(pos.round().as_ivec2()) - level.height(ahead)).abs() as f32
+                / segment_length as f32
+        } else {
+            0.
+        };
+        // Share of each step spent going down, the rest goes across the slope
+        let along = (MAX_GRADE / grade).min(1.);
+        let dir = downhill * along + downhill.perp() * side * (1. - along * along).sqrt();
+        for _ in 0..segment_length {
+            if pos.distance(end.as_vec2()) < 4. {
+                break 'segments;
+            }
+            pos += dir;
+            let column = pos.round().as_ivec2();
+            if !area.contains(column) {
+                break 'segments;
+            }
+            for column in [column, column + IVec2::X] {
+                let ground = level.ground(column);
+                if level.get(ground).soil() {
+                    level.set(ground, Path);
+                } else if level.water_level(column).is_none() {
+                    level.set(ground, Full(Cobble));
+                }
+                level.modify(ground + IVec3::Z, &mut |block| {
+                    if matches!(
+                        block,
+                        GroundPlant(..) | GrowingCrop(..) | SmallPlant(..) | TallPlant(..)
+                    ) {
+                        Air
+                    } else {
+                        block
+                    }
+                });
+            }
+        }
+        if along < 1. {
+            side *= -1.;
+        }
+    }
+}
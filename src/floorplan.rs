@@ -0,0 +1,202 @@
+use crate::*;
+
+/// Restarts before giving up, both after contradictions and after rejected layouts
+const ATTEMPTS: u32 = 50;
+
+/// What a column on the floor of a building is used for
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Cell {
+    Wall,
+    Door,
+    Corridor,
+    Room,
+}
+
+impl Cell {
+    const ALL: [Cell; 4] = [Cell::Wall, Cell::Door, Cell::Corridor, Cell::Room];
+
+    fn weight(self) -> f32 {
+        match self {
+            Cell::Wall => 2.,
+            Cell::Door => 0.5,
+            Cell::Corridor => 2.,
+            Cell::Room => 6.,
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// Rooms are closed off from corridors by walls and doors, doors don't sit next to each
+    /// other
+    fn fits_next_to(self, other: Cell) -> bool {
+        !matches!(
+            (self, other),
+            (Cell::Room, Cell::Corridor) | (Cell::Corridor, Cell::Room) | (Cell::Door, Cell::Door)
+        )
+    }
+
+    fn open(self) -> bool {
+        matches!(self, Cell::Corridor | Cell::Room)
+    }
+}
+
+/// Layout of the interior walls of a story
+pub struct Floorplan {
+    area: Rect,
+    // Covers whole chunks, `None` outside of the area
+    cells: ColumnMap<Option<Cell>>,
+}
+
+impl Floorplan {
+    pub fn get(&self, column: IVec2) -> Option<Cell> {
+        if self.area.contains(column) {
+            (self.cells)(column)
+        } else {
+            None
+        }
+    }
+
+    /// Places the walls and doors, `height` blocks high starting at `z`
+    pub fn build(&self, level: &mut impl WorldView, z: i32, height: i32, species: TreeSpecies) {
+        for column in self.area {
+            match self.get(column).unwrap() {
+                Cell::Wall => {
+                    for z in z..z + height {
+                        level.set(column.extend(z), Full(Wood(species)));
+                    }
+                }
+                Cell::Door => {
+                    let facing = if self.get(column + IVec2::Y).is_some_and(Cell::open) {
+                        YPos
+                    } else {
+                        XPos
+                    };
+                    place_door(level, column.extend(z), facing, species);
+                    for z in z + 2..z + height {
+                        level.set(column.extend(z), Full(Wood(species)));
+                    }
+                }
+                Cell::Corridor | Cell::Room => (),
+            }
+        }
+    }
+
+    /// Doors need to sit in a wall between two open cells, walls can't stand alone and
+    /// everything open needs to be reachable from the entrance
+    fn valid(&self, entrance: IVec2) -> bool {
+        let solid = |column: IVec2| self.get(column).is_none_or(|cell| cell == Cell::Wall);
+        let open = |column: IVec2| self.get(column).is_some_and(Cell::open);
+        for column in self.area {
+            if self.get(column) == Some(Cell::Wall)
+                && !NEIGHBORS_2D.iter().any(|off| solid(column + *off))
+            {
+                return false;
+            }
+            if self.get(column) == Some(Cell::Door)
+                && !(open(column + IVec2::X)
+                    && open(column - IVec2::X)
+                    && solid(column + IVec2::Y)
+                    && solid(column - IVec2::Y))
+                && !(open(column + IVec2::Y)
+                    && open(column - IVec2::Y)
+                    && solid(column + IVec2::X)
+                    && solid(column - IVec2::X))
+            {
+                return false;
+            }
+        }
+
+        let mut reached = HashSet::default();
+        let mut queue = vec![entrance];
+        while let Some(column) = queue.pop() {
+            if solid(column) || !reached.insert(column) {
+                continue;
+            }
+            queue.extend(self.cells.neighbors(column));
+        }
+        self.area
+            .into_iter()
+            .all(|column| solid(column) || reached.contains(&column))
+    }
+}
+
+/// Generates a floorplan for `area` (the inside of the outer walls) with wave function
+/// collapse. `entrance` is the column behind the outside door, `open` columns need to stay
+/// free (e.g. for furniture). Every room is reachable from the entrance.
+pub fn floorplan(area: Rect, entrance: IVec2, open: &[IVec2]) -> Option<Floorplan> {
+    (0..ATTEMPTS).find_map(|_| {
+        let mut options = ColumnMap::new(area.min.into(), area.max.into(), 1, 0b1111u8);
+        let mut changed = Vec::new();
+        options(entrance, Cell::Corridor.bit() | Cell::Room.bit());
+        changed.push(entrance);
+        for &column in open {
+            options(column, Cell::Room.bit());
+            changed.push(column);
+        }
+        if !propagate(area, &mut options, changed) {
+            return None;
+        }
+        // Collapse the cell with the fewest options left until all are decided
+        while let Some(column) = area
+            .into_iter()
+            .filter(|column| options(*column).count_ones() > 1)
+            .min_by_key(|column| (options(*column).count_ones(), rand_range::<i32, _>(0..1000)))
+        {
+            let possible: Vec<Cell> = Cell::ALL
+                .into_iter()
+                .filter(|cell| options(column) & cell.bit() != 0)
+                .collect();
+            let total: f32 = possible.iter().map(|cell| cell.weight()).sum();
+            let mut pick = rand_f32(0., total);
+            let cell = *possible
+                .iter()
+                .find(|cell| {
+                    pick -= cell.weight();
+                    pick <= 0.
+                })
+                .unwrap_or(possible.last().unwrap());
+            options(column, cell.bit());
+            if !propagate(area, &mut options, vec![column]) {
+                return None;
+            }
+        }
+        // Only the columns outside of the area still have several options
+        let cells = options.map(|bits| Cell::ALL.into_iter().find(|cell| cell.bit() == bits));
+        let plan = Floorplan { area, cells };
+        plan.valid(entrance).then_some(plan)
+    })
+}
+
+/// Removes options that aren't compatible with any option of a neighbor. `false` on a
+/// contradiction.
+fn propagate(area: Rect, options: &mut ColumnMap<u8>, mut changed: Vec<IVec2>) -> bool {
+    while let Some(column) = changed.pop() {
+        let here = options(column);
+        let neighbors = options.neighbors(column).collect::<Vec<_>>();
+        for neighbor in neighbors {
+            if !area.contains(neighbor) {
+                continue;
+            }
+            let there = options(neighbor);
+            let allowed = Cell::ALL
+                .into_iter()
+                .filter(|&cell| there & cell.bit() != 0)
+                .filter(|&cell| {
+                    Cell::ALL
+                        .into_iter()
+                        .any(|other| (here & other.bit() != 0) && other.fits_next_to(cell))
+                })
+                .fold(0, |bits, cell| bits | cell.bit());
+            if allowed == 0 {
+                return false;
+            }
+            if allowed != there {
+                options(neighbor, allowed);
+                changed.push(neighbor);
+            }
+        }
+    }
+    true
+}
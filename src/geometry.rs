@@ -2,6 +2,7 @@ use std::{ops::Add, str::FromStr};
 
 use bevy_math::Vec2Swizzles;
 pub use bevy_math::{ivec2, ivec3, vec2, vec3, IVec2, IVec3, Vec2, Vec3};
+use enum_iterator::Sequence;
 use itertools::Itertools;
 use num_derive::FromPrimitive;
 
@@ -37,6 +38,17 @@ impl Rect {
         self.min + self.size() / 2
     }
 
+    /// Column in the middle of the side towards `side`
+    pub fn side_center(self, side: HDir) -> IVec2 {
+        let center = self.center();
+        match side {
+            YPos => ivec2(center.x, self.max.y),
+            XNeg => ivec2(self.min.x, center.y),
+            YNeg => ivec2(center.x, self.min.y),
+            XPos => ivec2(self.max.x, center.y),
+        }
+    }
+
     pub fn center_vec2(self) -> Vec2 {
         self.min.as_vec2() + self.size().as_vec2() / 2.
     }
@@ -64,9 +76,43 @@ impl Rect {
 
     pub fn overlap(self, other: Rect) -> Rect {
         Rect {
-            min: ivec2(self.min.x.max(other.min.x), self.min.x.max(other.min.y)),
-            max: ivec2(self.max.x.min(other.max.x), self.max.x.min(other.max.y)),
-        }
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    /// `None` if they don't overlap
+    pub fn intersection(self, other: Rect) -> Option<Rect> {
+        self.overlapps(other).then(|| self.overlap(other))
+    }
+
+    /// Up to four disjoint rects covering the columns of `self` outside of `other`
+    pub fn subtract(self, other: Rect) -> Vec<Rect> {
+        let Some(hole) = self.intersection(other) else {
+            return vec![self];
+        };
+        // Full-width strips above and below the hole, then the parts left and right of it
+        [
+            Rect {
+                min: self.min,
+                max: ivec2(self.max.x, hole.min.y - 1),
+            },
+            Rect {
+                min: ivec2(self.min.x, hole.max.y + 1),
+                max: self.max,
+            },
+            Rect {
+                min: ivec2(self.min.x, hole.min.y),
+                max: ivec2(hole.min.x - 1, hole.max.y),
+            },
+            Rect {
+                min: ivec2(hole.max.x + 1, hole.min.y),
+                max: ivec2(self.max.x, hole.max.y),
+            },
+        ]
+        .into_iter()
+        .filter(|rect| rect.min.cmple(rect.max).all())
+        .collect()
     }
 
     pub fn grow(self, amount: i32) -> Self {
@@ -170,7 +216,7 @@ pub struct Polyline(pub Vec<IVec2>);
 pub struct Polygon(pub Vec<IVec2>);
 // Todo: areas with shared borders/corners
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 #[repr(u8)]
 pub enum HAxis {
     X,
@@ -203,7 +249,7 @@ impl From<HAxis> for Axis {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, Hash, Sequence)]
 #[repr(u8)]
 pub enum Axis {
     X,
@@ -234,7 +280,7 @@ impl FromStr for Axis {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, Hash, Sequence)]
 #[repr(u8)]
 pub enum HDir {
     YPos,
@@ -432,77 +478,83 @@ impl Polyline {
 }
 
 impl Polygon {
-    // Test code (not working):
-    /*
-    let test_polygon = Polygon(vec![
-        Vec2(-10, -10),
-        Vec2(0, 10),
-        Vec2(10, -10),
-        Vec2(2, -4),
-        Vec2(-3, 0),
-    ]);
-
-    for column in test_polygon.border() {
-        world[column.at_height(91)] = Block::Debug(1)
-    }
-    for column in test_polygon.iter() {
-        world[column.at_height(90)] = Block::Debug(0);
-    }
-    */
-
-    // TODO important: Make sure this doesn't include borders! (also add version that does)
+    /// Whether the center of the column lies inside. Columns the outline passes through
+    /// exactly don't count, but [`Polygon::border`] can be used to include them.
     pub fn contains(&self, column: IVec2) -> bool {
         let mut inside = false;
-
-        // Cast ray in x+ direction
-        // Iterate through edges, check if we cross them
-        for i in 0..self.0.len() {
-            let line_start = self.0[i];
-            let line_end = self.0[(i + 1) % self.0.len()];
-            // Todo: fix corners
-            if (line_start.y <= column.y) & (line_end.y > column.y)
-                | (line_start.y >= column.y) & (line_end.y < column.y)
+        for (start, end) in self.segments() {
+            let (along, to_column) = (end - start, column - start);
+            if (along.x * to_column.y == along.y * to_column.x)
+                & (0..=along.length_squared()).contains(&along.dot(to_column))
             {
-                // Calculate possible intersection
-                let angle = (line_end.y - line_start.y) as f32 / (line_end.x - line_start.x) as f32;
-                let x = line_start.x as f32 + ((column.y - line_start.y) as f32 / angle + 0.5);
-                if inside {
-                    if x >= column.x as f32 {
-                        inside = false;
-                    }
-                } else if x > column.x as f32 + 0.5 {
-                    inside = true;
+                return false;
+            }
+            // Cast ray in x+ direction. Vertices only count for the segment they're the lower
+            // end of, so the ray doesn't cross twice at a corner.
+            if (start.y > column.y) != (end.y > column.y) {
+                let x =
+                    start.x as f32 + (column.y - start.y) as f32 * along.x as f32 / along.y as f32;
+                if x > column.x as f32 {
+                    inside ^= true;
                 }
-            } else if (line_start.y == column.y) & (line_end.y == column.y) {
-                if (line_start.x <= column.x) & (line_end.x >= column.x)
-                    | (line_end.x <= column.x) & (line_start.x >= column.x)
+            }
+        }
+        inside
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.0.iter().fold(
+            Rect {
+                min: self.0[0],
+                max: self.0[0],
+            },
+            |bounds, &column| Rect {
+                min: bounds.min.min(column),
+                max: bounds.max.max(column),
+            },
+        )
+    }
+
+    /// Smallest convex polygon containing all the points, counterclockwise
+    pub fn convex_hull(points: impl IntoIterator<Item = IVec2>) -> Polygon {
+        let mut points: Vec<IVec2> = points.into_iter().collect();
+        points.sort_by_key(|point| (point.x, point.y));
+        points.dedup();
+        if points.len() < 3 {
+            return Polygon(points);
+        }
+        let turns_left = |a: IVec2, b: IVec2, c: IVec2| {
+            let (ab, ac) = (b - a, c - a);
+            ab.x * ac.y - ab.y * ac.x > 0
+        };
+        // Andrew's monotone chain: lower half from left to right, then upper half back
+        let mut hull: Vec<IVec2> = Vec::new();
+        for pass in [
+            &points[..],
+            &points.iter().rev().copied().collect::<Vec<_>>()[..],
+        ] {
+            let base = hull.len();
+            for &point in pass {
+                while hull.len() >= base + 2
+                    && !turns_left(hull[hull.len() - 2], hull[hull.len() - 1], point)
                 {
-                    return false;
-                } else {
-                    // Todo: what if there are multiple segments right after another on the same z coord?
-                    let before = self.0[(i - 1) % self.0.len()];
-                    let after = self.0[(i + 2) % self.0.len()];
-                    if before.y.signum() != after.y.signum() {
-                        inside ^= true;
-                    }
+                    hull.pop();
                 }
+                hull.push(point);
             }
+            // The last point is the first one of the other half
+            hull.pop();
         }
-
-        inside
+        Polygon(hull)
     }
 
+    /// Columns inside the polygon, see [`Polygon::contains`]
     pub fn iter(&self) -> PolygonIterator {
-        let mut min = self.0[0];
-        let mut max = self.0[0];
-        for column in self.0.iter() {
-            min = min.min(*column);
-            max = max.max(*column);
-        }
+        let bounds = self.bounds();
         PolygonIterator {
             polygon: self,
-            bounds: Rect { min, max },
-            current: min,
+            bounds,
+            current: bounds.min,
         }
     }
 
@@ -528,6 +580,88 @@ impl Polygon {
     }
 }
 
+/// Arbitrary set of columns, for shapes that rects and polygons can't describe on their own:
+/// L-shaped buildings, plots with a courtyard carved out, …
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Footprint(pub HashSet<IVec2>);
+
+impl Footprint {
+    pub fn contains(&self, column: IVec2) -> bool {
+        self.0.contains(&column)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// `None` if empty
+    pub fn bounds(&self) -> Option<Rect> {
+        let mut columns = self.iter();
+        let first = columns.next()?;
+        Some(columns.fold(
+            Rect {
+                min: first,
+                max: first,
+            },
+            |bounds, column| Rect {
+                min: bounds.min.min(column),
+                max: bounds.max.max(column),
+            },
+        ))
+    }
+
+    pub fn overlaps(&self, other: &Footprint) -> bool {
+        let (smaller, larger) = if self.0.len() < other.0.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        smaller.iter().any(|column| larger.contains(column))
+    }
+
+    pub fn intersection(&self, other: &Footprint) -> Footprint {
+        self.iter()
+            .filter(|&column| other.contains(column))
+            .collect()
+    }
+
+    pub fn union(&self, other: &Footprint) -> Footprint {
+        self.iter().chain(other.iter()).collect()
+    }
+
+    pub fn subtract(&self, other: &Footprint) -> Footprint {
+        self.iter()
+            .filter(|&column| !other.contains(column))
+            .collect()
+    }
+}
+
+impl FromIterator<IVec2> for Footprint {
+    fn from_iter<T: IntoIterator<Item = IVec2>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl From<Rect> for Footprint {
+    fn from(rect: Rect) -> Self {
+        rect.into_iter().collect()
+    }
+}
+
+/// Includes the outline
+impl From<&Polygon> for Footprint {
+    fn from(polygon: &Polygon) -> Self {
+        polygon
+            .iter()
+            .chain(polygon.border(LineStyle::Thick))
+            .collect()
+    }
+}
+
 pub struct PolygonIterator<'a> {
     polygon: &'a Polygon,
     bounds: Rect, // We can't use Rect::iter() here :(
@@ -736,9 +870,9 @@ impl Iterator for ColumnLineIter {
         if (self.prev.x != next.x) & (self.prev.y != next.y) {
             let interpol = if self.style == LineStyle::ThickWobbly {
                 if rand() {
-                    ivec2(self.prev.x, next.x)
+                    ivec2(self.prev.x, next.y)
                 } else {
-                    ivec2(next.x, self.prev.x)
+                    ivec2(next.x, self.prev.y)
                 }
             } else if self.prev.x < next.x {
                 ivec2(self.prev.x, next.y)
@@ -772,3 +906,35 @@ impl ColumnLineIter {
         }
     }
 }
+
+/// Columns whose centers are within the ellipse with the given radii along x and y
+pub fn ellipse(center: Vec2, radii: Vec2) -> impl Iterator<Item = IVec2> {
+    let bounds = Rect {
+        min: (center - radii).floor().as_ivec2(),
+        max: (center + radii).ceil().as_ivec2(),
+    };
+    bounds
+        .into_iter()
+        .filter(move |column| ((column.as_vec2() - center) / radii).length_squared() <= 1.)
+}
+
+pub fn disc(center: Vec2, radius: f32) -> impl Iterator<Item = IVec2> {
+    ellipse(center, Vec2::splat(radius))
+}
+
+/// Columns of the ellipse that are next to a column outside of it, connected without gaps
+pub fn ellipse_border(center: Vec2, radii: Vec2) -> impl Iterator<Item = IVec2> {
+    let inside = move |column: IVec2| ((column.as_vec2() - center) / radii).length_squared() <= 1.;
+    ellipse(center, radii)
+        .filter(move |&column| NEIGHBORS_2D.iter().any(|off| !inside(column + *off)))
+}
+
+/// Bresenham line in 3D, including both ends
+pub fn line_3d(start: IVec3, end: IVec3) -> impl Iterator<Item = IVec3> {
+    let diff = end - start;
+    let steps = diff.abs().max_element();
+    // Exact rounding of start + diff * i / steps, without floats
+    (0..=steps).map(move |i| {
+        start + (diff * 2 * i + IVec3::splat(steps)).div_euclid(IVec3::splat(2 * steps.max(1)))
+    })
+}
@@ -1,28 +1,105 @@
-use crate::{sim::PlaceList, *};
+use std::sync::LazyLock;
+
+use crate::{
+    sim::{HeldTool, PlaceList},
+    *,
+};
 use bevy_derive::{Deref, DerefMut};
 use bevy_ecs::prelude::*;
+use enum_iterator::{all, Sequence};
+use serde::{Deserialize, Serialize};
 
 // Material for construction
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize, Deserialize, Sequence)]
+#[serde(rename_all = "snake_case")]
 pub enum Good {
     Stone,
     Wood,
     Soil,
     Brick,
+    Tools,
+    Vegetables,
 }
 
 impl Good {
-    pub fn display_as_block(self) -> Block {
+    /// None for goods that aren't shown as a block, see [`Self::display_item`]
+    pub fn display_as_block(self) -> Option<Block> {
         match self {
-            Self::Stone => Full(Cobble),
-            Self::Wood => Full(Wood(Oak)),
-            Self::Soil => PackedMud,
-            Self::Brick => Full(Brick),
+            Self::Stone => Some(Full(Cobble)),
+            Self::Wood => Some(Full(Wood(Oak))),
+            Self::Soil => Some(PackedMud),
+            Self::Brick => Some(Full(Brick)),
+            Self::Tools => None,
+            Self::Vegetables => Some(GroundPlant(Pumpkin)),
+        }
+    }
+
+    /// Item shown for the good as SNBT, e.g. when a villager carries it
+    pub fn display_item(self, unknown: &UnknownBlocks) -> String {
+        match self.display_as_block() {
+            Some(block) => block.blockstate(unknown).item_snbt(),
+            None => format!("{{id:\"{}\",Count:1}}", HeldTool::Pickaxe.item()),
         }
     }
+
+    pub fn info(self) -> &'static GoodInfo {
+        &ECONOMY.goods[&self]
+    }
+
+    /// How much fits into one slot of a villager's [`Inventory`]
+    pub fn stack_size(self) -> f32 {
+        self.info().stack_size
+    }
+}
+
+/// Properties of a [`Good`]. The goods themselves are fixed in code, goods.json can only
+/// tune these
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GoodInfo {
+    pub stack_size: f32,
+    /// Fraction lost per tick while stored
+    #[serde(default)]
+    pub decay: f32,
 }
 
-#[derive(Copy, Clone)]
+/// Converts goods into other goods at a workplace
+#[derive(Debug, Deserialize)]
+pub struct Recipe {
+    pub inputs: Vec<Stack>,
+    pub output: Stack,
+    pub ticks: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Economy {
+    pub goods: HashMap<Good, GoodInfo>,
+    pub recipes: HashMap<String, Recipe>,
+}
+
+impl Economy {
+    pub fn recipe(&self, name: &str) -> &Recipe {
+        self.recipes
+            .get(name)
+            .unwrap_or_else(|| panic!("Missing recipe {name}"))
+    }
+}
+
+/// Embedded, so it doesn't depend on the working directory. Panics when it doesn't describe
+/// every good (since it is not specified by the user) or names one that doesn't exist
+pub static ECONOMY: LazyLock<Economy> = LazyLock::new(|| {
+    let economy: Economy = serde_json::from_str(include_str!("../goods.json"))
+        .unwrap_or_else(|err| panic!("Invalid goods.json: {err}"));
+    for good in all::<Good>() {
+        assert!(
+            economy.goods.contains_key(&good),
+            "goods.json is missing {good:?}"
+        );
+    }
+    economy
+});
+
+#[derive(Copy, Clone, Deserialize)]
 pub struct Stack {
     pub kind: Good,
     pub amount: f32,
@@ -32,6 +109,11 @@ impl Stack {
     pub fn new(kind: Good, amount: f32) -> Self {
         Self { kind, amount }
     }
+
+    /// In full stacks, see [`Inventory`]
+    pub fn weight(self) -> f32 {
+        self.amount / self.kind.stack_size()
+    }
 }
 
 impl std::fmt::Debug for Stack {
@@ -46,7 +128,102 @@ impl std::fmt::Display for Stack {
     }
 }
 
-pub const CARRY_CAPACITY: f32 = 64.;
+/// Goods a villager carries: Each good takes up a slot holding up to its stack size, and all
+/// together can't weigh more than `CAPACITY` full stacks
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    stacks: Vec<Stack>,
+}
+
+impl Inventory {
+    pub const SLOTS: usize = 3;
+    pub const CAPACITY: f32 = 2.;
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Stack> + '_ {
+        self.stacks.iter().copied()
+    }
+
+    /// Heaviest stack, the one that's visible
+    pub fn main(&self) -> Option<Stack> {
+        self.iter().max_by(|a, b| a.weight().total_cmp(&b.weight()))
+    }
+
+    pub fn get(&self, good: Good) -> Option<Stack> {
+        self.iter().find(|stack| stack.kind == good)
+    }
+
+    /// Full stacks worth of weight carried
+    pub fn load(&self) -> f32 {
+        self.iter().map(Stack::weight).sum()
+    }
+
+    /// How much more of the good can be carried
+    pub fn room_for(&self, good: Good) -> f32 {
+        let slot = match self.get(good) {
+            Some(stack) => good.stack_size() - stack.amount,
+            None if self.stacks.len() < Self::SLOTS => good.stack_size(),
+            None => 0.,
+        };
+        slot.min((Self::CAPACITY - self.load()) * good.stack_size())
+            .max(0.)
+    }
+
+    /// Doesn't check the limits, those are for planning pickups
+    pub fn add(&mut self, stack: Stack) {
+        match self.stacks.iter_mut().find(|slot| slot.kind == stack.kind) {
+            Some(slot) => slot.amount += stack.amount,
+            None => self.stacks.push(stack),
+        }
+    }
+
+    /// Removes all of the good
+    pub fn take(&mut self, good: Good) -> Option<Stack> {
+        let index = self.stacks.iter().position(|stack| stack.kind == good)?;
+        Some(self.stacks.remove(index))
+    }
+
+    pub fn take_all(&mut self) -> Vec<Stack> {
+        std::mem::take(&mut self.stacks)
+    }
+
+    /// Stacks to pick up from the `available` goods to cover as much of the `requested` ones
+    /// as fits into the inventory, the goods that make up the most weight first. With `only`,
+    /// just that good is considered.
+    pub fn plan_pickup(
+        &self,
+        available: &Goods,
+        requested: &Goods,
+        only: Option<Good>,
+    ) -> Vec<Stack> {
+        let mut wanted: Vec<Stack> = requested
+            .iter()
+            .filter(|(good, _)| only.is_none_or(|only| only == **good))
+            .filter_map(|(&good, &requested)| {
+                let amount = requested.min(available.get(&good).copied().unwrap_or_default());
+                (amount > 0.).then_some(Stack::new(good, amount))
+            })
+            .collect();
+        wanted.sort_by(|a, b| {
+            b.weight()
+                .total_cmp(&a.weight())
+                .then((a.kind as u8).cmp(&(b.kind as u8)))
+        });
+        let mut planned = self.clone();
+        let mut pickup = Vec::new();
+        for stack in wanted {
+            let amount = stack.amount.min(planned.room_for(stack.kind));
+            if amount > 0. {
+                planned.add(Stack::new(stack.kind, amount));
+                pickup.push(Stack::new(stack.kind, amount));
+            }
+        }
+        pickup
+    }
+}
 
 pub fn next_stack(list: &PlaceList) -> Option<Stack> {
     let mut stack: Option<Stack> = None;
@@ -55,10 +232,10 @@ pub fn next_stack(list: &PlaceList) -> Option<Stack> {
             if let Some(stack) = &mut stack {
                 if stack.kind == next.kind {
                     stack.amount += next.amount;
-                    if stack.amount >= CARRY_CAPACITY {
+                    if stack.amount >= stack.kind.stack_size() {
                         return Some(Stack {
                             kind: stack.kind,
-                            amount: CARRY_CAPACITY,
+                            amount: stack.kind.stack_size(),
                         });
                     }
                 } else {
@@ -108,8 +285,11 @@ pub fn goods_for_block(block: Block) -> Option<Stack> {
         Slab(mat, ..) => Some(Stack::new(get_blockmaterial(mat), 0.5)),
         Fence(mat) => Some(Stack::new(get_blockmaterial(mat), 0.5)),
         Barrel => Some(Stack::new(Good::Wood, 1.)),
+        Anvil(..) => Some(Stack::new(Good::Stone, 3.)),
+        Furnace(..) => Some(Stack::new(Good::Stone, 1.)),
         Trapdoor(..) => Some(Stack::new(Good::Wood, 0.25)),
         Door(..) => Some(Stack::new(Good::Wood, 0.25)),
+        Bed(..) => Some(Stack::new(Good::Wood, 0.75)),
         MangroveRoots => Some(Stack::new(Good::Wood, 0.1875)),
         MuddyMangroveRoots => Some(Stack::new(Good::Soil, 0.8125)),
         _ if block.dirtsoil() => Some(Stack::new(Good::Soil, 1.)),
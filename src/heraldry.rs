@@ -0,0 +1,124 @@
+use bevy_ecs::prelude::*;
+use enum_iterator::{all, Sequence};
+use nbt::CompoundTag;
+
+use crate::*;
+
+/// Colors that look good next to each other on a banner, as long as they aren't the same
+const TINCTURES: [Color; 8] = [White, Yellow, Red, Blue, Green, Black, Purple, Orange];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
+pub enum BannerPattern {
+    StripeBottom,
+    StripeTop,
+    StripeLeft,
+    StripeRight,
+    StripeCenter,
+    StripeMiddle,
+    StripeDownright,
+    StripeDownleft,
+    Cross,
+    StraightCross,
+    TriangleBottom,
+    TriangleTop,
+    HalfVertical,
+    HalfHorizontal,
+    Border,
+    Circle,
+    Rhombus,
+    SmallStripes,
+    Flower,
+}
+
+impl BannerPattern {
+    /// Id as stored in the block entity
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::StripeBottom => "bs",
+            Self::StripeTop => "ts",
+            Self::StripeLeft => "ls",
+            Self::StripeRight => "rs",
+            Self::StripeCenter => "cs",
+            Self::StripeMiddle => "ms",
+            Self::StripeDownright => "drs",
+            Self::StripeDownleft => "dls",
+            Self::Cross => "cr",
+            Self::StraightCross => "sc",
+            Self::TriangleBottom => "bt",
+            Self::TriangleTop => "tt",
+            Self::HalfVertical => "vh",
+            Self::HalfHorizontal => "hh",
+            Self::Border => "bo",
+            Self::Circle => "mc",
+            Self::Rhombus => "mr",
+            Self::SmallStripes => "ss",
+            Self::Flower => "flo",
+        }
+    }
+}
+
+/// Block entity of a banner with the patterns layered on top of its base color
+pub fn banner_nbt(patterns: &[(BannerPattern, Color)]) -> CompoundTag {
+    let mut nbt = CompoundTag::new();
+    nbt.insert_str("id", "minecraft:banner");
+    nbt.insert_compound_tag_vec(
+        "Patterns",
+        patterns.iter().map(|(pattern, color)| {
+            let mut layer = CompoundTag::new();
+            layer.insert_str("Pattern", pattern.code());
+            layer.insert_i32("Color", *color as i32);
+            layer
+        }),
+    );
+    nbt
+}
+
+/// Banner design of a settlement, shown on its walls, gates and most important building
+#[derive(Component, Debug, Clone, Eq, PartialEq)]
+pub struct TownCrest {
+    pub base: Color,
+    pub patterns: Vec<(BannerPattern, Color)>,
+}
+
+impl TownCrest {
+    /// Random crest that differs from the `existing` ones
+    pub fn unique(existing: &[TownCrest]) -> Self {
+        let patterns: Vec<BannerPattern> = all::<BannerPattern>().collect();
+        loop {
+            let base = *TINCTURES.choose();
+            let crest = Self {
+                base,
+                patterns: (0..rand_range(1..=2))
+                    .map(|_| {
+                        let color = loop {
+                            let color = *TINCTURES.choose();
+                            if color != base {
+                                break color;
+                            }
+                        };
+                        (*patterns.choose(), color)
+                    })
+                    .collect(),
+            };
+            if !existing.contains(&crest) {
+                return crest;
+            }
+        }
+    }
+
+    pub fn nbt(&self) -> CompoundTag {
+        banner_nbt(&self.patterns)
+    }
+
+    /// Standing banner, see [`Banner`] for the rotation
+    pub fn place(&self, level: &mut (impl WorldView + ?Sized), pos: IVec3, rotation: u8) {
+        level.set(pos, Banner(self.base, rotation));
+        level.set_tile_entity(pos, self.nbt());
+    }
+
+    /// Banner hanging on the side of the block behind it
+    pub fn place_on_wall(&self, level: &mut (impl WorldView + ?Sized), pos: IVec3, facing: HDir) {
+        level.set(pos, WallBanner(facing, self.base));
+        level.set_tile_entity(pos, self.nbt());
+    }
+}
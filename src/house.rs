@@ -1,6 +1,20 @@
-use crate::{remove_foliage::remove_trees, roof::roof, sim::PlaceList, *};
-
-pub fn house(level: &mut Level, area: Rect) -> PlaceList {
+use crate::{
+    floorplan::floorplan,
+    remove_foliage::{remove_giant_mushrooms, remove_trees},
+    roof::roof,
+    sim::PlaceList,
+    *,
+};
+
+const JETTY_LENGTH: i32 = 5;
+/// Space between the water or ground and the deck of stilt houses
+const STILT_CLEARANCE: i32 = 3;
+/// How far apart stilt houses may be to get connected by a bridge
+pub const BRIDGE_RANGE: i32 = 16;
+/// Houses with at least this much space inside get interior walls
+const FLOORPLAN_MIN_SIZE: i32 = 7;
+
+pub fn house(level: &mut impl WorldView, area: Rect) -> PlaceList {
     let inner = area.shrink(1);
 
     let (floor, mut rec) = foundation(level, area);
@@ -13,10 +27,24 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
     }
 
     let door_pos = ivec3(rand_range(inner.min.x..=inner.max.x), area.min.y, floor + 1);
-    level(door_pos, Air);
-    level(door_pos + IVec3::Z, Air);
-    level(door_pos.add(HDir::YNeg), Air);
-    level(door_pos.add(HDir::YNeg) + IVec3::Z, Air);
+    level.set(door_pos, Air);
+    level.set(door_pos + IVec3::Z, Air);
+    level.set(door_pos.add(HDir::YNeg), Air);
+    level.set(door_pos.add(HDir::YNeg) + IVec3::Z, Air);
+
+    // Bed in the back corner, away from the door
+    let bed = ivec3(inner.min.x, inner.max.y, floor + 1);
+    place_bed(level, bed - IVec3::Y, YPos, Red);
+
+    if inner.size().min_element() >= FLOORPLAN_MIN_SIZE
+        && let Some(plan) = floorplan(
+            inner,
+            door_pos.truncate() + IVec2::Y,
+            &[bed.truncate(), bed.truncate() - IVec2::Y],
+        )
+    {
+        plan.build(level, floor + 1, 2, Oak);
+    }
 
     let second_floor = floor + 3;
 
@@ -28,7 +56,7 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
 
     for y in [area.min.y, area.max.y] {
         for x in inner.min.x..=inner.max.x {
-            level(
+            level.set(
                 ivec3(x, y, second_floor),
                 Log(Oak, LogType::Normal(Axis::X)),
             )
@@ -36,7 +64,7 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
     }
     for x in [area.min.x, area.max.x] {
         for y in inner.min.y..=inner.max.y {
-            level(
+            level.set(
                 ivec3(x, y, second_floor),
                 Log(Oak, LogType::Normal(Axis::Y)),
             )
@@ -48,17 +76,17 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
     let mut roof_fixup = Vec::new();
     // TODO: Instead return roof height from roof function
     // to avoid issues if another roof is poking in
-    let mut column_till_roof = |level: &mut Level, col: IVec2, block: Block| {
+    let mut column_till_roof = |level: &mut dyn WorldView, col: IVec2, block: Block| {
         for z in second_floor.. {
             let pos = col.extend(z);
-            match level(pos) {
+            match level.get(pos) {
                 Log(..) => (),
-                Full(..) | Slab(_, Bottom) | Stair(_, _, Bottom) => return,
+                Full(..) | Slab(_, Bottom) | Stair(_, _, Bottom, _) => return,
                 Slab(..) | Stair(..) => {
                     roof_fixup.push(pos);
                     return;
                 }
-                _ => level(pos, block),
+                _ => level.set(pos, block),
             }
         }
     };
@@ -82,8 +110,8 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
     'outer: for pos in area.border() {
         for z in second_floor + 1.. {
             let pos = pos.extend(z);
-            match level(pos) {
-                MangroveRoots => level(pos, MuddyMangroveRoots),
+            match level.get(pos) {
+                MangroveRoots => level.set(pos, MuddyMangroveRoots),
                 _ => continue 'outer,
             }
         }
@@ -111,8 +139,8 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
     'outer: for pos in area.border() {
         for z in second_floor + 1.. {
             let pos = pos.extend(z);
-            match level(pos) {
-                MuddyMangroveRoots => level(pos, paint),
+            match level.get(pos) {
+                MuddyMangroveRoots => level.set(pos, paint),
                 _ => continue 'outer,
             }
         }
@@ -122,36 +150,287 @@ pub fn house(level: &mut Level, area: Rect) -> PlaceList {
     rec
 }
 
-pub fn shack(level: &mut Level, area: Rect) -> PlaceList {
+/// Spruce shack with a campfire and a bed, for snowy areas
+pub fn spruce_hut(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let (floor, rec) = foundation(level, area);
+    let mut rec = shack_on(level, area, floor, Spruce, rec);
+
+    let cursor = level.recording_cursor();
+    let door = ivec2(area.center().x, area.min.y);
+    level.set(door.extend(floor + 1), Air);
+    level.set(door.extend(floor + 2), Air);
+    furnish_cold(level, area.shrink(1), floor);
+    rec.extend(level.pop_recording(cursor));
+    rec
+}
+
+/// Dome of snow with an entrance tunnel and a smoke hole above the campfire
+pub fn igloo(level: &mut impl WorldView, area: Rect) -> PlaceList {
     let (floor, mut rec) = foundation(level, area);
 
+    let cursor = level.recording_cursor();
+    let center = area.center_vec2();
+    let radius = area.size().min_element() as f32 / 2.;
+    let snow = Block::from_name("snow_block", &[]);
+    let dome = |distance: f32| (radius * radius - distance * distance).max(0.).sqrt();
+    for column in area {
+        let distance = (column.as_vec2() + Vec2::splat(0.5)).distance(center);
+        if distance > radius {
+            continue;
+        }
+        let top = floor + dome(distance).round() as i32;
+        let inner_top = floor + dome(distance + 1.).round() as i32 - 1;
+        // Smoke hole above the campfire
+        let hole = column == area.center();
+        for z in floor + 1..=top {
+            level.set(
+                column.extend(z),
+                if (z <= inner_top) | hole { Air } else { snow },
+            );
+        }
+    }
+
+    // Entrance tunnel through the dome wall
+    for y in area.min.y..area.center().y {
+        let column = ivec2(area.center().x, y);
+        level.set(column.extend(floor + 1), Air);
+        level.set(column.extend(floor + 2), Air);
+    }
+
+    furnish_cold(level, area.shrink(1), floor);
+    rec.extend(level.pop_recording(cursor));
+    rec
+}
+
+/// Campfire in the middle and a bed along the back
+fn furnish_cold(level: &mut impl WorldView, inner: Rect, floor: i32) {
+    level.set(
+        inner.center().extend(floor + 1),
+        Block::from_name("campfire", &[("lit", "true")]),
+    );
+    let bed = ivec3(inner.min.x, inner.max.y, floor + 1);
+    if level.get(bed) == Air && level.get(bed - IVec3::Y) == Air {
+        place_bed(level, bed - IVec3::Y, YPos, Red);
+    }
+}
+
+/// Position of the head of the bed, if the building has one
+pub fn find_bed(rec: &PlaceList) -> Option<IVec3> {
+    rec.iter()
+        .find(|set| matches!(set.block, Bed(_, _, BedPart::Head)))
+        .map(|set| set.pos)
+}
+
+pub fn shack(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let (floor, rec) = foundation(level, area);
+    shack_on(level, area, floor, Oak, rec)
+}
+
+/// Shack on stilts with a jetty, for mostly-ocean areas
+pub fn fisher_hut(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let (floor, mut rec) = pier_foundation(level, area);
+
+    let cursor = level.recording_cursor();
+    // First column outside of the hut in that direction
+    let edge = |dir: HDir| {
+        let mut col = area.center();
+        while area.contains(col) {
+            col = col + dir;
+        }
+        col
+    };
+    // Boats need deep water, so the jetty leads to wherever it's deepest
+    if let Some(dir) = HDir::ALL
+        .into_iter()
+        .filter(|&dir| level.water_level(edge(dir) + dir).is_some())
+        .max_by_key(|&dir| {
+            let end = edge(dir) + IVec2::from(dir) * (JETTY_LENGTH - 1);
+            if level.area().contains(end) {
+                hydrology::water_depth(level, end)
+            } else {
+                hydrology::water_depth(level, edge(dir) + dir)
+            }
+        })
+    {
+        let mut col = edge(dir);
+        for i in 0..JETTY_LENGTH {
+            if !level.area().contains(col) {
+                break;
+            }
+            level.set(col.extend(floor), Slab(Wood(Oak), Top));
+            if i % 3 == 2 {
+                stilt(level, col, floor - 1, Oak);
+            }
+            col = col + dir;
+        }
+        let end = (col - IVec2::from(dir)).extend(floor + 1);
+        if level.area().contains(end.truncate()) {
+            level.set(end, Barrel);
+        }
+    }
+    rec.extend(level.pop_recording(cursor));
+
+    shack_on(level, area, floor, Oak, rec)
+}
+
+/// Deck height of a stilt house, high enough above the water and the roots
+pub fn stilt_floor(level: &impl WorldView, area: Rect) -> i32 {
+    area.into_iter()
+        .map(|col| level.water_level(col).unwrap_or(level.height(col)))
+        .max()
+        .unwrap()
+        + STILT_CLEARANCE
+}
+
+/// Mangrove shack on stilts above the waterline, with stairs down, for swamps
+pub fn stilt_house(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let floor = stilt_floor(level, area);
+
+    let cursor = level.recording_cursor();
+    remove_trees(level, area.grow(1));
+    remove_giant_mushrooms(level, area.grow(1));
+    for z in (floor + 1..floor + 10).rev() {
+        level.fill_at(area, z, Air)
+    }
+    for col in area.border() {
+        let rel = col - area.min;
+        let corner = (col.x == area.min.x || col.x == area.max.x)
+            & (col.y == area.min.y || col.y == area.max.y);
+        if corner | ((rel.x + rel.y) % 3 == 0) {
+            stilt(level, col, floor - 1, Mangrove);
+        }
+    }
+    level.fill_at(area, floor, Full(Wood(Mangrove)));
+    let rec = level.pop_recording(cursor).into();
+
+    let mut rec = shack_on(level, area, floor, Mangrove, rec);
+
+    let cursor = level.recording_cursor();
+    // Door at the front and stairs down to the ground
+    let door = ivec2(area.center().x, area.min.y);
+    level.set(door.extend(floor + 1), Air);
+    level.set(door.extend(floor + 2), Air);
+    let mut col = door - IVec2::Y;
+    let mut z = floor;
+    while level.area().contains(col) && z > level.water_level(col).unwrap_or(level.height(col)) {
+        level.set(
+            col.extend(z),
+            Stair(Wood(Mangrove), YPos, Bottom, StairShape::Straight),
+        );
+        for z in z + 1..z + 4 {
+            level.set(col.extend(z), Air);
+        }
+        stilt(level, col, z - 1, Mangrove);
+        col -= IVec2::Y;
+        z -= 1;
+    }
+    let bed = ivec3(area.min.x + 1, area.max.y - 1, floor + 1);
+    place_bed(level, bed - IVec3::Y, YPos, Red);
+    rec.extend(level.pop_recording(cursor));
+    rec
+}
+
+/// Bridge of slabs with chains as railings between two stilt houses, through their walls.
+/// Only straight bridges are built, so the areas have to overlap along one axis.
+pub fn rope_bridge(
+    level: &mut impl WorldView,
+    (from, from_floor): (Rect, i32),
+    (to, to_floor): (Rect, i32),
+) -> Option<PlaceList> {
+    if (from_floor - to_floor).abs() > 1 {
+        return None;
+    }
+    let floor = from_floor.min(to_floor);
+    let ((from, from_floor), (to, to_floor)) = if (from.min.x > to.max.x) | (from.min.y > to.max.y)
+    {
+        ((to, to_floor), (from, from_floor))
+    } else {
+        ((from, from_floor), (to, to_floor))
+    };
+    // Direction along the bridge, and the columns where it leaves the houses
+    let overlap = from.overlap(to);
+    let (start, end, dir) = if (from.max.x < to.min.x) & (overlap.size().y > 2) {
+        let y = overlap.center().y;
+        (ivec2(from.max.x, y), ivec2(to.min.x, y), XPos)
+    } else if (from.max.y < to.min.y) & (overlap.size().x > 2) {
+        let x = overlap.center().x;
+        (ivec2(x, from.max.y), ivec2(x, to.min.y), YPos)
+    } else {
+        return None;
+    };
+    let length = (end - start).abs().max_element();
+    if !(2..=BRIDGE_RANGE).contains(&length) {
+        return None;
+    }
+    let side = IVec2::from(dir.rotated(1));
+    let axis = if dir == XPos { "x" } else { "z" };
+    let mut col = start + IVec2::from(dir);
+    while col != end {
+        if level.get(col.extend(floor + 1)) != Air {
+            return None;
+        }
+        col += IVec2::from(dir);
+    }
+
+    let cursor = level.recording_cursor();
+    for (col, floor) in [(start, from_floor), (end, to_floor)] {
+        level.set(col.extend(floor + 1), Air);
+        level.set(col.extend(floor + 2), Air);
+    }
+    let mut col = start + IVec2::from(dir);
+    let mut i = 0;
+    while col != end {
+        level.set(col.extend(floor), Slab(Wood(Mangrove), Top));
+        for rail in [col + side, col - side] {
+            level.set(
+                rail.extend(floor + 1),
+                if i % 4 == 0 {
+                    Fence(Wood(Mangrove))
+                } else {
+                    Block::from_name("chain", &[("axis", axis)])
+                },
+            );
+        }
+        col += IVec2::from(dir);
+        i += 1;
+    }
+    Some(level.pop_recording(cursor).into())
+}
+
+fn shack_on(
+    level: &mut impl WorldView,
+    area: Rect,
+    floor: i32,
+    species: TreeSpecies,
+    mut rec: PlaceList,
+) -> PlaceList {
     // Roof build now so we know how high the walls have to be
-    let roof_rec = roof(level, area.grow(1), floor + 3, Wood(Oak));
+    let roof_rec = roof(level, area.grow(1), floor + 3, Wood(species));
 
     let cursor = level.recording_cursor();
     let mut roof_fixup = Vec::new();
     // TODO: Instead return roof height from roof function
     // to avoid issues if another roof is poking in
-    let mut column_till_roof = |level: &mut Level, col: IVec2, block: Block| {
+    let mut column_till_roof = |level: &mut dyn WorldView, col: IVec2, block: Block| {
         for z in floor + 1.. {
             let pos = col.extend(z);
-            match level(pos) {
+            match level.get(pos) {
                 Log(..) => (),
-                Full(..) | Slab(_, Bottom) | Stair(_, _, Bottom) => return,
+                Full(..) | Slab(_, Bottom) | Stair(_, _, Bottom, _) => return,
                 Slab(..) | Stair(..) => {
                     roof_fixup.push(pos);
                     return;
                 }
-                _ => level(pos, block),
+                _ => level.set(pos, block),
             }
         }
     };
 
-    let wall_mat = if rand() { Cobble } else { Wood(Oak) };
+    let wall_mat = if rand() { Cobble } else { Wood(species) };
 
     if let Wood(_) = wall_mat {
         for pos in area.corners() {
-            column_till_roof(level, pos, Log(Oak, LogType::Normal(Axis::Z)))
+            column_till_roof(level, pos, Log(species, LogType::Normal(Axis::Z)))
         }
     }
 
@@ -163,47 +442,59 @@ pub fn shack(level: &mut Level, area: Rect) -> PlaceList {
     rec.extend(roof_rec);
 
     let cursor = level.recording_cursor();
-    level.fill(roof_fixup, Full(Wood(Oak)));
+    level.fill(roof_fixup, Full(Wood(species)));
 
     rec.extend(level.pop_recording(cursor));
     rec
 }
 
-fn foundation(level: &mut Level, area: Rect) -> (i32, PlaceList) {
+fn foundation(level: &mut impl WorldView, area: Rect) -> (i32, PlaceList) {
+    let flooded = area
+        .into_iter()
+        .filter(|&col| level.water_level(col).is_some())
+        .count();
+    if flooded * 2 > area.total() as usize {
+        return pier_foundation(level, area);
+    }
+
     let floor = level.average_height(area.border()).round() as i32;
 
     let cursor = level.recording_cursor();
     remove_trees(level, area.grow(1));
+    remove_giant_mushrooms(level, area.grow(1));
 
     for z in (floor + 1..floor + 10).rev() {
         level.fill_at(area, z, Air)
     }
     for col in area {
-        for z in ((level.height)(col) + 1..=floor).rev() {
-            level(col, z, Air)
+        for z in (level.height(col) + 1..=floor).rev() {
+            level.set(col.extend(z), Air)
         }
     }
-    let mut rec: PlaceList = level.pop_recording(cursor).collect();
+    let mut rec: PlaceList = level.pop_recording(cursor).into();
     let cursor = level.recording_cursor();
     for col in area.border() {
         // TODO: if ground is too far down, try to make supports against the nearest wall instead
         let mut pos = col.extend(floor);
         loop {
-            if level(pos).solid() & !level(pos).soil() {
+            if level.get(pos).solid() & !level.get(pos).soil() {
                 break;
             }
-            level(pos, Full(Cobble));
-            if NEIGHBORS_2D.iter().all(|dir| level(pos.add(*dir)).solid()) {
+            level.set(pos, Full(Cobble));
+            if NEIGHBORS_2D
+                .iter()
+                .all(|dir| level.get(pos.add(*dir)).solid())
+            {
                 break;
             }
             pos -= IVec3::Z;
         }
     }
     for col in area.shrink(1) {
-        for z in ((level.height)(col) - 1).min(floor)..=floor {
+        for z in (level.height(col) - 1).min(floor)..=floor {
             let pos = col.extend(z);
-            if (!level(pos).solid()) | (level(pos).soil()) {
-                level(pos, PackedMud)
+            if (!level.get(pos).solid()) | (level.get(pos).soil()) {
+                level.set(pos, PackedMud)
             }
         }
     }
@@ -211,3 +502,42 @@ fn foundation(level: &mut Level, area: Rect) -> (i32, PlaceList) {
 
     (floor, rec)
 }
+
+/// Plank deck on log stilts, standing on the sea floor
+fn pier_foundation(level: &mut impl WorldView, area: Rect) -> (i32, PlaceList) {
+    let floor = area
+        .into_iter()
+        .map(|col| level.surface(col).z)
+        .max()
+        .unwrap()
+        + 1;
+
+    let cursor = level.recording_cursor();
+    remove_trees(level, area.grow(1));
+    remove_giant_mushrooms(level, area.grow(1));
+    for z in (floor + 1..floor + 10).rev() {
+        level.fill_at(area, z, Air)
+    }
+    let mut rec: PlaceList = level.pop_recording(cursor).into();
+
+    let cursor = level.recording_cursor();
+    for col in area.border() {
+        let rel = col - area.min;
+        let corner = (col.x == area.min.x || col.x == area.max.x)
+            & (col.y == area.min.y || col.y == area.max.y);
+        if corner | ((rel.x + rel.y) % 3 == 0) {
+            stilt(level, col, floor - 1, Oak);
+        }
+    }
+    level.fill_at(area, floor, Full(Wood(Oak)));
+    rec.extend(level.pop_recording(cursor));
+
+    (floor, rec)
+}
+
+/// Log from `top` down to the ground
+pub fn stilt(level: &mut impl WorldView, col: IVec2, top: i32, species: TreeSpecies) {
+    for z in (level.height(col) + 1..=top).rev() {
+        level.set(col.extend(z), Log(species, LogType::Normal(Axis::Z)))
+    }
+}
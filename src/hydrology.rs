@@ -0,0 +1,214 @@
+use crate::*;
+
+/// Bodies with fewer columns are ponds, not worth distinguishing
+const MIN_SIZE: usize = 16;
+/// Water bodies narrower than this on average count as rivers…
+const MAX_RIVER_WIDTH: f32 = 12.;
+/// …if they're also this many times longer than wide
+const MIN_RIVER_ELONGATION: f32 = 4.;
+/// Radius of the neighborhood used to find the direction of a river
+const FLOW_RADIUS: i32 = 6;
+/// Water at least this deep can be navigated by boats and fits a dock
+pub const DEEP_WATER: i32 = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WaterKind {
+    Ocean,
+    Lake,
+    River,
+    /// Too small to tell
+    Pond,
+}
+
+/// Connected area of water
+#[derive(Debug, Clone)]
+pub struct WaterBody {
+    pub kind: WaterKind,
+    pub columns: Vec<IVec2>,
+    /// Dry columns next to the water
+    pub banks: Vec<IVec2>,
+    pub bounds: Rect,
+}
+
+/// Water of the level, segmented into oceans, lakes and rivers
+pub struct Hydrology {
+    /// Index of the body each column belongs to
+    body: ColumnMap<Option<u32>>,
+    pub bodies: Vec<WaterBody>,
+}
+
+impl Hydrology {
+    pub fn new(level: &Level) -> Self {
+        let area = level.area();
+        let mut body = level.column_map(1, None);
+        let mut bodies = Vec::new();
+        for column in area {
+            if level.water_level(column).is_none() || body(column).is_some() {
+                continue;
+            }
+            let columns: Vec<IVec2> = level
+                .water
+                .flood_fill(column, |a, b| a.is_some() & b.is_some())
+                .into_iter()
+                .filter(|column| area.contains(*column))
+                .collect();
+            let index = bodies.len() as u32;
+            for &column in &columns {
+                body(column, Some(index));
+            }
+            bodies.push(columns);
+        }
+
+        let bodies = bodies
+            .into_iter()
+            .map(|columns| {
+                let banks: Vec<IVec2> = columns
+                    .iter()
+                    .flat_map(|column| NEIGHBORS_2D.map(|off| *column + off))
+                    .filter(|&bank| area.contains(bank) && level.water_level(bank).is_none())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                let bounds = columns.iter().fold(
+                    Rect {
+                        min: columns[0],
+                        max: columns[0],
+                    },
+                    |bounds, &column| Rect {
+                        min: bounds.min.min(column),
+                        max: bounds.max.max(column),
+                    },
+                );
+                let kind = classify(level, &columns, &banks, bounds);
+                WaterBody {
+                    kind,
+                    columns,
+                    banks,
+                    bounds,
+                }
+            })
+            .collect();
+        Self { body, bodies }
+    }
+
+    /// Body of water at the column, `None` on land
+    pub fn body(&self, column: IVec2) -> Option<&WaterBody> {
+        self.body
+            .get(column)
+            .flatten()
+            .map(|index| &self.bodies[index as usize])
+    }
+
+    pub fn kind(&self, column: IVec2) -> Option<WaterKind> {
+        self.body(column).map(|body| body.kind)
+    }
+
+    /// Direction a river runs in at the column (either way along it), `None` outside of rivers
+    pub fn flow(&self, column: IVec2) -> Option<Vec2> {
+        let body = self.body.get(column).flatten()?;
+        if self.bodies[body as usize].kind != WaterKind::River {
+            return None;
+        }
+        let nearby: Vec<Vec2> = Rect::new_centered(column, IVec2::splat(FLOW_RADIUS * 2 + 1))
+            .into_iter()
+            .filter(|near| self.body.get(*near).flatten() == Some(body))
+            .map(|near| near.as_vec2())
+            .collect();
+        Some(principal_axis(&nearby))
+    }
+
+    /// Shortest straight way over the river or lake at `column` from bank to bank, across the
+    /// flow for rivers. Returns the banks on both sides.
+    pub fn crossing(&self, level: &Level, column: IVec2) -> Option<(IVec2, IVec2)> {
+        let directions: Vec<IVec2> = match self.flow(column) {
+            Some(flow) => vec![if flow.x.abs() > flow.y.abs() {
+                IVec2::Y
+            } else {
+                IVec2::X
+            }],
+            None => vec![IVec2::X, IVec2::Y],
+        };
+        directions
+            .into_iter()
+            .filter_map(|dir| {
+                let bank = |dir: IVec2| {
+                    let mut bank = column;
+                    while level.water_level(bank).is_some() {
+                        bank += dir;
+                        if !level.area().contains(bank) {
+                            return None;
+                        }
+                    }
+                    Some(bank)
+                };
+                Some((bank(dir)?, bank(-dir)?))
+            })
+            .min_by_key(|(a, b)| (a.x - b.x).abs() + (a.y - b.y).abs())
+    }
+
+    /// Narrowest crossing of the river or lake within `radius` of `column`
+    pub fn narrowest_crossing(
+        &self,
+        level: &Level,
+        column: IVec2,
+        radius: i32,
+    ) -> Option<(IVec2, IVec2)> {
+        let body = self.body.get(column).flatten()?;
+        Rect::new_centered(column, IVec2::splat(radius * 2 + 1))
+            .into_iter()
+            .filter(|near| self.body.get(*near).flatten() == Some(body))
+            .filter_map(|near| self.crossing(level, near))
+            .min_by_key(|(a, b)| (a.x - b.x).abs() + (a.y - b.y).abs())
+    }
+}
+
+/// Depth of the water at the column, 0 on land
+pub fn water_depth(level: &(impl WorldView + ?Sized), column: IVec2) -> i32 {
+    level
+        .water_level(column)
+        .map_or(0, |surface| surface - level.height(column))
+}
+
+fn classify(level: &Level, columns: &[IVec2], banks: &[IVec2], bounds: Rect) -> WaterKind {
+    if columns.len() < MIN_SIZE {
+        return WaterKind::Pond;
+    }
+    let biome_share = |biome: Biome| {
+        columns
+            .iter()
+            .filter(|column| (level.biome)(**column) == biome)
+            .count() as f32
+            / columns.len() as f32
+    };
+    let area = level.area();
+    let touches_border = (bounds.min.cmple(area.min) | bounds.max.cmpge(area.max)).any();
+    if biome_share(Biome::Ocean) > 0.5
+        || (touches_border && columns.len() > area.total() as usize / 4)
+    {
+        return WaterKind::Ocean;
+    }
+    // Each column of river has banks on two sides
+    let average_width = 2. * columns.len() as f32 / banks.len().max(1) as f32;
+    let length = bounds.size().max_element() as f32;
+    if biome_share(Biome::River) > 0.5
+        || (average_width < MAX_RIVER_WIDTH && length > average_width * MIN_RIVER_ELONGATION)
+    {
+        WaterKind::River
+    } else {
+        WaterKind::Lake
+    }
+}
+
+/// Direction in which the points are spread out the most
+fn principal_axis(points: &[Vec2]) -> Vec2 {
+    let mean = points.iter().sum::<Vec2>() / points.len() as f32;
+    let (mut xx, mut xy, mut yy) = (0., 0., 0.);
+    for point in points {
+        let offset = *point - mean;
+        xx += offset.x * offset.x;
+        xy += offset.x * offset.y;
+        yy += offset.y * offset.y;
+    }
+    let angle = 0.5 * f32::atan2(2. * xy, xx - yy);
+    Vec2::from_angle(angle)
+}
@@ -0,0 +1,121 @@
+//! Procedural furniture for rooms whose shell is hand-built, see the zone markers of
+//! [`Prefab`](crate::prefab::Prefab). Furniture goes against the walls, so the middle of the
+//! room stays free to walk through.
+
+use crate::*;
+
+/// Kinds of rooms that can be furnished
+pub const ZONE_KINDS: &[&str] = &["bedroom", "kitchen", "storage", "workshop", "library"];
+
+/// Furnishes the room, `zone` being the air above its floor. Doorways are kept clear.
+pub fn furnish(level: &mut impl WorldView, zone: Cuboid, kind: &str) {
+    if kind == "bedroom" {
+        bed(level, zone);
+    }
+    let mut spots = wall_spots(level, zone);
+    match kind {
+        "bedroom" => place(level, &mut spots, |facing| {
+            Block::from_name("chest", &[("facing", facing.to_str())])
+        }),
+        "kitchen" => {
+            place(level, &mut spots, Furnace);
+            place(level, &mut spots, |_| CraftingTable);
+            place(level, &mut spots, |_| Barrel);
+            place(level, &mut spots, |_| Cauldron { water: 3 });
+        }
+        "storage" => {
+            for _ in 0..zone.d2().total() / 4 {
+                place(level, &mut spots, |_| Barrel);
+            }
+        }
+        "workshop" => {
+            place(level, &mut spots, |_| CraftingTable);
+            place(level, &mut spots, |facing| Stonecutter(axis(facing)));
+            place(level, &mut spots, |facing| Anvil(axis(facing)));
+            place(level, &mut spots, |_| Barrel);
+        }
+        "library" => {
+            // A lectern to read at, shelves stacked up to the ceiling along the other walls
+            place(level, &mut spots, |facing| {
+                Block::from_name("lectern", &[("facing", facing.to_str())])
+            });
+            for _ in 0..zone.d2().total() / 3 {
+                let Some((pos, _)) = spots.pop() else {
+                    break;
+                };
+                for z in pos.z..=zone.max.z.min(pos.z + 2) {
+                    level.set(pos.truncate().extend(z), Block::from_name("bookshelf", &[]));
+                }
+            }
+        }
+        _ => unreachable!("Unknown zone kind {kind}"),
+    }
+}
+
+/// Puts the block on a random one of the spots, if there are any left
+fn place(
+    level: &mut impl WorldView,
+    spots: &mut Vec<(IVec3, HDir)>,
+    block: impl Fn(HDir) -> Block,
+) {
+    if spots.is_empty() {
+        return;
+    }
+    let (pos, facing) = spots.swap_remove(rand_range(0..spots.len()));
+    level.set(pos, block(facing));
+}
+
+/// Free spots on the floor next to a wall, facing away from it
+fn wall_spots(level: &impl WorldView, zone: Cuboid) -> Vec<(IVec3, HDir)> {
+    let z = zone.min.z;
+    let mut spots = Vec::new();
+    for column in zone.d2() {
+        let pos = column.extend(z);
+        if (level.get(pos) != Air) | !level.get(pos - IVec3::Z).solid() {
+            continue;
+        }
+        let next_to_door = HDir::ALL
+            .into_iter()
+            .any(|dir| matches!(level.get(pos + IVec3::from(dir)), Door(..)));
+        if next_to_door {
+            continue;
+        }
+        if let Some(wall) = HDir::ALL
+            .into_iter()
+            .find(|dir| level.get(pos + IVec3::from(*dir)).solid())
+        {
+            spots.push((pos, wall.rotated(2)));
+        }
+    }
+    spots
+}
+
+/// Bed with its head against a wall, if there's room for one
+fn bed(level: &mut impl WorldView, zone: Cuboid) {
+    let z = zone.min.z;
+    for head in zone.d2() {
+        let head = head.extend(z);
+        for dir in HDir::ALL {
+            let foot = head - IVec3::from(dir);
+            if free(&*level, head)
+                && free(&*level, foot)
+                && zone.d2().contains(foot.truncate())
+                && level.get(head + IVec3::from(dir)).solid()
+            {
+                place_bed(level, foot, dir, *[Red, White, LightBlue, Lime].choose());
+                return;
+            }
+        }
+    }
+}
+
+fn free(level: &impl WorldView, pos: IVec3) -> bool {
+    (level.get(pos) == Air) & level.get(pos - IVec3::Z).solid()
+}
+
+fn axis(facing: HDir) -> HAxis {
+    match facing {
+        XPos | XNeg => HAxis::X,
+        YPos | YNeg => HAxis::Y,
+    }
+}
@@ -0,0 +1,60 @@
+//! Makes sure farmland stays farmland once players load the world: Minecraft turns it back
+//! into dirt unless there's water at most 4 blocks away horizontally, at the same height or
+//! one above.
+
+use bevy_ecs::prelude::*;
+
+use crate::*;
+
+const HYDRATION_RANGE: i32 = 4;
+
+fn hydrated(level: &impl WorldView, pos: IVec3) -> bool {
+    (-HYDRATION_RANGE..=HYDRATION_RANGE).any(|dx| {
+        (-HYDRATION_RANGE..=HYDRATION_RANGE).any(|dy| {
+            (0..=1).any(|dz| {
+                let pos = pos + ivec3(dx, dy, dz);
+                level.area().contains(pos.truncate()) && level.get(pos) == Water
+            })
+        })
+    })
+}
+
+/// Pass for [`sim::passes::Phase::Finish`] that waters all dry farmland. The water sources get
+/// hidden in the fields under trapdoors, each one placed where it waters the most dry blocks.
+pub fn irrigate(level: &mut Level, _world: &mut World) {
+    let mut dry: HashSet<IVec3> = level
+        .area()
+        .into_iter()
+        .map(|column| level.ground(column))
+        .filter(|&pos| (level.get(pos) == Farmland) && !hydrated(&*level, pos))
+        .collect();
+    let mut sources = 0;
+    while let Some(&start) = dry.iter().next() {
+        let watered = |pos: IVec3| {
+            dry.iter()
+                .filter(|other| {
+                    (other.z == pos.z) & ((**other - pos).abs().max_element() <= HYDRATION_RANGE)
+                })
+                .count()
+        };
+        // Any block within range of the dry one will water it, pick the best of those
+        let source = dry
+            .iter()
+            .copied()
+            .filter(|pos| {
+                (pos.z == start.z) & ((*pos - start).abs().max_element() <= HYDRATION_RANGE)
+            })
+            .max_by_key(|&pos| watered(pos))
+            .unwrap();
+        level.set(source, Water);
+        level.set(source + IVec3::Z, Trapdoor(Oak, YPos, DoorMeta::empty()));
+        dry.retain(|pos| {
+            (pos.z != source.z) | ((*pos - source).abs().max_element() > HYDRATION_RANGE)
+        });
+        dry.remove(&source);
+        sources += 1;
+    }
+    if sources > 0 {
+        println!("Irrigated farmland with {sources} hidden water sources");
+    }
+}
@@ -0,0 +1,263 @@
+//! Reading and writing of Minecraft's region files (`r.<x>.<z>.mca`). Each region holds
+//! 32×32 chunks, stored as compressed NBT in 4 KiB sectors after a two-sector header of
+//! chunk locations and timestamps.
+//!
+//! Chunks whose compressed data doesn't fit into 255 sectors (1 MiB) are stored in a separate
+//! `c.<x>.<z>.mcc` file next to the region, like Minecraft does.
+//!
+//! Regions are locked individually, so chunks in different regions can be written in
+//! parallel.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::ZlibEncoder,
+    Compression,
+};
+use nbt::CompoundTag;
+
+use super::*;
+
+const SECTOR: usize = 4096;
+const HEADER_SECTORS: usize = 2;
+/// The sector count of a chunk is stored in a single byte
+const MAX_CHUNK_SECTORS: usize = 255;
+
+const GZIP: u8 = 1;
+const ZLIB: u8 = 2;
+const UNCOMPRESSED: u8 = 3;
+/// Flag on the compression type for chunks stored in an .mcc file
+const EXTERNAL: u8 = 128;
+
+/// Open region files by region coordinates, and whether they're open for writing
+type OpenRegions = HashMap<(i32, i32), (Arc<Mutex<RegionFile>>, bool)>;
+
+/// Folder of region files, such as `region` or `entities` in a save
+pub struct RegionFolder {
+    path: PathBuf,
+    regions: Mutex<OpenRegions>,
+}
+
+impl RegionFolder {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            regions: default(),
+        }
+    }
+
+    /// `None` if the chunk hasn't been generated
+    pub fn read_chunk(&self, chunk: ChunkIndex) -> Result<Option<CompoundTag>> {
+        let Some(region) = self.region(chunk, false)? else {
+            return Ok(None);
+        };
+        let payload = region.lock().unwrap().read(local_index(chunk))?;
+        let (compression, data) = match payload {
+            None => return Ok(None),
+            Some((compression, _)) if compression & EXTERNAL != 0 => (
+                compression & !EXTERNAL,
+                std::fs::read(self.external_path(chunk))?,
+            ),
+            Some(payload) => payload,
+        };
+        let nbt = match compression {
+            GZIP => nbt::decode::read_compound_tag(&mut GzDecoder::new(&data[..])),
+            ZLIB => nbt::decode::read_compound_tag(&mut ZlibDecoder::new(&data[..])),
+            UNCOMPRESSED => nbt::decode::read_compound_tag(&mut &data[..]),
+            other => bail!("Unsupported chunk compression {other}"),
+        };
+        nbt.map(Some)
+            .map_err(|err| anyhow!("Invalid chunk NBT: {err:?}"))
+    }
+
+    pub fn write_chunk(&self, chunk: ChunkIndex, nbt: &CompoundTag) -> Result<()> {
+        // Compress before locking the region, that's the expensive part
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        nbt::encode::write_compound_tag(&mut encoder, nbt)?;
+        let data = encoder.finish()?;
+        let region = self.region(chunk, true)?.unwrap();
+        let mut region = region.lock().unwrap();
+        let external = self.external_path(chunk);
+        if (data.len() + 5).div_ceil(SECTOR) > MAX_CHUNK_SECTORS {
+            let tmp = external.with_extension("mcc.tmp");
+            std::fs::write(&tmp, &data)?;
+            std::fs::rename(tmp, external)?;
+            region.write(local_index(chunk), ZLIB | EXTERNAL, &[])
+        } else {
+            region.write(local_index(chunk), ZLIB, &data)?;
+            // The chunk might have been oversized before
+            if external.exists() {
+                std::fs::remove_file(external)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn external_path(&self, chunk: ChunkIndex) -> PathBuf {
+        self.path.join(format!("c.{}.{}.mcc", chunk.0, chunk.1))
+    }
+
+    /// Opens the region containing the chunk. Regions are only opened for writing (and created
+    /// if needed) if `write` is set, so reading never touches the files.
+    fn region(&self, chunk: ChunkIndex, write: bool) -> Result<Option<Arc<Mutex<RegionFile>>>> {
+        let key = (chunk.0.div_euclid(32), chunk.1.div_euclid(32));
+        let mut regions = self.regions.lock().unwrap();
+        if let Some((region, writable)) = regions.get(&key)
+            && (*writable || !write)
+        {
+            return Ok(Some(region.clone()));
+        }
+        let path = self.path.join(format!("r.{}.{}.mca", key.0, key.1));
+        if !write && !path.exists() {
+            return Ok(None);
+        }
+        if write {
+            std::fs::create_dir_all(&self.path)?;
+        }
+        let region = Arc::new(Mutex::new(RegionFile::open(path, write)?));
+        regions.insert(key, (region.clone(), write));
+        Ok(Some(region))
+    }
+}
+
+/// Index of the chunk within its region
+fn local_index(chunk: ChunkIndex) -> usize {
+    (chunk.0.rem_euclid(32) + chunk.1.rem_euclid(32) * 32) as usize
+}
+
+struct RegionFile {
+    file: File,
+    /// Offset in sectors in the upper three bytes, number of sectors in the lowest byte
+    locations: [u32; 1024],
+    /// Which sectors are taken, including the header
+    used: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Fails on files too short to hold the header instead of overwriting them, those are
+    /// corrupt. Empty files are treated as an empty region.
+    fn open(path: PathBuf, writable: bool) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .create(writable)
+            .truncate(false)
+            .open(&path)?;
+        let mut header = vec![0; SECTOR * HEADER_SECTORS];
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            if writable {
+                file.write_all(&header)?;
+            }
+        } else if len < header.len() {
+            bail!("Region file {path:?} is too short for its header, {len} bytes");
+        } else {
+            file.read_exact(&mut header)?;
+        }
+        let mut locations = [0; 1024];
+        for (location, bytes) in locations.iter_mut().zip(header.chunks_exact(4)) {
+            *location = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        let mut used = vec![false; len.div_ceil(SECTOR).max(HEADER_SECTORS)];
+        used[..HEADER_SECTORS].fill(true);
+        for (offset, count) in locations.iter().filter_map(|&location| sectors(location)) {
+            if used.len() < offset + count {
+                used.resize(offset + count, false);
+            }
+            used[offset..offset + count].fill(true);
+        }
+        Ok(Self {
+            file,
+            locations,
+            used,
+        })
+    }
+
+    /// Compression type and compressed data
+    fn read(&mut self, index: usize) -> Result<Option<(u8, Vec<u8>)>> {
+        let Some((offset, count)) = sectors(self.locations[index]) else {
+            return Ok(None);
+        };
+        self.file.seek(SeekFrom::Start((offset * SECTOR) as u64))?;
+        let mut head = [0; 5];
+        self.file.read_exact(&mut head)?;
+        let length = u32::from_be_bytes(head[..4].try_into().unwrap()) as usize;
+        if (length == 0) | (length + 4 > count * SECTOR) {
+            bail!("Chunk length {length} doesn't fit its {count} sectors");
+        }
+        let mut data = vec![0; length - 1];
+        self.file.read_exact(&mut data)?;
+        Ok(Some((head[4], data)))
+    }
+
+    fn write(&mut self, index: usize, compression: u8, data: &[u8]) -> Result<()> {
+        let length = data.len() + 1;
+        let count = (length + 4).div_ceil(SECTOR);
+        if count > MAX_CHUNK_SECTORS {
+            bail!("Chunk too large for a region file: {length} bytes");
+        }
+        // Reuse the old sectors if the chunk still fits, otherwise find new ones
+        let offset = match sectors(self.locations[index]) {
+            Some((offset, old_count)) if old_count >= count => {
+                self.used[offset + count..offset + old_count].fill(false);
+                offset
+            }
+            old => {
+                if let Some((offset, old_count)) = old {
+                    self.used[offset..offset + old_count].fill(false);
+                }
+                let offset = self.allocate(count);
+                self.used[offset..offset + count].fill(true);
+                offset
+            }
+        };
+
+        let mut sectors = Vec::with_capacity(count * SECTOR);
+        sectors.extend((length as u32).to_be_bytes());
+        sectors.push(compression);
+        sectors.extend(data);
+        sectors.resize(count * SECTOR, 0);
+        self.file.seek(SeekFrom::Start((offset * SECTOR) as u64))?;
+        self.file.write_all(&sectors)?;
+
+        self.locations[index] = ((offset as u32) << 8) | count as u32;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as u32);
+        self.file.seek(SeekFrom::Start(index as u64 * 4))?;
+        self.file.write_all(&self.locations[index].to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// First free run of sectors that is long enough, or the end of the file
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut run = 0;
+        for (i, used) in self.used.iter().enumerate() {
+            run = if *used { 0 } else { run + 1 };
+            if run == count {
+                return i + 1 - count;
+            }
+        }
+        let offset = self.used.len() - run;
+        self.used.resize(offset + count, false);
+        offset
+    }
+}
+
+/// Offset and number of sectors, `None` if the chunk isn't present
+fn sectors(location: u32) -> Option<(usize, usize)> {
+    let offset = (location >> 8) as usize;
+    let count = (location & 0xFF) as usize;
+    (offset >= HEADER_SECTORS && count > 0).then_some((offset, count))
+}
@@ -1,6 +1,9 @@
+use itertools::Itertools;
+use nbt::CompoundTag;
+
 use crate::*;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Biome {
     Basic,
@@ -116,3 +119,92 @@ impl Biome {
         }
     }
 }
+
+/// Biome ids of the 4×4×4 cells of a section, indexed by `y * 16 + z * 4 + x` in Minecraft's
+/// axes (so x, y within the chunk and the height here)
+pub(super) type SectionBiomes = [String; 64];
+
+/// Reads the `biomes` compound of a section. Missing or broken data means plains.
+pub(super) fn unpack_biomes(nbt: Option<&CompoundTag>) -> SectionBiomes {
+    let plains = || std::array::from_fn(|_| "minecraft:plains".to_owned());
+    let Some(palette) = nbt.and_then(|nbt| nbt.get_str_vec("palette").ok()) else {
+        return plains();
+    };
+    if palette.is_empty() {
+        return plains();
+    }
+    let Some(data) = nbt
+        .and_then(|nbt| nbt.get_i64_vec("data").ok())
+        .filter(|_| palette.len() > 1)
+    else {
+        return std::array::from_fn(|_| palette[0].to_string());
+    };
+    let bits_per_index = palette.len().next_power_of_two().ilog2() as usize;
+    let per_long = 64 / bits_per_index;
+    std::array::from_fn(|i| {
+        let index = data.get(i / per_long).map_or(0, |&long| {
+            (long as u64 >> (i % per_long * bits_per_index)) as usize % (1 << bits_per_index)
+        });
+        palette.get(index).unwrap_or(&palette[0]).to_string()
+    })
+}
+
+/// Inverse of [`unpack_biomes`]
+pub(super) fn pack_biomes(biomes: &SectionBiomes) -> CompoundTag {
+    let palette: Vec<&str> = biomes.iter().map(String::as_str).unique().collect();
+    let mut nbt = CompoundTag::new();
+    if palette.len() > 1 {
+        let bits_per_index = palette.len().next_power_of_two().ilog2() as usize;
+        let per_long = 64 / bits_per_index;
+        let mut data = vec![0; 64usize.div_ceil(per_long)];
+        for (i, biome) in biomes.iter().enumerate() {
+            let index = palette.iter().position(|entry| entry == biome).unwrap();
+            data[i / per_long] |= (index << (i % per_long * bits_per_index)) as i64;
+        }
+        nbt.insert_i64_vec("data", data);
+    }
+    nbt.insert_str_vec("palette", palette);
+    nbt
+}
+
+impl Level {
+    /// Sets the biome of the columns in `area` (at 4×4 granularity, from bottom to top of the
+    /// world) when saving, replacing the one Minecraft generated. `id` is a namespaced id such
+    /// as `minecraft:meadow`.
+    ///
+    /// This only affects [`Level::save_chunks`]; the replay needs a `fillbiome` instead.
+    pub fn set_custom_biome(&mut self, area: Rect, id: &str) {
+        let area = area.overlap(self.area());
+        for column in area {
+            let cell = column.div_euclid(IVec2::splat(4));
+            if self.custom_biomes.insert(cell, id.to_owned()).is_none() {
+                let chunk_index = self.chunk_index(column.into());
+                self.dirty_chunks[chunk_index] = true;
+            }
+            (self.biome)(column, Biome::from_id(id));
+        }
+    }
+
+    /// Biomes of a section to be saved: the loaded ones with the custom biomes applied
+    pub(super) fn section_biomes(&self, chunk: ChunkIndex, y_index: usize) -> CompoundTag {
+        let stored = self.stored_biomes[self.chunk_index(chunk) * 24 + y_index].as_ref();
+        let base = chunk.area().min.div_euclid(IVec2::splat(4));
+        let custom = |x: i32, z: i32| {
+            self.custom_biomes
+                .get(&(base + ivec2(x, z)))
+                .map(String::as_str)
+        };
+        if let Some(stored) = stored
+            && !(0..4).any(|z| (0..4).any(|x| custom(x, z).is_some()))
+        {
+            return stored.clone();
+        }
+        let mut biomes = unpack_biomes(stored);
+        for (i, biome) in biomes.iter_mut().enumerate() {
+            if let Some(custom) = custom(i as i32 % 4, i as i32 / 4 % 4) {
+                custom.clone_into(biome);
+            }
+        }
+        pack_biomes(&biomes)
+    }
+}
@@ -3,15 +3,15 @@ use std::{
     cell::RefCell,
     fmt::{Display, Write},
     mem::size_of,
-    str::FromStr,
     sync::{LazyLock, RwLock},
 };
 
 pub use self::GroundPlant::*;
 use crate::{default, geometry::*, HashMap};
-use enum_iterator::Sequence;
+use enum_iterator::{all, Sequence};
 use nbt::CompoundTag;
 use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
 
 pub use Block::*;
 pub use BlockMaterial::*;
@@ -25,8 +25,11 @@ pub enum Block {
     Air,
     Full(BlockMaterial),
     Slab(BlockMaterial, Half),
-    Stair(BlockMaterial, HDir, Half),
+    Stair(BlockMaterial, HDir, Half, StairShape),
     Fence(BlockMaterial),
+    /// Fence or wall with its connections; generators place plain [`Fence`]s, which get
+    /// connected when saving
+    ConnectedFence(BlockMaterial, FenceSides),
     Ladder(HDir),
     Water,
     Lava,
@@ -43,6 +46,8 @@ pub enum Block {
     Log(TreeSpecies, LogType),
     // Store distance from log if not persistent
     Leaves(TreeSpecies, Option<i8>),
+    /// Crop that isn't fully grown yet, with its age
+    GrowingCrop(Crop, u8),
     SmallPlant(SmallPlant),
     TallPlant(TallPlant, Half),
     GroundPlant(GroundPlant),
@@ -56,6 +61,8 @@ pub enum Block {
     Glowstone,
     GlassPane(Option<Color>),
     WallBanner(HDir, Color),
+    /// Standing banner, rotated clockwise from south in sixteenths of a turn
+    Banner(Color, u8),
     Hay,
     Cauldron {
         water: u8,
@@ -71,36 +78,119 @@ pub enum Block {
     Bedrock,
     CraftingTable,
     Stonecutter(HAxis),
-    Other(u16),
+    Anvil(HAxis),
+    Furnace(HDir),
+    LavaCauldron,
+    /// Facing and honey level (0 to 5)
+    Beehive(HDir, u8),
+    /// Natural beehive found on trees; facing and honey level (0 to 5)
+    BeeNest(HDir, u8),
+    /// Fill level, 0 to 8 (ready)
+    Composter(u8),
+    FlowerPot(Option<SmallPlant>),
+    /// Facing from foot to head
+    Bed(Color, HDir, BedPart),
+    Other(UnknownIndex),
 }
 
 const _: () = assert!(size_of::<Block>() == 4);
 
+/// Index into `UnknownBlocks`. 24 bits wide so that `Block` still fits into 4 bytes while
+/// allowing for heavily modded worlds.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UnknownIndex([u8; 3]);
+
+impl UnknownIndex {
+    pub const MAX: usize = (1 << 24) - 1;
+
+    fn new(index: usize) -> Option<Self> {
+        (index <= Self::MAX).then(|| {
+            let [a, b, c, _] = (index as u32).to_le_bytes();
+            Self([a, b, c])
+        })
+    }
+
+    pub fn get(self) -> usize {
+        let [a, b, c] = self.0;
+        u32::from_le_bytes([a, b, c, 0]) as usize
+    }
+}
+
 /// Used to deduplicate unknown blocks.
 /// Does not affect performance but greatly reduced memory usage
 /// (block only 4 bytes, fewer boxes → 1000×1000 fits into 1 gb ).
 #[derive(Default, Clone)]
 pub struct UnknownBlocks {
-    map: HashMap<Blockstate, u16>,
+    map: HashMap<Blockstate, UnknownIndex>,
     states: Vec<Blockstate>,
 }
 
+impl UnknownBlocks {
+    fn register(&mut self, blockstate: Blockstate) -> UnknownIndex {
+        if let Some(&index) = self.map.get(&blockstate) {
+            return index;
+        }
+        let Some(index) = UnknownIndex::new(self.states.len()) else {
+            // Dropping the properties at least keeps the kind of block
+            if !blockstate.1.is_empty() {
+                static WARNED: std::sync::Once = std::sync::Once::new();
+                WARNED.call_once(|| {
+                    eprintln!(
+                        "More than {} different unknown blockstates, ignoring properties of further ones",
+                        UnknownIndex::MAX + 1
+                    )
+                });
+                return self.register(Blockstate(blockstate.0, Vec::new()));
+            }
+            panic!(
+                "More than {} different unknown blocks",
+                UnknownIndex::MAX + 1
+            );
+        };
+        self.map.insert(blockstate.clone(), index);
+        self.states.push(blockstate);
+        index
+    }
+}
+
 pub static UNKNOWN_BLOCKS: LazyLock<RwLock<UnknownBlocks>> = LazyLock::new(default);
 
-pub fn debug_read_unknown(index: u16) -> Blockstate {
-    UNKNOWN_BLOCKS.read().unwrap().states[index as usize].clone()
+/// Reverse of `Block::blockstate`: For each block name, the properties identifying each block
+type KnownBlockstates = HashMap<String, Vec<(Vec<(String, String)>, Block)>>;
+
+static KNOWN_BLOCKSTATES: LazyLock<KnownBlockstates> = LazyLock::new(|| {
+    let mut known = KnownBlockstates::default();
+    let unknown = UnknownBlocks::default();
+    for block in Block::all_known() {
+        let Blockstate(name, props) = block.blockstate(&unknown);
+        known.entry(name.into_owned()).or_default().push((
+            props
+                .into_iter()
+                .map(|(prop, value)| (prop.into_owned(), value.into_owned()))
+                .collect(),
+            block,
+        ));
+    }
+    known
+});
+
+pub fn debug_read_unknown(index: UnknownIndex) -> Blockstate {
+    UNKNOWN_BLOCKS.read().unwrap().states[index.get()].clone()
 }
 
 bitflags::bitflags! {
     #[derive(Copy,Clone, Debug, Eq, PartialEq, Hash)]
     pub struct DoorMeta: u8 {
-        const TOP = 0b01;
-        const OPEN = 0b10;
+        const TOP = 0b0001;
+        const OPEN = 0b0010;
+        /// Doors only; seen from the front, otherwise the hinge is on the left
+        const HINGE_RIGHT = 0b0100;
+        const POWERED = 0b1000;
     }
 }
 
 // TODO: Flatten Normal to make this more pleasant to construct
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 #[repr(u8)]
 pub enum LogType {
     Normal(Axis),
@@ -152,7 +242,7 @@ impl Display for TreeSpecies {
 }
 
 // TODO: remove
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 #[repr(u8)]
 pub enum GroundPlant {
     Sapling(TreeSpecies),
@@ -162,7 +252,7 @@ pub enum GroundPlant {
     Crop(Crop),
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 #[repr(u8)]
 pub enum SmallPlant {
     Grass,
@@ -184,7 +274,14 @@ pub enum SmallPlant {
     Seagrass,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+impl SmallPlant {
+    /// Whether it can be put into a flower pot
+    pub fn pottable(self) -> bool {
+        !matches!(self, SmallPlant::Grass | SmallPlant::Seagrass)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 #[repr(u8)]
 pub enum TallPlant {
     Grass,
@@ -196,7 +293,7 @@ pub enum TallPlant {
     Seagrass,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 #[repr(u8)]
 pub enum Crop {
     Wheat,
@@ -205,8 +302,32 @@ pub enum Crop {
     Beetroot,
 }
 
+impl Crop {
+    /// Age when fully grown, as [`GroundPlant::Crop`]
+    pub fn max_age(self) -> u8 {
+        match self {
+            Crop::Beetroot => 3,
+            _ => 7,
+        }
+    }
+
+    fn blockstate(self, age: u8) -> Blockstate {
+        const AGES: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+        Blockstate(
+            match self {
+                Crop::Wheat => "wheat",
+                Crop::Carrot => "carrots",
+                Crop::Potato => "potatoes",
+                Crop::Beetroot => "beetroots",
+            }
+            .into(),
+            vec![("age".into(), AGES[age as usize].into())],
+        )
+    }
+}
+
 // Note: for dyes, id order is reversed
-#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, Hash, Sequence)]
 #[repr(u8)]
 pub enum Color {
     White,
@@ -254,14 +375,14 @@ impl Display for Color {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 pub enum Half {
     Bottom,
     Top,
 }
 pub use Half::*;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 pub enum BlockMaterial {
     Stone,
     SmoothStone,
@@ -322,7 +443,18 @@ impl BlockMaterial {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+/// Left and right as seen when walking up the stairs. Outer corners have only a quarter raised,
+/// inner corners have three quarters raised.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
+pub enum StairShape {
+    Straight,
+    InnerLeft,
+    InnerRight,
+    OuterLeft,
+    OuterRight,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
 pub enum BellAttachment {
     Floor,
     Ceiling,
@@ -330,6 +462,44 @@ pub enum BellAttachment {
     DoubleWall,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, FromPrimitive, Sequence)]
+#[repr(u8)]
+pub enum WallSide {
+    None,
+    Low,
+    Tall,
+}
+
+/// Connection of a fence or wall to each side, two bits per [`HDir`]. For fences, `Low` and
+/// `Tall` both just mean connected.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FenceSides(u8);
+
+impl FenceSides {
+    pub fn get(self, dir: HDir) -> WallSide {
+        WallSide::from_u8((self.0 >> (dir as u8 * 2)) & 0b11).unwrap()
+    }
+
+    pub fn set(&mut self, dir: HDir, side: WallSide) {
+        let shift = dir as u8 * 2;
+        self.0 = (self.0 & !(0b11 << shift)) | ((side as u8) << shift);
+    }
+
+    /// Walls only have no post in the middle if they continue straight on
+    fn post(self) -> bool {
+        let connected = |dir| self.get(dir) != WallSide::None;
+        let straight = |a, b| connected(a) & connected(b);
+        !((straight(YNeg, YPos) & !connected(XNeg) & !connected(XPos))
+            | (straight(XNeg, XPos) & !connected(YNeg) & !connected(YPos)))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Sequence)]
+pub enum BedPart {
+    Head,
+    Foot,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Blockstate(
     pub Cow<'static, str>,
@@ -372,6 +542,7 @@ impl Block {
                 Wood(species) => format!("{}_planks", species).into(),
                 Brick => "bricks".into(),
                 StoneBrick => "stone_bricks".into(),
+                MossyStonebrick => "mossy_stone_bricks".into(),
                 MudBrick => "mud_bricks".into(),
                 PolishedBlackstoneBrick => "polished_blackstone_bricks".into(),
                 material => material.to_str().into(),
@@ -381,7 +552,7 @@ impl Block {
             Sand => "sand".into(),
             Gravel => "gravel".into(),
             Farmland => "farmland".into(),
-            Path => "grass_path".into(),
+            Path => "dirt_path".into(),
             CoarseDirt => "coarse_dirt".into(),
             Podzol => "podzol".into(),
             SoulSand => "soul_sand".into(),
@@ -408,7 +579,7 @@ impl Block {
                 ),
                 LogType::Stripped(axis) => Blockstate(
                     match species {
-                        Warped | Crimson => format!("{}_stem", species),
+                        Warped | Crimson => format!("stripped_{}_stem", species),
                         _ => format!("stripped_{}_log", species),
                     }
                     .into(),
@@ -469,19 +640,32 @@ impl Block {
                 GroundPlant::Cactus => "cactus".into(),
                 GroundPlant::Reeds => "sugar_cane".into(),
                 GroundPlant::Pumpkin => "pumpkin".into(),
-                GroundPlant::Crop(crop) => match crop {
-                    Crop::Wheat => Blockstate("wheat".into(), vec![("age".into(), "7".into())]),
-                    Crop::Carrot => Blockstate("carrot".into(), vec![("age".into(), "7".into())]),
-                    Crop::Potato => Blockstate("potato".into(), vec![("age".into(), "7".into())]),
-                    Crop::Beetroot => {
-                        Blockstate("beetroot".into(), vec![("age".into(), "3".into())])
-                    }
-                },
+                GroundPlant::Crop(crop) => crop.blockstate(crop.max_age()),
             },
+            GrowingCrop(crop, age) => crop.blockstate(*age),
             Fence(material) => match material {
                 Wood(species) => format!("{}_fence", species).into(),
                 material => format!("{}_wall", material).into(),
             },
+            ConnectedFence(material, sides) => {
+                let Blockstate(name, _) = Fence(*material).blockstate(unknown);
+                let mut props: Vec<_> = all::<HDir>()
+                    .map(|dir| {
+                        let value = match (material, sides.get(dir)) {
+                            (Wood(_), WallSide::None) => "false",
+                            (Wood(_), _) => "true",
+                            (_, WallSide::None) => "none",
+                            (_, WallSide::Low) => "low",
+                            (_, WallSide::Tall) => "tall",
+                        };
+                        (dir.to_str().into(), value.into())
+                    })
+                    .collect();
+                if !matches!(material, Wood(_)) {
+                    props.push(("up".into(), sides.post().to_string().into()));
+                }
+                Blockstate(name, props)
+            }
             Ladder(dir) => Blockstate(
                 "ladder".into(),
                 vec![("facing".into(), dir.to_str().into())],
@@ -506,6 +690,10 @@ impl Block {
                 format!("{}_wall_banner", color).into(),
                 vec![("facing".into(), facing.to_str().into())],
             ),
+            Banner(color, rotation) => Blockstate(
+                format!("{}_banner", color).into(),
+                vec![("rotation".into(), rotation.to_string().into())],
+            ),
             Hay => "hay_block".into(),
             Slab(material, half) => Blockstate(
                 format!("{}_slab", material).into(),
@@ -518,7 +706,7 @@ impl Block {
                     .into(),
                 )],
             ),
-            Stair(material, dir, half) => Blockstate(
+            Stair(material, dir, half, shape) => Blockstate(
                 format!("{}_stairs", material).into(),
                 vec![
                     (
@@ -530,6 +718,17 @@ impl Block {
                         .into(),
                     ),
                     ("facing".into(), dir.to_str().into()),
+                    (
+                        "shape".into(),
+                        match shape {
+                            StairShape::Straight => "straight",
+                            StairShape::InnerLeft => "inner_left",
+                            StairShape::InnerRight => "inner_right",
+                            StairShape::OuterLeft => "outer_left",
+                            StairShape::OuterRight => "outer_right",
+                        }
+                        .into(),
+                    ),
                 ],
             ),
             Cauldron { water } => Blockstate(
@@ -563,6 +762,10 @@ impl Block {
                         "open".into(),
                         format!("{}", meta.contains(DoorMeta::OPEN)).into(),
                     ),
+                    (
+                        "powered".into(),
+                        format!("{}", meta.contains(DoorMeta::POWERED)).into(),
+                    ),
                 ],
             ),
             Door(species, dir, meta) => Blockstate(
@@ -578,10 +781,23 @@ impl Block {
                         }
                         .into(),
                     ),
+                    (
+                        "hinge".into(),
+                        if meta.contains(DoorMeta::HINGE_RIGHT) {
+                            "right"
+                        } else {
+                            "left"
+                        }
+                        .into(),
+                    ),
                     (
                         "open".into(),
                         format!("{}", meta.contains(DoorMeta::OPEN)).into(),
                     ),
+                    (
+                        "powered".into(),
+                        format!("{}", meta.contains(DoorMeta::POWERED)).into(),
+                    ),
                 ],
             ),
             Bell(facing, attachment) => Blockstate(
@@ -640,7 +856,86 @@ impl Block {
                     .into(),
                 )],
             ),
-            Other(index) => unknown.states[*index as usize].clone(), // Unneccesary clone?
+            Anvil(axis) => Blockstate(
+                "anvil".into(),
+                vec![(
+                    "facing".into(),
+                    match axis {
+                        HAxis::X => "south",
+                        HAxis::Y => "east",
+                    }
+                    .into(),
+                )],
+            ),
+            Furnace(facing) => Blockstate(
+                "furnace".into(),
+                vec![
+                    ("facing".into(), facing.to_str().into()),
+                    ("lit".into(), "false".into()),
+                ],
+            ),
+            LavaCauldron => "lava_cauldron".into(),
+            Beehive(facing, honey) | BeeNest(facing, honey) => Blockstate(
+                if matches!(self, Beehive(..)) {
+                    "beehive"
+                } else {
+                    "bee_nest"
+                }
+                .into(),
+                vec![
+                    ("facing".into(), facing.to_str().into()),
+                    (
+                        "honey_level".into(),
+                        match honey {
+                            0 => "0".into(),
+                            1 => "1".into(),
+                            2 => "2".into(),
+                            3 => "3".into(),
+                            4 => "4".into(),
+                            5 => "5".into(),
+                            _ => panic!("Honey level {}", honey),
+                        },
+                    ),
+                ],
+            ),
+            Composter(level) => Blockstate(
+                "composter".into(),
+                vec![(
+                    "level".into(),
+                    match level {
+                        0 => "0".into(),
+                        1 => "1".into(),
+                        2 => "2".into(),
+                        3 => "3".into(),
+                        4 => "4".into(),
+                        5 => "5".into(),
+                        6 => "6".into(),
+                        7 => "7".into(),
+                        8 => "8".into(),
+                        _ => panic!("Composter level {}", level),
+                    },
+                )],
+            ),
+            FlowerPot(None) => "flower_pot".into(),
+            FlowerPot(Some(plant)) => {
+                format!("potted_{}", SmallPlant(*plant).blockstate(unknown).0).into()
+            }
+            Bed(color, facing, part) => Blockstate(
+                format!("{}_bed", color).into(),
+                vec![
+                    ("facing".into(), facing.to_str().into()),
+                    ("occupied".into(), "false".into()),
+                    (
+                        "part".into(),
+                        match part {
+                            BedPart::Head => "head",
+                            BedPart::Foot => "foot",
+                        }
+                        .into(),
+                    ),
+                ],
+            ),
+            Other(index) => unknown.states[index.get()].clone(), // Unneccesary clone?
         }
     }
 
@@ -651,11 +946,26 @@ impl Block {
                 nbt.insert_str("id", "bell");
                 Some(nbt)
             }
-            WallBanner(..) => {
+            WallBanner(..) | Banner(..) => {
                 let mut nbt = CompoundTag::new();
                 nbt.insert_str("id", "banner");
                 Some(nbt)
             }
+            Furnace(..) => {
+                let mut nbt = CompoundTag::new();
+                nbt.insert_str("id", "furnace");
+                Some(nbt)
+            }
+            Beehive(..) | BeeNest(..) => {
+                let mut nbt = CompoundTag::new();
+                nbt.insert_str("id", "beehive");
+                Some(nbt)
+            }
+            Bed(..) => {
+                let mut nbt = CompoundTag::new();
+                nbt.insert_str("id", "bed");
+                Some(nbt)
+            }
             _ => None,
         }
         .map(|mut nbt| {
@@ -666,237 +976,175 @@ impl Block {
         })
     }
 
-    /// This is for loading of the structure block format and very much incomplete
-    /// (and panics on invalid blocks)
-    pub fn from_nbt(nbt: &CompoundTag) -> Block {
-        let name = nbt.get_str("Name").expect("Invalid block: no name");
-        let name = name.strip_prefix("minecraft:").unwrap_or(name);
-        let default_props = CompoundTag::new();
-        let props = nbt.get_compound_tag("Properties").unwrap_or(&default_props);
-
-        fn slab(material: BlockMaterial, props: &CompoundTag) -> Block {
-            match props.get_str("type").unwrap() {
-                "top" => Slab(material, Top),
-                "double" => Full(material),
-                _ => Slab(material, Bottom),
+    /// Every block that has its own representation (i.e. isn't `Other`), within the value ranges
+    /// that occur in the game. Tall seagrass is left out since it gets loaded as water, connected
+    /// fences since they get loaded as plain fences.
+    pub fn all_known() -> Vec<Block> {
+        let mut blocks = vec![
+            Air,
+            Water,
+            Lava,
+            Dirt,
+            Grass,
+            Sand,
+            Gravel,
+            Farmland,
+            Path,
+            Podzol,
+            CoarseDirt,
+            SoulSand,
+            PackedMud,
+            Terracotta(None),
+            MushroomStem,
+            MangroveRoots,
+            MuddyMangroveRoots,
+            SmoothQuartz,
+            SnowLayer,
+            Glowstone,
+            GlassPane(None),
+            Hay,
+            Barrel,
+            Barrier,
+            Bedrock,
+            CraftingTable,
+            LavaCauldron,
+            FlowerPot(None),
+        ];
+        for material in all::<BlockMaterial>() {
+            blocks.push(Full(material));
+            blocks.push(Fence(material));
+            for half in all::<Half>() {
+                blocks.push(Slab(material, half));
+                for dir in all::<HDir>() {
+                    blocks
+                        .extend(all::<StairShape>().map(|shape| Stair(material, dir, half, shape)));
+                }
             }
         }
-
-        fn stair(material: BlockMaterial, props: &CompoundTag) -> Block {
-            Stair(
-                material,
-                HDir::from_str(props.get_str("facing").unwrap()).unwrap(),
-                half(props),
-            )
+        for species in all::<TreeSpecies>() {
+            blocks.extend(all::<LogType>().map(|log_type| Log(species, log_type)));
+            blocks.push(Leaves(species, None));
+            blocks.extend((1..=7).map(|distance| Leaves(species, Some(distance))));
+            for dir in all::<HDir>() {
+                for bits in 0..16 {
+                    let meta = DoorMeta::from_bits_truncate(bits);
+                    if !meta.contains(DoorMeta::HINGE_RIGHT) {
+                        blocks.push(Trapdoor(species, dir, meta));
+                    }
+                    blocks.push(Door(species, dir, meta));
+                }
+            }
         }
-
-        fn leaves(species: TreeSpecies, props: &CompoundTag) -> Block {
-            Leaves(
-                species,
-                if props.get_str("persistent").unwrap() == "false" {
-                    Some(props.get_str("distance").unwrap().parse().unwrap())
-                } else {
-                    None
-                },
-            )
+        blocks.extend(all::<SmallPlant>().map(SmallPlant));
+        blocks.extend(
+            all::<SmallPlant>()
+                .filter(|plant| plant.pottable())
+                .map(|plant| FlowerPot(Some(plant))),
+        );
+        blocks.extend((0..=8).map(Composter));
+        for plant in all::<TallPlant>().filter(|plant| *plant != TallPlant::Seagrass) {
+            blocks.extend(all::<Half>().map(|half| TallPlant(plant, half)));
         }
-
-        fn wall_banner(color: Color, props: &CompoundTag) -> Block {
-            WallBanner(
-                HDir::from_str(props.get_str("facing").unwrap()).unwrap(),
-                color,
-            )
+        blocks.extend(all::<GroundPlant>().map(GroundPlant));
+        for crop in all::<Crop>() {
+            blocks.extend((0..crop.max_age()).map(|age| GrowingCrop(crop, age)));
         }
-
-        fn trapdoor(species: TreeSpecies, props: &CompoundTag) -> Block {
-            Trapdoor(
-                species,
-                HDir::from_str(props.get_str("facing").unwrap()).unwrap(),
-                {
-                    let mut meta = DoorMeta::empty();
-                    if props.get_str("half").unwrap() == "top" {
-                        meta |= DoorMeta::TOP;
-                    }
-                    if props.get_str("open").unwrap() == "true" {
-                        meta |= DoorMeta::OPEN;
-                    }
-                    meta
-                },
-            )
+        for color in all::<Color>() {
+            blocks.push(Wool(color));
+            blocks.push(Terracotta(Some(color)));
+            blocks.push(GlassPane(Some(color)));
+            blocks.extend(all::<HDir>().map(|dir| WallBanner(dir, color)));
+            blocks.extend((0..16).map(|rotation| Banner(color, rotation)));
+            for dir in all::<HDir>() {
+                blocks.extend(all::<BedPart>().map(|part| Bed(color, dir, part)));
+            }
         }
-
-        fn door(species: TreeSpecies, props: &CompoundTag) -> Block {
-            Door(
-                species,
-                HDir::from_str(props.get_str("facing").unwrap()).unwrap(),
-                {
-                    let mut meta = DoorMeta::empty();
-                    if props.get_str("half").unwrap() == "upper" {
-                        meta |= DoorMeta::TOP;
-                    }
-                    if props.get_str("open").unwrap() == "true" {
-                        meta |= DoorMeta::OPEN;
-                    }
-                    meta
-                },
-            )
+        blocks.extend((0..=3).map(|water| Cauldron { water }));
+        for dir in all::<HDir>() {
+            blocks.push(Ladder(dir));
+            blocks.push(Furnace(dir));
+            blocks.extend(all::<BellAttachment>().map(|attachment| Bell(dir, attachment)));
+            blocks.extend((1..=4).map(|delay| Repeater(dir, delay)));
+            for honey in 0..=5 {
+                blocks.push(Beehive(dir, honey));
+                blocks.push(BeeNest(dir, honey));
+            }
+        }
+        for axis in all::<HAxis>() {
+            blocks.push(Rail(axis));
+            blocks.push(Stonecutter(axis));
+            blocks.push(Anvil(axis));
         }
+        blocks
+    }
 
-        fn half(props: &CompoundTag) -> Half {
-            if matches!(props.get_str("half").unwrap(), "upper" | "top") {
-                Top
-            } else {
-                Bottom
+    /// For blocks that don't have their own variant, e.g. `from_name("vine", &[("north", "true")])`
+    pub fn from_name(name: &str, properties: &[(&str, &str)]) -> Block {
+        let mut nbt = CompoundTag::new();
+        nbt.insert_str("Name", format!("minecraft:{name}"));
+        if !properties.is_empty() {
+            let mut props = CompoundTag::new();
+            for (key, value) in properties {
+                props.insert_str(*key, *value);
             }
+            nbt.insert("Properties", props);
         }
+        Block::from_nbt(&nbt)
+    }
 
-        fn log_axis(props: &CompoundTag) -> Axis {
-            props.get_str("axis").unwrap().parse().unwrap()
-        }
+    /// Block in command syntax, as written by `Blockstate`'s `Display`, e.g.
+    /// `oak_stairs[facing=north,half=bottom]`
+    pub fn from_blockstate_str(blockstate: &str) -> Block {
+        let (name, properties) = match blockstate.split_once('[') {
+            Some((name, properties)) => (name, properties.trim_end_matches(']')),
+            None => (blockstate, ""),
+        };
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        let properties: Vec<(&str, &str)> = properties
+            .split(',')
+            .filter_map(|property| property.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        Block::from_name(name, &properties)
+    }
 
-        fn known_block(name: &str, props: &CompoundTag) -> Option<Block> {
-            // TODO: expand this
-            Some(match name {
-                "air" | "cave_air" => Air,
-                // Let's ignore flowing water for now, maybe revise later
-                "water" => match props.get_str("level") {
-                    Ok("0") => Water,
+    /// Inverse of `blockstate` (as nbt). Blocks without their own representation get stored as
+    /// `Other`.
+    pub fn from_nbt(nbt: &CompoundTag) -> Block {
+        let name = nbt.get_str("Name").expect("Invalid block: no name");
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        let default_props = CompoundTag::new();
+        let props = nbt.get_compound_tag("Properties").unwrap_or(&default_props);
+
+        // Blockstates that don't map one-to-one
+        match name {
+            "cave_air" | "void_air" => return Air,
+            // Let's ignore flowing water for now, maybe revise later
+            "water" => {
+                return match props.get_str("level") {
+                    Ok("0") | Err(_) => Water,
                     _ => Air,
-                },
-                "tall_seagrass" => Water,
-                "stone" => Full(Stone),
-                "granite" => Full(Granite),
-                "diorite" => Full(Diorite),
-                "andesite" => Full(Andesite),
-                "cobblestone" => Full(Cobble),
-                "bricks" => Full(Brick),
-                "stone_bricks" => Full(StoneBrick),
-                "mud_bricks" => Full(MudBrick),
-                "packed_pud" => PackedMud,
-                "bedrock" => Bedrock,
-                "gravel" => Gravel,
-                "grass_block" => Grass,
-                "sand" => Sand,
-                "dirt" if props.get_str("variant").is_err() => Dirt,
-                "dirt" if matches!(props.get_str("variant"), Ok("coarse_dirt")) => CoarseDirt,
-                "oak_planks" => Full(Wood(Oak)),
-                "oak_log" => Log(Oak, LogType::Normal(log_axis(props))),
-                "spruce_log" => Log(Spruce, LogType::Normal(log_axis(props))),
-                "birch_log" => Log(Birch, LogType::Normal(log_axis(props))),
-                "jungle_log" => Log(Jungle, LogType::Normal(log_axis(props))),
-                "acacia_log" => Log(Acacia, LogType::Normal(log_axis(props))),
-                "dark_oak_log" => Log(DarkOak, LogType::Normal(log_axis(props))),
-                "mangrove_log" => Log(Mangrove, LogType::Normal(log_axis(props))),
-                "cherry_log" => Log(Cherry, LogType::Normal(log_axis(props))),
-                "stripped_oak_log" => Log(Oak, LogType::Stripped(log_axis(props))),
-                "oak_leaves" => leaves(Oak, props),
-                "spruce_leaves" => leaves(Spruce, props),
-                "birch_leaves" => leaves(Birch, props),
-                "jungle_leaves" => leaves(Jungle, props),
-                "acacie_leaves" => leaves(Acacia, props),
-                "dark_oak_leaves" => leaves(DarkOak, props),
-                "azalea_leaves" => leaves(Azalea, props),
-                "flowering_azalea_leaves" => leaves(FloweringAzalea, props),
-                "grass" => SmallPlant(SmallPlant::Grass),
-                "fern" => SmallPlant(SmallPlant::Fern),
-                "dead_bush" => SmallPlant(SmallPlant::DeadBush),
-                "brown_mushroom" => SmallPlant(SmallPlant::BrownMushroom),
-                "red_mushroom" => SmallPlant(SmallPlant::RedMushroom),
-                "dandelion" => SmallPlant(SmallPlant::Dandelion),
-                "poppy" => SmallPlant(SmallPlant::Poppy),
-                "cornflower" => SmallPlant(SmallPlant::Cornflower),
-                "blue_orchid" => SmallPlant(SmallPlant::BlueOrchid),
-                "allium" => SmallPlant(SmallPlant::Allium),
-                "azure_bluet" => SmallPlant(SmallPlant::AzureBluet),
-                "red_tulip" => SmallPlant(SmallPlant::RedTulip),
-                "orange_tulip" => SmallPlant(SmallPlant::OrangeTulip),
-                "white_tulip" => SmallPlant(SmallPlant::WhiteTulip),
-                "pink_tulip" => SmallPlant(SmallPlant::PinkTulip),
-                "oxeye_daisy" => SmallPlant(SmallPlant::OxeyeDaisy),
-                "seagrass" => SmallPlant(SmallPlant::Seagrass),
-                "tall_grass" => TallPlant(TallPlant::Grass, half(props)),
-                "large_fern" => TallPlant(TallPlant::Fern, half(props)),
-                "sunflower" => TallPlant(TallPlant::Sunflower, half(props)),
-                "lilac" => TallPlant(TallPlant::Lilac, half(props)),
-                "rose_bush" => TallPlant(TallPlant::Rose, half(props)),
-                "peony" => TallPlant(TallPlant::Peony, half(props)),
-                // "tall_seagrass" => TallPlant(TallPlant::Seagrass, half(props)),
-                "snow" => SnowLayer, // Todo: store layer
-                "fence" => Fence(Wood(Oak)),
-                "cobblestone_wall" => Fence(MossyCobble),
-                "mossy_cobblestone_wall" => Fence(MossyCobble),
-                "oak_slab" => slab(Wood(Oak), props),
-                "spruce_slab" => slab(Wood(Spruce), props),
-                "birch_slab" => slab(Wood(Birch), props),
-                "jungle_slab" => slab(Wood(Jungle), props),
-                "acacia_slab" => slab(Wood(Acacia), props),
-                "dark_oak_slab" => slab(Wood(DarkOak), props),
-                "cobblestone_slab" => slab(Cobble, props),
-                "mossy_cobblestone_slab" => slab(MossyCobble, props),
-                "stone_brick_slab" => slab(StoneBrick, props),
-                "mossy_stone_brick_slab" => slab(MossyStonebrick, props),
-                "blackstone_slab" => slab(Blackstone, props),
-                "polished_blackstone_slab" => slab(PolishedBlackstone, props),
-                "mud_brick_slab" => slab(MudBrick, props),
-                "oak_stairs" => stair(Wood(Oak), props),
-                "spruce_stairs" => stair(Wood(Spruce), props),
-                "birch_stairs" => stair(Wood(Birch), props),
-                "jungle_stairs" => stair(Wood(Jungle), props),
-                "acacia_stairs" => stair(Wood(Acacia), props),
-                "dark_oak_stairs" => stair(Wood(DarkOak), props),
-                "cobblestone_stairs" => stair(Cobble, props),
-                "stone_brick_stairs" => stair(StoneBrick, props),
-                "blackstone_stairs" => stair(Blackstone, props),
-                "mud_brick_stairs" => stair(MudBrick, props),
-                "terracotta" => Terracotta(None),
-                "mushroom_stem" => MushroomStem,
-                "mangrove_roots" => MangroveRoots,
-                "muddy_mangrove_roots" => MuddyMangroveRoots,
-                "white_terracotta" => Terracotta(Some(White)),
-                "orange_terracotta" => Terracotta(Some(Orange)),
-                "magenta_terracotta" => Terracotta(Some(Magenta)),
-                "light_blue_terracotta" => Terracotta(Some(LightBlue)),
-                "yellow_terracotta" => Terracotta(Some(Yellow)),
-                "lime_terracotta" => Terracotta(Some(Lime)),
-                "pink_terracotta" => Terracotta(Some(Pink)),
-                "gray_terracotta" => Terracotta(Some(Gray)),
-                "light_gray_terracotta" => Terracotta(Some(LightGray)),
-                "cyan_terracotta" => Terracotta(Some(Cyan)),
-                "purple_terracotta" => Terracotta(Some(Purple)),
-                "blue_terracotta" => Terracotta(Some(Blue)),
-                "brown_terracotta" => Terracotta(Some(Brown)),
-                "green_terracotta" => Terracotta(Some(Green)),
-                "red_terracotta" => Terracotta(Some(Red)),
-                "black_terracotta" => Terracotta(Some(Black)),
-                "cauldron" => Cauldron {
-                    water: props.get_str("level").unwrap_or("0").parse().unwrap(),
-                },
-                "barrel" => Barrel,
-                "oak_trapdoor" => trapdoor(Oak, props),
-                "spruce_trapdoor" => trapdoor(Spruce, props),
-                "oak_door" => door(Oak, props),
-                "spruce_door" => door(Spruce, props),
-                "bell" => Bell(
-                    HDir::from_str(props.get_str("facing").unwrap()).unwrap(),
-                    match props.get_str("attachment").unwrap() {
-                        "floor" => BellAttachment::Floor,
-                        "ceiling" => BellAttachment::Ceiling,
-                        "single_wall" => BellAttachment::SingleWall,
-                        _ => BellAttachment::DoubleWall,
-                    },
-                ),
-                "red_wall_banner" => wall_banner(Red, props),
-                "white_wall_banner" => wall_banner(Red, props),
-                "blue_wall_banner" => wall_banner(Red, props),
-                "green_wall_banner" => wall_banner(Red, props),
-                "yellow_wall_banner" => wall_banner(Red, props),
-                "ladder" => Ladder(HDir::from_str(props.get_str("facing").unwrap()).unwrap()),
-                _ => return None,
-            })
+                }
+            }
+            "tall_seagrass" => return Water,
+            "grass_path" => return Path,
+            "snow" => return SnowLayer, // Todo: store layer
+            "cauldron" if props.get_str("level").is_err() => return Cauldron { water: 0 },
+            _ => (),
         }
 
-        if let Some(known) = known_block(name, props) {
-            return known;
+        // Properties not used by the representation (e.g. waterlogged) get ignored
+        if let Some(candidates) = KNOWN_BLOCKSTATES.get(name)
+            && let Some((_, known)) = candidates
+                .iter()
+                .filter(|(state, _)| {
+                    state
+                        .iter()
+                        .all(|(prop, value)| props.get_str(prop).is_ok_and(|v| value == v))
+                })
+                .max_by_key(|(state, _)| state.len())
+        {
+            return *known;
         }
 
         let blockstate = Blockstate(
@@ -921,22 +1169,16 @@ impl Block {
         );
 
         thread_local! {
-            static THREAD_PALETTE: RefCell<HashMap<Blockstate, u16>> = default();
+            static THREAD_PALETTE: RefCell<HashMap<Blockstate, UnknownIndex>> = default();
         }
 
         THREAD_PALETTE.with_borrow_mut(|thread_palette| {
             Other(if let Some(&index) = thread_palette.get(&blockstate) {
                 index
             } else {
-                let mut unknown_blocks = UNKNOWN_BLOCKS.write().unwrap();
-                if let Some(&index) = unknown_blocks.map.get(&blockstate) {
-                    index
-                } else {
-                    let index = unknown_blocks.states.len() as u16;
-                    unknown_blocks.map.insert(blockstate.clone(), index);
-                    unknown_blocks.states.push(blockstate);
-                    index
-                }
+                let index = UNKNOWN_BLOCKS.write().unwrap().register(blockstate.clone());
+                thread_palette.insert(blockstate, index);
+                index
             })
         })
     }
@@ -966,12 +1208,14 @@ impl Block {
                 | SmallPlant(..)
                 | TallPlant(..)
                 | GroundPlant(..)
+                | GrowingCrop(..)
                 | Leaves(..)
                 | SnowLayer
                 | Ladder(..)
                 | Trapdoor(..)
                 | Door(..)
                 | WallBanner(..)
+                | Banner(..)
                 | Repeater(..)
                 | Rail(..)
         )
@@ -1003,6 +1247,151 @@ impl Block {
         )
     }
 
+    /// Whether this block is unlikely to have been generated naturally
+    pub fn artificial(self) -> bool {
+        match self {
+            Full(Stone | Granite | Diorite | Andesite | Sandstone | RedSandstone | Blackstone) => {
+                false
+            }
+            Full(_) | Slab(..) | Stair(..) | Fence(..) | Ladder(..) => true,
+            Log(_, LogType::Stripped(_)) => true,
+            Wool(_) | SmoothQuartz | Glowstone | GlassPane(_) | WallBanner(..) | Banner(..) => true,
+            Hay => true,
+            Cauldron { .. } | Barrel | Trapdoor(..) | Door(..) | Bell(..) | Repeater(..) => true,
+            CraftingTable | Stonecutter(_) | Anvil(_) | Furnace(_) | LavaCauldron => true,
+            Bed(..) => true,
+            Other(index) => {
+                const KEYWORDS: &[&str] = &[
+                    "glass",
+                    "torch",
+                    "lantern",
+                    "chest",
+                    "_bed",
+                    "sign",
+                    "carpet",
+                    "concrete",
+                    "planks",
+                    "brick",
+                    "button",
+                    "lever",
+                    "pressure_plate",
+                    "redstone",
+                    "piston",
+                    "hopper",
+                    "dispenser",
+                    "dropper",
+                    "observer",
+                    "bookshelf",
+                    "shulker",
+                    "iron_block",
+                    "gold_block",
+                    "diamond_block",
+                    "emerald_block",
+                    "polished",
+                    "cut_",
+                    "chiseled",
+                    "_wall",
+                    "_bars",
+                    "flower_pot",
+                    "beacon",
+                    "enchanting_table",
+                    "brewing_stand",
+                    "jukebox",
+                    "note_block",
+                    "smoker",
+                    "blast_furnace",
+                    "loom",
+                    "cartography_table",
+                    "fletching_table",
+                    "grindstone",
+                    "lectern",
+                    "composter",
+                    "target",
+                    "tnt",
+                    "quartz",
+                    "glazed_terracotta",
+                ];
+                let unknown = UNKNOWN_BLOCKS.read().unwrap();
+                let name = &unknown.states[index.get()].0;
+                KEYWORDS.iter().any(|keyword| name.contains(keyword))
+            }
+            _ => false,
+        }
+    }
+
+    /// Blocks that hurt or trap whoever steps into them
+    pub fn hazardous(self) -> bool {
+        match self {
+            Lava | LavaCauldron => true,
+            Other(index) => {
+                const HAZARDS: &[&str] = &[
+                    "powder_snow",
+                    "magma_block",
+                    "fire",
+                    "sweet_berry_bush",
+                    "pointed_dripstone",
+                    "cobweb",
+                ];
+                let unknown = UNKNOWN_BLOCKS.read().unwrap();
+                let name = &unknown.states[index.get()].0;
+                HAZARDS.iter().any(|hazard| name.ends_with(hazard))
+            }
+            _ => false,
+        }
+    }
+
+    /// Block light the block gives off, 0 to 15
+    pub fn light(self) -> u8 {
+        match self {
+            Glowstone | Lava | LavaCauldron => 15,
+            Other(index) => {
+                let unknown = UNKNOWN_BLOCKS.read().unwrap();
+                let Blockstate(name, properties) = &unknown.states[index.get()];
+                let unlit = properties.iter().any(|(k, v)| k == "lit" && v == "false");
+                match name.strip_prefix("minecraft:").unwrap_or(name) {
+                    _ if unlit => 0,
+                    "lantern" | "sea_lantern" | "shroomlight" | "jack_o_lantern" | "campfire"
+                    | "redstone_lamp" | "beacon" | "conduit" | "end_gateway" => 15,
+                    "torch" | "wall_torch" | "end_rod" => 14,
+                    "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_campfire" => 10,
+                    "glow_lichen" | "redstone_torch" | "redstone_wall_torch" => 7,
+                    _ => 0,
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    /// Ice on top of a lake, the surface counts as water level
+    pub fn frozen_water(self) -> bool {
+        match self {
+            Other(index) => {
+                let unknown = UNKNOWN_BLOCKS.read().unwrap();
+                let name = &unknown.states[index.get()].0;
+                matches!(
+                    name.strip_prefix("minecraft:").unwrap_or(name),
+                    "ice" | "packed_ice" | "blue_ice" | "frosted_ice"
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Cap of a giant mushroom
+    pub fn mushroom_cap(self) -> bool {
+        match self {
+            Other(index) => {
+                let unknown = UNKNOWN_BLOCKS.read().unwrap();
+                let name = &unknown.states[index.get()].0;
+                matches!(
+                    name.strip_prefix("minecraft:").unwrap_or(name),
+                    "brown_mushroom_block" | "red_mushroom_block"
+                )
+            }
+            _ => false,
+        }
+    }
+
     pub fn no_pathing(self) -> bool {
         matches!(self, Water | Lava | GroundPlant(Cactus))
     }
@@ -1011,15 +1400,123 @@ impl Block {
         matches!(self, Ladder(..))
     }
 
+    /// Offset to the block this one is attached to and pops off without. For the upper half of
+    /// doors and tall plants, that's the lower half.
+    pub fn support(self) -> Option<IVec3> {
+        match self {
+            Ladder(dir) | WallBanner(dir, _) => Some(-IVec3::from(dir)),
+            Door(..)
+            | Rail(_)
+            | Repeater(..)
+            | Banner(..)
+            | GrowingCrop(..)
+            | SmallPlant(_)
+            | TallPlant(..)
+            | SnowLayer
+            | Bell(_, BellAttachment::Floor) => Some(-IVec3::Z),
+            Bell(_, BellAttachment::Ceiling) => Some(IVec3::Z),
+            Other(index) => {
+                let unknown = UNKNOWN_BLOCKS.read().unwrap();
+                let Blockstate(name, properties) = &unknown.states[index.get()];
+                let name = name.strip_prefix("minecraft:").unwrap_or(name);
+                let property = |key: &str| {
+                    properties
+                        .iter()
+                        .find(|(k, _)| k == key)
+                        .map(|(_, value)| value.as_ref())
+                };
+                // Not jack_o_lantern or sea_lantern, those are full blocks
+                let lantern = matches!(name, "lantern" | "soul_lantern");
+                if name.ends_with("wall_torch") {
+                    let facing: HDir = property("facing")?.parse().ok()?;
+                    Some(-IVec3::from(facing))
+                } else if lantern && property("hanging") == Some("true") {
+                    Some(IVec3::Z)
+                } else if name.ends_with("torch")
+                    || lantern
+                    || name.ends_with("carpet")
+                    || name.ends_with("pressure_plate")
+                {
+                    Some(-IVec3::Z)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// For fences and walls, connects them to the blocks around them. `neighbor` gets the block
+    /// at an offset from this one.
+    pub fn connect_fence(self, neighbor: impl Fn(IVec3) -> Block) -> Block {
+        let Fence(material) = self else {
+            return self;
+        };
+        let above = neighbor(IVec3::Z);
+        let mut sides = FenceSides::default();
+        for dir in all::<HDir>() {
+            if !self.fence_connects_to(neighbor(dir.into())) {
+                continue;
+            }
+            // Walls get tall next to what's above them, like a wall continuing above
+            let tall = !matches!(material, Wood(_))
+                && (above.full_sides()
+                    || (matches!(above, Fence(_))
+                        && above.fence_connects_to(neighbor(IVec3::from(dir) + IVec3::Z))));
+            sides.set(dir, if tall { WallSide::Tall } else { WallSide::Low });
+        }
+        ConnectedFence(material, sides)
+    }
+
+    fn fence_connects_to(self, other: Block) -> bool {
+        match (self, other) {
+            (Fence(Wood(_)), Fence(Wood(_)) | ConnectedFence(Wood(_), _)) => true,
+            (Fence(Wood(_)), _) | (_, Fence(Wood(_)) | ConnectedFence(Wood(_), _)) => false,
+            (_, Fence(_) | ConnectedFence(..) | GlassPane(_)) => true,
+            _ => other.full_sides(),
+        }
+    }
+
+    /// Whether this is solid on every side, so that e.g. fences connect to it
+    pub fn full_sides(self) -> bool {
+        self.solid()
+            && !matches!(
+                self,
+                Slab(..)
+                    | Stair(..)
+                    | Fence(_)
+                    | ConnectedFence(..)
+                    | GlassPane(_)
+                    | Cauldron { .. }
+                    | LavaCauldron
+                    | Bell(..)
+                    | Stonecutter(_)
+                    | Anvil(_)
+                    | FlowerPot(_)
+                    | Composter(_)
+                    | Bed(..)
+                    | Other(_)
+            )
+    }
+
     pub fn rotated(self, turns: i32) -> Self {
         match self {
             Log(species, LogType::Normal(Axis::X)) => Log(species, LogType::Normal(Axis::Y)),
             Log(species, LogType::Normal(Axis::Y)) => Log(species, LogType::Normal(Axis::X)),
-            Stair(material, facing, flipped) => Stair(material, facing.rotated(turns), flipped),
+            Stair(material, facing, flipped, shape) => {
+                Stair(material, facing.rotated(turns), flipped, shape)
+            }
             WallBanner(facing, color) => WallBanner(facing.rotated(turns), color),
+            Banner(color, rotation) => {
+                Banner(color, (rotation as i32 + turns * 4).rem_euclid(16) as u8)
+            }
             Repeater(dir, delay) => Repeater(dir.rotated(turns), delay),
             Trapdoor(species, dir, meta) => Trapdoor(species, dir.rotated(turns), meta),
             Door(species, dir, meta) => Door(species, dir.rotated(turns), meta),
+            Furnace(dir) => Furnace(dir.rotated(turns)),
+            Beehive(dir, honey) => Beehive(dir.rotated(turns), honey),
+            BeeNest(dir, honey) => BeeNest(dir.rotated(turns), honey),
+            Bed(color, dir, part) => Bed(color, dir.rotated(turns), part),
             _ => self,
         }
     }
@@ -1028,7 +1525,7 @@ impl Block {
         match self {
             Full(Wood(Oak)) => Full(Wood(species)),
             Slab(Wood(Oak), flipped) => Slab(Wood(species), flipped),
-            Stair(Wood(Oak), dir, flipped) => Stair(Wood(species), dir, flipped),
+            Stair(Wood(Oak), dir, flipped, shape) => Stair(Wood(species), dir, flipped, shape),
             Fence(Wood(Oak)) => Fence(Wood(species)),
             Log(Oak, typ) => Log(species, typ),
             Leaves(Oak, dist) => Leaves(species, dist),
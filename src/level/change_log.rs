@@ -0,0 +1,61 @@
+//! Append-only log of block changes, so long simulations don't need to keep every change in
+//! memory
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use anyhow::bail;
+
+use super::*;
+
+const CHANGE_LOG: &str = "mcgen-changes.log";
+
+impl Level {
+    pub fn change_log_path(&self) -> PathBuf {
+        self.path.join(CHANGE_LOG)
+    }
+
+    /// Appends the recorded changes to the change log in the save folder and clears the
+    /// recording. Must not be called while a recording cursor is in use.
+    pub fn flush_recording(&mut self) -> Result<()> {
+        if self.setblock_recording.is_empty() {
+            return Ok(());
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.change_log_path())?;
+        let mut file = BufWriter::new(file);
+        let unknown = UNKNOWN_BLOCKS.read().unwrap();
+        for set in self.setblock_recording.drain(..) {
+            writeln!(
+                file,
+                "{} {} {} {}",
+                set.pos.x,
+                set.pos.z,
+                set.pos.y,
+                set.block.blockstate(&unknown)
+            )?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Applies the changes from the change log, e.g. to reconstruct the final state of a
+    /// simulation on a freshly loaded level
+    pub fn apply_change_log(&mut self) -> Result<()> {
+        let file = BufReader::new(File::open(self.change_log_path())?);
+        for line in file.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.splitn(4, ' ').collect();
+            let [x, y, z, block] = parts[..] else {
+                bail!("Invalid change log entry: {line}");
+            };
+            let pos = IVec3::new(x.parse()?, z.parse()?, y.parse()?);
+            self(pos, Block::from_blockstate_str(block));
+        }
+        Ok(())
+    }
+}
@@ -18,6 +18,97 @@ impl<T: Copy> ColumnMap<T> {
         }
     }
 
+    /// Columns covered, at whole-chunk granularity
+    pub fn area(&self) -> Rect {
+        Rect {
+            min: self.chunk_min.area().min,
+            max: self.chunk_max.area().max,
+        }
+    }
+
+    pub fn contains(&self, column: IVec2) -> bool {
+        self.area().contains(column)
+    }
+
+    /// `None` outside the map instead of panicking
+    pub fn get(&self, column: IVec2) -> Option<T> {
+        self.contains(column)
+            .then(|| self.data[self.column_index(column)])
+    }
+
+    /// One column per cell (the one with the lowest coordinates) and its value
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, T)> + '_ {
+        self.area()
+            .into_iter()
+            .filter(move |column| column.rem_euclid(IVec2::splat(self.resolution)) == IVec2::ZERO)
+            .map(move |column| (column, self(column)))
+    }
+
+    /// Cells sharing a side with the cell containing `column`, as long as they're in the map
+    pub fn neighbors(&self, column: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        let cell = column - column.rem_euclid(IVec2::splat(self.resolution));
+        NEIGHBORS_2D
+            .into_iter()
+            .map(move |off| cell + off * self.resolution)
+            .filter(move |neighbor| self.contains(*neighbor))
+    }
+
+    pub fn map<U: Copy>(&self, f: impl Fn(T) -> U) -> ColumnMap<U> {
+        ColumnMap {
+            chunk_min: self.chunk_min,
+            chunk_max: self.chunk_max,
+            resolution: self.resolution,
+            data: self.data.iter().map(|value| f(*value)).collect(),
+        }
+    }
+
+    /// Weighted average of `value` over the cells up to `radius` cells away in each direction.
+    /// `weight` gets the offset in cells; cells outside the map are left out.
+    pub fn convolve(
+        &self,
+        radius: i32,
+        weight: impl Fn(IVec2) -> f32,
+        value: impl Fn(T) -> f32,
+    ) -> ColumnMap<f32> {
+        let mut result = ColumnMap::new(self.chunk_min, self.chunk_max, self.resolution, 0.);
+        for (column, _) in self.iter() {
+            let mut sum = 0.;
+            let mut total_weight = 0.;
+            for off in (Rect {
+                min: IVec2::splat(-radius),
+                max: IVec2::splat(radius),
+            }) {
+                if let Some(neighbor) = self.get(column + off * self.resolution) {
+                    let weight = weight(off);
+                    sum += value(neighbor) * weight;
+                    total_weight += weight;
+                }
+            }
+            result(column, sum / total_weight);
+        }
+        result
+    }
+
+    /// Cells reachable from the one containing `start` by stepping to neighbors for which
+    /// `connected(from, to)` holds
+    pub fn flood_fill(&self, start: IVec2, connected: impl Fn(T, T) -> bool) -> Vec<IVec2> {
+        let mut visited = ColumnMap::new(self.chunk_min, self.chunk_max, self.resolution, false);
+        let start = start - start.rem_euclid(IVec2::splat(self.resolution));
+        let mut cells = vec![start];
+        let mut queue = vec![start];
+        visited(start, true);
+        while let Some(cell) = queue.pop() {
+            for neighbor in self.neighbors(cell) {
+                if !visited(neighbor) && connected(self(cell), self(neighbor)) {
+                    visited(neighbor, true);
+                    cells.push(neighbor);
+                    queue.push(neighbor);
+                }
+            }
+        }
+        cells
+    }
+
     fn column_index(&self, column: IVec2) -> usize {
         self.chunk_index(column.into())
             * (16 / self.resolution as usize)
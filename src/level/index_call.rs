@@ -20,7 +20,7 @@ impl Fn<(IVec3,)> for Level {
     extern "rust-call" fn call(&self, (pos,): (IVec3,)) -> Self::Output {
         let section_index = self.section_index(pos);
         match &self.sections.get(section_index) {
-            Some(Some(section)) => section.blocks[Self::block_in_section_index(pos)],
+            Some(Some(section)) => section.get(Self::block_in_section_index(pos)),
             Some(None) => Air,
             None => {
                 eprintln!("Out of bounds access at {pos}");
@@ -44,15 +44,17 @@ impl FnMut<(IVec3, Block)> for Level {
         self.dirty_chunks[chunk_index] = true;
         let index = self.section_index(pos);
         let section = self.sections[index].get_or_insert_default();
-        let previous = &mut section.blocks[Self::block_in_section_index(pos)];
-        if *previous != block {
+        let previous = section.set(Self::block_in_section_index(pos), block);
+        if previous != block {
+            self.tile_entities.remove(&pos);
+            self.dirty_columns.insert(pos.truncate());
+            self.chunk_changes[chunk_index] += 1;
             self.setblock_recording.push(SetBlock {
                 pos,
-                previous: *previous,
+                previous,
                 block,
             });
         }
-        *previous = block;
     }
 }
 
@@ -70,16 +72,20 @@ impl<F: FnOnce(Block) -> Block> FnMut<(IVec3, F)> for Level {
         self.dirty_chunks[chunk_index] = true;
         let index = self.section_index(pos);
         let section = self.sections[index].get_or_insert_default();
-        let previous = &mut section.blocks[Self::block_in_section_index(pos)];
-        let block = fun(*previous);
-        if *previous != block {
+        let index = Self::block_in_section_index(pos);
+        let previous = section.get(index);
+        let block = fun(previous);
+        if previous != block {
+            section.set(index, block);
+            self.tile_entities.remove(&pos);
+            self.dirty_columns.insert(pos.truncate());
+            self.chunk_changes[chunk_index] += 1;
             self.setblock_recording.push(SetBlock {
                 pos,
-                previous: *previous,
+                previous,
                 block,
             });
         }
-        *previous = block;
     }
 }
 
@@ -0,0 +1,159 @@
+//! Edits to level.dat: the name and game rules the replay needs, and optional settings such
+//! as the world border and spawn point. The areas changed by each run are recorded there too,
+//! so later runs on the same save know about them.
+
+use std::{fs::File, str::FromStr};
+
+use super::*;
+
+/// Compound in level.dat's `Data` with the generator's own information
+const MARKER: &str = "mcgen";
+const GENERATED_SUFFIX: &str = " [generated";
+
+/// Optional changes to level.dat
+#[derive(Clone, Debug, Default)]
+pub struct LevelSettings {
+    /// Square world border with the given center and side length
+    pub world_border: Option<(IVec2, i32)>,
+    /// Game mode of the player in singleplayer and of new players
+    pub game_mode: Option<GameMode>,
+    pub spawn: Option<IVec3>,
+    /// Set in addition to the game rules the replay needs
+    pub game_rules: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl FromStr for GameMode {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "survival" => Ok(GameMode::Survival),
+            "creative" => Ok(GameMode::Creative),
+            "adventure" => Ok(GameMode::Adventure),
+            "spectator" => Ok(GameMode::Spectator),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Level {
+    pub fn save_metadata(&self, settings: &LevelSettings) -> Result<()> {
+        let level_nbt_path = self.path.join("level.dat");
+        let mut file = File::open(&level_nbt_path).expect("Failed to open level.dat");
+        let mut nbt =
+            nbt::decode::read_gzip_compound_tag(&mut file).expect("Failed to open level.dat");
+        let data: &mut CompoundTag = nbt.get_mut("Data").expect("Corrupt level.dat");
+
+        let mut areas = generated_areas(data);
+        areas.extend(self.changed_area());
+        let mut marker = CompoundTag::new();
+        marker.insert_compound_tag_vec(
+            "Generations",
+            areas.iter().map(|area| {
+                let mut generation = CompoundTag::new();
+                generation
+                    .insert_i32_vec("Area", vec![area.min.x, area.min.y, area.max.x, area.max.y]);
+                generation
+            }),
+        );
+        data.insert_compound_tag(MARKER, marker);
+
+        // Replaces the suffix of earlier runs instead of adding another one
+        let name: &mut String = data.get_mut("LevelName").expect("Corrupt level.dat");
+        if let Some(start) = name.find(GENERATED_SUFFIX) {
+            name.truncate(start);
+        }
+        match areas.len() {
+            0 | 1 => name.push_str(" [generated]"),
+            runs => name.push_str(&format!(" [generated {runs} times]")),
+        }
+
+        data.insert(
+            "LastPlayed",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64,
+        );
+
+        data.insert_i8("Difficulty", 0);
+
+        if let Some((center, size)) = settings.world_border {
+            data.insert_f64("BorderCenterX", center.x as f64 + 0.5);
+            data.insert_f64("BorderCenterZ", center.y as f64 + 0.5);
+            data.insert_f64("BorderSize", size as f64);
+        }
+        if let Some(mode) = settings.game_mode {
+            data.insert_i32("GameType", mode as i32);
+            // Otherwise the existing singleplayer player keeps their mode
+            let player: Result<&mut CompoundTag, _> = data.get_mut("Player");
+            if let Ok(player) = player {
+                player.insert_i32("playerGameType", mode as i32);
+            }
+        }
+        if let Some(spawn) = settings.spawn {
+            data.insert_i32("SpawnX", spawn.x);
+            data.insert_i32("SpawnY", spawn.z);
+            data.insert_i32("SpawnZ", spawn.y);
+        }
+
+        let gamerules: &mut CompoundTag = data.get_mut("GameRules").unwrap();
+        gamerules.insert_str("commandBlockOutput", "false");
+        gamerules.insert_str("gameLoopFunction", "mc-gen:loop");
+        for (rule, value) in &settings.game_rules {
+            gamerules.insert_str(rule, value);
+        }
+
+        let mut file = File::create(&level_nbt_path).expect("Failed to open level.dat");
+        nbt::encode::write_gzip_compound_tag(&mut file, &nbt).expect("Failed to write level.dat");
+        Ok(())
+    }
+    /// Areas changed by earlier runs of the generator on this save
+    pub fn previous_generations(&self) -> Vec<Rect> {
+        File::open(self.path.join("level.dat"))
+            .ok()
+            .and_then(|mut file| nbt::decode::read_gzip_compound_tag(&mut file).ok())
+            .and_then(|nbt| nbt.get_compound_tag("Data").ok().map(generated_areas))
+            .unwrap_or_default()
+    }
+
+    /// Bounding box of the chunks with changes
+    fn changed_area(&self) -> Option<Rect> {
+        self.chunks()
+            .filter(|&chunk| self.chunk_changes(chunk) > 0)
+            .map(ChunkIndex::area)
+            .reduce(|a, b| Rect {
+                min: a.min.min(b.min),
+                max: a.max.max(b.max),
+            })
+    }
+}
+
+fn generated_areas(data: &CompoundTag) -> Vec<Rect> {
+    let Ok(generations) = data
+        .get_compound_tag(MARKER)
+        .and_then(|marker| marker.get_compound_tag_vec("Generations"))
+    else {
+        return Vec::new();
+    };
+    generations
+        .into_iter()
+        .filter_map(
+            |generation| match generation.get_i32_vec("Area").map(Vec::as_slice) {
+                Ok(&[min_x, min_y, max_x, max_y]) => Some(Rect {
+                    min: ivec2(min_x, min_y),
+                    max: ivec2(max_x, max_y),
+                }),
+                _ => None,
+            },
+        )
+        .collect()
+}
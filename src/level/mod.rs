@@ -1,12 +1,16 @@
+mod anvil;
 mod biome;
 mod block;
+mod change_log;
 mod column_map;
 mod index_call;
+mod metadata;
+mod region;
+mod section;
+mod structure;
+mod synthesize;
+mod world_view;
 
-use anvil_region::{
-    position::{RegionChunkPosition, RegionPosition},
-    provider::{FolderRegionProvider, RegionProvider},
-};
 use anyhow::{anyhow, Result};
 use bevy_ecs::system::Resource;
 use itertools::Itertools;
@@ -17,10 +21,23 @@ use std::{
     path::PathBuf,
 };
 
-use crate::{default, geometry::*, HashMap, DATA_VERSION};
+use crate::{default, geometry::*, HashMap, HashSet, DATA_VERSION};
+pub use anvil::RegionFolder;
 pub use biome::*;
 pub use block::*;
 pub use column_map::ColumnMap;
+pub use metadata::{GameMode, LevelSettings};
+pub use region::{Region, RegionChanges};
+pub use section::Section;
+pub use structure::{StructurePiece, StructureStart};
+pub use world_view::{place_bed, place_door, stair_run, WorldView};
+
+/// Height of the terrain replacing unloadable chunks if there's nothing to go by
+const DEFAULT_FLAT_HEIGHT: i32 = 64;
+/// Columns with terrain this much higher on both sides are considered to be in a ravine
+const RAVINE_DEPTH: i32 = 6;
+/// How far to look to both sides for ravine walls
+const RAVINE_WIDTH: i32 = 4;
 
 #[derive(Resource)]
 pub struct Level {
@@ -33,14 +50,38 @@ pub struct Level {
     sections: Vec<Option<Box<Section>>>,
     /// Minecraft stores biomes in 3d, but we only store 2d (at height 64)
     pub biome: ColumnMap<Biome>,
+    /// The 3d biomes of each section as loaded, written back unless overridden
+    stored_biomes: Vec<Option<CompoundTag>>,
+    /// Biomes to write instead, by 4×4 column cell (see [`Level::set_custom_biome`])
+    custom_biomes: HashMap<IVec2, String>,
     pub height: ColumnMap<i32>,
     pub water: ColumnMap<Option<i32>>,
     // This might store a Option<Entity> later
-    pub blocked: ColumnMap<bool>,
+    blocked: ColumnMap<bool>,
+    /// Number of blocked columns in each chunk, so chunks that are completely free or
+    /// completely blocked don't need to be checked column by column
+    blocked_per_chunk: ColumnMap<u16>,
     // Pathfinding cost from center (may not be up to date)
     pub reachability: ColumnMap<u32>,
+    /// Structures such as villages that were generated by Minecraft
+    pub structures: Vec<StructureStart>,
+    /// Block entity data that isn't implied by the block itself (chest contents, sign text, …),
+    /// either loaded with the world or set explicitly. Cleared when the block changes.
+    tile_entities: HashMap<IVec3, CompoundTag>,
+    /// Entities added by the generator, with their position. Written to the entities region
+    /// alongside the entities already there.
+    entities: Vec<(Vec3, CompoundTag)>,
     dirty_chunks: Vec<bool>,
+    /// Number of block changes in each chunk, so that data derived from the blocks (e.g.
+    /// [`NavGraph`](crate::nav_graph::NavGraph)) can tell when it's outdated
+    chunk_changes: Vec<u32>,
+    /// Columns whose blocks changed since their height was last computed
+    dirty_columns: HashSet<IVec2>,
     setblock_recording: Vec<SetBlock>,
+    /// Problems encountered while loading, to be reported at the end of the run
+    pub load_warnings: Vec<String>,
+    /// Whether some terrain was made up because chunks were missing or broken
+    made_up_terrain: bool,
 }
 
 impl Level {
@@ -54,12 +95,7 @@ impl Level {
                 copy_dir::copy_dir(read_path, write_path).expect("Failed to create save");
             });
         }
-        let region_path = {
-            let mut region_path = PathBuf::from(read_path);
-            region_path.push("region");
-            region_path.into_os_string().into_string().unwrap()
-        };
-        let chunk_provider = FolderRegionProvider::new(&region_path);
+        let chunk_provider = RegionFolder::new(PathBuf::from(read_path).join("region"));
         // TODO: use area just as a settlement area but try to load a wider margin around it (need to detect if chunks are present)
         let chunk_min = ChunkIndex::from(area.min - ivec2(crate::LOAD_MARGIN, crate::LOAD_MARGIN));
         let chunk_max = ChunkIndex::from(area.max + ivec2(crate::LOAD_MARGIN, crate::LOAD_MARGIN));
@@ -73,112 +109,245 @@ impl Level {
         let mut water = ColumnMap::new(chunk_min, chunk_max, 1, None);
 
         // Load chunks. Collecting indexes to vec neccessary for zip
-        (chunk_min.1..=chunk_max.1)
-            .flat_map(|z| (chunk_min.0..=chunk_max.0).map(move |x| (x, z)))
-            .collect_vec()
+        let indices = (chunk_min.1..=chunk_max.1)
+            .flat_map(|z| (chunk_min.0..=chunk_max.0).map(move |x| ChunkIndex(x, z)))
+            .collect_vec();
+        let results: Vec<Result<LoadedChunk>> = indices
             .par_iter()
             .zip(sections.par_chunks_exact_mut(24))
             .zip(biome.data.par_chunks_exact_mut(4 * 4))
             .zip(height.data.par_chunks_exact_mut(16 * 16))
             .zip(water.data.par_chunks_exact_mut(16 * 16))
-            .for_each(|((((index, sections), biome), heightmap), watermap)| {
+            .map(|((((index, sections), biome), heightmap), watermap)| {
                 load_chunk(
                     &chunk_provider,
-                    (*index).into(),
+                    *index,
                     sections,
                     biome,
                     heightmap,
                     watermap,
                 )
-                .expect(&format!("Failed to load chunk ({},{}): ", index.0, index.1))
-            });
+            })
+            .collect();
+
+        // A single truncated region file shouldn't kill the run
+        let mut structures = Vec::new();
+        let mut tile_entities = HashMap::default();
+        let mut stored_biomes = vec![None; chunk_count * 24];
+        let mut failed = Vec::new();
+        let mut ungenerated = Vec::new();
+        let mut load_warnings = Vec::new();
+        for (i, (index, result)) in indices.iter().zip(results).enumerate() {
+            match result {
+                Ok(chunk) => {
+                    structures.extend(chunk.structures);
+                    tile_entities.extend(chunk.block_entities);
+                    stored_biomes[i * 24..(i + 1) * 24].clone_from_slice(&chunk.biomes);
+                }
+                Err(err) if err.is::<Ungenerated>() => ungenerated.push(*index),
+                Err(err) => {
+                    load_warnings.push(format!(
+                        "Failed to load chunk ({},{}), replaced with flat terrain: {err}",
+                        index.0, index.1
+                    ));
+                    failed.push(*index);
+                }
+            }
+        }
+        if !failed.is_empty() {
+            let loaded: Vec<i32> = indices
+                .iter()
+                .zip(height.data.chunks_exact(16 * 16))
+                .filter(|(index, _)| !failed.contains(index) && !ungenerated.contains(index))
+                .flat_map(|(_, heightmap)| heightmap.iter().copied())
+                .collect();
+            let flat_height = if loaded.is_empty() {
+                DEFAULT_FLAT_HEIGHT
+            } else {
+                (loaded.iter().map(|&h| h as i64).sum::<i64>() / loaded.len() as i64) as i32
+            };
+            for (i, index) in indices.iter().enumerate() {
+                if failed.contains(index) {
+                    flat_chunk(
+                        &mut sections[i * 24..(i + 1) * 24],
+                        &mut height.data[i * 16 * 16..(i + 1) * 16 * 16],
+                        &mut water.data[i * 16 * 16..(i + 1) * 16 * 16],
+                        flat_height,
+                    );
+                }
+            }
+        }
 
-        Self {
+        let mut level = Self {
             path: PathBuf::from(write_path),
             chunk_min,
             chunk_max,
             sections,
             biome,
+            stored_biomes,
+            custom_biomes: default(),
             height,
             water,
             blocked: ColumnMap::new(chunk_min, chunk_max, 1, false),
+            blocked_per_chunk: ColumnMap::new(chunk_min, chunk_max, 16, 0),
             reachability: ColumnMap::new(chunk_min, chunk_max, 1, 0),
+            structures,
+            tile_entities,
+            entities: default(),
             dirty_chunks: vec![false; chunk_count],
+            chunk_changes: vec![0; chunk_count],
+            dirty_columns: default(),
             setblock_recording: default(),
+            load_warnings,
+            made_up_terrain: !failed.is_empty() | !ungenerated.is_empty(),
+        };
+        // Don't build on made-up terrain
+        for index in failed {
+            level.set_blocked(index.area());
+        }
+        if !ungenerated.is_empty() {
+            level.load_warnings.push(format!(
+                "Synthesized terrain for {} ungenerated chunks",
+                ungenerated.len()
+            ));
+            level.synthesize_chunks(&ungenerated);
         }
+        level
     }
 
     /// Saves the world to disk. This is suitable only for debug visualizations:
     /// Some blocks may be changes/information is discarded even though it's not touched,
-    /// blockstates ignore neighboring blocks.
+    /// blockstates ignore neighboring blocks (except for fences and walls).
     pub fn debug_save(&self) {
-        // Write chunks
-        let mut region_path = self.path.clone();
-        region_path.push("region");
-        // Internally, AnvilChunkProvider stores a path. So why require a str??
-        let region_path = region_path.into_os_string().into_string().unwrap();
-        let chunk_provider = FolderRegionProvider::new(&region_path);
-
-        // Saving isn't thread safe
-        for ((index, sections), dirty) in (self.chunk_min.1..=self.chunk_max.1)
-            .flat_map(|z| (self.chunk_min.0..=self.chunk_max.0).map(move |x| (x, z)))
-            .zip(self.sections.chunks_exact(24))
-            .zip(&self.dirty_chunks)
-        {
-            // Don't save outermost chunks, since we don't modify them & leaving out the border simplifies things
-            if dirty
-                & (index.0 > self.chunk_min.0)
-                & (index.0 < self.chunk_max.0)
-                & (index.1 > self.chunk_min.1)
-                & (index.1 < self.chunk_max.1)
-            {
-                save_chunk(&chunk_provider, index.into(), sections)
-                    .unwrap_or_else(|_| panic!("Failed to save chunk ({},{}): ", index.0, index.1))
-            }
-        }
-
-        self.save_metadata().unwrap();
+        self.save_chunks();
+        self.save_metadata(&default()).unwrap();
     }
 
-    pub fn save_metadata(&self) -> Result<()> {
-        // Edit metadata
-        let level_nbt_path =
-            self.path.clone().into_os_string().into_string().unwrap() + "/level.dat";
-        let mut file = std::fs::File::open(&level_nbt_path).expect("Failed to open level.dat");
-        let mut nbt =
-            nbt::decode::read_gzip_compound_tag(&mut file).expect("Failed to open level.dat");
-        let data: &mut CompoundTag = nbt.get_mut("Data").expect("Corrupt level.dat");
-
-        let name: &mut String = data.get_mut("LevelName").expect("Corrupt level.dat");
-        // TODO: adjust if multiple invocations ("[2 settlements generated]")
-        name.push_str(" [generated]");
-
-        data.insert(
-            "LastPlayed",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as i64,
-        );
-
-        data.insert_i8("Difficulty", 0);
+    /// Writes the modified chunks, with the same caveats as [`Level::debug_save`]. Doesn't
+    /// need a level.dat, so it also works for other dimensions.
+    pub fn save_chunks(&self) {
+        self.save_area(Rect {
+            min: self.chunk_min.area().min,
+            max: self.chunk_max.area().max,
+        });
+    }
 
-        let gamerules: &mut CompoundTag = data.get_mut("GameRules").unwrap();
-        gamerules.insert_str("commandBlockOutput", "false");
-        gamerules.insert_str("gameLoopFunction", "mc-gen:loop");
+    /// Like [`Level::save_chunks`], but only writes the chunks overlapping `area`, so a large
+    /// area can be loaded for context while only a part of it gets changed on disk. Changes
+    /// outside of `area` in those chunks are saved too.
+    pub fn save_area(&self, area: Rect) {
+        let chunk_provider = RegionFolder::new(self.path.join("region"));
+        let entity_provider = RegionFolder::new(self.path.join("entities"));
 
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .open(&level_nbt_path)
-            .expect("Failed to open level.dat");
-        nbt::encode::write_gzip_compound_tag(&mut file, &nbt).expect("Failed to write level.dat");
-        Ok(())
+        let dirty = (self.chunk_min.1..=self.chunk_max.1)
+            .flat_map(|z| (self.chunk_min.0..=self.chunk_max.0).map(move |x| (x, z)))
+            .zip(self.sections.chunks_exact(24))
+            .zip(&self.dirty_chunks)
+            .filter(|((index, _), dirty)| {
+                // Don't save outermost chunks, since we don't modify them & leaving out the border simplifies things
+                **dirty
+                    & (index.0 > self.chunk_min.0)
+                    & (index.0 < self.chunk_max.0)
+                    & (index.1 > self.chunk_min.1)
+                    & (index.1 < self.chunk_max.1)
+                    & ChunkIndex(index.0, index.1).area().overlapps(area)
+            })
+            .map(|(chunk, _)| chunk)
+            .collect_vec();
+        // Regions are locked individually, so this only waits on chunks in the same region
+        dirty.into_par_iter().for_each(|(index, sections)| {
+            let index = index.into();
+            let sections = self.connect_fences(index, sections);
+            let biomes = (0..24)
+                .map(|y_index| self.section_biomes(index, y_index))
+                .collect_vec();
+            save_chunk(
+                &chunk_provider,
+                index,
+                &sections,
+                &biomes,
+                &self.tile_entities,
+            )
+            .unwrap_or_else(|err| panic!("Failed to save chunk ({},{}): {err}", index.0, index.1));
+            let entities = self
+                .entities
+                .iter()
+                .filter(|(pos, _)| ChunkIndex::from(pos.truncate().floor().as_ivec2()) == index)
+                .map(|(pos, nbt)| {
+                    let mut nbt = nbt.clone();
+                    nbt.insert(
+                        "Pos",
+                        nbt::Tag::List(vec![
+                            nbt::Tag::Double(pos.x as f64),
+                            nbt::Tag::Double(pos.z as f64),
+                            nbt::Tag::Double(pos.y as f64),
+                        ]),
+                    );
+                    nbt
+                })
+                .collect_vec();
+            if !entities.is_empty() {
+                save_entities(&entity_provider, index, entities).unwrap_or_else(|err| {
+                    panic!("Failed to save entities ({},{}): {err}", index.0, index.1)
+                })
+            }
+        });
+    }
+
+    /// Entity to be written when saving. `nbt` must contain the id, the position gets added.
+    pub fn add_entity(&mut self, pos: Vec3, nbt: CompoundTag) {
+        let chunk_index = self.chunk_index(pos.truncate().floor().as_ivec2().into());
+        self.dirty_chunks[chunk_index] = true;
+        self.entities.push((pos, nbt));
+    }
+
+    /// Copies of a chunk's sections with fences and walls connected to their neighbors, since
+    /// Minecraft only updates their shape when something next to them changes
+    fn connect_fences(
+        &self,
+        index: ChunkIndex,
+        sections: &[Option<Box<Section>>],
+    ) -> Vec<Option<Box<Section>>> {
+        sections
+            .iter()
+            .enumerate()
+            .map(|(y_index, section)| {
+                let mut section = section.clone()?;
+                let section_base = ivec3(index.0 * 16, index.1 * 16, (y_index as i32 - 4) * 16);
+                for i in 0..16 * 16 * 16 {
+                    let block = section.get(i);
+                    if matches!(block, Fence(_)) {
+                        let pos = section_base
+                            + ivec3(
+                                i as i32 % 16,
+                                i as i32 % (16 * 16) / 16,
+                                i as i32 / (16 * 16),
+                            );
+                        section.set(i, block.connect_fence(|offset| self(pos + offset)));
+                    }
+                }
+                Some(section)
+            })
+            .collect()
     }
 
     pub fn column_map<T: Copy>(&self, resolution: i32, default: T) -> ColumnMap<T> {
         ColumnMap::new(self.chunk_min, self.chunk_max, resolution, default)
     }
 
+    /// Increases whenever a block in the chunk changes. Always 0 outside the loaded area.
+    pub fn chunk_changes(&self, chunk: ChunkIndex) -> u32 {
+        if (chunk.0 < self.chunk_min.0)
+            | (chunk.0 > self.chunk_max.0)
+            | (chunk.1 < self.chunk_min.1)
+            | (chunk.1 > self.chunk_max.1)
+        {
+            0
+        } else {
+            self.chunk_changes[self.chunk_index(chunk)]
+        }
+    }
+
     fn chunk_index(&self, chunk: ChunkIndex) -> usize {
         if (chunk.0 < self.chunk_min.0)
             | (chunk.0 > self.chunk_max.0)
@@ -223,17 +392,98 @@ impl Level {
         .shrink(crate::LOAD_MARGIN)
     }
 
-    pub fn unblocked(&self, area: impl IntoIterator<Item = IVec2>) -> bool {
-        area.into_iter()
-            .all(|column| self.area().contains(column) && !(self.blocked)(column))
+    pub fn unblocked(&self, area: Rect) -> bool {
+        if !self.area().has_subrect(area) {
+            return false;
+        }
+        let chunks = Rect {
+            min: area.min.div_euclid(IVec2::splat(16)),
+            max: area.max.div_euclid(IVec2::splat(16)),
+        };
+        chunks.into_iter().all(|chunk| {
+            let chunk = ChunkIndex(chunk.x, chunk.y);
+            match (self.blocked_per_chunk)(chunk.area().min) {
+                0 => true,
+                256 => false,
+                _ => chunk
+                    .area()
+                    .overlap(area)
+                    .into_iter()
+                    .all(|column| !(self.blocked)(column)),
+            }
+        })
+    }
+
+    /// Whether all chunks were loaded from the save, without stand-in terrain
+    pub fn fully_loaded(&self) -> bool {
+        !self.made_up_terrain
+    }
+
+    pub fn is_blocked(&self, column: IVec2) -> bool {
+        (self.blocked)(column)
+    }
+
+    /// Unblocked area of the given size closest to `center`, at most `max_distance` away
+    pub fn free_spot_near(&self, center: IVec2, size: IVec2, max_distance: i32) -> Option<Rect> {
+        std::iter::once(center)
+            .chain((1..=max_distance).flat_map(|distance| {
+                Rect::new_centered(center, IVec2::splat(distance * 2 + 1)).border()
+            }))
+            .map(|center| Rect::new_centered(center, size))
+            .find(|&area| self.unblocked(area))
+    }
+
+    /// Whether the surface of the column is dangerous: lava, powder snow etc. or the bottom
+    /// of a ravine
+    pub fn hazard(&self, column: IVec2) -> bool {
+        let ground = self.ground(column);
+        if self(ground).hazardous() | self(ground + IVec3::Z).hazardous() {
+            return true;
+        }
+        let deep = |dir: HDir| {
+            let side = column + IVec2::from(dir) * RAVINE_WIDTH;
+            self.area().contains(side) && (self.height)(side) - ground.z >= RAVINE_DEPTH
+        };
+        (deep(HDir::XPos) & deep(HDir::XNeg)) | (deep(HDir::YPos) & deep(HDir::YNeg))
     }
 
     pub fn set_blocked(&mut self, area: impl IntoIterator<Item = IVec2>) {
         for column in area {
-            (self.blocked)(column, true);
+            if !(self.blocked)(column) {
+                (self.blocked)(column, true);
+                let count = (self.blocked_per_chunk)(column);
+                (self.blocked_per_chunk)(column, count + 1);
+            }
+        }
+    }
+
+    /// Updates the height & water map of the columns in the area that were modified since
+    /// they were last computed
+    pub fn recompute_heights(&mut self, area: impl IntoIterator<Item = IVec2>) {
+        for column in area {
+            if self.dirty_columns.remove(&column) {
+                self.recompute_column(column);
+            }
+        }
+    }
+
+    /// Updates the height & water map of all modified columns
+    pub fn recompute_all_heights(&mut self) {
+        for column in std::mem::take(&mut self.dirty_columns) {
+            self.recompute_column(column);
         }
     }
 
+    fn recompute_column(&mut self, column: IVec2) {
+        let chunk_index = self.chunk_index(column.into());
+        let (height, water) = scan_column(
+            &self.sections[chunk_index * 24..(chunk_index + 1) * 24],
+            Self::block_in_section_index(column.extend(0)),
+        );
+        (self.height)(column, height.unwrap_or(-64));
+        (self.water)(column, water);
+    }
+
     pub fn recording_cursor(&self) -> RecordingCursor {
         RecordingCursor(self.setblock_recording.len())
     }
@@ -339,27 +589,42 @@ impl<F: FnMut(Block) -> Block> BlockOrFn for F {
     }
 }
 
+struct LoadedChunk {
+    structures: Vec<StructureStart>,
+    block_entities: Vec<(IVec3, CompoundTag)>,
+    /// Per section, from the bottom
+    biomes: Vec<Option<CompoundTag>>,
+}
+
+/// Minecraft hasn't generated the chunk yet
+#[derive(Debug)]
+struct Ungenerated;
+
+impl std::fmt::Display for Ungenerated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Chunk not generated")
+    }
+}
+
+impl std::error::Error for Ungenerated {}
+
 // TODO: load stored heightmaps, compare to found heightmaps to detect
 // man-made structures
 fn load_chunk(
-    chunk_provider: &FolderRegionProvider,
+    chunk_provider: &RegionFolder,
     chunk_index: ChunkIndex,
     sections: &mut [Option<Box<Section>>],
     biomes: &mut [Biome],
     heightmap: &mut [i32],
     watermap: &mut [Option<i32>],
-) -> Result<()> {
+) -> Result<LoadedChunk> {
     let nbt = chunk_provider
-        .get_region(RegionPosition::from_chunk_position(
-            chunk_index.0,
-            chunk_index.1,
-        ))?
-        .read_chunk(RegionChunkPosition::from_chunk_position(
-            chunk_index.0,
-            chunk_index.1,
-        ))
-        .map_err(|_| anyhow!("Chunk read error"))?;
-    let version = nbt.get_i32("DataVersion").unwrap();
+        .read_chunk(chunk_index)?
+        .ok_or_else(|| anyhow!(Ungenerated))?;
+    let corrupt = |what: &str| anyhow!("Corrupt chunk: {what}");
+    let version = nbt
+        .get_i32("DataVersion")
+        .map_err(|_| corrupt("no DataVersion"))?;
     if !(3465..=DATA_VERSION).contains(&version) {
         eprintln!(
             "Using version {}; only 1.20.2 is currently tested.",
@@ -370,15 +635,29 @@ fn load_chunk(
     // TODO: store CarvingMasks::AIR, seems useful
     // Also, check out Heightmaps. Maybe we can reuse them or gleam additional information from them
 
-    let sections_nbt = nbt.get_compound_tag_vec("sections").unwrap();
+    let sections_nbt = nbt
+        .get_compound_tag_vec("sections")
+        .map_err(|_| corrupt("no sections"))?;
 
+    let mut section_biomes = vec![None; 24];
     for section_nbt in sections_nbt {
-        let y_index = section_nbt.get_i8("Y").unwrap();
+        let y_index = section_nbt
+            .get_i8("Y")
+            .map_err(|_| corrupt("section without Y"))?;
+        if !(-4..20).contains(&y_index) {
+            continue;
+        }
+        section_biomes[(y_index + 4) as usize] =
+            section_nbt.get_compound_tag("biomes").ok().cloned();
 
         // Use a 2d representation of biomes
         if y_index == 5 {
-            let biome = section_nbt.get_compound_tag("biomes").unwrap();
-            let palette = biome.get_str_vec("palette").unwrap();
+            let biome = section_nbt
+                .get_compound_tag("biomes")
+                .map_err(|_| corrupt("no biomes"))?;
+            let palette = biome
+                .get_str_vec("palette")
+                .map_err(|_| corrupt("no biome palette"))?;
             let palette: Vec<Biome> = palette.iter().map(|n| Biome::from_id(n)).collect();
             if palette.len() == 1 {
                 for biome in &mut *biomes {
@@ -406,185 +685,206 @@ fn load_chunk(
             }
         }
 
-        let block_states = section_nbt.get_compound_tag("block_states").unwrap();
-        let palette = block_states.get_compound_tag_vec("palette").unwrap();
+        let block_states = section_nbt
+            .get_compound_tag("block_states")
+            .map_err(|_| corrupt("no block states"))?;
+        let palette = block_states
+            .get_compound_tag_vec("palette")
+            .map_err(|_| corrupt("no block palette"))?;
         let palette: Vec<Block> = palette.iter().map(|nbt| Block::from_nbt(nbt)).collect();
 
-        sections[(y_index + 4) as usize] = Some(Default::default());
-        let section = sections[(y_index + 4) as usize].as_mut().unwrap();
-        let Ok(indices) = block_states.get_i64_vec("data") else {
-            continue;
-        };
-        let bits_per_index = bits_per_index(palette.len());
-
-        let mut current_long = 0;
-        let mut current_bit_shift = 0;
-        for i in 0..(16 * 16 * 16) {
-            let packed = indices[current_long] as u64;
-            let index = packed.shr(current_bit_shift) as usize % (1 << bits_per_index);
-            section.blocks[i] = palette[index];
-
-            current_bit_shift += bits_per_index;
-            if current_bit_shift > (64 - bits_per_index) {
-                current_bit_shift = 0;
-                current_long += 1;
-            }
-        }
+        let data = block_states
+            .get_i64_vec("data")
+            .map_or(&[][..], |data| &data[..]);
+        let section = Section::unpack(palette, data).map_err(|err| corrupt(&err.to_string()))?;
+        sections[(y_index + 4) as usize] = Some(Box::new(section));
     }
 
     // Build water- & heightmap
     // There are build in heightmaps, but they don't ignore logs nor do they work on custom-made maps
-    // TODO: Ignore (packed)ice
-    for x in 0..16 {
-        for z in 0..16 {
-            'column: for section_index in (-4..20).rev() {
-                if let Some(section) = &sections[(section_index + 4i32) as usize] {
-                    for y in (0..16).rev() {
-                        let block = &section.blocks[x + z * 16 + y as usize * 16 * 16];
-                        let height = section_index * 16 + y;
-                        if match block {
-                            Block::Log(..) => false,
-                            _ => block.solid(),
-                        } {
-                            heightmap[x + z * 16] = height;
-                            break 'column;
-                        } else if matches!(block, Block::Water /*TODO: | Block::Ice*/) {
-                            watermap[x + z * 16].get_or_insert(section_index * 16 + y);
-                        }
-                    }
+    for index in 0..16 * 16 {
+        let (height, water) = scan_column(sections, index);
+        if let Some(height) = height {
+            heightmap[index] = height;
+        }
+        watermap[index] = water;
+    }
+
+    // Kept so that untouched blocks (especially unknown ones) keep their contents on save
+    let block_entities = nbt
+        .get_compound_tag_vec("block_entities")
+        .map(|entities| {
+            entities
+                .into_iter()
+                .filter_map(|entity| {
+                    let pos = ivec3(
+                        entity.get_i32("x").ok()?,
+                        entity.get_i32("z").ok()?,
+                        entity.get_i32("y").ok()?,
+                    );
+                    Some((pos, entity.clone()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(LoadedChunk {
+        structures: structure::load_structure_starts(&nbt),
+        block_entities,
+        biomes: section_biomes,
+    })
+}
+
+/// Stand-in for chunks that couldn't be loaded
+fn flat_chunk(
+    sections: &mut [Option<Box<Section>>],
+    heightmap: &mut [i32],
+    watermap: &mut [Option<i32>],
+    height: i32,
+) {
+    for (section_index, section) in sections.iter_mut().enumerate() {
+        let base = (section_index as i32 - 4) * 16;
+        *section = if base + 15 < height {
+            Some(Box::new(Section::single(Block::Full(Stone))))
+        } else if base <= height {
+            let mut flat = Section::default();
+            for i in 0..16 * 16 * 16 {
+                let z = base + i as i32 / (16 * 16);
+                if z < height {
+                    flat.set(i, Block::Full(Stone));
+                } else if z == height {
+                    flat.set(i, Block::Grass);
                 }
             }
-        }
+            Some(Box::new(flat))
+        } else {
+            None
+        };
     }
-
-    Ok(())
+    heightmap.fill(height);
+    watermap.fill(None);
 }
 
-fn bits_per_index(palette_len: usize) -> usize {
-    palette_len.next_power_of_two().ilog2().max(4) as usize
+/// Finds the height & water level of a column of a chunk. Ice counts as water, so frozen
+/// lakes still have their bottom as height.
+fn scan_column(sections: &[Option<Box<Section>>], index: usize) -> (Option<i32>, Option<i32>) {
+    let mut water = None;
+    for section_index in (-4..20).rev() {
+        if let Some(section) = &sections[(section_index + 4i32) as usize] {
+            for z in (0..16).rev() {
+                let block = section.get(index + z as usize * 16 * 16);
+                let height = section_index * 16 + z;
+                if matches!(block, Block::Water) | block.frozen_water() {
+                    water.get_or_insert(height);
+                } else if match block {
+                    Block::Log(..) => false,
+                    _ => block.solid(),
+                } {
+                    return (Some(height), water);
+                }
+            }
+        }
+    }
+    (None, water)
 }
 
 fn save_chunk(
-    chunk_provider: &FolderRegionProvider,
+    chunk_provider: &RegionFolder,
     index: ChunkIndex,
     sections: &[Option<Box<Section>>],
+    biomes: &[CompoundTag],
+    stored_tile_entities: &HashMap<IVec3, CompoundTag>,
 ) -> Result<()> {
-    chunk_provider
-        .get_region(RegionPosition::from_chunk_position(index.0, index.1))?
-        .write_chunk(
-            RegionChunkPosition::from_chunk_position(index.0, index.1),
-            {
-                let mut nbt = CompoundTag::new();
-                nbt.insert_i32("DataVersion", DATA_VERSION);
-                nbt.insert_i32("xVec3", index.0);
-                nbt.insert_i32("zVec3", index.1);
-
-                nbt.insert_i64("LastUpdate", 0);
-                nbt.insert_i8("TerrainPopulated", 1);
-                nbt.insert_i64("InhabitetTime", 0);
-                nbt.insert_str("Status", "full");
-
-                // Collect tile entities
-                let mut tile_entities = Vec::new();
-
-                nbt.insert_compound_tag_vec("sections", {
-                    sections
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(y_index, section)| {
-                            let y_index = y_index as i32 - 4;
-                            //https://github.com/rust-lang/rust-clippy/issues/8281
-                            #[allow(clippy::question_mark)]
-                            let Some(section) = section
-                            else {
-                                return None;
-                            };
-                            let mut nbt = CompoundTag::new();
-                            nbt.insert_i8("Y", y_index as i8);
-
-                            let mut block_states = CompoundTag::new();
-                            // Build the palette first (for length)
-                            // Minecraft seems to always have Air as id 0 even if there is none
-                            let unknown_blocks = UNKNOWN_BLOCKS.read().unwrap();
-                            let mut palette = HashMap::default();
-                            block_states.insert_compound_tag_vec(
-                                "palette",
-                                Some(Air)
-                                    .iter()
-                                    .chain(section.blocks.iter())
-                                    .flat_map(|block| {
-                                        if !palette.contains_key(block) {
-                                            palette.insert(block, palette.len());
-                                            Some(block.to_nbt(&unknown_blocks))
-                                        } else {
-                                            None
-                                        }
-                                    }),
+    chunk_provider.write_chunk(index, &{
+        let mut nbt = CompoundTag::new();
+        nbt.insert_i32("DataVersion", DATA_VERSION);
+        nbt.insert_i32("xVec3", index.0);
+        nbt.insert_i32("zVec3", index.1);
+
+        nbt.insert_i64("LastUpdate", 0);
+        nbt.insert_i8("TerrainPopulated", 1);
+        nbt.insert_i64("InhabitetTime", 0);
+        nbt.insert_str("Status", "full");
+
+        // Collect tile entities
+        let mut tile_entities = Vec::new();
+
+        nbt.insert_compound_tag_vec("sections", {
+            sections
+                .iter()
+                .enumerate()
+                .filter_map(|(y_index, section)| {
+                    let y_index = y_index as i32 - 4;
+                    //https://github.com/rust-lang/rust-clippy/issues/8281
+                    #[allow(clippy::question_mark)]
+                    let Some(section) = section
+                    else {
+                        return None;
+                    };
+                    let mut nbt = CompoundTag::new();
+                    nbt.insert_i8("Y", y_index as i8);
+
+                    let (palette, data) = section.pack();
+                    let unknown_blocks = UNKNOWN_BLOCKS.read().unwrap();
+                    let mut block_states = CompoundTag::new();
+                    block_states.insert_compound_tag_vec(
+                        "palette",
+                        palette.iter().map(|block| block.to_nbt(&unknown_blocks)),
+                    );
+                    block_states.insert_i64_vec("data", data);
+                    nbt.insert("block_states", block_states);
+                    nbt.insert("biomes", biomes[(y_index + 4) as usize].clone());
+
+                    // Collect TileEntity data
+                    let section_base = ivec3(index.0 * 16, index.1 * 16, y_index * 16);
+                    for (i, block) in section.iter().enumerate() {
+                        let pos = section_base
+                            + ivec3(
+                                i as i32 % 16,
+                                i as i32 % (16 * 16) / 16,
+                                i as i32 / (16 * 16),
                             );
+                        if let Some(stored) = stored_tile_entities.get(&pos) {
+                            let mut nbt = stored.clone();
+                            nbt.insert_i32("x", pos.x);
+                            nbt.insert_i32("y", pos.z);
+                            nbt.insert_i32("z", pos.y);
+                            tile_entities.push(nbt);
+                        } else {
+                            tile_entities.extend(block.tile_entity_nbt(pos));
+                        }
+                    }
 
-                            let bits_per_index = bits_per_index(palette.len());
-
-                            // Reserve minimum required
-                            let mut blocks = Vec::with_capacity(4096 / 64 * 4);
-                            blocks.push(0);
-                            let mut current_long = 0;
-                            let mut current_bit_shift = 0;
-
-                            for (i, block) in section.blocks.iter().enumerate() {
-                                blocks[current_long] |=
-                                    (palette[block] << current_bit_shift) as i64;
-                                current_bit_shift += bits_per_index;
-                                if current_bit_shift > 64 - bits_per_index {
-                                    current_bit_shift = 0;
-                                    current_long += 1;
-                                    // If there's an unnecessary empty long at the end,
-                                    // the chunk can't be loaded
-                                    if (i < 4095) | (64 % bits_per_index != 0) {
-                                        blocks.push(0);
-                                    }
-                                }
-
-                                // Collect TileEntity data
-                                {
-                                    let section_base =
-                                        ivec3(index.0 * 16, index.1 * 16, y_index * 16);
-                                    let pos = section_base
-                                        + ivec3(
-                                            i as i32 % 16,
-                                            i as i32 % (16 * 16) / 16,
-                                            i as i32 / (16 * 16),
-                                        );
-                                    tile_entities.extend(block.tile_entity_nbt(pos));
-                                }
-                            }
-                            block_states.insert_i64_vec("data", blocks);
-                            nbt.insert("block_states", block_states);
-
-                            Some(nbt)
-                        })
-                });
-
-                nbt.insert_compound_tag_vec("block_entities", tile_entities);
-
-                nbt
-            },
-        )
-        .map_err(|_| anyhow!("Chunk write error"))?;
-    Ok(())
-}
+                    Some(nbt)
+                })
+        });
 
-#[derive(Clone)]
-pub struct Section {
-    blocks: [Block; 16 * 16 * 16],
+        nbt.insert_compound_tag_vec("block_entities", tile_entities);
+
+        nbt
+    })
 }
 
-impl Default for Section {
-    fn default() -> Self {
-        const AIR: Block = Block::Air;
-        Section {
-            blocks: [AIR; 16 * 16 * 16],
+/// Adds the entities to the chunk in the entity region, keeping the ones already there
+fn save_entities(
+    entity_provider: &RegionFolder,
+    index: ChunkIndex,
+    mut entities: Vec<CompoundTag>,
+) -> Result<()> {
+    let mut nbt = match entity_provider.read_chunk(index)? {
+        Some(nbt) => nbt,
+        None => {
+            let mut nbt = CompoundTag::new();
+            nbt.insert_i32_vec("Position", vec![index.0, index.1]);
+            nbt
         }
+    };
+    if let Ok(existing) = nbt.get_compound_tag_vec("Entities") {
+        entities.splice(0..0, existing.into_iter().cloned());
     }
+    nbt.insert_i32("DataVersion", DATA_VERSION);
+    nbt.insert_compound_tag_vec("Entities", entities);
+    entity_provider.write_chunk(index, &nbt)
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -595,4 +895,4 @@ pub struct SetBlock {
 }
 
 #[derive(Default)]
-pub struct RecordingCursor(usize);
+pub struct RecordingCursor(pub(crate) usize);
@@ -0,0 +1,193 @@
+use itertools::Itertools;
+use nbt::CompoundTag;
+
+use crate::*;
+
+/// View of a part of the level that can be modified independently of the level itself.
+/// Changes are buffered in the region, so several disjoint regions can be worked on at the
+/// same time (e.g. on different threads) and then applied via [`Level::apply_region`].
+pub struct Region<'a> {
+    level: &'a Level,
+    area: Rect,
+    changes: RegionChanges,
+}
+
+/// Changes made in a [`Region`]
+pub struct RegionChanges {
+    area: Rect,
+    blocks: HashMap<IVec3, Block>,
+    // None if removed
+    tile_entities: HashMap<IVec3, Option<CompoundTag>>,
+    heights: HashMap<IVec2, i32>,
+    water: HashMap<IVec2, Option<i32>>,
+    // Only the part of the recording that hasn't been popped by the generator
+    recording: Vec<SetBlock>,
+}
+
+impl RegionChanges {
+    pub fn area(&self) -> Rect {
+        self.area
+    }
+
+    pub fn recording(&self) -> &[SetBlock] {
+        &self.recording
+    }
+}
+
+impl Level {
+    /// Regions that are modified at the same time must not overlap
+    pub fn region_mut(&self, area: Rect) -> Region<'_> {
+        Region {
+            level: self,
+            area,
+            changes: RegionChanges {
+                area,
+                blocks: default(),
+                tile_entities: default(),
+                heights: default(),
+                water: default(),
+                recording: default(),
+            },
+        }
+    }
+
+    pub fn apply_region(&mut self, changes: RegionChanges) {
+        let cursor = self.recording_cursor();
+        // Sorted so the level's change log doesn't depend on the hash map's order
+        let mut blocks = changes.blocks.into_iter().collect_vec();
+        blocks.sort_unstable_by_key(|(pos, _)| pos.to_array());
+        for (pos, block) in blocks {
+            self(pos, block);
+        }
+        // Blocks popped from the region's recording are handled by the generator (e.g. placed by
+        // a construction site), so only the remaining ones get recorded, in their original order
+        self.setblock_recording.truncate(cursor.0);
+        self.setblock_recording.extend(changes.recording);
+        for (pos, nbt) in changes.tile_entities {
+            match nbt {
+                Some(nbt) => self.set_tile_entity(pos, nbt),
+                // Usually already removed by setting the block, but not if the region ended up
+                // putting back the block that was there before
+                None => {
+                    if self.tile_entities.remove(&pos).is_some() {
+                        let chunk_index = self.chunk_index(pos.into());
+                        self.dirty_chunks[chunk_index] = true;
+                    }
+                }
+            }
+        }
+        for (column, height) in changes.heights {
+            (self.height)(column, height);
+        }
+        for (column, water) in changes.water {
+            (self.water)(column, water);
+        }
+    }
+}
+
+impl<'a> Region<'a> {
+    pub fn level(&self) -> &'a Level {
+        self.level
+    }
+
+    pub fn finish(self) -> RegionChanges {
+        self.changes
+    }
+
+    // Writes outside the region could race with other regions. That's a bug in the caller,
+    // release builds drop them.
+    fn in_bounds(&self, column: IVec2) -> bool {
+        let contained = self.area.contains(column);
+        debug_assert!(
+            contained,
+            "Write outside of region {:?} at {column}",
+            self.area
+        );
+        contained
+    }
+}
+
+impl WorldView for Region<'_> {
+    fn get(&self, pos: IVec3) -> Block {
+        if let Some(block) = self.changes.blocks.get(&pos) {
+            *block
+        } else {
+            (self.level)(pos)
+        }
+    }
+
+    fn set(&mut self, pos: IVec3, block: Block) {
+        if !self.in_bounds(pos.truncate()) {
+            return;
+        }
+        let previous = self.get(pos);
+        if previous != block {
+            self.changes.tile_entities.insert(pos, None);
+            self.changes.recording.push(SetBlock {
+                pos,
+                block,
+                previous,
+            });
+        }
+        self.changes.blocks.insert(pos, block);
+    }
+
+    fn tile_entity(&self, pos: IVec3) -> Option<CompoundTag> {
+        match self.changes.tile_entities.get(&pos) {
+            Some(nbt) => nbt.clone(),
+            None => self.level.tile_entity(pos),
+        }
+    }
+
+    fn set_tile_entity(&mut self, pos: IVec3, nbt: CompoundTag) {
+        if !self.in_bounds(pos.truncate()) {
+            return;
+        }
+        self.changes.tile_entities.insert(pos, Some(nbt));
+    }
+
+    fn height(&self, column: IVec2) -> i32 {
+        if let Some(height) = self.changes.heights.get(&column) {
+            *height
+        } else {
+            (self.level.height)(column)
+        }
+    }
+
+    fn set_height(&mut self, column: IVec2, height: i32) {
+        if !self.in_bounds(column) {
+            return;
+        }
+        self.changes.heights.insert(column, height);
+    }
+
+    fn water_level(&self, column: IVec2) -> Option<i32> {
+        match self.changes.water.get(&column) {
+            Some(water) => *water,
+            None => (self.level.water)(column),
+        }
+    }
+
+    fn set_water_level(&mut self, column: IVec2, level: Option<i32>) {
+        if !self.in_bounds(column) {
+            return;
+        }
+        self.changes.water.insert(column, level);
+    }
+
+    fn biome(&self, column: IVec2) -> Biome {
+        (self.level.biome)(column)
+    }
+
+    fn area(&self) -> Rect {
+        self.area
+    }
+
+    fn recording_cursor(&self) -> RecordingCursor {
+        RecordingCursor(self.changes.recording.len())
+    }
+
+    fn pop_recording(&mut self, cursor: RecordingCursor) -> Vec<SetBlock> {
+        self.changes.recording.drain(cursor.0..).collect()
+    }
+}
@@ -0,0 +1,185 @@
+use std::ops::Shr;
+
+use anyhow::{bail, Result};
+
+use crate::{default, HashMap};
+
+use super::Block;
+
+const SECTION_VOLUME: usize = 16 * 16 * 16;
+
+/// 16×16×16 blocks in X->Y->Z order.
+///
+/// Most sections are all air/stone or only contain a handful of different blocks, so instead of
+/// always storing 16 KiB of blocks, they are stored as indices into a section-local palette.
+/// Sections with too many different blocks get converted to plain block storage.
+#[derive(Clone)]
+pub struct Section(Storage);
+
+#[derive(Clone)]
+enum Storage {
+    Single(Block),
+    Paletted {
+        palette: Vec<Block>,
+        indices: Box<[u8; SECTION_VOLUME]>,
+    },
+    Direct(Box<[Block; SECTION_VOLUME]>),
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Section(Storage::Single(Block::Air))
+    }
+}
+
+impl Section {
+    /// Section consisting only of `block`
+    pub fn single(block: Block) -> Self {
+        Section(Storage::Single(block))
+    }
+
+    /// Builds a section from a palette and a palette index for each block. Fails if an index
+    /// is out of the palette's bounds.
+    pub fn from_palette(
+        palette: Vec<Block>,
+        indices: impl IntoIterator<Item = usize>,
+    ) -> Result<Self> {
+        let Some(&first) = palette.first() else {
+            bail!("Empty palette")
+        };
+        if palette.len() == 1 {
+            return Ok(Self::single(first));
+        }
+        let mut packed = Box::new([0; SECTION_VOLUME]);
+        for (packed, index) in packed.iter_mut().zip(indices) {
+            if index >= palette.len() {
+                bail!(
+                    "Palette index {index} out of bounds for {} entries",
+                    palette.len()
+                );
+            }
+            *packed = index;
+        }
+        Ok(if palette.len() <= u8::MAX as usize + 1 {
+            Section(Storage::Paletted {
+                palette,
+                indices: Box::new(packed.map(|index| index as u8)),
+            })
+        } else {
+            Section(Storage::Direct(Box::new(
+                packed.map(|index| palette[index]),
+            )))
+        })
+    }
+
+    pub fn get(&self, index: usize) -> Block {
+        match &self.0 {
+            Storage::Single(block) => *block,
+            Storage::Paletted { palette, indices } => palette[indices[index] as usize],
+            Storage::Direct(blocks) => blocks[index],
+        }
+    }
+
+    /// Returns the previous block
+    pub fn set(&mut self, index: usize, block: Block) -> Block {
+        let previous = self.get(index);
+        if previous == block {
+            return previous;
+        }
+        match &mut self.0 {
+            Storage::Single(single) => {
+                let single = *single;
+                self.0 = Storage::Paletted {
+                    palette: vec![single, block],
+                    indices: Box::new([0; SECTION_VOLUME]),
+                };
+                self.set(index, block);
+            }
+            Storage::Paletted { palette, indices } => {
+                if let Some(palette_index) = palette.iter().position(|b| *b == block) {
+                    indices[index] = palette_index as u8;
+                } else if palette.len() <= u8::MAX as usize {
+                    indices[index] = palette.len() as u8;
+                    palette.push(block);
+                } else {
+                    // Palette entries aren't removed when they stop being used, so try that first
+                    self.compact();
+                    if let Storage::Paletted { palette, .. } = &self.0
+                        && palette.len() > u8::MAX as usize
+                    {
+                        self.0 = Storage::Direct(Box::new(std::array::from_fn(|i| self.get(i))));
+                    }
+                    self.set(index, block);
+                }
+            }
+            Storage::Direct(blocks) => blocks[index] = block,
+        }
+        previous
+    }
+
+    /// Palette and palette indices packed into longs, as stored in chunk nbt.
+    /// Minecraft seems to always have Air as id 0 even if there is none.
+    pub fn pack(&self) -> (Vec<Block>, Vec<i64>) {
+        let mut palette = vec![Block::Air];
+        let mut palette_indices: HashMap<Block, usize> = default();
+        palette_indices.insert(Block::Air, 0);
+        for block in self.iter() {
+            palette_indices.entry(block).or_insert_with(|| {
+                palette.push(block);
+                palette.len() - 1
+            });
+        }
+
+        let bits_per_index = bits_per_index(palette.len());
+        let per_long = 64 / bits_per_index;
+        let mut data = vec![0; SECTION_VOLUME.div_ceil(per_long)];
+        for (i, block) in self.iter().enumerate() {
+            data[i / per_long] |=
+                (palette_indices[&block] << (i % per_long * bits_per_index)) as i64;
+        }
+        (palette, data)
+    }
+
+    /// Inverse of `pack`. Missing data means all blocks are the first palette entry.
+    pub fn unpack(palette: Vec<Block>, data: &[i64]) -> Result<Self> {
+        if data.is_empty() {
+            return Self::from_palette(palette, []);
+        }
+        let bits_per_index = bits_per_index(palette.len());
+        let per_long = 64 / bits_per_index;
+        if data.len() < SECTION_VOLUME.div_ceil(per_long) {
+            bail!("Only {} longs of block data", data.len());
+        }
+        let indices = (0..SECTION_VOLUME).map(|i| {
+            (data[i / per_long] as u64).shr(i % per_long * bits_per_index) as usize
+                % (1 << bits_per_index)
+        });
+        Self::from_palette(palette, indices)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Block> + '_ {
+        (0..SECTION_VOLUME).map(|i| self.get(i))
+    }
+
+    fn compact(&mut self) {
+        let Storage::Paletted { palette, indices } = &self.0 else {
+            return;
+        };
+        let mut compacted = Vec::new();
+        let mut remap = vec![None; palette.len()];
+        let indices = (**indices).map(|index| {
+            *remap[index as usize].get_or_insert_with(|| {
+                compacted.push(palette[index as usize]);
+                (compacted.len() - 1) as u8
+            })
+        });
+        self.0 = Storage::Paletted {
+            palette: compacted,
+            indices: Box::new(indices),
+        };
+    }
+}
+
+fn bits_per_index(palette_len: usize) -> usize {
+    palette_len.next_power_of_two().ilog2().max(4) as usize
+}
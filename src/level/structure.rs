@@ -0,0 +1,84 @@
+use nbt::CompoundTag;
+
+use crate::geometry::*;
+
+/// Structure generated by Minecraft, such as a village
+#[derive(Debug, Clone)]
+pub struct StructureStart {
+    /// E.g. `minecraft:village_plains`
+    pub id: String,
+    /// The first piece is where generation started (for villages, the meeting point)
+    pub pieces: Vec<StructurePiece>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructurePiece {
+    /// Pool element for jigsaw pieces (e.g. `minecraft:village/plains/houses/plains_small_house_1`),
+    /// otherwise the piece type
+    pub element: String,
+    pub bounds: Cuboid,
+}
+
+impl StructureStart {
+    pub fn area(&self) -> Rect {
+        self.pieces
+            .iter()
+            .map(|piece| piece.bounds.d2())
+            .reduce(|a, b| Rect {
+                min: a.min.min(b.min),
+                max: a.max.max(b.max),
+            })
+            .unwrap()
+    }
+}
+
+/// Structure starts are only stored in the chunk they started in
+pub(super) fn load_structure_starts(chunk: &CompoundTag) -> Vec<StructureStart> {
+    let Ok(starts) = chunk
+        .get_compound_tag("structures")
+        .and_then(|structures| structures.get_compound_tag("starts"))
+    else {
+        return Vec::new();
+    };
+    starts
+        .iter()
+        .filter_map(|(_, start)| {
+            let nbt::Tag::Compound(start) = start else {
+                return None;
+            };
+            let id = start.get_str("id").ok()?;
+            // Structures that were supposed to generate here but couldn't
+            if id == "INVALID" {
+                return None;
+            }
+            let pieces = start
+                .get_compound_tag_vec("children")
+                .ok()?
+                .into_iter()
+                .filter_map(load_piece)
+                .collect::<Vec<_>>();
+            (!pieces.is_empty()).then(|| StructureStart {
+                id: id.to_owned(),
+                pieces,
+            })
+        })
+        .collect()
+}
+
+fn load_piece(piece: &CompoundTag) -> Option<StructurePiece> {
+    let bb = piece.get_i32_vec("BB").ok()?;
+    let [min_x, min_y, min_z, max_x, max_y, max_z] = bb[..] else {
+        return None;
+    };
+    let element = piece
+        .get_compound_tag("pool_element")
+        .and_then(|element| element.get_str("location"))
+        .or_else(|_| piece.get_str("id"))
+        .ok()?
+        .to_owned();
+    Some(StructurePiece {
+        element,
+        // Minecraft's y axis points up
+        bounds: Cuboid::new(ivec3(min_x, min_z, min_y), ivec3(max_x, max_z, max_y)),
+    })
+}
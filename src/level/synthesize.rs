@@ -0,0 +1,142 @@
+//! Stand-in terrain for chunks Minecraft hasn't generated yet, so the selected area only
+//! needs to be roughly explored
+
+use super::*;
+
+const SEA_LEVEL: i32 = 62;
+/// Distance from generated terrain over which the noise fades in
+const FADE_DISTANCE: f32 = 24.;
+const NOISE_AMPLITUDE: f32 = 4.;
+const NOISE_SCALE: f32 = 24.;
+/// Only every nth column of generated chunks is used as reference
+const SAMPLE_SPACING: i32 = 4;
+
+impl Level {
+    /// Fills the given (empty) chunks with a noisy surface that continues the height and biome
+    /// of the surrounding generated chunks
+    pub(super) fn synthesize_chunks(&mut self, chunks: &[ChunkIndex]) {
+        let missing: HashSet<ChunkIndex> = chunks.iter().copied().collect();
+        let mut samples = Vec::new();
+        for chunk in self.bordering_chunks(&missing) {
+            let area = chunk.area();
+            for x in (area.min.x..=area.max.x).step_by(SAMPLE_SPACING as usize) {
+                for y in (area.min.y..=area.max.y).step_by(SAMPLE_SPACING as usize) {
+                    let column = ivec2(x, y);
+                    samples.push((column, (self.height)(column), (self.biome)(column)));
+                }
+            }
+        }
+
+        for &chunk in chunks {
+            for column in chunk.area() {
+                let (height, biome) = surface(&samples, column);
+                self.fill_column(column, height, biome);
+            }
+            let index = self.chunk_index(chunk);
+            self.dirty_chunks[index] = true;
+        }
+    }
+
+    fn bordering_chunks(&self, missing: &HashSet<ChunkIndex>) -> HashSet<ChunkIndex> {
+        let mut bordering = HashSet::default();
+        for chunk in missing {
+            for dx in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbor = ChunkIndex(chunk.0 + dx, chunk.1 + dz);
+                    if (self.chunk_min.0..=self.chunk_max.0).contains(&neighbor.0)
+                        & (self.chunk_min.1..=self.chunk_max.1).contains(&neighbor.1)
+                        & !missing.contains(&neighbor)
+                    {
+                        bordering.insert(neighbor);
+                    }
+                }
+            }
+        }
+        bordering
+    }
+
+    fn fill_column(&mut self, column: IVec2, height: i32, biome: Biome) {
+        let sandy = matches!(biome, Biome::Desert | Biome::Beach);
+        let underwater = height < SEA_LEVEL;
+        for z in -64..=height.max(SEA_LEVEL) {
+            let block = if z == -64 {
+                Bedrock
+            } else if z < height - 3 {
+                Full(Stone)
+            } else if z < height {
+                if sandy {
+                    Sand
+                } else {
+                    Dirt
+                }
+            } else if z == height {
+                match (sandy, underwater) {
+                    (true, _) => Sand,
+                    (false, true) => Dirt,
+                    (false, false) => Grass,
+                }
+            } else {
+                Water
+            };
+            self.set_unrecorded(column.extend(z), block);
+        }
+        (self.height)(column, height);
+        (self.water)(column, underwater.then_some(SEA_LEVEL));
+        (self.biome)(column, biome);
+    }
+
+    /// Doesn't go through the recording, the terrain is supposed to have been there all along
+    fn set_unrecorded(&mut self, pos: IVec3, block: Block) {
+        let index = self.section_index(pos);
+        self.sections[index]
+            .get_or_insert_default()
+            .set(Self::block_in_section_index(pos), block);
+    }
+}
+
+/// Height and biome of a synthesized column: inverse-distance weighted sample height, plus
+/// noise that grows stronger further away from generated terrain
+fn surface(samples: &[(IVec2, i32, Biome)], column: IVec2) -> (i32, Biome) {
+    let Some(&(_, _, biome)) = samples
+        .iter()
+        .min_by_key(|(pos, ..)| pos.distance_squared(column))
+    else {
+        return (DEFAULT_FLAT_HEIGHT, Biome::Basic);
+    };
+    let mut total = 0.;
+    let mut weights = 0.;
+    let mut nearest = f32::MAX;
+    for &(pos, height, _) in samples {
+        let distance_squared = pos.distance_squared(column) as f32;
+        let weight = 1. / (distance_squared + 1.);
+        total += height as f32 * weight;
+        weights += weight;
+        nearest = nearest.min(distance_squared);
+    }
+    let fade = (nearest.sqrt() / FADE_DISTANCE).min(1.);
+    let pos = column.as_vec2() / NOISE_SCALE;
+    let noise = value_noise(pos) + 0.5 * value_noise(pos * 2.);
+    let height = total / weights + fade * NOISE_AMPLITUDE * noise;
+    (height.round() as i32, biome)
+}
+
+/// Smoothly interpolated lattice noise in -1..1
+fn value_noise(pos: Vec2) -> f32 {
+    let cell = pos.floor();
+    let frac = pos - cell;
+    let smooth = frac * frac * (Vec2::splat(3.) - 2. * frac);
+    let corner = |offset: IVec2| lattice(cell.as_ivec2() + offset);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let bottom = lerp(corner(IVec2::ZERO), corner(IVec2::X), smooth.x);
+    let top = lerp(corner(IVec2::Y), corner(IVec2::ONE), smooth.x);
+    lerp(bottom, top, smooth.y)
+}
+
+fn lattice(pos: IVec2) -> f32 {
+    let mut hash =
+        (pos.x as u32).wrapping_mul(0x8da6b343) ^ (pos.y as u32).wrapping_mul(0xd8163841);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1e995);
+    hash ^= hash >> 15;
+    hash as f32 / u32::MAX as f32 * 2. - 1.
+}
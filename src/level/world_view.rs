@@ -0,0 +1,220 @@
+use nbt::CompoundTag;
+
+use crate::*;
+
+/// Block & column access shared by [`Level`] and wrappers around it, so that generator
+/// functions don't need to care whether they write to the level directly.
+/// Object safe, so `&mut dyn WorldView` works too.
+pub trait WorldView {
+    fn get(&self, pos: IVec3) -> Block;
+
+    fn set(&mut self, pos: IVec3, block: Block);
+
+    fn tile_entity(&self, pos: IVec3) -> Option<CompoundTag>;
+
+    /// Overrides the block entity data derived from the block. Must contain the id.
+    /// Gets cleared when the block is changed.
+    fn set_tile_entity(&mut self, pos: IVec3, nbt: CompoundTag);
+
+    /// Highest solid block (ignoring logs)
+    fn height(&self, column: IVec2) -> i32;
+
+    fn set_height(&mut self, column: IVec2, height: i32);
+
+    fn water_level(&self, column: IVec2) -> Option<i32>;
+
+    fn set_water_level(&mut self, column: IVec2, level: Option<i32>);
+
+    fn biome(&self, column: IVec2) -> Biome;
+
+    /// Area that may be modified
+    fn area(&self) -> Rect;
+
+    /// Marks the current end of the recorded changes
+    fn recording_cursor(&self) -> RecordingCursor;
+
+    /// Removes the changes recorded since the cursor was taken
+    fn pop_recording(&mut self, cursor: RecordingCursor) -> Vec<SetBlock>;
+
+    fn ground(&self, column: IVec2) -> IVec3 {
+        column.extend(self.height(column))
+    }
+
+    /// Like [`ground`](Self::ground), but the water surface instead of the sea floor
+    fn surface(&self, column: IVec2) -> IVec3 {
+        let ground = self.height(column);
+        column.extend(
+            self.water_level(column)
+                .map_or(ground, |water| water.max(ground)),
+        )
+    }
+
+    fn modify(&mut self, pos: IVec3, fun: &mut dyn FnMut(Block) -> Block) {
+        let block = fun(self.get(pos));
+        self.set(pos, block)
+    }
+
+    fn fill(&mut self, iter: impl IntoIterator<Item = IVec3>, mut block: impl BlockOrFn)
+    where
+        Self: Sized,
+    {
+        for pos in iter {
+            self.modify(pos, &mut |b| block.get(b));
+        }
+    }
+
+    fn fill_at(
+        &mut self,
+        iter: impl IntoIterator<Item = IVec2>,
+        z: impl RangeOrSingle,
+        mut block: impl BlockOrFn,
+    ) where
+        Self: Sized,
+    {
+        for pos in iter {
+            for z in z.start()..=z.end() {
+                self.modify(pos.extend(z), &mut |b| block.get(b));
+            }
+        }
+    }
+
+    fn average_height(&self, area: impl IntoIterator<Item = IVec2>) -> f32
+    where
+        Self: Sized,
+    {
+        let mut count = 0;
+        let total: f32 = area
+            .into_iter()
+            .map(|p| {
+                count += 1;
+                self.height(p) as f32
+            })
+            .sum();
+        total / count as f32
+    }
+}
+
+impl WorldView for Level {
+    fn get(&self, pos: IVec3) -> Block {
+        self(pos)
+    }
+
+    fn set(&mut self, pos: IVec3, block: Block) {
+        self(pos, block)
+    }
+
+    fn tile_entity(&self, pos: IVec3) -> Option<CompoundTag> {
+        self.tile_entities.get(&pos).cloned()
+    }
+
+    fn set_tile_entity(&mut self, pos: IVec3, nbt: CompoundTag) {
+        let chunk_index = self.chunk_index(pos.into());
+        self.dirty_chunks[chunk_index] = true;
+        self.tile_entities.insert(pos, nbt);
+    }
+
+    fn height(&self, column: IVec2) -> i32 {
+        (self.height)(column)
+    }
+
+    fn set_height(&mut self, column: IVec2, height: i32) {
+        (self.height)(column, height)
+    }
+
+    fn water_level(&self, column: IVec2) -> Option<i32> {
+        (self.water)(column)
+    }
+
+    fn set_water_level(&mut self, column: IVec2, level: Option<i32>) {
+        (self.water)(column, level)
+    }
+
+    fn biome(&self, column: IVec2) -> Biome {
+        (self.biome)(column)
+    }
+
+    fn area(&self) -> Rect {
+        Level::area(self)
+    }
+
+    fn recording_cursor(&self) -> RecordingCursor {
+        Level::recording_cursor(self)
+    }
+
+    fn pop_recording(&mut self, cursor: RecordingCursor) -> Vec<SetBlock> {
+        Level::pop_recording(self, cursor).collect()
+    }
+
+    fn ground(&self, column: IVec2) -> IVec3 {
+        Level::ground(self, column)
+    }
+}
+
+/// Closed door in the block at `pos` and the one above, facing `dir`
+pub fn place_door(
+    level: &mut (impl WorldView + ?Sized),
+    pos: IVec3,
+    dir: HDir,
+    species: TreeSpecies,
+) {
+    level.set(pos, Door(species, dir, DoorMeta::empty()));
+    level.set(pos + IVec3::Z, Door(species, dir, DoorMeta::TOP));
+}
+
+/// Bed with its foot at `pos` and its head towards `dir`
+pub fn place_bed(level: &mut (impl WorldView + ?Sized), pos: IVec3, dir: HDir, color: Color) {
+    level.set(pos, Bed(color, dir, BedPart::Foot));
+    level.set(pos + IVec3::from(dir), Bed(color, dir, BedPart::Head));
+}
+
+/// Staircase from `from` to `to`, first along x, then along y, with one step per block.
+/// Excess length becomes a landing of full blocks at the top. The step at the turn is an outer
+/// corner so that it can be walked both ways.
+pub fn stair_run(
+    level: &mut (impl WorldView + ?Sized),
+    from: IVec3,
+    to: IVec3,
+    material: BlockMaterial,
+) {
+    // Columns with their direction of travel towards the next one
+    let mut path = Vec::new();
+    let mut column = from.truncate();
+    for (delta, pos, neg) in [(to.x - from.x, XPos, XNeg), (to.y - from.y, YPos, YNeg)] {
+        let dir = if delta > 0 { pos } else { neg };
+        for _ in 0..delta.abs() {
+            path.push((column, dir));
+            column += IVec2::from(dir);
+        }
+    }
+    path.push((column, path.last().map_or(XPos, |&(_, dir)| dir)));
+
+    let rising = to.z >= from.z;
+    let len = path.len() as i32 - 1;
+    for (i, &(column, dir)) in path.iter().enumerate() {
+        let prev_dir = path[i.saturating_sub(1)].1;
+        let i = i as i32;
+        let (z, step) = if rising {
+            ((from.z + i).min(to.z), from.z + i <= to.z)
+        } else {
+            ((to.z + len - i).min(from.z), to.z + len - i <= from.z)
+        };
+        if !step {
+            level.set(column.extend(z), Full(material));
+            continue;
+        }
+        // Directions in which the stairs are climbed, entering and leaving this column
+        let (enter, leave) = if rising {
+            (prev_dir, dir)
+        } else {
+            (dir.rotated(2), prev_dir.rotated(2))
+        };
+        let shape = if enter == leave {
+            StairShape::Straight
+        } else if leave == enter.rotated(-1) {
+            StairShape::OuterLeft
+        } else {
+            StairShape::OuterRight
+        };
+        level.set(column.extend(z), Stair(material, enter, Bottom, shape));
+    }
+}
@@ -6,24 +6,64 @@
 #![feature(fn_traits)]
 
 // Flat module hierarchy is ok for now
+pub mod accessibility;
+pub mod aqueduct;
+pub mod armor_stand;
+pub mod backup;
+#[cfg(feature = "bedrock")]
+pub mod bedrock;
+pub mod build_recorder;
+pub mod castle;
+pub mod catacombs;
+pub mod cliff_dwelling;
+pub mod compound_layout;
 pub mod debug_image;
+pub mod dzong;
+pub mod floorplan;
 mod geometry;
 mod level;
 // pub mod make_divider;
-pub mod make_name;
-pub mod make_trees;
-pub mod prefab;
-pub mod remove_foliage;
-pub mod sim;
-// pub mod terraform;
 pub mod goods;
+pub mod heraldry;
 pub mod house;
+pub mod hydrology;
+pub mod interior;
+pub mod irrigation;
+pub mod loot;
+pub mod lsystem;
+pub mod make_name;
+pub mod make_trees;
+pub mod map;
+pub mod mill;
+pub mod moat;
+pub mod mob_proofing;
+pub mod nav_graph;
+pub mod nether_outpost;
 pub mod optimize;
+pub mod parallel;
 pub mod pathfind;
+pub mod player_structures;
+pub mod players;
+pub mod portal;
+pub mod prefab;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod quest;
 pub mod rand;
+pub mod rcon;
+pub mod remove_foliage;
 pub mod replay;
+pub mod road;
 pub mod roof;
+pub mod ruins;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod sim;
+pub mod street_furniture;
+pub mod terraform;
 pub mod test_house;
+pub mod tour;
+pub mod transform;
 
 use std::cell::Cell;
 
@@ -0,0 +1,81 @@
+use nbt::{CompoundTag, Tag};
+
+use crate::{replay::Replay, *};
+
+/// Stack of items lying on the ground. It doesn't despawn and can't be picked up, so it stays
+/// as decoration.
+#[derive(Debug, Clone)]
+pub struct DroppedItem {
+    pub pos: Vec3,
+    /// Id without namespace, e.g. `"oak_log"`
+    pub item: &'static str,
+    pub count: u8,
+    /// Degrees clockwise, starting facing south
+    pub yaw: f32,
+}
+
+impl DroppedItem {
+    /// Entity data without the position
+    pub fn nbt(&self) -> CompoundTag {
+        let mut item = CompoundTag::new();
+        item.insert_str("id", format!("minecraft:{}", self.item));
+        item.insert_i8("Count", self.count as i8);
+        let mut nbt = CompoundTag::new();
+        nbt.insert_str("id", "minecraft:item");
+        nbt.insert_compound_tag("Item", item);
+        nbt.insert(
+            "Rotation",
+            Tag::List(vec![Tag::Float(self.yaw), Tag::Float(0.)]),
+        );
+        nbt.insert_i16("Age", -32768);
+        nbt.insert_i16("PickupDelay", 32767);
+        nbt.insert_i8("Invulnerable", 1);
+        nbt
+    }
+
+    /// Gets written to the entities region when saving
+    pub fn place(&self, level: &mut Level) {
+        level.add_entity(self.pos, self.nbt());
+    }
+
+    pub fn summon(&self, replay: &mut Replay) {
+        replay.command(format!(
+            "summon item {} {} {} {}",
+            self.pos.x,
+            self.pos.z,
+            self.pos.y,
+            replay::snbt(&self.nbt())
+        ));
+    }
+}
+
+/// Up to `count` stray items from `items` strewn on dry, free ground around `area`
+pub fn scatter_loot(
+    level: &Level,
+    area: Rect,
+    items: &[&'static str],
+    count: usize,
+) -> Vec<DroppedItem> {
+    let around = area.grow(3);
+    (0..count)
+        .filter_map(|_| {
+            let column = ivec2(
+                rand_range(around.min.x..=around.max.x),
+                rand_range(around.min.y..=around.max.y),
+            );
+            if !level.area().contains(column) || level.water_level(column).is_some() {
+                return None;
+            }
+            let ground = level.ground(column);
+            if !level(ground).solid() || (level(ground + IVec3::Z) != Air) {
+                return None;
+            }
+            Some(DroppedItem {
+                pos: ground.as_vec3() + vec3(rand_f32(0.2, 0.8), rand_f32(0.2, 0.8), 1.),
+                item: items.choose(),
+                count: rand_range(1..=3),
+                yaw: rand_f32(0., 360.),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use nanorand::WyRand;
+
+use crate::*;
+
+/// Stochastic L-system, drawn by a turtle on the block grid:
+/// `F` draws a step forward, `f` moves without drawing, `+`/`-` turn left/right by 90° and
+/// `[`/`]` save/restore position and heading. Other symbols are only used for rewriting.
+pub struct LSystem {
+    pub axiom: &'static str,
+    /// Each symbol gets replaced by one of its alternatives, picked at random
+    pub rules: &'static [(char, &'static [&'static str])],
+}
+
+/// Hilbert curve with side branches, a single winding path with dead ends
+pub const HEDGE_MAZE: LSystem = LSystem {
+    axiom: "A",
+    rules: &[
+        ('A', &["+BF-AFA-FB+"]),
+        ('B', &["-AF+BFB+FA-"]),
+        ('F', &["F", "F", "F[+F]", "F[-F]"]),
+    ],
+};
+
+/// Paths branching out from the middle in all four directions
+pub const GARDEN_PATHS: LSystem = LSystem {
+    axiom: "[X]+[X]+[X]+[X]",
+    rules: &[
+        ('X', &["FFX", "F[+X]F[-X]X", "F[+X]FX", "F[-X]FX"]),
+        ('F', &["F", "FF"]),
+    ],
+};
+
+/// Climbing vines, drawn upwards
+pub const VINES: LSystem = LSystem {
+    axiom: "X",
+    rules: &[
+        ('X', &["F[+X]FX", "F[-X]FX", "FFX", "F[+X][-X]"]),
+        ('F', &["F", "FF"]),
+    ],
+};
+
+impl LSystem {
+    /// Rewrites the axiom `iterations` times. The same seed gives the same result.
+    pub fn expand(&self, iterations: u32, seed: u64) -> String {
+        let rng = RNG.replace(WyRand::new_seed(seed));
+        let mut word = self.axiom.to_owned();
+        for _ in 0..iterations {
+            word = word
+                .chars()
+                .map(
+                    |symbol| match self.rules.iter().find(|(from, _)| *from == symbol) {
+                        Some((_, alternatives)) => alternatives.choose().to_string(),
+                        None => symbol.to_string(),
+                    },
+                )
+                .collect();
+        }
+        RNG.set(rng);
+        word
+    }
+
+    /// Columns the turtle draws over, starting at the origin heading towards +y, with
+    /// steps `step` blocks long
+    pub fn draw(&self, iterations: u32, seed: u64, step: i32) -> Vec<IVec2> {
+        let mut drawn = Vec::new();
+        let mut pos = IVec2::ZERO;
+        let mut heading = YPos;
+        let mut stack = Vec::new();
+        drawn.push(pos);
+        for symbol in self.expand(iterations, seed).chars() {
+            match symbol {
+                'F' | 'f' => {
+                    for _ in 0..step {
+                        pos += IVec2::from(heading);
+                        if symbol == 'F' {
+                            drawn.push(pos);
+                        }
+                    }
+                }
+                '+' => heading = heading.rotated(1),
+                '-' => heading = heading.rotated(-1),
+                '[' => stack.push((pos, heading)),
+                ']' => (pos, heading) = stack.pop().unwrap_or((pos, heading)),
+                _ => (),
+            }
+        }
+        drawn
+    }
+
+    /// Largest drawing (at most `max_iterations`) that fits into `size`, centered on the origin
+    pub fn draw_fitting(
+        &self,
+        size: IVec2,
+        max_iterations: u32,
+        seed: u64,
+        step: i32,
+    ) -> Vec<IVec2> {
+        let mut best = vec![IVec2::ZERO];
+        for iterations in 1..=max_iterations {
+            let drawing = self.draw(iterations, seed, step);
+            let bounds = drawing.iter().fold(
+                Rect {
+                    min: IVec2::ZERO,
+                    max: IVec2::ZERO,
+                },
+                |bounds, &column| Rect {
+                    min: bounds.min.min(column),
+                    max: bounds.max.max(column),
+                },
+            );
+            if bounds.size().cmpgt(size).any() {
+                break;
+            }
+            let center = bounds.center();
+            best = drawing.into_iter().map(|column| column - center).collect();
+        }
+        best
+    }
+}
+
+/// Hedges with a winding path through them, filling `area`
+pub fn hedge_maze(level: &mut impl WorldView, area: Rect, seed: u64) {
+    let path: HashSet<IVec2> = HEDGE_MAZE
+        .draw_fitting(area.size() - IVec2::splat(2), 5, seed, 2)
+        .into_iter()
+        .map(|column| column + area.center())
+        .collect();
+    let species = level.biome(area.center()).default_tree_species();
+    for column in area {
+        let ground = level.ground(column);
+        if path.contains(&column) {
+            level.set(ground, Path);
+        } else {
+            for z in 1..=2 {
+                level.set(ground + IVec3::Z * z, Leaves(species, None));
+            }
+        }
+    }
+}
+
+/// Ornamental paths on the ground of `area`
+pub fn garden_paths(level: &mut impl WorldView, area: Rect, seed: u64) {
+    for column in GARDEN_PATHS.draw_fitting(area.size(), 4, seed, 1) {
+        let column = column + area.center();
+        let ground = level.ground(column);
+        if level.get(ground).dirtsoil() && level.get(ground + IVec3::Z) == Air {
+            level.set(ground, Path);
+        }
+    }
+}
+
+/// Vines growing up a wall from `base` (in front of the wall, facing away from it), at most
+/// `width` blocks to each side
+pub fn wall_vines(level: &mut impl WorldView, base: IVec3, facing: HDir, width: i32, seed: u64) {
+    let vine = Block::from_name("vine", &[(facing.rotated(2).to_str(), "true")]);
+    let side = IVec2::from(facing.rotated(1));
+    for offset in VINES.draw_fitting(ivec2(width * 2 + 1, width * 4), 4, seed, 1) {
+        // The drawing is centered, but vines grow up from the base
+        let pos = base + (side * offset.x).extend(offset.y + width * 2);
+        if level.get(pos) == Air && level.get(pos - IVec3::from(facing)).solid() {
+            level.set(pos, vine);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs::{create_dir_all, File};
+
+use anyhow::{anyhow, Result};
+use nbt::CompoundTag;
+
+use crate::{heraldry::*, *};
+
+/// Side length of a map in pixels
+const MAP_SIZE: i32 = 128;
+/// Vanilla map scales go up to 1:16
+const MAX_SCALE: i32 = 4;
+const WATER: u8 = 12;
+
+/// Point of interest to mark on the map with a banner
+pub struct Marker {
+    pub pos: IVec3,
+    pub name: String,
+    pub color: Color,
+    pub patterns: Vec<(BannerPattern, Color)>,
+}
+
+impl Marker {
+    /// Standing banner to place at the marker. Maps only keep banners that actually exist
+    /// in the world, with the same name.
+    pub fn banner(&self) -> (Block, CompoundTag) {
+        let block = Banner(self.color, 0);
+        let mut nbt = banner_nbt(&self.patterns);
+        nbt.insert_str("CustomName", self.text());
+        (block, nbt)
+    }
+
+    fn text(&self) -> String {
+        format!("{{\"text\":\"{}\"}}", self.name)
+    }
+}
+
+/// Renders the area into a locked map (`data/map_<id>.dat`) showing the markers,
+/// returning the id of the map
+pub fn write_map(level: &Level, area: Rect, markers: &[Marker]) -> Result<i32> {
+    let scale = (0..=MAX_SCALE)
+        .find(|scale| MAP_SIZE << scale >= area.size().max_element())
+        .unwrap_or(MAX_SCALE);
+    let center = area.center();
+
+    let mut data = CompoundTag::new();
+    data.insert_str("dimension", "minecraft:overworld");
+    data.insert_i32("xCenter", center.x);
+    data.insert_i32("zCenter", center.y);
+    data.insert_i8("scale", scale as i8);
+    data.insert_i8("locked", 1);
+    data.insert_i8("trackingPosition", 1);
+    data.insert_i8("unlimitedTracking", 0);
+    data.insert_i8_vec("colors", render(level, center, scale));
+    data.insert_compound_tag_vec(
+        "banners",
+        markers.iter().map(|marker| {
+            let mut pos = CompoundTag::new();
+            pos.insert_i32("X", marker.pos.x);
+            pos.insert_i32("Y", marker.pos.z);
+            pos.insert_i32("Z", marker.pos.y);
+            let mut banner = CompoundTag::new();
+            banner.insert_compound_tag("Pos", pos);
+            banner.insert_str("Color", marker.color.to_string());
+            banner.insert_str("Name", marker.text());
+            banner
+        }),
+    );
+    data.insert_compound_tag_vec("frames", Vec::new());
+
+    let mut nbt = CompoundTag::new();
+    nbt.insert_i32("DataVersion", DATA_VERSION);
+    nbt.insert_compound_tag("data", data);
+
+    let id = next_map_id(level)?;
+    let mut file = File::create(level.path.join(format!("data/map_{id}.dat")))?;
+    nbt::encode::write_gzip_compound_tag(&mut file, &nbt)?;
+    Ok(id)
+}
+
+/// Chest holding the given maps, named by their titles
+pub fn map_chest(maps: &[(i32, String)]) -> (Block, CompoundTag) {
+    let block = Block::from_name("chest", &[("facing", "north")]);
+    let mut nbt = CompoundTag::new();
+    nbt.insert_str("id", "minecraft:chest");
+    nbt.insert_compound_tag_vec(
+        "Items",
+        maps.iter().enumerate().map(|(slot, (id, title))| {
+            let mut display = CompoundTag::new();
+            display.insert_str("Name", format!("{{\"text\":\"{title}\",\"italic\":false}}"));
+            let mut tag = CompoundTag::new();
+            tag.insert_i32("map", *id);
+            tag.insert_compound_tag("display", display);
+            let mut item = CompoundTag::new();
+            item.insert_i8("Slot", slot as i8);
+            item.insert_str("id", "minecraft:filled_map");
+            item.insert_i8("Count", 1);
+            item.insert_compound_tag("tag", tag);
+            item
+        }),
+    );
+    (block, nbt)
+}
+
+/// World spawn, from `level.dat`
+pub fn spawn_point(level: &Level) -> Result<IVec3> {
+    let mut file = File::open(level.path.join("level.dat"))?;
+    let nbt = nbt::decode::read_gzip_compound_tag(&mut file)
+        .map_err(|_| anyhow!("Failed to read level.dat"))?;
+    let data = nbt
+        .get_compound_tag("Data")
+        .map_err(|_| anyhow!("Corrupt level.dat"))?;
+    let coord = |name| data.get_i32(name).map_err(|_| anyhow!("Corrupt level.dat"));
+    Ok(ivec3(coord("SpawnX")?, coord("SpawnZ")?, coord("SpawnY")?))
+}
+
+/// Claims the next free map id from `data/idcounts.dat`
+fn next_map_id(level: &Level) -> Result<i32> {
+    let data_path = level.path.join("data");
+    create_dir_all(&data_path)?;
+    let path = data_path.join("idcounts.dat");
+    let mut nbt = File::open(&path)
+        .ok()
+        .and_then(|mut file| nbt::decode::read_gzip_compound_tag(&mut file).ok())
+        .unwrap_or_else(|| {
+            let mut nbt = CompoundTag::new();
+            nbt.insert_i32("DataVersion", DATA_VERSION);
+            nbt.insert_compound_tag("data", CompoundTag::new());
+            nbt
+        });
+    let counts: &mut CompoundTag = nbt.get_mut("data").unwrap();
+    let id = counts.get_i32("map").map_or(0, |last| last + 1);
+    counts.insert_i32("map", id);
+    nbt::encode::write_gzip_compound_tag(&mut File::create(&path)?, &nbt)?;
+    Ok(id)
+}
+
+/// Map colors of the top blocks, shaded by the height difference to the north like vanilla
+fn render(level: &Level, center: IVec2, scale: i32) -> Vec<i8> {
+    let area = level.area();
+    let mut colors = vec![0; (MAP_SIZE * MAP_SIZE) as usize];
+    for x in 0..MAP_SIZE {
+        let mut prev_height = None;
+        for y in 0..MAP_SIZE {
+            let column = center + (ivec2(x, y) - IVec2::splat(MAP_SIZE / 2)) * (1 << scale);
+            if !area.contains(column) {
+                prev_height = None;
+                continue;
+            }
+            let (height, base) = top_block(level, column);
+            let shade = match prev_height {
+                _ if base == WATER => 1,
+                Some(prev) if height > prev => 2,
+                Some(prev) if height < prev => 0,
+                _ => 1,
+            };
+            prev_height = Some(height);
+            colors[(x + y * MAP_SIZE) as usize] = (base * 4 + shade) as i8;
+        }
+    }
+    colors
+}
+
+fn top_block(level: &Level, column: IVec2) -> (i32, u8) {
+    let ground = (level.height)(column);
+    (ground..=ground + 16)
+        .rev()
+        .find_map(|z| map_color(level(column.extend(z))).map(|color| (z, color)))
+        .unwrap_or((ground, 11))
+}
+
+/// Base map color id, None for blocks that don't show up on maps
+fn map_color(block: Block) -> Option<u8> {
+    Some(match block {
+        Air | Barrier | GlassPane(None) => return None,
+        Grass => 1,
+        Sand | Full(Sandstone | SmoothSandstone) => 2,
+        Wool(White) | GlassPane(Some(White)) | Bed(White, ..) | SnowLayer => 8,
+        Wool(color) | GlassPane(Some(color)) | Bed(color, ..) => 14 + color as u8,
+        Terracotta(Some(color)) => 36 + color as u8,
+        Terracotta(None) => 15,
+        Leaves(..) | SmallPlant(..) | TallPlant(..) | GroundPlant(..) | GrowingCrop(..) => 7,
+        Dirt | CoarseDirt | Farmland | Path | PackedMud => 10,
+        Podzol => 34,
+        Water => WATER,
+        Lava => 4,
+        Hay => 18,
+        Log(..) | Barrel | CraftingTable | Trapdoor(..) | Door(..) => 13,
+        Full(Wood(_)) | Slab(Wood(_), _) | Stair(Wood(_), ..) | Fence(Wood(_)) => 13,
+        Full(Brick) | Slab(Brick, _) | Stair(Brick, ..) => 28,
+        _ => 11,
+    })
+}
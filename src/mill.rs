@@ -0,0 +1,133 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::{
+    house::shack,
+    hydrology::{Hydrology, WaterKind},
+    remove_foliage::remove_trees,
+    roof::roof,
+    sim::PlaceList,
+    *,
+};
+
+const SAIL_LENGTH: i32 = 6;
+const WHEEL_RADIUS: i32 = 3;
+
+/// Stone tower with a wooden cap and four sails, best placed on a hill
+pub fn windmill(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let floor = level.average_height(area.border()).round() as i32;
+    let top = floor + 9;
+    let facing = *HDir::ALL.choose();
+
+    let cursor = level.recording_cursor();
+    remove_trees(level, area.grow(SAIL_LENGTH));
+    for column in area {
+        let mut z = floor;
+        while !level.get(column.extend(z)).solid() | level.get(column.extend(z)).soil() {
+            level.set(column.extend(z), Full(Cobble));
+            z -= 1;
+        }
+        for z in (floor + 1..=level.height(column)).rev() {
+            level.set(column.extend(z), Air);
+        }
+        level.set(column.extend(floor), Full(Cobble));
+    }
+    for column in area.border() {
+        for z in floor + 1..=top {
+            level.set(column.extend(z), Full(Cobble));
+        }
+    }
+    let door = area.side_center(facing.rotated(2));
+    place_door(level, door.extend(floor + 1), facing.rotated(2), Oak);
+    let mut rec: PlaceList = level.pop_recording(cursor).into();
+    rec.extend(roof(level, area.grow(1), top + 1, Wood(Spruce)));
+
+    // Sails on the front, caught mid-turn
+    let cursor = level.recording_cursor();
+    let front = area.side_center(facing) + facing;
+    let hub = front.extend(top - 1);
+    let side = IVec3::from(facing.rotated(1)).as_vec3();
+    level.set(hub, Log(Spruce, LogType::Normal(horizontal_axis(facing))));
+    let rotation = rand_f32(0., FRAC_PI_2);
+    for arm in 0..4 {
+        let angle = rotation + arm as f32 * FRAC_PI_2;
+        let along = side * angle.cos() + Vec3::Z * angle.sin();
+        let across = side * (angle + FRAC_PI_2).cos() + Vec3::Z * (angle + FRAC_PI_2).sin();
+        for distance in 1..=SAIL_LENGTH {
+            let spar = hub + (along * distance as f32).round().as_ivec3();
+            level.set(spar, Fence(Wood(Spruce)));
+            if distance > 1 {
+                let cloth = hub + (along * distance as f32 + across).round().as_ivec3();
+                level.modify(cloth, &mut |block| {
+                    if block == Air {
+                        Wool(White)
+                    } else {
+                        block
+                    }
+                });
+            }
+        }
+    }
+    rec.extend(level.pop_recording(cursor));
+    rec
+}
+
+/// Side of the area that borders a river, if any. Lakes and the sea don't flow, so they
+/// can't drive a wheel.
+pub fn watermill_side(level: &impl WorldView, hydrology: &Hydrology, area: Rect) -> Option<HDir> {
+    HDir::ALL.into_iter().find(|&dir| {
+        let column = area.side_center(dir) + IVec2::from(dir) * (WHEEL_RADIUS + 1);
+        level.area().contains(column) && hydrology.kind(column) == Some(WaterKind::River)
+    })
+}
+
+/// Mill house with a water wheel reaching into the water on the `water` side
+pub fn watermill(level: &mut impl WorldView, area: Rect, water: HDir) -> PlaceList {
+    let mut rec = shack(level, area);
+
+    let cursor = level.recording_cursor();
+    let wheel_column = area.side_center(water) + IVec2::from(water) * 2;
+    let water_level = level
+        .water_level(wheel_column + IVec2::from(water) * WHEEL_RADIUS)
+        .unwrap_or_else(|| level.height(wheel_column));
+    let hub = wheel_column.extend(water_level + 1);
+    // The wheel turns around the axis pointing into the water
+    let axis = horizontal_axis(water);
+    let side = IVec3::from(water.rotated(1));
+
+    // Axle into the building
+    for distance in 0..=2 {
+        level.set(
+            hub - IVec3::from(water) * distance,
+            Log(Spruce, LogType::Normal(axis)),
+        );
+    }
+    // Spokes & paddles
+    let steps = 24;
+    for step in 0..steps {
+        let angle = step as f32 / steps as f32 * TAU;
+        let dir = side.as_vec3() * angle.cos() + Vec3::Z * angle.sin();
+        for distance in 1..WHEEL_RADIUS {
+            if step % (steps / 4) == 0 {
+                let pos = hub + (dir * distance as f32).round().as_ivec3();
+                level.set(pos, Fence(Wood(Spruce)));
+            }
+        }
+        let pos = hub + (dir * WHEEL_RADIUS as f32).round().as_ivec3();
+        let half = if pos.z > hub.z { Top } else { Bottom };
+        let paddle = if step % 2 == 0 {
+            Slab(Wood(Spruce), half)
+        } else {
+            Stair(Wood(Spruce), water.rotated(1), half, StairShape::Straight)
+        };
+        level.set(pos, paddle);
+    }
+    rec.extend(level.pop_recording(cursor));
+    rec
+}
+
+fn horizontal_axis(dir: HDir) -> Axis {
+    match dir {
+        XPos | XNeg => Axis::X,
+        YPos | YNeg => Axis::Y,
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{terraform::flood, *};
+
+/// Digs a ring channel around the outside of the wall, lines it with cobble and floods it.
+/// A drawbridge is placed in front of `gate` (a column on the wall outline).
+/// Returns the columns of the moat.
+pub fn moat(
+    level: &mut impl WorldView,
+    wall: &Polygon,
+    gate: IVec2,
+    width: i32,
+    depth: i32,
+) -> Vec<IVec2> {
+    // Keep some ground in front of the wall so it doesn't get undermined
+    let gap = 2;
+    let wall_columns: HashSet<IVec2> = wall.border(LineStyle::Thick).collect();
+    let bounds = wall_columns
+        .iter()
+        .fold(
+            Rect {
+                min: gate,
+                max: gate,
+            },
+            |bounds, column| Rect {
+                min: bounds.min.min(*column),
+                max: bounds.max.max(*column),
+            },
+        )
+        .grow(gap + width + 1);
+
+    let ring: Vec<IVec2> = bounds
+        .into_iter()
+        .filter(|column| !wall_columns.contains(column) & !wall.contains(*column))
+        .filter(|column| {
+            let distance = wall_columns
+                .iter()
+                .map(|wall| wall.as_vec2().distance(column.as_vec2()))
+                .fold(f32::INFINITY, f32::min);
+            (distance > gap as f32) & (distance <= (gap + width) as f32)
+        })
+        .filter(|column| level.area().contains(*column))
+        .collect();
+    if ring.is_empty() {
+        return ring;
+    }
+    let ring_set: HashSet<IVec2> = ring.iter().copied().collect();
+
+    // Stay below the lowest bank so that the water is held in
+    let surface = ring
+        .iter()
+        .map(|column| level.height(*column))
+        .min()
+        .unwrap()
+        - 1;
+    let bottom = surface - depth + 1;
+
+    for &column in &ring {
+        for z in (bottom..=level.height(column)).rev() {
+            level.set(column.extend(z), Air);
+        }
+        level.set(column.extend(bottom - 1), Full(Cobble));
+        for off in NEIGHBORS_2D {
+            let neighbor = column + off;
+            if ring_set.contains(&neighbor) {
+                continue;
+            }
+            for z in bottom..=surface {
+                let pos = neighbor.extend(z);
+                let block = level.get(pos);
+                if !block.solid() | block.soil() {
+                    level.set(pos, Full(Cobble));
+                }
+            }
+        }
+        level.set_height(column, bottom - 1);
+    }
+
+    let source = ring[0].extend(surface);
+    level.set(source, Water);
+    level.set_water_level(ring[0], Some(surface));
+    if flood(level, source, (ring.len() * depth as usize) * 2).is_none() {
+        eprintln!("Moat at {:?} doesn't hold water", ring[0]);
+    }
+
+    drawbridge(level, gate, &ring_set, &wall_columns);
+
+    ring
+}
+
+/// Lowered drawbridge spanning the moat in front of the gate
+fn drawbridge(
+    level: &mut impl WorldView,
+    gate: IVec2,
+    moat: &HashSet<IVec2>,
+    wall: &HashSet<IVec2>,
+) {
+    // The gate faces the side where the moat is closest
+    let Some(dir) = HDir::ALL
+        .into_iter()
+        .filter(|dir| !wall.contains(&(gate + *dir)))
+        .min_by_key(|dir| {
+            (1..32)
+                .find(|distance| moat.contains(&(gate + IVec2::from(*dir) * *distance)))
+                .unwrap_or(i32::MAX)
+        })
+    else {
+        return;
+    };
+    let z = level.height(gate);
+    let step = IVec2::from(dir);
+    let side = IVec2::from(dir.rotated(1));
+    let log_axis = if step.x != 0 { Axis::X } else { Axis::Y };
+
+    let mut crossed = false;
+    for distance in 1..32 {
+        let column = gate + step * distance;
+        if moat.contains(&column) {
+            crossed = true;
+        } else if crossed {
+            break;
+        }
+        for offset in -1..=1 {
+            let column = column + side * offset;
+            let block = if offset == 0 {
+                Full(Wood(Oak))
+            } else {
+                Log(Oak, LogType::Stripped(log_axis))
+            };
+            level.set(column.extend(z), block);
+            for z in z + 1..=z + 2 {
+                level.set(column.extend(z), Air);
+            }
+        }
+    }
+}
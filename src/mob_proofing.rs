@@ -0,0 +1,168 @@
+//! Finds dark spots where monsters could spawn inside buildings and under bridges. Since
+//! 1.18, monsters only spawn at block light 0, so every covered spot that no light source
+//! reaches is a problem on a survival server.
+//!
+//! Light is approximated: it's assumed to spread freely from each source, dropping by one per
+//! block of Manhattan distance, ignoring walls. Open-air spots are left out, the sky lights
+//! them during the day and lanterns on every road would be too much.
+
+use std::str::FromStr;
+
+use bevy_ecs::prelude::*;
+
+use crate::*;
+
+/// How far below the top block of a column to look for rooms
+const SCAN_DEPTH: i32 = 32;
+/// A spot counts as covered if there's a solid block at most this far above it
+const MAX_COVER_HEIGHT: i32 = 16;
+/// Brightest light there is, so no source is further away than this
+const MAX_LIGHT: i32 = 15;
+/// Dark spots printed in full when reporting, the rest only gets counted
+const REPORT_LIMIT: usize = 50;
+/// Lowest and highest block of the overworld
+const MIN_Y: i32 = -64;
+const MAX_Y: i32 = 319;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MobProofing {
+    /// Print the dark spots
+    Report,
+    /// Light up the dark spots with lanterns
+    Fix,
+}
+
+impl FromStr for MobProofing {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "report" => Ok(Self::Report),
+            "fix" => Ok(Self::Fix),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Pass for [`sim::passes::Phase::Finish`] that checks the blocked columns (buildings, roads
+/// and bridges) for dark spots. Should be added after the other passes that build something.
+pub fn audit(mode: MobProofing) -> impl FnMut(&mut Level, &mut World) {
+    move |level: &mut Level, _: &mut World| {
+        let mut lights = Lights::find(level);
+        let mut dark = Vec::new();
+        for column in level.area() {
+            if !level.is_blocked(column) {
+                continue;
+            }
+            let top = level.height(column);
+            for z in (top - SCAN_DEPTH).max(MIN_Y + 1)..=top.min(MAX_Y - 1) {
+                let pos = column.extend(z);
+                if spawnable(level, pos) && covered(level, pos) && !lights.reach(pos) {
+                    dark.push(pos);
+                }
+            }
+        }
+
+        match mode {
+            MobProofing::Report => {
+                for pos in dark.iter().take(REPORT_LIMIT) {
+                    println!("Dark spot at {} {} {}", pos.x, pos.z, pos.y);
+                }
+                if dark.len() > REPORT_LIMIT {
+                    println!("… and {} more dark spots", dark.len() - REPORT_LIMIT);
+                }
+            }
+            MobProofing::Fix => {
+                let mut placed = 0;
+                for pos in dark {
+                    // Earlier lanterns might reach this spot already
+                    if lights.reach(pos) {
+                        continue;
+                    }
+                    let lantern = place_lantern(level, pos);
+                    lights.add(lantern, MAX_LIGHT as u8);
+                    placed += 1;
+                }
+                println!("Placed {placed} lanterns against mob spawning");
+            }
+        }
+    }
+}
+
+/// Whether a monster fits here and can stand on the block below
+fn spawnable(level: &Level, pos: IVec3) -> bool {
+    let floor = level(pos - IVec3::Z);
+    let free = |block: Block| !block.solid() & !matches!(block, Water | Lava);
+    floor.full_sides()
+        && !matches!(floor, Barrier | Bedrock)
+        && free(level(pos))
+        && free(level(pos + IVec3::Z))
+}
+
+fn covered(level: &Level, pos: IVec3) -> bool {
+    (2..=MAX_COVER_HEIGHT.min(MAX_Y - pos.z)).any(|dz| level(pos + IVec3::Z * dz).solid())
+}
+
+/// Hangs a lantern from the ceiling if there's enough headroom, otherwise puts it on the
+/// floor. Returns where it went.
+fn place_lantern(level: &mut Level, pos: IVec3) -> IVec3 {
+    let ceiling = (2..=MAX_COVER_HEIGHT.min(MAX_Y - pos.z))
+        .map(|dz| pos + IVec3::Z * dz)
+        .find(|pos| level(*pos).solid())
+        .unwrap();
+    let hanging = ceiling - IVec3::Z;
+    if (hanging.z >= pos.z + 2) & (level(hanging) == Air) {
+        level(hanging, Block::from_name("lantern", &[("hanging", "true")]));
+        hanging
+    } else {
+        level(pos, Block::from_name("lantern", &[]));
+        pos
+    }
+}
+
+/// Light sources by chunk section, so only the nearby ones need to be checked
+struct Lights(HashMap<IVec3, Vec<(IVec3, u8)>>);
+
+impl Lights {
+    fn find(level: &Level) -> Self {
+        let mut lights = Self(default());
+        for column in level.area() {
+            let top = level.height(column);
+            let bottom = (top - SCAN_DEPTH - MAX_LIGHT).max(MIN_Y);
+            for z in bottom..=(top + MAX_COVER_HEIGHT).min(MAX_Y) {
+                let pos = column.extend(z);
+                let light = level(pos).light();
+                if light > 0 {
+                    lights.add(pos, light);
+                }
+            }
+        }
+        lights
+    }
+
+    fn add(&mut self, pos: IVec3, light: u8) {
+        self.0
+            .entry(pos.div_euclid(IVec3::splat(16)))
+            .or_default()
+            .push((pos, light));
+    }
+
+    /// Whether any light reaches this spot
+    fn reach(&self, pos: IVec3) -> bool {
+        let section = pos.div_euclid(IVec3::splat(16));
+        (-1..=1).any(|x| {
+            (-1..=1).any(|y| {
+                (-1..=1).any(|z| {
+                    self.0
+                        .get(&(section + ivec3(x, y, z)))
+                        .is_some_and(|lights| {
+                            lights.iter().any(|(light_pos, light)| {
+                                let d = (*light_pos - pos).abs();
+                                d.x + d.y + d.z < *light as i32
+                            })
+                        })
+                })
+            })
+        })
+    }
+}
@@ -0,0 +1,232 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::*;
+
+/// Cost of moving by one column, same as walking in [`pathfind`](crate::pathfind::pathfind)
+const STEP_COST: u32 = 3;
+/// Height difference that can still be walked between neighboring columns
+const MAX_STEP: i32 = 1;
+
+/// Crossing from a column on the border of a chunk into the neighboring chunk
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+struct Portal {
+    inside: IVec2,
+    outside: IVec2,
+}
+
+impl Portal {
+    /// The same crossing, seen from the neighboring chunk
+    fn reversed(self) -> Self {
+        Self {
+            inside: self.outside,
+            outside: self.inside,
+        }
+    }
+
+    fn key(self) -> [i32; 4] {
+        [self.inside.x, self.inside.y, self.outside.x, self.outside.y]
+    }
+
+    fn from_key([x, y, outside_x, outside_y]: [i32; 4]) -> Self {
+        Self {
+            inside: ivec2(x, y),
+            outside: ivec2(outside_x, outside_y),
+        }
+    }
+}
+
+/// Portals of a chunk and how expensive it is to get between them
+struct ChunkNav {
+    /// See [`version`]
+    version: u32,
+    portals: Vec<Portal>,
+    /// Indexed by the portals, `None` if there's no way between them within the chunk
+    costs: Vec<Vec<Option<u32>>>,
+}
+
+/// Coarse walkability graph for planning routes across large areas: The chunks are the
+/// clusters, with one node per stretch of border that can be crossed. Chunks get analyzed
+/// when a route first passes through them and are cached until blocks in them or their
+/// neighbors change.
+/// Only considers the heightmap, so the route needs to be refined with the detailed
+/// pathfinder, leg by leg.
+#[derive(Default)]
+pub struct NavGraph {
+    chunks: HashMap<ChunkIndex, ChunkNav>,
+}
+
+impl NavGraph {
+    /// Columns along a route from `from` to `to`, ending with `to`: The crossings from one
+    /// chunk to the next. `None` if there is no dry route.
+    pub fn route(&mut self, level: &Level, from: IVec2, to: IVec2) -> Option<Vec<IVec2>> {
+        let start_chunk = ChunkIndex::from(from);
+        let goal_chunk = ChunkIndex::from(to);
+        let from_start = local_costs(level, start_chunk, from);
+        if start_chunk == goal_chunk && from_start.contains_key(&to) {
+            return Some(vec![to]);
+        }
+        // Costs are symmetric, so this is also the cost from each column to the goal
+        let to_goal = local_costs(level, goal_chunk, to);
+
+        let heuristic = |portal: Portal| {
+            let offset = (portal.inside - to).abs();
+            (offset.x + offset.y) as u32 * STEP_COST
+        };
+        let mut best: HashMap<Portal, (u32, Option<Portal>)> = default();
+        let mut queue = BinaryHeap::new();
+        for &portal in &self.chunk(level, start_chunk).portals {
+            if let Some(&cost) = from_start.get(&portal.inside) {
+                best.insert(portal, (cost, None));
+                queue.push(Reverse((cost + heuristic(portal), cost, portal.key())));
+            }
+        }
+        let mut arrived: Option<(u32, Portal)> = None;
+        while let Some(Reverse((estimate, cost, portal))) = queue.pop() {
+            let portal = Portal::from_key(portal);
+            if let Some((total, _)) = arrived
+                && total <= estimate
+            {
+                break;
+            }
+            if best.get(&portal).is_some_and(|&(known, _)| known < cost) {
+                continue;
+            }
+            let chunk_index = ChunkIndex::from(portal.inside);
+            if chunk_index == goal_chunk
+                && let Some(&remaining) = to_goal.get(&portal.inside)
+                && arrived.is_none_or(|(total, _)| total > cost + remaining)
+            {
+                arrived = Some((cost + remaining, portal));
+            }
+
+            let chunk = self.chunk(level, chunk_index);
+            let index = chunk.portals.iter().position(|&p| p == portal).unwrap();
+            let mut next: Vec<(Portal, u32)> = chunk.costs[index]
+                .iter()
+                .zip(&chunk.portals)
+                .filter_map(|(cost, &other)| Some((other, (*cost)?)))
+                .collect();
+            // Make sure the neighbor is analyzed, it has the matching portal
+            self.chunk(level, ChunkIndex::from(portal.outside));
+            next.push((portal.reversed(), STEP_COST));
+
+            for (neighbor, step) in next {
+                let cost = cost + step;
+                if best.get(&neighbor).is_some_and(|&(known, _)| known <= cost) {
+                    continue;
+                }
+                best.insert(neighbor, (cost, Some(portal)));
+                queue.push(Reverse((cost + heuristic(neighbor), cost, neighbor.key())));
+            }
+        }
+
+        let (_, mut portal) = arrived?;
+        let mut route = vec![to, portal.inside];
+        while let Some(&(_, Some(prev))) = best.get(&portal) {
+            if prev.inside != route.last().copied().unwrap() {
+                route.push(prev.inside);
+            }
+            portal = prev;
+        }
+        route.reverse();
+        Some(route)
+    }
+
+    fn chunk(&mut self, level: &Level, index: ChunkIndex) -> &ChunkNav {
+        let version = version(level, index);
+        let chunk = self
+            .chunks
+            .entry(index)
+            .or_insert_with(|| analyze_chunk(level, index));
+        if chunk.version != version {
+            *chunk = analyze_chunk(level, index);
+        }
+        chunk
+    }
+}
+
+/// Changes if blocks in the chunk or its neighbors (which determine the portals) change
+fn version(level: &Level, index: ChunkIndex) -> u32 {
+    [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)]
+        .into_iter()
+        .map(|(x, z)| level.chunk_changes(ChunkIndex(index.0 + x, index.1 + z)))
+        .fold(0, u32::wrapping_add)
+}
+
+fn passable(level: &Level, from: IVec2, to: IVec2) -> bool {
+    let area = level.area();
+    area.contains(from)
+        && area.contains(to)
+        && level.water_level(from).is_none()
+        && level.water_level(to).is_none()
+        && ((level.height)(from) - (level.height)(to)).abs() <= MAX_STEP
+}
+
+/// Portals in the middle of each stretch of border that can be crossed. Neighboring chunks
+/// find the same stretches, so their portals match up.
+fn analyze_chunk(level: &Level, index: ChunkIndex) -> ChunkNav {
+    let area = index.area();
+    let mut portals = Vec::new();
+    for dir in HDir::ALL {
+        let border = area.side_center(dir);
+        let along = IVec2::from(dir.rotated(1)).abs();
+        let start = border - along * (border - area.min).dot(along);
+        let mut stretch: Vec<IVec2> = Vec::new();
+        for i in 0..=16 {
+            let inside = start + along * i;
+            if i < 16 && passable(level, inside, inside + IVec2::from(dir)) {
+                stretch.push(inside);
+            } else if !stretch.is_empty() {
+                let inside = stretch[stretch.len() / 2];
+                portals.push(Portal {
+                    inside,
+                    outside: inside + IVec2::from(dir),
+                });
+                stretch.clear();
+            }
+        }
+    }
+    let costs = portals
+        .iter()
+        .map(|portal| {
+            let reachable = local_costs(level, index, portal.inside);
+            portals
+                .iter()
+                .map(|other| reachable.get(&other.inside).copied())
+                .collect()
+        })
+        .collect();
+    ChunkNav {
+        version: version(level, index),
+        portals,
+        costs,
+    }
+}
+
+/// Dijkstra from `start` without leaving the chunk
+fn local_costs(level: &Level, index: ChunkIndex, start: IVec2) -> HashMap<IVec2, u32> {
+    let area = index.area();
+    let mut costs = HashMap::default();
+    let mut queue = BinaryHeap::new();
+    costs.insert(start, 0);
+    queue.push(Reverse((0, start.to_array())));
+    while let Some(Reverse((cost, column))) = queue.pop() {
+        let column = IVec2::from_array(column);
+        if costs[&column] < cost {
+            continue;
+        }
+        for dir in HDir::ALL {
+            let neighbor = column + IVec2::from(dir);
+            if !area.contains(neighbor) || !passable(level, column, neighbor) {
+                continue;
+            }
+            let cost = cost + STEP_COST;
+            if costs.get(&neighbor).is_some_and(|&known| known <= cost) {
+                continue;
+            }
+            costs.insert(neighbor, cost);
+            queue.push(Reverse((cost, neighbor.to_array())));
+        }
+    }
+    costs
+}
@@ -0,0 +1,195 @@
+//! Bastion-style outposts for the nether. The level needs to be loaded from the save's `DIM-1`
+//! folder.
+
+use crate::{sim::PlaceList, *};
+
+const OUTPOST_SIZE: i32 = 15;
+/// The floor gets searched for below the bedrock roof
+const CEILING: i32 = 120;
+pub(crate) const LAVA_SEA_LEVEL: i32 = 31;
+/// Air needed above a block for it to count as floor
+const HEADROOM: i32 = 3;
+const MAX_UNEVENNESS: i32 = 2;
+const WALL_HEIGHT: i32 = 5;
+const WALKWAY_LENGTH: i32 = 8;
+const WALL: Block = Full(PolishedBlackstoneBrick);
+
+/// Nether heightmap: the highest floor below the ceiling with room to stand on it. `None` for
+/// lava and columns without any floor.
+pub fn nether_height(level: &impl WorldView, column: IVec2) -> Option<i32> {
+    let mut air = 0;
+    for z in (LAVA_SEA_LEVEL..CEILING).rev() {
+        match level.get(column.extend(z)) {
+            Air => air += 1,
+            Lava if air >= HEADROOM => return None,
+            block if block.solid() && air >= HEADROOM => return Some(z),
+            _ => air = 0,
+        }
+    }
+    None
+}
+
+/// Flattest unblocked area of mostly netherrack big enough for an outpost, together with its
+/// floor height
+pub fn find_outpost_site(level: &Level, area: Rect) -> Option<(Rect, i32)> {
+    let netherrack = Block::from_name("netherrack", &[]);
+    let mut best: Option<(Rect, i32, i32)> = None;
+    for x in (area.min.x..=area.max.x - OUTPOST_SIZE).step_by(4) {
+        for y in (area.min.y..=area.max.y - OUTPOST_SIZE).step_by(4) {
+            let site = Rect {
+                min: ivec2(x, y),
+                max: ivec2(x, y) + IVec2::splat(OUTPOST_SIZE - 1),
+            };
+            if !level.unblocked(site) {
+                continue;
+            }
+            let Some(heights) = site
+                .into_iter()
+                .map(|column| nether_height(level, column))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let min = *heights.iter().min().unwrap();
+            let max = *heights.iter().max().unwrap();
+            if max - min > MAX_UNEVENNESS {
+                continue;
+            }
+            let netherrack_columns = site
+                .into_iter()
+                .zip(&heights)
+                .filter(|(column, z)| level(column.extend(**z)) == netherrack)
+                .count() as i32;
+            if netherrack_columns * 2 < site.total() {
+                continue;
+            }
+            if best.is_none_or(|(.., unevenness)| max - min < unevenness) {
+                best = Some((site, max, max - min));
+            }
+        }
+    }
+    best.map(|(site, floor, _)| (site, floor))
+}
+
+/// Walled courtyard of blackstone on a platform that keeps the lava out, with a roofed portal
+/// room at the back, gold accents and a walkway with parapets leading out of the gate
+pub fn nether_outpost(level: &mut impl WorldView, area: Rect, floor: i32) -> PlaceList {
+    let cursor = level.recording_cursor();
+    let top = floor + WALL_HEIGHT;
+
+    for column in area {
+        platform_column(level, column, floor);
+        for z in floor + 1..=top + 1 {
+            level.set(column.extend(z), Air);
+        }
+    }
+
+    // Outer wall with crenellations and gold on the corners
+    for column in area.border() {
+        for z in floor + 1..=top {
+            level.set(column.extend(z), WALL);
+        }
+        if (column.x + column.y) % 2 == 0 {
+            level.set(column.extend(top + 1), WALL);
+        }
+    }
+    for corner in area.corners() {
+        level.set(corner.extend(top + 1), Block::from_name("gold_block", &[]));
+    }
+    let gate = ivec2(area.center().x, area.min.y);
+    for dx in -1..=1 {
+        for z in floor + 1..=floor + 3 {
+            level.set((gate + ivec2(dx, 0)).extend(z), Air);
+        }
+    }
+
+    // Portal room, taking up the back half of the courtyard
+    let inner = area.shrink(1);
+    let room = Rect {
+        min: ivec2(area.center().x - 3, area.center().y + 1),
+        max: ivec2(area.center().x + 3, inner.max.y),
+    };
+    for column in room.grow(1).border() {
+        for z in floor + 1..=top {
+            level.set(column.extend(z), WALL);
+        }
+    }
+    level.fill_at(room.grow(1), top + 1, Full(PolishedBlackstone));
+    let doorway = ivec2(room.center().x, room.min.y - 1);
+    level.set(doorway.extend(floor + 1), Air);
+    level.set(doorway.extend(floor + 2), Air);
+
+    let portal_y = room.max.y - 1;
+    for dx in -1..=2 {
+        for dz in 0..=4 {
+            let pos = ivec3(room.center().x + dx, portal_y, floor + 1 + dz);
+            let frame = (dx == -1) | (dx == 2) | (dz == 0) | (dz == 4);
+            level.set(
+                pos,
+                if frame {
+                    Block::from_name("obsidian", &[])
+                } else {
+                    Block::from_name("nether_portal", &[("axis", "x")])
+                },
+            );
+        }
+    }
+    for column in [room.min, ivec2(room.max.x, room.min.y)] {
+        level.set(
+            column.extend(top),
+            Block::from_name("chain", &[("axis", "y")]),
+        );
+        level.set(
+            column.extend(top - 1),
+            Block::from_name("soul_lantern", &[("hanging", "true")]),
+        );
+    }
+
+    // Gilded blackstone speckled through the courtyard floor
+    let gilded = Block::from_name("gilded_blackstone", &[]);
+    for column in inner {
+        if !room.grow(1).contains(column) && (0.08 > rand()) {
+            level.set(column.extend(floor), gilded);
+        }
+    }
+
+    walkway(level, gate, floor);
+
+    level.pop_recording(cursor).into()
+}
+
+/// Three wide, with parapets on both sides so nobody falls into the lava
+fn walkway(level: &mut impl WorldView, gate: IVec2, floor: i32) {
+    for i in 1..=WALKWAY_LENGTH {
+        let row: Vec<IVec2> = (-2..=2).map(|dx| ivec2(gate.x + dx, gate.y - i)).collect();
+        if !row.iter().all(|&column| level.area().contains(column)) {
+            return;
+        }
+        for column in row {
+            platform_column(level, column, floor);
+            for z in floor + 1..=floor + 3 {
+                level.set(column.extend(z), Air);
+            }
+            if (column.x - gate.x).abs() == 2 {
+                level.set(
+                    column.extend(floor + 1),
+                    if i % 4 == 0 {
+                        WALL
+                    } else {
+                        Slab(PolishedBlackstoneBrick, Bottom)
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Floor block, supported down to solid ground so lava can't flow underneath
+pub(crate) fn platform_column(level: &mut impl WorldView, column: IVec2, floor: i32) {
+    level.set(column.extend(floor), Full(PolishedBlackstone));
+    let mut z = floor - 1;
+    while (z > LAVA_SEA_LEVEL - 8) && !level.get(column.extend(z)).solid() {
+        level.set(column.extend(z), Full(Blackstone));
+        z -= 1;
+    }
+}
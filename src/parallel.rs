@@ -0,0 +1,85 @@
+use nanorand::WyRand;
+use rayon::prelude::*;
+
+use crate::*;
+
+/// Runs generator passes on disjoint areas of the level in parallel.
+///
+/// Each pass gets its own [`Region`] and RNG seed, and the changes are applied in the order the
+/// passes were added, so the result doesn't depend on thread scheduling. Passes whose area
+/// overlaps an earlier pass are run in a later batch, after the earlier pass has been applied.
+pub struct ParallelPasses<'a, T> {
+    passes: Vec<Pass<'a, T>>,
+}
+
+struct Pass<'a, T> {
+    area: Rect,
+    seed: u64,
+    run: Box<dyn FnOnce(&mut Region) -> T + Send + 'a>,
+}
+
+impl<'a, T: Send> ParallelPasses<'a, T> {
+    pub fn new() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    /// The pass may only modify blocks within `area`
+    pub fn add(&mut self, area: Rect, run: impl FnOnce(&mut Region) -> T + Send + 'a) {
+        self.passes.push(Pass {
+            area,
+            // Drawn here so the seeds only depend on the order the passes were added in
+            seed: rand(),
+            run: Box::new(run),
+        });
+    }
+
+    /// Returns the output of each pass, in the order the passes were added
+    pub fn run(self, level: &mut Level) -> Vec<T> {
+        let mut results: Vec<Option<T>> = (0..self.passes.len()).map(|_| None).collect();
+        let mut remaining: Vec<_> = self.passes.into_iter().enumerate().collect();
+
+        while !remaining.is_empty() {
+            let mut batch = Vec::new();
+            let mut deferred = Vec::new();
+            for (index, pass) in remaining {
+                // Also check deferred passes so that overlapping passes keep their order
+                if batch
+                    .iter()
+                    .chain(&deferred)
+                    .any(|(_, other): &(usize, Pass<T>)| other.area.overlapps(pass.area))
+                {
+                    deferred.push((index, pass));
+                } else {
+                    batch.push((index, pass));
+                }
+            }
+
+            let level_ref = &*level;
+            let outputs: Vec<_> = batch
+                .into_par_iter()
+                .map(|(index, pass)| {
+                    // The calling thread might also run passes, so restore its RNG afterwards
+                    let rng = RNG.replace(WyRand::new_seed(pass.seed));
+                    let mut region = level_ref.region_mut(pass.area);
+                    let output = (pass.run)(&mut region);
+                    RNG.set(rng);
+                    (index, output, region.finish())
+                })
+                .collect();
+
+            for (index, output, changes) in outputs {
+                level.apply_region(changes);
+                results[index] = Some(output);
+            }
+            remaining = deferred;
+        }
+
+        results.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+impl<T: Send> Default for ParallelPasses<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
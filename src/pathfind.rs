@@ -37,6 +37,21 @@ const WALK_COST_PER_BLOCK: u32 = 3;
 const BOATING_COST_PER_BLOCK: u32 = 2;
 const STAIR_COOLDOWN: i8 = 7;
 const BOAT_TOGGLE_COST: u32 = 40 * WALK_COST_PER_BLOCK;
+/// Ravine floors are walkable, but you don't want to be there when something falls in
+const HAZARD_COST: u32 = 10 * WALK_COST_PER_BLOCK;
+
+/// Who a path is for, determining where it may go
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Profile {
+    /// Walks up and down single blocks and ladders, takes a boat across water only reluctantly
+    #[default]
+    Villager,
+    /// Needs a second column of space to the side, stays off ladders and water and takes at
+    /// most one step up or down every `STAIR_COOLDOWN` blocks
+    Cart,
+    /// Stays on water
+    Boat,
+}
 
 #[derive(Debug)]
 pub struct PathSearch {
@@ -51,11 +66,22 @@ pub struct PathingNode {
     pub boat: bool,
 }
 
+pub fn pathfind(level: &Level, start: IVec3, end: IVec3, range_to_end: i32) -> PathSearch {
+    pathfind_with(level, start, end, range_to_end, Profile::Villager)
+}
+
 // TODO: Make walking on paths faster; make stairs reduce stair cost
 // TODO: Acknowledge that boats are wider than one block
-pub fn pathfind(level: &Level, mut start: IVec3, mut end: IVec3, range_to_end: i32) -> PathSearch {
+pub fn pathfind_with(
+    level: &Level,
+    mut start: IVec3,
+    mut end: IVec3,
+    range_to_end: i32,
+    profile: Profile,
+) -> PathSearch {
     let area = level.area().shrink(2);
-    if range_to_end == 0 {
+    // Boats float on the water instead of sinking to the bottom
+    if (range_to_end == 0) & (profile != Profile::Boat) {
         for pos in [&mut end, &mut start] {
             while level(*pos).solid() {
                 *pos += IVec3::Z
@@ -76,7 +102,7 @@ pub fn pathfind(level: &Level, mut start: IVec3, mut end: IVec3, range_to_end: i
         cost: 0,
         cost_with_heuristic: 0,
         stair_cooldown: 0,
-        in_boat: false,
+        in_boat: profile == Profile::Boat,
     });
     'outer: while let Some(node) = queue.pop() {
         for off in NEIGHBORS_3D {
@@ -85,7 +111,7 @@ pub fn pathfind(level: &Level, mut start: IVec3, mut end: IVec3, range_to_end: i
                 new_cost,
                 boat,
                 stairs_taken,
-            }) = try_pos(level, area, &mut path, &node, off)
+            }) = try_pos(level, area, &mut path, &node, off, profile)
             else {
                 continue;
             };
@@ -176,7 +202,7 @@ pub fn reachability_2d_from(level: &Level, start: IVec2) -> ColumnMap<u32> {
                 new_cost,
                 boat,
                 stairs_taken,
-            }) = try_pos(level, area, &mut path, &node, off)
+            }) = try_pos(level, area, &mut path, &node, off, Profile::Villager)
             else {
                 continue;
             };
@@ -224,7 +250,7 @@ pub fn reachability_from(level: &Level, start: IVec3) -> HashMap<IVec3, u32> {
                 new_cost,
                 boat,
                 stairs_taken,
-            }) = try_pos(level, area, &mut path, &node, off)
+            }) = try_pos(level, area, &mut path, &node, off, Profile::Villager)
             else {
                 continue;
             };
@@ -262,6 +288,7 @@ fn try_pos(
     path: &mut HashMap<IVec3, (IVec3, bool)>,
     node: &Node,
     off: IVec3,
+    profile: Profile,
 ) -> Option<CheckedPos> {
     let mut new_pos = node.pos + off;
     // Only consider valid, novel paths
@@ -270,9 +297,17 @@ fn try_pos(
     }
     // Will we be in a boat in the new node?
     let boat = matches!(level(new_pos - IVec3::Z), Water);
+    if match profile {
+        Profile::Villager => false,
+        Profile::Cart => boat | (off.z != 0),
+        Profile::Boat => !boat,
+    } {
+        return None;
+    }
     let mut stairs_taken = false;
     if boat {
-        if off.z != 0 {
+        // Holes in the ice aren't for boating
+        if (off.z != 0) | level(node.pos - IVec3::Z).frozen_water() {
             return None;
         }
     } else {
@@ -312,9 +347,25 @@ fn try_pos(
             return None;
         }
     };
+    if (-1..=1).any(|z| level(new_pos + IVec3::Z * z).hazardous()) {
+        return None;
+    }
     if level(new_pos).solid() | level(new_pos + IVec3::Z).solid() {
         return None;
     }
+    if profile == Profile::Cart {
+        if stairs_taken & (node.stair_cooldown > 0) {
+            return None;
+        }
+        let side = new_pos + ivec3(-off.y, off.x, 0);
+        if !level(side - IVec3::Z).walkable()
+            | level(side).solid()
+            | level(side + IVec3::Z).solid()
+            | level(side).no_pathing()
+        {
+            return None;
+        }
+    }
     if path.contains_key(&new_pos) {
         return None;
     }
@@ -333,6 +384,11 @@ fn try_pos(
             BOAT_TOGGLE_COST
         } else {
             0
+        }
+        + if !boat && level.hazard(new_pos.truncate()) {
+            HAZARD_COST
+        } else {
+            0
         };
 
     Some(CheckedPos {
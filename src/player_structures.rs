@@ -0,0 +1,89 @@
+use crate::*;
+
+/// Columns are grouped into cells of this size
+const CELL_SIZE: i32 = 4;
+/// Artificial blocks needed for a cell to count as built-up
+const MIN_BLOCKS_PER_CELL: usize = 6;
+/// Artificial blocks needed for a group of cells to count as a structure, so that the odd
+/// stray block doesn't block off an area
+const MIN_BLOCKS: usize = 24;
+/// How far below the surface to look, e.g. for basements
+const DEPTH: i32 = 8;
+const MARGIN: i32 = 3;
+
+/// Finds structures outside of vanilla structures that were likely built by players, by looking
+/// for clusters of blocks that don't generate naturally
+pub fn find_player_structures(level: &Level) -> Vec<Rect> {
+    let area = level.area();
+    let vanilla: Vec<Rect> = level
+        .structures
+        .iter()
+        .flat_map(|start| start.pieces.iter().map(|piece| piece.bounds.d2().grow(1)))
+        .collect();
+
+    let mut cells: HashMap<IVec2, usize> = HashMap::default();
+    for column in area {
+        if vanilla.iter().any(|rect| rect.contains(column)) {
+            continue;
+        }
+        let top = (level.height)(column);
+        let count = (top - DEPTH..=top + 1)
+            .filter(|z| level(column.extend(*z)).artificial())
+            .count();
+        if count > 0 {
+            *cells
+                .entry(column.div_euclid(IVec2::splat(CELL_SIZE)))
+                .or_default() += count;
+        }
+    }
+    let built_up: HashSet<IVec2> = cells
+        .iter()
+        .filter(|(_, count)| **count >= MIN_BLOCKS_PER_CELL)
+        .map(|(cell, _)| *cell)
+        .collect();
+
+    // Group neighboring cells
+    let mut structures = Vec::new();
+    let mut visited = HashSet::default();
+    for &start in &built_up {
+        if !visited.insert(start) {
+            continue;
+        }
+        let mut bounds = Rect {
+            min: start,
+            max: start,
+        };
+        let mut blocks = 0;
+        let mut queue = vec![start];
+        while let Some(cell) = queue.pop() {
+            bounds.min = bounds.min.min(cell);
+            bounds.max = bounds.max.max(cell);
+            blocks += cells[&cell];
+            for off in NEIGHBORS_2D {
+                let neighbor = cell + off;
+                if built_up.contains(&neighbor) && visited.insert(neighbor) {
+                    queue.push(neighbor);
+                }
+            }
+        }
+        if blocks >= MIN_BLOCKS {
+            structures.push(
+                Rect {
+                    min: bounds.min * CELL_SIZE,
+                    max: bounds.max * CELL_SIZE + IVec2::splat(CELL_SIZE - 1),
+                }
+                .grow(MARGIN),
+            );
+        }
+    }
+    structures
+}
+
+/// Marks likely player-built structures as blocked so that they don't get built over
+pub fn block_player_structures(level: &mut Level) {
+    for structure in find_player_structures(level) {
+        println!("Avoiding player structure at {structure:?}");
+        let structure = structure.overlap(level.area());
+        level.set_blocked(structure);
+    }
+}
@@ -0,0 +1,172 @@
+//! Saved players whose position ended up inside the settlement could log in stuck in a wall.
+//! This moves them to the nearest free spot on the surface, optionally with a book about the
+//! town in their inventory.
+
+use std::fs::{read_dir, File};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use bevy_ecs::prelude::*;
+use nbt::{CompoundTag, Tag};
+
+use crate::{
+    sim::{building_plan::House, CityCenter, Pos, TownName, Villager},
+    *,
+};
+
+/// How far players may get moved
+const SEARCH_DISTANCE: i32 = 48;
+/// Slots of the main inventory and hotbar
+const INVENTORY_SLOTS: i8 = 36;
+
+/// Pass for [`sim::passes::Phase::Finish`] that moves players out of the way. Like
+/// [`portal::link_nether`], this needs to run at the end because the save only gets copied
+/// in the background at the start.
+pub fn relocate_players(give_book: bool) -> impl FnMut(&mut Level, &mut World) {
+    move |level: &mut Level, world: &mut World| {
+        let book = give_book.then(|| town_book(world));
+        // Singleplayer keeps its player in level.dat, other players have their own file
+        let mut files = vec![level.path.join("level.dat")];
+        if let Ok(dir) = read_dir(level.path.join("playerdata")) {
+            files.extend(
+                dir.flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "dat")),
+            );
+        }
+        for path in files {
+            if let Err(err) = relocate(level, &path, book.as_ref()) {
+                eprintln!("Failed to relocate player in {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+fn relocate(level: &Level, path: &Path, book: Option<&CompoundTag>) -> Result<()> {
+    let mut nbt = nbt::decode::read_gzip_compound_tag(&mut File::open(path)?)
+        .map_err(|_| anyhow!("Failed to read player data"))?;
+    let player = if path.ends_with("level.dat") {
+        let data: &mut CompoundTag = nbt
+            .get_mut("Data")
+            .map_err(|_| anyhow!("Corrupt level.dat"))?;
+        match data.get_mut("Player") {
+            Ok(player) => player,
+            // No singleplayer player yet
+            Err(_) => return Ok(()),
+        }
+    } else {
+        &mut nbt
+    };
+    let in_overworld = player
+        .get_str("Dimension")
+        .map_or(true, |dimension| dimension == "minecraft:overworld");
+    let Some(pos) = position(player) else {
+        return Ok(());
+    };
+    if !in_overworld || !level.area().contains(pos.truncate()) || safe(level, pos) {
+        return Ok(());
+    }
+    let Some(target) = nearest_safe(level, pos.truncate()) else {
+        eprintln!("No safe spot for player at {pos}");
+        return Ok(());
+    };
+    player.insert(
+        "Pos",
+        Tag::List(vec![
+            Tag::Double(target.x as f64 + 0.5),
+            Tag::Double(target.z as f64),
+            Tag::Double(target.y as f64 + 0.5),
+        ]),
+    );
+    if let Some(book) = book {
+        give(player, book);
+    }
+    nbt::encode::write_gzip_compound_tag(&mut File::create(path)?, &nbt)?;
+    Ok(())
+}
+
+fn position(player: &CompoundTag) -> Option<IVec3> {
+    let (_, Tag::List(pos)) = player.iter().find(|(name, _)| *name == "Pos")? else {
+        return None;
+    };
+    let [Tag::Double(x), Tag::Double(y), Tag::Double(z)] = pos.as_slice() else {
+        return None;
+    };
+    Some(vec3(*x as f32, *z as f32, *y as f32).floor().as_ivec3())
+}
+
+/// Whether a player can stand here without suffocating
+fn safe(level: &Level, pos: IVec3) -> bool {
+    !level(pos).solid() & !level(pos + IVec3::Z).solid()
+}
+
+fn nearest_safe(level: &Level, column: IVec2) -> Option<IVec3> {
+    let area = Rect::new_centered(column, IVec2::splat(SEARCH_DISTANCE * 2)).overlap(level.area());
+    area.into_iter()
+        .filter(|&column| !level.is_blocked(column) & level.water_level(column).is_none())
+        .map(|column| level.ground(column) + IVec3::Z)
+        .filter(|&pos| safe(level, pos))
+        .min_by_key(|pos| pos.truncate().distance_squared(column))
+}
+
+/// Puts the book into the first free slot, if there is one
+fn give(player: &mut CompoundTag, book: &CompoundTag) {
+    let mut inventory: Vec<CompoundTag> = player
+        .get_compound_tag_vec("Inventory")
+        .map(|items| items.into_iter().cloned().collect())
+        .unwrap_or_default();
+    let taken: Vec<i8> = inventory
+        .iter()
+        .filter_map(|item| item.get_i8("Slot").ok())
+        .collect();
+    let Some(slot) = (0..INVENTORY_SLOTS).find(|slot| !taken.contains(slot)) else {
+        return;
+    };
+    let mut item = book.clone();
+    item.insert_i8("Slot", slot);
+    inventory.push(item);
+    player.insert_compound_tag_vec("Inventory", inventory);
+}
+
+/// Written book introducing the settlements
+fn town_book(world: &mut World) -> CompoundTag {
+    let population = world
+        .query_filtered::<(), With<Villager>>()
+        .iter(world)
+        .count();
+    let houses = world
+        .query_filtered::<(), (With<House>, With<sim::Built>)>()
+        .iter(world)
+        .count();
+    let towns: Vec<(String, Vec3)> = world
+        .query_filtered::<(&TownName, &Pos), With<CityCenter>>()
+        .iter(world)
+        .map(|(name, pos)| (name.0.clone(), pos.0))
+        .collect();
+    let mut pages = vec![format!(
+        r#"{{"text":"While you were away, {} settlers built {} houses.\n\nTheir towns:\n{}"}}"#,
+        population,
+        houses,
+        towns
+            .iter()
+            .map(|(name, _)| format!("- {name}"))
+            .collect::<Vec<_>>()
+            .join("\\n")
+    )];
+    for (name, pos) in &towns {
+        pages.push(format!(
+            r#"{{"text":"{name}\n\nThe town square is at {:.0} {:.0} {:.0}."}}"#,
+            pos.x, pos.z, pos.y
+        ));
+    }
+
+    let mut tag = CompoundTag::new();
+    tag.insert_str("title", "The new towns");
+    tag.insert_str("author", "mc-gen");
+    tag.insert_str_vec("pages", pages);
+    let mut book = CompoundTag::new();
+    book.insert_str("id", "minecraft:written_book");
+    book.insert_i8("Count", 1);
+    book.insert_compound_tag("tag", tag);
+    book
+}
@@ -0,0 +1,154 @@
+//! Linked nether portals: a decorated one in the settlement and its counterpart in the nether,
+//! so players can travel between the dimensions right away
+
+use bevy_ecs::prelude::*;
+
+use crate::{
+    nether_outpost::{nether_height, platform_column, LAVA_SEA_LEVEL},
+    replay::Replay,
+    sim::{CityCenter, PlaceList, Pos},
+    *,
+};
+
+const DAIS_SIZE: i32 = 7;
+const PLATFORM_SIZE: i32 = 7;
+/// How far from the settlement center the portal may be
+const SEARCH_DISTANCE: i32 = 30;
+/// How far from the exact coordinates the nether portal may be. Minecraft looks for existing
+/// portals within 16 blocks in the nether.
+const NETHER_SEARCH_DISTANCE: i32 = 12;
+/// If there's only lava around, the platform gets built this far above it
+const BRIDGE_HEIGHT: i32 = 4;
+
+/// Pass for [`sim::passes::Phase::Finish`] that places a portal next to the first settlement
+/// and its counterpart in the nether. This runs at the end because the save only gets copied
+/// in the background at the start. Nether chunks that haven't been generated yet are left
+/// alone, Minecraft creates the counterpart itself when it generates them.
+pub fn link_nether(level: &mut Level, world: &mut World) {
+    let Some(center) = world
+        .query_filtered::<&Pos, With<CityCenter>>()
+        .iter(world)
+        .next()
+        .map(|pos| pos.block().truncate())
+    else {
+        return;
+    };
+    let Some(area) = level.free_spot_near(center, IVec2::splat(DAIS_SIZE), SEARCH_DISTANCE) else {
+        eprintln!("No space for a nether portal near {center}");
+        return;
+    };
+    let rec = decorated_portal(level, area);
+    level.set_blocked(area);
+    let mut replay = world.resource_mut::<Replay>();
+    for set in rec {
+        replay.block(set.pos, set.block);
+    }
+
+    let target = area.center().div_euclid(IVec2::splat(8));
+    let path = level
+        .path
+        .join("DIM-1")
+        .into_os_string()
+        .into_string()
+        .unwrap();
+    let mut nether = Level::new(
+        &path,
+        &path,
+        Rect::new_centered(
+            target,
+            IVec2::splat(NETHER_SEARCH_DISTANCE * 2 + PLATFORM_SIZE),
+        ),
+    );
+    if !nether.fully_loaded() {
+        println!(
+            "The nether around {target} hasn't been generated yet, leaving the portal to Minecraft"
+        );
+        return;
+    }
+    nether_portal(&mut nether, target);
+    nether.save_chunks();
+}
+
+/// Portal on a polished blackstone dais, with crying obsidian in the frame and lanterns on
+/// the corners
+pub fn decorated_portal(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let cursor = level.recording_cursor();
+    let floor = level.average_height(area).round() as i32;
+    for column in area {
+        level.set(column.extend(floor), Full(PolishedBlackstone));
+        let mut z = floor - 1;
+        while !level.get(column.extend(z)).solid() {
+            level.set(column.extend(z), Full(Blackstone));
+            z -= 1;
+        }
+        for z in floor + 1..floor + 7 {
+            level.set(column.extend(z), Air);
+        }
+    }
+    // Low rim, open in front of and behind the portal
+    for column in area.border() {
+        if column.x != area.center().x {
+            level.set(
+                column.extend(floor + 1),
+                Slab(PolishedBlackstoneBrick, Bottom),
+            );
+        }
+    }
+    for corner in area.corners() {
+        level.set(corner.extend(floor + 1), Full(PolishedBlackstoneBrick));
+        level.set(corner.extend(floor + 2), Block::from_name("lantern", &[]));
+    }
+    frame(level, area.center().extend(floor + 1), true);
+    level.pop_recording(cursor).into()
+}
+
+/// Portal on a platform with a parapet, on the floor closest to `target` or bridged over lava
+pub fn nether_portal(nether: &mut impl WorldView, target: IVec2) -> PlaceList {
+    let cursor = nether.recording_cursor();
+    let (center, floor) = std::iter::once(target)
+        .chain((1..=NETHER_SEARCH_DISTANCE).flat_map(|distance| {
+            Rect::new_centered(target, IVec2::splat(distance * 2 + 1)).border()
+        }))
+        .find_map(|column| Some((column, nether_height(&*nether, column)?)))
+        .unwrap_or((target, LAVA_SEA_LEVEL + BRIDGE_HEIGHT));
+    let area = Rect::new_centered(center, IVec2::splat(PLATFORM_SIZE));
+    for column in area {
+        platform_column(nether, column, floor);
+        for z in floor + 1..floor + 7 {
+            nether.set(column.extend(z), Air);
+        }
+    }
+    // Gaps in the middle of each side to leave through
+    for column in area.border() {
+        if (column.x != center.x) & (column.y != center.y) {
+            nether.set(
+                column.extend(floor + 1),
+                Slab(PolishedBlackstoneBrick, Bottom),
+            );
+        }
+    }
+    frame(nether, center.extend(floor + 1), false);
+    nether.pop_recording(cursor).into()
+}
+
+/// Lit obsidian frame along the x axis, 4 wide and 5 tall, standing on `base`
+fn frame(level: &mut impl WorldView, base: IVec3, decorated: bool) {
+    for dx in -1..=2 {
+        for dz in 0..=4 {
+            let pos = base + ivec3(dx, 0, dz);
+            let side = (dx == -1) | (dx == 2);
+            let block = if (dz == 0) | (dz == 4) {
+                if decorated & side {
+                    Block::from_name("crying_obsidian", &[])
+                } else {
+                    Block::from_name("obsidian", &[])
+                }
+            } else if side {
+                Block::from_name("obsidian", &[])
+            } else {
+                Block::from_name("nether_portal", &[("axis", "x")])
+            };
+            level.set(pos, block);
+        }
+    }
+}
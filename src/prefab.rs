@@ -6,6 +6,7 @@ use std::{
     sync::LazyLock,
 };
 
+use itertools::Itertools;
 use nbt::{decode::read_gzip_compound_tag, CompoundTag, Tag};
 
 use crate::{config::TEMPLATE_PATH, *};
@@ -13,12 +14,28 @@ use crate::{config::TEMPLATE_PATH, *};
 #[derive(Clone, Debug)]
 pub struct TemplateMark(IVec3, Option<HDir>, Vec<String>);
 
-// Hand-build structure, stored via structure blocks
+/// Room to be furnished by [`interior::furnish`], marked in the structure by two armor stands
+/// at opposite corners with the same `zone:<kind>` tag (`zone:<kind>:<n>` for several rooms of
+/// the same kind). The corners are the air above the floor.
+#[derive(Clone, Debug)]
+pub struct Zone {
+    pub kind: String,
+    /// Relative to the origin
+    pub area: Cuboid,
+}
+
+// Hand-build structure, stored via structure blocks. Air in the structure clears whatever is
+// there, structure voids (and positions missing from the file) leave the terrain alone, so e.g.
+// a back wall can be embedded into a hill.
 #[derive(Clone)]
 pub struct Prefab {
     _size: IVec3,
     blocks: VecDeque<(IVec3, Block)>,
     markers: HashMap<String, TemplateMark>,
+    zones: Vec<Zone>,
+    /// Chance for each block to get a variant, so repeated prefabs don't look stamped. Set via a
+    /// `jitter:<percent>` tag on the origin marker.
+    jitter: f32,
 }
 
 impl Prefab {
@@ -27,9 +44,11 @@ impl Prefab {
         for (offset, block) in self.blocks.iter() {
             level(
                 pos + offset.rotated(rotation),
-                block.rotated(rotation).swap_wood_type(wood),
+                self.vary(block.rotated(rotation).swap_wood_type(wood)),
             );
         }
+        let area = level.area();
+        self.furnish(level, pos, rotation, area);
     }
 
     pub fn build_clipped(&self, level: &mut Level, pos: IVec3, facing: HDir, area: Rect) {
@@ -37,11 +56,52 @@ impl Prefab {
         for (offset, block) in self.blocks.iter() {
             let pos = pos + offset.rotated(rotation);
             if area.contains(pos.truncate()) {
-                level(pos, block.rotated(rotation));
+                level(pos, self.vary(block.rotated(rotation)));
+            }
+        }
+        self.furnish(level, pos, rotation, area);
+    }
+
+    /// Fills the zones that lie completely within `area`
+    fn furnish(&self, level: &mut Level, pos: IVec3, rotation: i32, area: Rect) {
+        for zone in &self.zones {
+            let zone_area = Cuboid::new(
+                pos + zone.area.min.rotated(rotation),
+                pos + zone.area.max.rotated(rotation),
+            );
+            if area.has_subrect(zone_area.d2()) {
+                interior::furnish(level, zone_area, &zone.kind);
             }
         }
     }
 
+    /// Copy with a different chance for block variants, see [`Prefab::build`]
+    pub fn with_jitter(&self, jitter: f32) -> Self {
+        Self {
+            jitter,
+            ..self.clone()
+        }
+    }
+
+    /// Weathered stone and stripped wood accents
+    fn vary(&self, block: Block) -> Block {
+        // Without jitter, don't use up random numbers
+        if self.jitter <= 0. || self.jitter <= rand() {
+            return block;
+        }
+        match block {
+            Full(StoneBrick) if 0.5 > rand() => Block::from_name("cracked_stone_bricks", &[]),
+            Full(StoneBrick) => Full(MossyStonebrick),
+            Slab(StoneBrick, half) => Slab(MossyStonebrick, half),
+            Stair(StoneBrick, dir, half, shape) => Stair(MossyStonebrick, dir, half, shape),
+            Full(Cobble) => Full(MossyCobble),
+            Slab(Cobble, half) => Slab(MossyCobble, half),
+            Stair(Cobble, dir, half, shape) => Stair(MossyCobble, dir, half, shape),
+            Full(Wood(species)) => Log(species, LogType::Stripped(Axis::Z)),
+            _ => block,
+        }
+    }
+
     // TODO: palette swap
 }
 
@@ -92,6 +152,7 @@ fn load_from_nbt(nbt: &CompoundTag, name: &str) -> Prefab {
     let size = read_pos(nbt.get("size").unwrap());
 
     // Look for markers such as the origin
+    let mut zone_corners: HashMap<String, Vec<IVec3>> = default();
     let markers: HashMap<_, _> = nbt
         .get_compound_tag_vec("entities")
         .unwrap()
@@ -106,6 +167,10 @@ fn load_from_nbt(nbt: &CompoundTag, name: &str) -> Prefab {
                     .iter()
                     .map(|tag| (*tag).to_owned())
                     .collect();
+                if let Some(zone) = tags.iter().find(|tag| tag.starts_with("zone:")) {
+                    zone_corners.entry(zone.clone()).or_default().push(pos);
+                    return None;
+                }
                 // For some reason, CustomName doesn't work anymore?
                 let name = tags
                     .iter()
@@ -138,11 +203,35 @@ fn load_from_nbt(nbt: &CompoundTag, name: &str) -> Prefab {
         .unwrap_or_else(|| panic!("Failed to load prefab {}: No origin set", name))
         .0;
 
-    let palette: Vec<Block> = nbt
+    // Structure voids are `None`. Structure blocks leave them out when saving, but other tools
+    // keep them.
+    let zones = zone_corners
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(tag, corners)| {
+            let kind = tag.split(':').nth(1).unwrap().to_owned();
+            assert!(
+                interior::ZONE_KINDS.contains(&kind.as_str()),
+                "Unknown zone kind {kind} in prefab {name}"
+            );
+            let [a, b] = corners[..] else {
+                panic!("Zone {tag} in prefab {name} needs two corners");
+            };
+            Zone {
+                kind,
+                area: Cuboid::new(a - origin, b - origin),
+            }
+        })
+        .collect();
+
+    let palette: Vec<Option<Block>> = nbt
         .get_compound_tag_vec("palette")
         .unwrap()
         .iter()
-        .map(|nbt| Block::from_nbt(nbt))
+        .map(|nbt| {
+            let void = matches!(nbt.get_str("Name"), Ok("minecraft:structure_void"));
+            (!void).then(|| Block::from_nbt(nbt))
+        })
         .collect();
 
     // for block in &palette {
@@ -159,7 +248,9 @@ fn load_from_nbt(nbt: &CompoundTag, name: &str) -> Prefab {
         .rev()
     {
         let pos = read_pos(nbt.get("pos").unwrap());
-        let block = palette[nbt.get_i32("state").unwrap() as usize];
+        let Some(block) = palette[nbt.get_i32("state").unwrap() as usize] else {
+            continue;
+        };
         // TODO: nbt data
         if block == Air {
             // Clear out the area first (from top to bottom)
@@ -171,9 +262,22 @@ fn load_from_nbt(nbt: &CompoundTag, name: &str) -> Prefab {
     }
     blocks.extend(air);
 
+    let jitter = markers["origin"]
+        .2
+        .iter()
+        .find_map(|tag| tag.strip_prefix("jitter:"))
+        .map_or(0., |percent| {
+            percent
+                .parse::<f32>()
+                .unwrap_or_else(|_| panic!("Invalid jitter in prefab {name}"))
+                / 100.
+        });
+
     Prefab {
         _size: size,
         blocks,
         markers,
+        zones,
+        jitter,
     }
 }
@@ -0,0 +1,118 @@
+//! Live preview of a running simulation, for development.
+//!
+//! Serves a small viewer page over HTTP on the given port, which connects to a websocket on
+//! the port after it. Every few ticks, the blocks placed by the replay and the positions of the
+//! villagers get sent to it as JSON:
+//! `{"tick": 120, "blocks": [[x, y, z, "minecraft:stone"], …], "villagers": [[x, y, z], …]}`,
+//! in Minecraft's axes (y up).
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use serde_json::json;
+use tungstenite::{Message, WebSocket};
+
+use crate::{replay::Id, *};
+
+const VIEWER: &str = include_str!("preview.html");
+/// Ticks between updates sent to the viewers
+const SEND_INTERVAL: u32 = 10;
+
+pub struct Preview {
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+    blocks: Vec<(IVec3, Block)>,
+    villagers: HashMap<String, Vec3>,
+    tick: u32,
+}
+
+impl Preview {
+    /// Serves the viewer on `port` and the updates on `port + 1`
+    pub fn start(port: u16) -> Result<Self> {
+        let page = TcpListener::bind(("127.0.0.1", port))?;
+        let updates = TcpListener::bind(("127.0.0.1", port + 1))?;
+        std::thread::spawn(move || {
+            for stream in page.incoming().flatten() {
+                if let Err(err) = serve_viewer(stream) {
+                    eprintln!("Failed to serve preview: {err}");
+                }
+            }
+        });
+        let clients: Arc<Mutex<Vec<_>>> = default();
+        let accepted = clients.clone();
+        std::thread::spawn(move || {
+            for stream in updates.incoming().flatten() {
+                match tungstenite::accept(stream) {
+                    Ok(socket) => accepted.lock().unwrap().push(socket),
+                    Err(err) => eprintln!("Failed to connect preview: {err}"),
+                }
+            }
+        });
+        println!("Preview at http://localhost:{port}");
+        Ok(Self {
+            clients,
+            blocks: Vec::new(),
+            villagers: default(),
+            tick: 0,
+        })
+    }
+
+    pub fn block(&mut self, pos: IVec3, block: Block) {
+        self.blocks.push((pos, block));
+    }
+
+    pub fn villager(&mut self, id: Id, pos: Vec3) {
+        self.villagers.insert(id.to_string(), pos);
+    }
+
+    /// Called once per tick, sends what happened since the last update. Viewers that
+    /// disconnected get dropped.
+    pub fn tick(&mut self) {
+        self.tick += 1;
+        if self.tick % SEND_INTERVAL != 0 {
+            return;
+        }
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            self.blocks.clear();
+            return;
+        }
+        let unknown = UNKNOWN_BLOCKS.read().unwrap();
+        let blocks: Vec<_> = self
+            .blocks
+            .drain(..)
+            .map(|(pos, block)| {
+                let name = format!("minecraft:{}", block.blockstate(&unknown).0);
+                json!([pos.x, pos.z, pos.y, name])
+            })
+            .collect();
+        let villagers: Vec<_> = self
+            .villagers
+            .values()
+            .map(|pos| json!([pos.x, pos.z, pos.y]))
+            .collect();
+        let update = json!({
+            "tick": self.tick,
+            "blocks": blocks,
+            "villagers": villagers,
+        })
+        .to_string();
+        clients.retain_mut(|client| client.send(Message::Text(update.clone())).is_ok());
+    }
+}
+
+fn serve_viewer(mut stream: TcpStream) -> Result<()> {
+    // Whatever was requested, it gets the viewer
+    let mut request = BufReader::new(&stream);
+    let mut line = String::new();
+    while request.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{VIEWER}",
+        VIEWER.len()
+    )?;
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::{fmt::Write, fs::write, path::Path};
+
+use anyhow::Result;
+
+use crate::*;
+
+/// Chest slots checked for deliveries
+const CONTAINER_SLOTS: u8 = 27;
+/// How close a player needs to get to count as having visited a place
+const VISIT_DISTANCE: u32 = 5;
+/// How close a player needs to be to a bell when ringing it
+const BELL_DISTANCE: u32 = 6;
+
+/// What needs to be done to complete a quest
+pub enum Goal {
+    /// Fill the container at `pos` with `amount` of `item` (id without namespace)
+    Deliver {
+        item: &'static str,
+        amount: u32,
+        pos: IVec3,
+    },
+    RingBell(IVec3),
+    /// Come close to each of the places
+    Visit(Vec<IVec3>),
+}
+
+/// Objective referencing generated content, tracked with scoreboards. Quests are shared by all
+/// players and completed once.
+pub struct Quest {
+    pub title: String,
+    /// Instructions in the quest book
+    pub description: String,
+    /// Shown on completion
+    pub flavor: String,
+    pub goal: Goal,
+}
+
+/// Writes the quest functions into `function_path`: `quest_tick` checks the open quests and
+/// has to be run every tick, `quest_book` gives the player a book listing them. The
+/// objectives `<namespace>_quest` and `<namespace>_bell` need to be set up with [`setup`].
+pub fn write_functions(function_path: &Path, namespace: &str, quests: &[Quest]) -> Result<()> {
+    let score = format!("{namespace}_quest");
+    let mut tick = String::new();
+    for (i, quest) in quests.iter().enumerate() {
+        // Containers only get checked when someone is around
+        let guard = match &quest.goal {
+            Goal::Deliver { pos, .. } => format!(
+                "positioned {} {} {} if entity @a[distance=..16] ",
+                pos.x, pos.z, pos.y
+            ),
+            _ => String::new(),
+        };
+        writeln!(
+            tick,
+            "execute unless score #done_{i} {score} matches 1 {guard}run function {namespace}:quest_{i}"
+        )?;
+
+        let mut check = String::new();
+        let done = format!("function {namespace}:quest_{i}_done");
+        match &quest.goal {
+            Goal::Deliver { item, amount, pos } => {
+                let (x, y, z) = (pos.x, pos.z, pos.y);
+                writeln!(check, "scoreboard players set #count_{i} {score} 0")?;
+                for slot in 0..CONTAINER_SLOTS {
+                    writeln!(
+                        check,
+                        "execute store result score #slot {score} run data get block {x} {y} {z} Items[{{Slot:{slot}b,id:\"minecraft:{item}\"}}].Count"
+                    )?;
+                    writeln!(
+                        check,
+                        "scoreboard players operation #count_{i} {score} += #slot {score}"
+                    )?;
+                }
+                writeln!(
+                    check,
+                    "execute if score #count_{i} {score} matches {amount}.. run {done}"
+                )?;
+            }
+            Goal::RingBell(pos) => writeln!(
+                check,
+                "execute positioned {} {} {} if entity @a[distance=..{BELL_DISTANCE},scores={{{namespace}_bell=1..}}] run {done}",
+                pos.x, pos.z, pos.y
+            )?,
+            Goal::Visit(places) => {
+                for (j, pos) in places.iter().enumerate() {
+                    writeln!(
+                        check,
+                        "execute positioned {} {} {} if entity @a[distance=..{VISIT_DISTANCE}] run scoreboard players set #visited_{i}_{j} {score} 1",
+                        pos.x, pos.z, pos.y
+                    )?;
+                }
+                writeln!(check, "scoreboard players set #count_{i} {score} 0")?;
+                for j in 0..places.len() {
+                    writeln!(
+                        check,
+                        "scoreboard players operation #count_{i} {score} += #visited_{i}_{j} {score}"
+                    )?;
+                }
+                writeln!(
+                    check,
+                    "execute if score #count_{i} {score} matches {}.. run {done}",
+                    places.len()
+                )?;
+            }
+        }
+        write(function_path.join(format!("quest_{i}.mcfunction")), check)?;
+
+        write(
+            function_path.join(format!("quest_{i}_done.mcfunction")),
+            format!(
+                r#"scoreboard players set #done_{i} {score} 1
+tellraw @a {{"text":"Quest complete: {}","color":"gold"}}
+tellraw @a {{"text":"{}","italic":true}}
+playsound minecraft:ui.toast.challenge_complete master @a
+"#,
+                quest.title, quest.flavor
+            ),
+        )?;
+    }
+    // Bell rings only count in the tick they happen
+    writeln!(tick, "scoreboard players reset @a {namespace}_bell")?;
+    write(function_path.join("quest_tick.mcfunction"), tick)?;
+
+    let pages = quests
+        .iter()
+        .map(|quest| {
+            format!(
+                r#"'[{{"text":"{}\\n\\n","bold":true}},{{"text":"{}"}}]'"#,
+                quest.title, quest.description
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    write(
+        function_path.join("quest_book.mcfunction"),
+        format!(r#"give @s written_book{{title:"Quests",author:"mc-gen",pages:[{pages}]}}"#),
+    )?;
+    Ok(())
+}
+
+/// Commands creating the scoreboard objectives, to be run on load
+pub fn setup(namespace: &str) -> String {
+    format!(
+        "scoreboard objectives add {namespace}_quest dummy
+scoreboard objectives add {namespace}_bell minecraft.custom:minecraft.bell_ring
+tellraw @a \"Run /function {namespace}:quest_book for a list of quests\"
+"
+    )
+}
@@ -0,0 +1,184 @@
+//! Applies the generated changes to a running server over RCON instead of (or in addition to)
+//! writing them to the save, so structures can be added without restarting the server.
+//!
+//! The server's world must be the one the generator read from: only the difference between
+//! the save on disk and the final level gets sent, as `setblock` and `fill` commands. Use
+//! with `--dry-run` to leave the save on disk untouched.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use bevy_ecs::prelude::*;
+
+use crate::{replay::snbt, *};
+
+/// Longest command the server accepts over RCON
+const MAX_COMMAND_LENGTH: usize = 1446;
+const LOGIN: i32 = 3;
+const COMMAND: i32 = 2;
+/// Lowest and highest block of the overworld
+const MIN_Y: i32 = -64;
+const MAX_Y: i32 = 319;
+
+pub struct RconConfig {
+    /// `host:port`, usually port 25575
+    pub address: String,
+    pub password: String,
+    /// Rate limit, so that the server doesn't fall behind
+    pub commands_per_second: f32,
+    /// Save the server's world was loaded from
+    pub read_path: String,
+}
+
+pub struct Rcon {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl Rcon {
+    pub fn connect(address: &str, password: &str) -> Result<Self> {
+        let mut rcon = Self {
+            stream: TcpStream::connect(address)?,
+            next_id: 0,
+        };
+        let (id, _) = rcon.send(LOGIN, password)?;
+        if id == -1 {
+            bail!("RCON login to {address} failed");
+        }
+        Ok(rcon)
+    }
+
+    /// Runs a command and returns the server's response
+    pub fn command(&mut self, command: &str) -> Result<String> {
+        if command.len() > MAX_COMMAND_LENGTH {
+            bail!("Command too long for RCON: {} bytes", command.len());
+        }
+        Ok(self.send(COMMAND, command)?.1)
+    }
+
+    fn send(&mut self, kind: i32, payload: &str) -> Result<(i32, String)> {
+        self.next_id += 1;
+        let mut packet = Vec::with_capacity(payload.len() + 14);
+        packet.extend((payload.len() as i32 + 10).to_le_bytes());
+        packet.extend(self.next_id.to_le_bytes());
+        packet.extend(kind.to_le_bytes());
+        packet.extend(payload.as_bytes());
+        packet.extend([0, 0]);
+        self.stream.write_all(&packet)?;
+
+        let mut int = [0; 4];
+        self.stream.read_exact(&mut int)?;
+        let length = i32::from_le_bytes(int);
+        if !(10..=4096 + 10).contains(&length) {
+            bail!("Invalid RCON response length {length}");
+        }
+        let mut response = vec![0; length as usize];
+        self.stream.read_exact(&mut response)?;
+        let id = i32::from_le_bytes(response[..4].try_into().unwrap());
+        let body = String::from_utf8_lossy(&response[8..response.len() - 2]).into_owned();
+        Ok((id, body))
+    }
+}
+
+/// A `setblock` or `fill` along the x axis
+struct Change {
+    start: IVec3,
+    length: i32,
+    block: Block,
+    nbt: Option<String>,
+}
+
+/// Pass for [`sim::passes::Phase::Finish`] that sends the changes to the server. It runs
+/// after the other passes if it's added last.
+pub fn apply(config: RconConfig) -> impl FnMut(&mut Level, &mut World) {
+    move |level: &mut Level, _: &mut World| {
+        if let Err(err) = apply_changes(&config, level) {
+            eprintln!("Failed to apply changes over RCON: {err}");
+        }
+    }
+}
+
+fn apply_changes(config: &RconConfig, level: &Level) -> Result<()> {
+    let original = Level::new(&config.read_path, &config.read_path, level.area());
+    let mut rcon = Rcon::connect(&config.address, &config.password)?;
+    let interval = Duration::from_secs_f32(1. / config.commands_per_second);
+    let mut next = Instant::now();
+    let mut run = |command: &str| -> Result<()> {
+        sleep(next.saturating_duration_since(Instant::now()));
+        next = Instant::now() + interval;
+        let response = rcon.command(command)?;
+        // Successful setblocks and fills also produce output, just not these
+        if response.contains("not loaded") || response.contains("Unknown") {
+            eprintln!("{command}: {response}");
+        }
+        Ok(())
+    };
+
+    let unknown = UNKNOWN_BLOCKS.read().unwrap();
+    let mut count = 0;
+    for chunk in level
+        .chunks()
+        .filter(|chunk| level.chunk_changes(*chunk) > 0)
+    {
+        let changes = diff(level, &original, chunk);
+        if changes.is_empty() {
+            continue;
+        }
+        // Commands only work on loaded chunks
+        let min = chunk.area().min;
+        run(&format!("forceload add {} {}", min.x, min.y))?;
+        for change in changes {
+            let block = change.block.blockstate(&unknown);
+            let IVec3 { x, y, z } = change.start;
+            let command = match change.nbt {
+                Some(nbt) => format!("setblock {x} {z} {y} {block}{nbt}"),
+                None if change.length == 1 => format!("setblock {x} {z} {y} {block}"),
+                None => format!("fill {x} {z} {y} {} {z} {y} {block}", x + change.length - 1),
+            };
+            if let Err(err) = run(&command) {
+                eprintln!("Skipped {command}: {err}");
+            }
+            count += 1;
+        }
+        run(&format!("forceload remove {} {}", min.x, min.y))?;
+    }
+    println!("Applied {count} changes over RCON");
+    Ok(())
+}
+
+fn diff(level: &Level, original: &Level, chunk: ChunkIndex) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    let area = chunk.area();
+    for z in MIN_Y..=MAX_Y {
+        for y in area.min.y..=area.max.y {
+            for x in area.min.x..=area.max.x {
+                let pos = ivec3(x, y, z);
+                let block = level(pos);
+                let nbt = level.tile_entity(pos).map(|nbt| snbt(&nbt));
+                if block == original(pos) && nbt == original.tile_entity(pos).map(|nbt| snbt(&nbt))
+                {
+                    continue;
+                }
+                if let Some(last) = changes.last_mut()
+                    && last.start + IVec3::X * last.length == pos
+                    && last.block == block
+                    && last.nbt.is_none()
+                    && nbt.is_none()
+                {
+                    last.length += 1;
+                } else {
+                    changes.push(Change {
+                        start: pos,
+                        length: 1,
+                        block,
+                        nbt,
+                    });
+                }
+            }
+        }
+    }
+    changes
+}
@@ -1,15 +1,20 @@
 use crate::*;
+use nbt::CompoundTag;
 
-pub fn ground(level: &mut Level, area: Rect) {
+/// How far a giant mushroom may reach from where it was found, so a painted wall next to it
+/// doesn't get removed as well
+const MAX_MUSHROOM_SIZE: i32 = 8;
+
+pub fn ground(level: &mut (impl WorldView + ?Sized), area: Rect) {
     for column in area {
-        let base_height = if let Some(water_height) = (level.water)(column) {
+        let base_height = if let Some(water_height) = level.water_level(column) {
             water_height
         } else {
-            (level.height)(column)
+            level.height(column)
         };
         for z in base_height + 1..=base_height + 2 {
-            level(column, z, |block| {
-                if matches!(block, GroundPlant(..)) {
+            level.modify(column.extend(z), &mut |block| {
+                if matches!(block, GroundPlant(..) | GrowingCrop(..)) {
                     Block::Air
                 } else {
                     block
@@ -20,28 +25,28 @@ pub fn ground(level: &mut Level, area: Rect) {
 }
 
 pub fn find_trees(
-    level: &Level,
+    level: &(impl WorldView + ?Sized),
     area: impl IntoIterator<Item = IVec2>,
 ) -> Vec<(IVec3, TreeSpecies)> {
     let mut trees = HashSet::default();
     for column in area {
-        let z = (level.height)(column) + 1;
-        if let Block::Log(species, _) = level(column.extend(z)) {
+        let z = level.height(column) + 1;
+        if let Block::Log(species, _) = level.get(column.extend(z)) {
             // Check whether this is a tree instead of part of a man-made structure
             let mut pos = column.extend(z);
-            while let Block::Log(..) = level(pos) {
+            while let Block::Log(..) = level.get(pos) {
                 pos += IVec3::Z;
             }
-            if !matches!(level(pos), Leaves(..)) {
+            if !matches!(level.get(pos), Leaves(..)) {
                 continue;
             }
             // Find origin
             // TODO: find connected blocks to make this work for all kinds of trees
             let mut pos = column.extend(z);
-            if let Block::Log(..) = level(pos - IVec3::X) {
+            if let Block::Log(..) = level.get(pos - IVec3::X) {
                 pos -= IVec3::X
             }
-            if let Block::Log(..) = level(pos - IVec3::Y) {
+            if let Block::Log(..) = level.get(pos - IVec3::Y) {
                 pos -= IVec3::Y
             }
             trees.insert((pos, species));
@@ -50,29 +55,45 @@ pub fn find_trees(
     trees.into_iter().collect()
 }
 
+/// Bee nest taken off a tree, together with its block entity so the bees survive the move
+pub type RemovedNest = (Block, Option<CompoundTag>);
+
 // TODO: Remove any tree entities
-pub fn remove_tree(level: &mut Level, pos: IVec3) {
-    let Log(species, ..) = level(pos) else {
+/// Returns the bee nests that hung on the tree. Parts of the tree outside of the level's area
+/// are left standing, for a [`Region`] that's the region's area.
+pub fn remove_tree(level: &mut (impl WorldView + ?Sized), pos: IVec3) -> Vec<RemovedNest> {
+    let Log(species, ..) = level.get(pos) else {
         println!("Tried to remove tree at {pos:?} but not found");
-        return;
+        return Vec::new();
     };
+    let area = level.area();
+    let mut nests = Vec::new();
     // Store distance from log, 0 means log
     let mut blocks = vec![(pos, 0)];
     while let Some((pos, distance)) = blocks.pop() {
-        level(pos, Air);
+        if !area.contains(pos.truncate()) {
+            continue;
+        }
+        level.set(pos, Air);
         for off_x in -1..=1 {
             for off_y in -1..=1 {
                 for off_z in -1..=1 {
                     let off = ivec3(off_x, off_y, off_z);
                     let pos = pos + off;
-                    match level(pos) {
+                    if !area.contains(pos.truncate()) {
+                        continue;
+                    }
+                    match level.get(pos) {
                         Log(s, ..) if (s == species) & (distance <= 1) => blocks.push((pos, 0)),
                         // Checking species can leave leaves behind when trees intersect
                         // Also, azalea
                         Leaves(_, Some(d)) if (d > distance) & (off.length_squared() == 1) => {
                             blocks.push((pos, d))
                         }
-                        // TODO: Beehives
+                        nest @ BeeNest(..) if distance == 0 => {
+                            nests.push((nest, level.tile_entity(pos)));
+                            level.set(pos, Air);
+                        }
                         // TODO: Snoe
                         _ => (),
                     }
@@ -80,12 +101,58 @@ pub fn remove_tree(level: &mut Level, pos: IVec3) {
             }
         }
     }
+    nests
 }
 
-pub fn remove_trees(level: &mut Level, area: impl IntoIterator<Item = IVec2>) {
+pub fn remove_trees(level: &mut (impl WorldView + ?Sized), area: impl IntoIterator<Item = IVec2>) {
     for (pos, _) in find_trees(level, area) {
-        remove_tree(level, pos)
+        remove_tree(level, pos);
     }
 }
 
-// Todo: remove_giant_mushroom()
+/// Removes the cap and stem of the giant mushroom `pos` is part of
+pub fn remove_giant_mushroom(level: &mut (impl WorldView + ?Sized), pos: IVec3) {
+    let mut blocks = vec![pos];
+    let mut columns = HashSet::default();
+    while let Some(current) = blocks.pop() {
+        level.set(current, Air);
+        columns.insert(current.truncate());
+        for off_x in -1..=1 {
+            for off_y in -1..=1 {
+                for off_z in -1..=1 {
+                    let next = current + ivec3(off_x, off_y, off_z);
+                    if (next - pos).abs().max_element() > MAX_MUSHROOM_SIZE {
+                        continue;
+                    }
+                    let block = level.get(next);
+                    // Only follow stems downwards from the cap, they're also used as paint
+                    let stem = (block == MushroomStem) & (off_x == 0) & (off_y == 0);
+                    if block.mushroom_cap() | stem {
+                        blocks.push(next);
+                    }
+                }
+            }
+        }
+    }
+    // The caps were part of the heightmap
+    for column in columns {
+        let mut z = level.height(column);
+        while !level.get(column.extend(z)).solid() {
+            z -= 1;
+        }
+        level.set_height(column, z);
+    }
+}
+
+/// Giant mushrooms count as ground for the heightmap, so their caps are found on top
+pub fn remove_giant_mushrooms(
+    level: &mut (impl WorldView + ?Sized),
+    area: impl IntoIterator<Item = IVec2>,
+) {
+    for column in area {
+        let pos = column.extend(level.height(column));
+        if level.get(pos).mushroom_cap() {
+            remove_giant_mushroom(level, pos);
+        }
+    }
+}
@@ -1,15 +1,18 @@
+use crate::quest::{self, Quest};
 use crate::sim::lumberjack::Lumberworker;
 use crate::sim::quarry::Mason;
-use crate::sim::*;
+use crate::sim::{checkpoint::Checkpoint, *};
+use crate::tour::{self, TourStop};
 use crate::*;
 use bevy_ecs::prelude::*;
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use itertools::Itertools;
 use nbt::encode::write_compound_tag;
 use nbt::{CompoundTag, Tag};
 
 use std::fmt::{Display, Write};
-use std::fs::{create_dir_all, read, write, File};
+use std::fs::{create_dir_all, read, rename, write, File};
 use std::io::Write as _;
 use std::ops::DerefMut;
 use std::path::PathBuf;
@@ -45,12 +48,32 @@ static NEXT_ID: AtomicU32 = AtomicU32::new(1);
 enum Command {
     Literal(String),
     Block(IVec3, Block),
+    /// Block with block entity data, already formatted as snbt
+    BlockEntity(IVec3, Block, String),
     Dust(IVec3),
+    /// Particles of a block being broken
+    BlockParticles(IVec3, Block),
+    Sound(IVec3, String),
     Tp(Id, Vec3, Vec3),
 }
 
 impl Command {
-    fn format(self, block_cache: &mut HashMap<Block, String>) -> String {
+    fn format(
+        self,
+        block_cache: &mut HashMap<Block, String>,
+        activation_range: Option<u32>,
+    ) -> String {
+        // Purely visual commands are skipped when nobody is around to see them.
+        // Tps are absolute, so villagers jump to the right place once a player comes by.
+        let guard = |pos: Vec3, command: String| {
+            match activation_range {
+            Some(range) => format!(
+                "execute positioned {:.2} {:.2} {:.2} if entity @a[distance=..{range}] run {command}",
+                pos.x, pos.z, pos.y
+            ),
+            None => command,
+        }
+        };
         match self {
             Command::Literal(s) => s,
             Command::Block(pos, block) => {
@@ -61,25 +84,72 @@ impl Command {
                 });
                 format!("setblock {} {} {} {block_string}", pos.x, pos.z, pos.y)
             }
-            Command::Dust(pos) => format!(
-                "particle campfire_cosy_smoke {} {} {} 1.3 1.3 1.3 0.006 10",
-                pos.x, pos.z, pos.y
+            Command::BlockEntity(pos, block, nbt) => {
+                let block_string = block_cache.entry(block).or_insert_with(|| {
+                    block
+                        .blockstate(&UNKNOWN_BLOCKS.read().unwrap())
+                        .to_string()
+                });
+                format!("setblock {} {} {} {block_string}{nbt}", pos.x, pos.z, pos.y)
+            }
+            Command::Dust(pos) => guard(
+                pos.as_vec3(),
+                format!(
+                    "particle campfire_cosy_smoke {} {} {} 1.3 1.3 1.3 0.006 10",
+                    pos.x, pos.z, pos.y
+                ),
+            ),
+            Command::BlockParticles(pos, block) => {
+                let block_string = block_cache.entry(block).or_insert_with(|| {
+                    block
+                        .blockstate(&UNKNOWN_BLOCKS.read().unwrap())
+                        .to_string()
+                });
+                guard(
+                    pos.as_vec3(),
+                    format!(
+                        "particle block {block_string} {}.5 {}.5 {}.5 0.3 0.3 0.3 0 12",
+                        pos.x, pos.z, pos.y
+                    ),
+                )
+            }
+            Command::Sound(pos, sound) => guard(
+                pos.as_vec3(),
+                format!(
+                    "playsound {sound} block @a {}.5 {}.5 {}.5",
+                    pos.x, pos.z, pos.y
+                ),
             ),
-            // TODO: how to do facing when climbing a ladder?
-            Command::Tp(id, pos, facing) => format!(
-                "tp {} {:.2} {:.2} {:.2} facing {:.2} {:.2} {:.2}",
-                id,
-                pos.x + 0.5,
-                pos.z,
-                pos.y + 0.5,
-                facing.x + 0.5,
-                facing.z,
-                facing.y + 0.5
+            Command::Tp(id, pos, facing) => guard(
+                pos,
+                format!(
+                    "tp {} {:.2} {:.2} {:.2} facing {:.2} {:.2} {:.2}",
+                    id,
+                    pos.x + 0.5,
+                    pos.z,
+                    pos.y + 0.5,
+                    facing.x + 0.5,
+                    facing.z,
+                    facing.y + 0.5
+                ),
             ),
         }
     }
 }
 
+/// What becomes of the recorded commands
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ReplayMode {
+    /// Written to a datapack that plays the simulation back in-game
+    #[default]
+    Record,
+    /// Recorded, but nothing gets written
+    DryRun,
+    /// Nothing gets recorded apart from the placed blocks for the stats. The final blocks need
+    /// to be saved to the level instead.
+    Headless,
+}
+
 #[derive(Resource)]
 pub struct Replay {
     level_path: PathBuf,
@@ -94,6 +164,17 @@ pub struct Replay {
     total_commands: u64,
     writes_in_flight: Arc<AtomicU32>,
     carry_ids: Vec<(Id, Id)>,
+    placed: HashMap<Block, u64>,
+    activation_range: Option<u32>,
+    tour: Vec<TourStop>,
+    /// Optional, nothing gets written without quests
+    quests: Vec<Quest>,
+    mode: ReplayMode,
+    /// When resuming from a checkpoint, chunks below this that are already in the save don't
+    /// get written again
+    resumed_chunks: i32,
+    #[cfg(feature = "preview")]
+    preview: Option<crate::preview::Preview>,
 }
 
 impl Replay {
@@ -120,6 +201,14 @@ impl Replay {
             total_commands: 0,
             writes_in_flight: default(),
             carry_ids: default(),
+            placed: default(),
+            activation_range: None,
+            tour: default(),
+            quests: default(),
+            mode: default(),
+            resumed_chunks: 0,
+            #[cfg(feature = "preview")]
+            preview: None,
         };
 
         // Wait for the player to load in
@@ -129,36 +218,141 @@ impl Replay {
         replay
     }
 
+    /// Only animate villagers and dust within this many blocks of a player, to save command
+    /// budget in large worlds. Blocks are always placed, so unvisited areas don't end up
+    /// missing anything.
+    pub fn set_activation_range(&mut self, range: Option<u32>) {
+        self.activation_range = range;
+    }
+
+    pub fn set_mode(&mut self, mode: ReplayMode) {
+        self.mode = mode;
+    }
+
+    /// Also streams the placed blocks and villager movements to a live preview
+    #[cfg(feature = "preview")]
+    pub fn set_preview(&mut self, preview: crate::preview::Preview) {
+        self.preview = Some(preview);
+    }
+
+    /// Skips writing the chunks that were written before the checkpoint. Returns false if the
+    /// checkpoint belongs to another replay.
+    pub fn resume(&mut self, checkpoint: &Checkpoint) -> bool {
+        if checkpoint.invocation != self.invocation {
+            return false;
+        }
+        self.resumed_chunks = checkpoint.replay_chunks;
+        true
+    }
+
+    pub fn invocation(&self) -> u8 {
+        self.invocation
+    }
+
+    /// Chunks handed off for writing so far
+    pub fn chunks(&self) -> i32 {
+        self.command_chunk
+    }
+
     pub fn dbg(&mut self, msg: &str) {
         self.command(format!("say {msg}"));
     }
 
     pub fn dust(&mut self, pos: IVec3) {
-        self.commands_this_tick.push(Command::Dust(pos));
-        self.commands_this_chunk += 1;
-        self.total_commands += 1;
+        self.push(Command::Dust(pos));
     }
 
     pub fn block(&mut self, pos: IVec3, block: Block) {
-        self.commands_this_tick.push(Command::Block(pos, block));
-        self.commands_this_chunk += 1;
-        self.total_commands += 1;
+        self.push(Command::Block(pos, block));
+        *self.placed.entry(block).or_default() += 1;
+        #[cfg(feature = "preview")]
+        if let Some(preview) = &mut self.preview {
+            preview.block(pos, block);
+        }
+    }
+
+    pub fn block_entity(&mut self, pos: IVec3, block: Block, nbt: &CompoundTag) {
+        *self.placed.entry(block).or_default() += 1;
+        #[cfg(feature = "preview")]
+        if let Some(preview) = &mut self.preview {
+            preview.block(pos, block);
+        }
+        if self.mode != ReplayMode::Headless {
+            self.push(Command::BlockEntity(pos, block, snbt(nbt)));
+        }
+    }
+
+    /// Particles and sound of a block being broken or placed
+    pub fn block_effect(&mut self, pos: IVec3, block: Block, breaking: bool) {
+        if (block == Air) | (self.mode == ReplayMode::Headless) {
+            return;
+        }
+        let action = if breaking { "break" } else { "place" };
+        if breaking {
+            self.push(Command::BlockParticles(pos, block));
+        }
+        self.push(Command::Sound(
+            pos,
+            format!("minecraft:block.{}.{action}", sound_group(block)),
+        ));
+    }
+
+    /// How often each block was placed so far
+    pub fn placed_blocks(&self) -> &HashMap<Block, u64> {
+        &self.placed
     }
 
     pub fn tp(&mut self, id: Id, pos: Vec3, facing: Vec3) {
-        self.commands_this_tick.push(Command::Tp(id, pos, facing));
-        self.commands_this_chunk += 1;
-        self.total_commands += 1;
+        #[cfg(feature = "preview")]
+        if let Some(preview) = &mut self.preview {
+            preview.villager(id, pos);
+        }
+        self.push(Command::Tp(id, pos, facing));
+    }
+
+    /// Appends a stop to the guided tour
+    pub fn tour_stop(&mut self, stop: TourStop) {
+        self.tour.push(stop);
+    }
+
+    /// Lectern with the book that starts the guided tour
+    pub fn tour_lectern(&self) -> (Block, CompoundTag) {
+        tour::lectern(&format!("sim_{}", self.invocation), &self.tour)
+    }
+
+    /// Name shown above the entity
+    pub fn name(&mut self, id: Id, name: &str) {
+        self.command(format!(
+            "data modify entity {id} CustomName set value \"{{\\\"text\\\":\\\"{name}\\\"}}\""
+        ));
+    }
+
+    pub fn quest(&mut self, quest: Quest) {
+        self.quests.push(quest);
     }
 
     pub fn command(&mut self, msg: String) {
-        self.commands_this_tick.push(Command::Literal(msg));
+        self.push(Command::Literal(msg));
+    }
+
+    fn push(&mut self, command: Command) {
+        if self.mode == ReplayMode::Headless {
+            return;
+        }
+        self.commands_this_tick.push(command);
         self.commands_this_chunk += 1;
         self.total_commands += 1;
     }
 
     fn tick(&mut self) {
         const MAX_COMMANDS_PER_CHUNK: i32 = 30000;
+        #[cfg(feature = "preview")]
+        if let Some(preview) = &mut self.preview {
+            preview.tick();
+        }
+        if self.mode == ReplayMode::Headless {
+            return;
+        }
         if self.commands_this_chunk < MAX_COMMANDS_PER_CHUNK {
             let commands = std::mem::take(&mut self.commands_this_tick);
             self.commands.push(commands);
@@ -179,10 +373,22 @@ impl Replay {
         let mut commands =
             std::mem::replace(&mut self.commands, Vec::with_capacity(INITIAL_CAPACITY));
         commands.push(tick_commands);
+        let chunk = self.command_chunk;
+        self.command_chunk += 1;
+        self.commands_this_chunk = 0;
+        if self.mode != ReplayMode::Record {
+            return;
+        }
 
         let data_path = self.level_path.join("data/");
-        let invocation = self.invocation;
-        let chunk = self.command_chunk;
+        let chunk_path = data_path.join(format!(
+            "command_storage_sim_{}_{chunk}.dat",
+            self.invocation
+        ));
+        if chunk < self.resumed_chunks && chunk_path.exists() {
+            return;
+        }
+        let activation_range = self.activation_range;
         let arc = self.writes_in_flight.clone();
         arc.fetch_add(1, Ordering::Relaxed);
         rayon::spawn(move || {
@@ -198,7 +404,7 @@ impl Replay {
                                 .rev()
                                 .map(|c| {
                                     let mut nbt = CompoundTag::new();
-                                    nbt.insert("cmd", c.format(&mut block_cache));
+                                    nbt.insert("cmd", c.format(&mut block_cache, activation_range));
                                     nbt.into()
                                 })
                                 .collect(),
@@ -221,10 +427,10 @@ impl Replay {
                 });
                 nbt
             });
-            let mut file = File::create(
-                data_path.join(format!("command_storage_sim_{invocation}_{chunk}.dat")),
-            )
-            .unwrap();
+            // Written under another name first, so a chunk in the save is always complete
+            // when resuming after a crash
+            let temp_path = chunk_path.with_extension("tmp");
+            let mut file = File::create(&temp_path).unwrap();
             // If writing directly to a GzEncoder and the chunk size is too big, it
             // gets silently trunctated?!?
             let mut uncompressed = Vec::new();
@@ -232,16 +438,22 @@ impl Replay {
             GzEncoder::new(&mut file, Compression::new(1))
                 .write_all(&mut uncompressed)
                 .unwrap();
+            drop(file);
+            rename(temp_path, chunk_path).unwrap();
 
             arc.fetch_sub(1, Ordering::Relaxed);
         });
-
-        self.command_chunk += 1;
-        self.commands_this_chunk = 0;
     }
 
     pub fn finish(mut self) {
+        if self.mode == ReplayMode::Headless {
+            return;
+        }
         self.flush_chunk();
+        if self.mode == ReplayMode::DryRun {
+            println!("Total commands: {}", self.total_commands);
+            return;
+        }
 
         let pack_path = self
             .level_path
@@ -274,23 +486,30 @@ impl Replay {
             gamerule mobGriefing false
             gamerule doFireTick false
             gamerule doTileDrops false
+            tellraw @a \"Run /function sim_{0}:controls for a book to control the replay\"
             ",
                 self.invocation
             ),
         )
         .unwrap();
 
-        write(
-            sim_path.join("check_setup.mcfunction"),
-            format!(
-                "
+        let mut check_setup = format!(
+            "
             scoreboard objectives add sim_tick dummy
             execute unless score SIM_{0} sim_tick matches 0.. run function sim_{0}:setup
+            # Players without op can use /trigger, so the controls work through it
+            scoreboard objectives add sim_control trigger
+            scoreboard objectives add resume_speed dummy
+            scoreboard objectives add sim_{0}_tour trigger
             ",
-                self.invocation
-            ),
-        )
-        .unwrap();
+            self.invocation
+        );
+        if !self.quests.is_empty() {
+            check_setup.push_str(&quest::setup(&format!("sim_{}", self.invocation)));
+            quest::write_functions(&sim_path, &format!("sim_{}", self.invocation), &self.quests)
+                .unwrap();
+        }
+        write(sim_path.join("check_setup.mcfunction"), check_setup).unwrap();
 
         let tag_path = pack_path.join("data/minecraft/tags/functions/");
         create_dir_all(&tag_path).unwrap();
@@ -309,6 +528,36 @@ impl Replay {
         )
         .unwrap();
 
+        // Replay controls: clicking in the book sets the trigger, game_tick dispatches on it
+        let mut control = String::new();
+        let mut page = Vec::new();
+        for (value, (label, commands)) in REPLAY_CONTROLS.iter().enumerate() {
+            let value = value + 1;
+            for command in *commands {
+                let command = command.replace("SIM", &format!("SIM_{}", self.invocation));
+                writeln!(
+                    control,
+                    "execute if score @s sim_control matches {value} run {command}"
+                )
+                .unwrap();
+            }
+            page.push(format!(
+                r#"{{"text":"{label}\\n","clickEvent":{{"action":"run_command","value":"/trigger sim_control set {value}"}}}}"#
+            ));
+        }
+        writeln!(control, "scoreboard players set @s sim_control 0").unwrap();
+        write(sim_path.join("control.mcfunction"), control).unwrap();
+        write(
+            sim_path.join("controls.mcfunction"),
+            format!(
+                r#"give @s written_book{{title:"Replay controls",author:"mc-gen",pages:['[{}]']}}"#,
+                page.join(",")
+            ),
+        )
+        .unwrap();
+
+        tour::write_functions(&sim_path, &format!("sim_{}", self.invocation), &self.tour).unwrap();
+
         write(sim_path.join("eval.mcfunction"), "$$(cmd)").unwrap();
         write(
             sim_path.join("run_current_commands.mcfunction"),
@@ -334,11 +583,18 @@ impl Replay {
         write(sim_path.join("game_tick.mcfunction"), {
             let mut tick = format!(
                 "
+                scoreboard players enable @a sim_control
+                execute as @a[scores={{sim_control=1..}}] run function sim_{0}:control
+                scoreboard players enable @a sim_{0}_tour
+                execute as @a[scores={{sim_{0}_tour=1..}}] run function sim_{0}:tour
                 scoreboard players operation SIM_{0} warp += SIM_{0} speed
                 execute if score SIM_{0} warp matches 1.. run function sim_{0}:sim_tick
             ",
                 self.invocation
             );
+            if !self.quests.is_empty() {
+                writeln!(tick, "function sim_{}:quest_tick", self.invocation).unwrap();
+            }
             for (vill, carry) in self.carry_ids {
                 writeln!(tick, "tp {carry} {vill}").unwrap();
             }
@@ -374,6 +630,107 @@ impl Replay {
     }
 }
 
+/// Labels in the controls book and what they do. `SIM` gets replaced by the scoreboard
+/// holder of this invocation.
+const REPLAY_CONTROLS: &[(&str, &[&str])] = &[
+    (
+        "Pause",
+        &[
+            "execute if score SIM speed matches 1.. run scoreboard players operation SIM resume_speed = SIM speed",
+            "scoreboard players set SIM speed 0",
+        ],
+    ),
+    (
+        "Resume",
+        &[
+            "execute unless score SIM resume_speed matches 1.. run scoreboard players set SIM resume_speed 1",
+            "execute if score SIM speed matches 0 run scoreboard players operation SIM speed = SIM resume_speed",
+        ],
+    ),
+    (
+        "Faster",
+        &[
+            "execute if score SIM speed matches 1..99 run scoreboard players operation SIM speed += SIM speed",
+            "execute if score SIM speed matches 0 run scoreboard players set SIM speed 1",
+        ],
+    ),
+    (
+        "Slower",
+        &[
+            "scoreboard players set #2 resume_speed 2",
+            "execute if score SIM speed matches 2.. run scoreboard players operation SIM speed /= #2 resume_speed",
+        ],
+    ),
+    (
+        "Skip to the end",
+        &["scoreboard players set SIM speed 200"],
+    ),
+];
+
+fn sound_group(block: Block) -> &'static str {
+    match block {
+        Log(..) | Full(Wood(_)) | Slab(Wood(_), _) | Stair(Wood(_), ..) | Fence(Wood(_)) => "wood",
+        Barrel | CraftingTable | Trapdoor(..) | Door(..) => "wood",
+        Leaves(..) | SmallPlant(..) | TallPlant(..) | GroundPlant(..) | GrowingCrop(..) | Grass
+        | Hay => "grass",
+        Sand => "sand",
+        Dirt | CoarseDirt | Podzol | Farmland | Path | Gravel => "gravel",
+        Wool(_) | Bed(..) => "wool",
+        GlassPane(_) => "glass",
+        _ => "stone",
+    }
+}
+
+/// Formats nbt for use in commands
+pub fn snbt(nbt: &CompoundTag) -> String {
+    let mut out = String::new();
+    write_snbt_compound(&mut out, nbt);
+    out
+}
+
+fn write_snbt_compound(out: &mut String, nbt: &CompoundTag) {
+    out.push('{');
+    for (i, (name, tag)) in nbt.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(out, "{name}:").unwrap();
+        write_snbt(out, tag);
+    }
+    out.push('}');
+}
+
+fn write_snbt(out: &mut String, tag: &Tag) {
+    match tag {
+        Tag::Byte(v) => write!(out, "{v}b"),
+        Tag::Short(v) => write!(out, "{v}s"),
+        Tag::Int(v) => write!(out, "{v}"),
+        Tag::Long(v) => write!(out, "{v}L"),
+        Tag::Float(v) => write!(out, "{v}f"),
+        Tag::Double(v) => write!(out, "{v}d"),
+        Tag::String(v) => write!(out, "\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")),
+        Tag::ByteArray(v) => write!(out, "[B;{}]", v.iter().map(|v| format!("{v}b")).join(",")),
+        Tag::IntArray(v) => write!(out, "[I;{}]", v.iter().join(",")),
+        Tag::LongArray(v) => write!(out, "[L;{}]", v.iter().map(|v| format!("{v}L")).join(",")),
+        Tag::List(list) => {
+            out.push('[');
+            for (i, tag) in list.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_snbt(out, tag);
+            }
+            out.push(']');
+            Ok(())
+        }
+        Tag::Compound(nbt) => {
+            write_snbt_compound(out, nbt);
+            Ok(())
+        }
+    }
+    .unwrap()
+}
+
 pub fn tick_replay(
     mut level: ResMut<Level>,
     mut replay: ResMut<Replay>,
@@ -384,6 +741,9 @@ pub fn tick_replay(
     jobless: Query<&Id, Added<Jobless>>,
     lumberjacks: Query<&Id, Added<Lumberworker>>,
     masons: Query<&Id, Added<Mason>>,
+    tools: Query<(&Villager, &HeldTool), Changed<HeldTool>>,
+    mut put_away: RemovedComponents<HeldTool>,
+    villagers: Query<&Villager>,
 ) {
     let replay = replay.deref_mut();
     // Blocks
@@ -413,15 +773,21 @@ pub fn tick_replay(
     }
     // Names
     for (id, name) in &named {
-        replay.command(format!(
-            "data modify entity {id} CustomName set value \"{{\\\"text\\\":\\\"{}\\\"}}\"",
-            name.0
-        ));
+        replay.name(*id, &name.0);
     }
     // Movement
     for (id, pos, mut prev, in_boat) in &mut moved {
-        let delta = pos.0 - prev.0;
-        let facing = pos.0 + delta;
+        const EYE_HEIGHT: f32 = 1.6;
+        let delta = (pos.0 - prev.0).truncate();
+        let direction = if delta.length() > 0.01 {
+            delta.normalize()
+        } else if let Ladder(dir) = level.get(pos.block()) {
+            // Face the wall the ladder is on
+            -IVec2::from(dir).as_vec2()
+        } else {
+            delta
+        };
+        let facing = pos.0 + direction.extend(EYE_HEIGHT);
         if let Some(in_boat) = in_boat {
             let off = vec3(0., 0., -0.48);
             // Unfortunately the boat lags behind (visually only?)
@@ -433,15 +799,11 @@ pub fn tick_replay(
     }
     // Carrying
     for vill in &changed_vills {
-        if let Some(stack) = vill.carry {
+        if let Some(stack) = vill.carry.main() {
             replay.command(format!(
                 "data modify entity {} ArmorItems[3] set value {}",
                 vill.carry_id,
-                stack
-                    .kind
-                    .display_as_block()
-                    .blockstate(&UNKNOWN_BLOCKS.write().unwrap())
-                    .item_snbt()
+                stack.kind.display_item(&UNKNOWN_BLOCKS.write().unwrap())
             ));
         } else {
             replay.command(format!(
@@ -450,6 +812,22 @@ pub fn tick_replay(
             ));
         }
     }
+    // Tools, held by the carry armor stand since villagers don't show their hand items
+    for (vill, tool) in &tools {
+        replay.command(format!(
+            "data modify entity {} HandItems[0] set value {{id:\"{}\",Count:1b}}",
+            vill.carry_id,
+            tool.item()
+        ));
+    }
+    for entity in put_away.read() {
+        if let Ok(vill) = villagers.get(entity) {
+            replay.command(format!(
+                "data modify entity {} HandItems[0] set value {{}}",
+                vill.carry_id
+            ));
+        }
+    }
     // Professions
     for id in &jobless {
         replay.command(format!(
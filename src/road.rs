@@ -0,0 +1,395 @@
+use crate::{
+    house::stilt,
+    hydrology::{water_depth, Hydrology},
+    remove_foliage::remove_trees,
+    *,
+};
+
+/// Distance between the pathfinding nodes used as control points of the spline
+const CONTROL_SPACING: usize = 5;
+/// Bumps up to this height get leveled
+const MAX_LEVELING: i32 = 2;
+/// Water up to this depth can be waded through
+const FORD_DEPTH: i32 = 1;
+/// Widest water that still gets a bridge, anything wider is crossed by ferry
+const MAX_BRIDGE_SPAN: i32 = 20;
+/// How far up- and downstream to look for a narrower crossing
+const CROSSING_SEARCH_RADIUS: i32 = 16;
+/// Distance between the piers of a bridge
+const PIER_SPACING: i32 = 4;
+const FERRY_DOCK_LENGTH: i32 = 4;
+
+/// Turns the blocky path from the pathfinder into a smooth curve (Catmull-Rom spline through
+/// every few nodes), sampled roughly every half block
+pub fn smooth_path(path: &[IVec3]) -> Vec<Vec2> {
+    if path.len() < 2 {
+        return path.iter().map(|pos| pos.truncate().as_vec2()).collect();
+    }
+    let mut control: Vec<Vec2> = path
+        .iter()
+        .step_by(CONTROL_SPACING)
+        .map(|pos| pos.truncate().as_vec2())
+        .collect();
+    let last = path.last().unwrap().truncate().as_vec2();
+    if *control.last().unwrap() != last {
+        control.push(last);
+    }
+
+    let mut samples = Vec::new();
+    for i in 0..control.len() - 1 {
+        let p0 = control[i.saturating_sub(1)];
+        let p1 = control[i];
+        let p2 = control[i + 1];
+        let p3 = control[(i + 2).min(control.len() - 1)];
+        let steps = (p1.distance(p2) * 2.).ceil().max(1.) as usize;
+        for step in 0..steps {
+            let t = step as f32 / steps as f32;
+            let t2 = t * t;
+            let t3 = t2 * t;
+            samples.push(
+                0.5 * (2. * p1
+                    + (p2 - p0) * t
+                    + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2
+                    + (3. * p1 - p0 - 3. * p2 + p3) * t3),
+            );
+        }
+    }
+    samples.push(last);
+    samples
+}
+
+/// Carves a road of the given width along the curve: Clears vegetation, levels small bumps,
+/// paves it with a soft edge (path → coarse dirt → grass) and puts steps on steep slopes.
+/// Returns the columns of the road.
+pub fn carve_road(level: &mut impl WorldView, curve: &[Vec2], width: i32) -> Vec<IVec2> {
+    let area = level.area();
+    let half_width = width as f32 / 2.;
+    let edge = half_width + 1.5;
+
+    // Smoothed height profile along the road
+    let ground: Vec<Option<i32>> = curve
+        .iter()
+        .map(|sample| {
+            let column = sample.round().as_ivec2();
+            area.contains(column).then(|| level.height(column))
+        })
+        .collect();
+    let profile: Vec<Option<i32>> = (0..curve.len())
+        .map(|i| {
+            let window: Vec<i32> = ground[i.saturating_sub(4)..(i + 5).min(curve.len())]
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+            (!window.is_empty())
+                .then(|| (window.iter().sum::<i32>() as f32 / window.len() as f32).round() as i32)
+        })
+        .collect();
+
+    // Closest sample for each column near the road
+    let mut closest: HashMap<IVec2, (f32, usize)> = HashMap::default();
+    for (i, sample) in curve.iter().enumerate() {
+        let reach = edge.ceil() as i32;
+        for column in Rect::new_centered(sample.round().as_ivec2(), IVec2::splat(reach * 2 + 1)) {
+            let distance = column.as_vec2().distance(*sample);
+            if (distance > edge) | !area.contains(column) {
+                continue;
+            }
+            let entry = closest.entry(column).or_insert((f32::INFINITY, 0));
+            if distance < entry.0 {
+                *entry = (distance, i);
+            }
+        }
+    }
+
+    let mut road = Vec::new();
+    let mut columns: Vec<_> = closest.into_iter().collect();
+    columns.sort_by_key(|(column, _)| (column.x, column.y));
+    remove_trees(
+        level,
+        columns
+            .iter()
+            .filter(|(_, (distance, _))| *distance <= half_width)
+            .map(|(column, _)| *column),
+    );
+    for &(column, (distance, sample)) in &columns {
+        if level.water_level(column).is_some() {
+            continue;
+        }
+        let mut top = level.height(column);
+        let target = profile[sample];
+        // Clear plants
+        let above = column.extend(top + 1);
+        if matches!(
+            level.get(above),
+            SmallPlant(..) | TallPlant(..) | GroundPlant(..) | GrowingCrop(..) | SnowLayer
+        ) {
+            level.set(above, Air);
+            if let TallPlant(..) = level.get(above + IVec3::Z) {
+                level.set(above + IVec3::Z, Air);
+            }
+        }
+        if !level.get(column.extend(top)).soil() {
+            continue;
+        }
+
+        if distance <= half_width {
+            road.push(column);
+            // Level minor bumps
+            if let Some(target) = target
+                && (top != target)
+                && ((top - target).abs() <= MAX_LEVELING)
+            {
+                let fill = level.get(column.extend(top));
+                for z in target + 1..=top {
+                    level.set(column.extend(z), Air);
+                }
+                for z in top + 1..=target {
+                    level.set(column.extend(z), fill);
+                }
+                top = target;
+                level.set_height(column, top);
+            }
+            level.set(column.extend(top), Path);
+        } else if distance <= half_width + 0.8 {
+            if level.get(column.extend(top)).dirtsoil() {
+                level.set(column.extend(top), CoarseDirt);
+            }
+        } else if level.get(column.extend(top)) == Dirt {
+            level.set(column.extend(top), Grass);
+        }
+    }
+
+    // Steps where the road climbs a block within a short distance
+    for i in 0..curve.len().saturating_sub(2) {
+        let (lower, upper) = (i, i + 2);
+        let (Some(lower_height), Some(upper_height)) = (profile[lower], profile[upper]) else {
+            continue;
+        };
+        let rise = upper_height - lower_height;
+        if rise.abs() != 1 {
+            continue;
+        }
+        let (low, high) = if rise > 0 {
+            (lower, upper)
+        } else {
+            (upper, lower)
+        };
+        let low_column = curve[low].round().as_ivec2();
+        let high_column = curve[high].round().as_ivec2();
+        let diff = high_column - low_column;
+        if (diff.abs().max_element() != 1) | (diff.abs().min_element() != 0) {
+            continue;
+        }
+        let uphill = HDir::ALL
+            .into_iter()
+            .find(|dir| IVec2::from(*dir) == diff)
+            .unwrap();
+        let side = IVec2::from(uphill.rotated(1));
+        for offset in -(width / 2)..=(width - 1) / 2 {
+            let column = low_column + side * offset;
+            let high_column = high_column + side * offset;
+            if !area.contains(column) || !area.contains(high_column) {
+                continue;
+            }
+            let low_top = level.height(column);
+            if (level.height(high_column) == low_top + 1)
+                & (level.get(column.extend(low_top)) == Path)
+                & (level.get(column.extend(low_top + 1)) == Air)
+            {
+                level.set(
+                    column.extend(low_top + 1),
+                    Stair(Cobble, uphill, Bottom, StairShape::Straight),
+                );
+                level.set_height(column, low_top + 1);
+            }
+        }
+    }
+
+    road
+}
+
+/// Height travellers move at along the column: On the ground, on top of bridges or on the
+/// water where it's too deep to wade
+pub fn travel_height(level: &impl WorldView, column: IVec2) -> i32 {
+    if water_depth(level, column) > FORD_DEPTH {
+        level.water_level(column).unwrap()
+    } else {
+        level.height(column)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CrossingKind {
+    /// Shallow enough to wade through, gets paved with gravel
+    Ford,
+    Bridge,
+    /// Docks on both sides, too wide for a bridge
+    Ferry,
+}
+
+/// Where and how a road gets over water, straight from bank to bank
+#[derive(Debug, Copy, Clone)]
+pub struct Crossing {
+    pub kind: CrossingKind,
+    /// Bank on the side the road comes from
+    pub start: IVec2,
+    /// Bank on the other side
+    pub end: IVec2,
+}
+
+impl Crossing {
+    /// Picks the crossing over the first water on the straight line from `from` to `to`: The
+    /// narrowest point nearby, then whichever kind fits its depth and width
+    pub fn plan(level: &Level, hydrology: &Hydrology, from: IVec2, to: IVec2) -> Option<Self> {
+        let steps = (to - from).abs().max_element().max(1);
+        let water = (0..=steps)
+            .map(|i| {
+                from + ((to - from).as_vec2() * i as f32 / steps as f32)
+                    .round()
+                    .as_ivec2()
+            })
+            .find(|column| hydrology.body(*column).is_some())?;
+        let (mut start, mut end) =
+            hydrology.narrowest_crossing(level, water, CROSSING_SEARCH_RADIUS)?;
+        if start.distance_squared(from) > end.distance_squared(from) {
+            (start, end) = (end, start);
+        }
+        let mut crossing = Self {
+            kind: CrossingKind::Ford,
+            start,
+            end,
+        };
+        let depth = crossing
+            .water()
+            .map(|column| water_depth(level, column))
+            .max()
+            .unwrap_or(0);
+        crossing.kind = if depth <= FORD_DEPTH {
+            CrossingKind::Ford
+        } else if crossing.water().count() as i32 <= MAX_BRIDGE_SPAN {
+            CrossingKind::Bridge
+        } else {
+            CrossingKind::Ferry
+        };
+        Some(crossing)
+    }
+
+    fn dir(&self) -> IVec2 {
+        (self.end - self.start).signum()
+    }
+
+    /// Columns between the banks
+    pub fn water(&self) -> impl Iterator<Item = IVec2> {
+        let (start, dir) = (self.start, self.dir());
+        let length = (self.end - self.start).abs().max_element();
+        (1..length).map(move |i| start + dir * i)
+    }
+
+    /// Builds the ford, bridge or ferry docks for a road of the given width and updates the
+    /// heightmap of the columns built on
+    pub fn build(&self, level: &mut Level, width: i32) {
+        let dir = self.dir();
+        let side = ivec2(-dir.y, dir.x);
+        let offsets = -(width / 2)..=(width - 1) / 2;
+        let species = level.biome(self.start).default_tree_species();
+        let mut built = Vec::new();
+        match self.kind {
+            CrossingKind::Ford => {
+                for column in self.water() {
+                    for offset in offsets.clone() {
+                        let column = column + side * offset;
+                        if level.area().contains(column) && level.water_level(column).is_some() {
+                            let bed = level.height(column);
+                            level(column.extend(bed), Gravel);
+                        }
+                    }
+                }
+            }
+            CrossingKind::Bridge => {
+                let surface = self
+                    .water()
+                    .filter_map(|column| level.water_level(column))
+                    .max()
+                    .unwrap_or(i32::MIN);
+                let deck = level
+                    .height(self.start)
+                    .max(level.height(self.end))
+                    .max(surface + 1);
+                let railing = width / 2 + 1;
+                let columns = [self.start]
+                    .into_iter()
+                    .chain(self.water())
+                    .chain([self.end]);
+                for (i, column) in columns.enumerate() {
+                    for offset in -railing..=railing {
+                        let column = column + side * offset;
+                        if !level.area().contains(column) {
+                            continue;
+                        }
+                        level(column.extend(deck), Full(Wood(species)));
+                        if offset.abs() == railing {
+                            level(column.extend(deck + 1), Fence(Wood(species)));
+                            if i as i32 % PIER_SPACING == 0 {
+                                for z in level.height(column) + 1..deck {
+                                    level(column.extend(z), Full(StoneBrick));
+                                }
+                            }
+                        } else {
+                            for z in deck + 1..deck + 3 {
+                                level(column.extend(z), Air);
+                            }
+                        }
+                        built.push(column);
+                    }
+                }
+                // Ramps down to the banks
+                for (bank, outwards) in [(self.start, -dir), (self.end, dir)] {
+                    let mut column = bank + outwards;
+                    let mut top = deck - 1;
+                    while level.area().contains(column) && level.height(column) < top {
+                        for offset in offsets.clone() {
+                            let column = column + side * offset;
+                            if level.area().contains(column) {
+                                for z in level.height(column) + 1..=top {
+                                    level(column.extend(z), Full(Cobble));
+                                }
+                                built.push(column);
+                            }
+                        }
+                        column += outwards;
+                        top -= 1;
+                    }
+                }
+            }
+            CrossingKind::Ferry => {
+                for (bank, outwards) in [(self.start, dir), (self.end, -dir)] {
+                    let mut column = bank + outwards;
+                    for _ in 0..FERRY_DOCK_LENGTH {
+                        let Some(surface) = level.water_level(column) else {
+                            break;
+                        };
+                        for offset in offsets.clone() {
+                            let column = column + side * offset;
+                            if level.area().contains(column) {
+                                level(column.extend(surface + 1), Slab(Wood(species), Bottom));
+                            }
+                        }
+                        column += outwards;
+                    }
+                    let end = column - outwards;
+                    for offset in [offsets.start() - 1, offsets.end() + 1] {
+                        let post = end + side * offset;
+                        if level.area().contains(post)
+                            && let Some(surface) = level.water_level(post)
+                        {
+                            stilt(level, post, surface + 1, species);
+                            level(post.extend(surface + 2), Fence(Wood(species)));
+                        }
+                    }
+                }
+            }
+        }
+        level.recompute_heights(built);
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{sim::PlaceList, *};
 use bevy_math::Vec2Swizzles;
 
-pub fn roof(level: &mut Level, area: Rect, mut base_z: i32, mat: BlockMaterial) -> PlaceList {
+pub fn roof(
+    level: &mut impl WorldView,
+    area: Rect,
+    mut base_z: i32,
+    mat: BlockMaterial,
+) -> PlaceList {
     let cursor = level.recording_cursor();
 
     let curve = *[straight, straight_high, straight_low, kerb, reverse_kerb].choose();
@@ -39,54 +44,62 @@ pub fn roof(level: &mut Level, area: Rect, mut base_z: i32, mat: BlockMaterial)
         ];
         grad.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
         let block = if grad[3].0 >= z + 0.5 {
-            Stair(mat, grad[3].1, Bottom)
+            Stair(mat, grad[3].1, Bottom, StairShape::Straight)
         } else if z >= z_block {
             Slab(mat, Top)
         } else {
             Slab(mat, Bottom)
         };
-        level(pos, z_block as i32, |b| b | block);
+        level.modify(pos.extend(z_block as i32), &mut |b| b | block);
     }
 
     for pos in area {
         let z_block = shape(pos.as_vec2()).round() as i32;
         for dir in HDir::ALL {
             // Fix-up outer corners
-            if (level((pos + dir).extend(z_block)) == Stair(mat, dir.rotated(1), Bottom))
-                & (level((pos + dir.rotated(1)).extend(z_block)) == Stair(mat, dir, Bottom))
+            if (level.get((pos + dir).extend(z_block))
+                == Stair(mat, dir.rotated(1), Bottom, StairShape::Straight))
+                & (level.get((pos + dir.rotated(1)).extend(z_block))
+                    == Stair(mat, dir, Bottom, StairShape::Straight))
             {
-                level(pos, z_block, Stair(mat, dir, Bottom))
+                level.set(
+                    pos.extend(z_block),
+                    Stair(mat, dir, Bottom, StairShape::Straight),
+                )
             }
 
             // Fill holes in steep roofs
             let mut lower = shape(pos.as_vec2() + IVec2::from(dir).as_vec2()).round() as i32;
-            let adjacent = level((pos + dir).extend(lower));
-            if matches!(adjacent, Slab(_, Top) | Full(..) | Stair(_, _, Top))
-                | matches!(adjacent, Stair(_, d, Bottom) if d==dir.rotated(2))
+            let adjacent = level.get((pos + dir).extend(lower));
+            if matches!(adjacent, Slab(_, Top) | Full(..) | Stair(_, _, Top, _))
+                | matches!(adjacent, Stair(_, d, Bottom, _) if d==dir.rotated(2))
                 | !area.contains(pos + dir)
             {
                 lower += 1;
             }
             let mut upper = z_block;
-            if matches!(level(pos.extend(upper)), Slab(_, Top) | Stair(_, _, Top)) {
+            if matches!(
+                level.get(pos.extend(upper)),
+                Slab(_, Top) | Stair(_, _, Top, _)
+            ) {
                 upper += 1;
             }
             for z in lower..upper {
-                let block = if level(pos.extend(z - 1)).solid()
-                    || (matches!(level(pos.extend(z)), Full(..) | Stair(..)))
+                let block = if level.get(pos.extend(z - 1)).solid()
+                    || (matches!(level.get(pos.extend(z)), Full(..) | Stair(..)))
                 {
                     Full(mat)
-                } else if matches!(level((pos + dir).extend(z)), Slab(..)) {
+                } else if matches!(level.get((pos + dir).extend(z)), Slab(..)) {
                     Slab(mat, Top)
                 } else {
-                    Stair(mat, dir, Top)
+                    Stair(mat, dir, Top, StairShape::Straight)
                 };
-                level(pos, z, block);
+                level.set(pos.extend(z), block);
             }
         }
     }
 
-    let mut list = level.pop_recording(cursor).collect::<Vec<_>>();
+    let mut list = level.pop_recording(cursor);
     list.sort_by_key(|setblock| setblock.pos.z);
     list.into()
 }
@@ -0,0 +1,76 @@
+use crate::{house::shack, sim::PlaceList, transform::ruin, *};
+
+/// Prefabs that can be found in ruined form
+const RUINED_PREFABS: &[&str] = &[
+    "well_1",
+    "well_2",
+    "well_3",
+    "fountain_1",
+    "fountain_2",
+    "monument_1",
+];
+/// Keep ruins out of sight of the settlement's planning area
+const DISTANCE_TO_SETTLEMENT: i32 = 30;
+
+/// Places collapsed, overgrown buildings outside of the settlements.
+/// `density` is the number of ruins per 100×100 columns.
+pub fn ruins(level: &mut Level, settlements: &[Rect], density: f32) -> PlaceList {
+    // Too small to leave a margin
+    if level.area().size().min_element() <= 32 {
+        return PlaceList::new();
+    }
+    let area = level.area().shrink(16);
+    let count = (area.total() as f32 / 10000. * density).round() as usize;
+    let mut rec = PlaceList::new();
+    for _ in 0..count {
+        let Some(site) = find_site(level, area, settlements) else {
+            continue;
+        };
+        let mut ruined = if 0.4 > rand()
+            && let Some(prefab) = PREFABS.get(*RUINED_PREFABS.choose())
+        {
+            let cursor = level.recording_cursor();
+            let pos = level.ground(site.center());
+            let species = (level.biome)(site.center()).default_tree_species();
+            prefab.build(level, pos, *HDir::ALL.choose(), species);
+            level.pop_recording(cursor).collect()
+        } else {
+            shack(level, site)
+        };
+        let Some(bounds) = ruined
+            .iter()
+            .map(|set| Cuboid::new(set.pos, set.pos))
+            .reduce(|a, b| a.extend_to(b.min))
+        else {
+            continue;
+        };
+        let cursor = level.recording_cursor();
+        ruin(level, bounds, rand_f32(0.3, 0.8));
+        ruined.extend(level.pop_recording(cursor));
+        level.set_blocked(bounds.d2().overlap(level.area()));
+        rec.extend(ruined);
+    }
+    rec
+}
+
+fn find_site(level: &Level, area: Rect, settlements: &[Rect]) -> Option<Rect> {
+    for _ in 0..20 {
+        let center = ivec2(
+            rand_range(area.min.x..=area.max.x),
+            rand_range(area.min.y..=area.max.y),
+        );
+        let site = Rect::new_centered(center, ivec2(rand_range(5..=7), rand_range(5..=9)));
+        if area.has_subrect(site)
+            && level.unblocked(site)
+            && !settlements
+                .iter()
+                .any(|settlement| settlement.grow(DISTANCE_TO_SETTLEMENT).overlapps(site))
+            && site
+                .into_iter()
+                .all(|column| level.water_level(column).is_none())
+        {
+            return Some(site);
+        }
+    }
+    None
+}
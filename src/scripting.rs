@@ -0,0 +1,132 @@
+//! Building scripts, so buildings can be written without recompiling the crate.
+//!
+//! Scripts are [rhai](https://rhai.rs) files in the `plugins/` directory, loaded at startup.
+//! Each defines `fn build(area)`, where `area` has the fields `min_x`, `min_z`, `max_x` and
+//! `max_z`. Coordinates are in Minecraft's axes (y up). Available functions:
+//! - `height(x, z)`: height of the ground
+//! - `set_block(x, y, z, block)`, with blocks given like in commands, e.g.
+//!   `"oak_stairs[facing=north]"`
+//! - `fill(x1, y1, z1, x2, y2, z2, block)`
+//! - `rand()`: random number in 0..1
+//!
+//! Blocks outside of the area are ignored.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use bevy_ecs::prelude::*;
+use rhai::{Engine, Map, Scope, AST};
+
+use crate::{sim::PlaceList, *};
+
+/// Scripts can't loop forever
+const MAX_OPERATIONS: u64 = 10_000_000;
+/// Chance a house is built by a script instead, if there are any
+const SCRIPT_CHANCE: f32 = 0.5;
+
+pub struct BuildingScript {
+    pub name: String,
+    ast: AST,
+}
+
+#[derive(Resource, Default)]
+pub struct BuildingScripts(pub Vec<BuildingScript>);
+
+impl BuildingScripts {
+    /// Compiles all scripts in `dir`. Scripts that fail to compile are reported and skipped.
+    pub fn load(dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Self::default();
+        };
+        let engine = Engine::new();
+        let mut scripts = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "rhai") {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            match engine.compile_file(path.clone()) {
+                Ok(ast) => scripts.push(BuildingScript { name, ast }),
+                Err(err) => eprintln!("Failed to load {}: {err}", path.display()),
+            }
+        }
+        if !scripts.is_empty() {
+            println!(
+                "Loaded {} building scripts from {}",
+                scripts.len(),
+                dir.display()
+            );
+        }
+        Self(scripts)
+    }
+
+    /// Script to build the next house with, if any
+    pub fn pick(&self) -> Option<&BuildingScript> {
+        (!self.0.is_empty() && SCRIPT_CHANCE > rand()).then(|| self.0.choose())
+    }
+}
+
+impl BuildingScript {
+    pub fn build(&self, level: &mut impl WorldView, area: Rect) -> Result<PlaceList> {
+        let placed = Arc::new(Mutex::new(Vec::new()));
+        let heights: HashMap<IVec2, i32> = area
+            .into_iter()
+            .map(|column| (column, level.height(column)))
+            .collect();
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.register_fn("height", move |x: i64, z: i64| {
+            let column = ivec2(x as i32, z as i32).clamp(area.min, area.max);
+            heights[&column] as i64
+        });
+        engine.register_fn("rand", || rand::<f32>() as f64);
+        {
+            let placed = placed.clone();
+            engine.register_fn("set_block", move |x: i64, y: i64, z: i64, block: &str| {
+                let pos = ivec3(x as i32, z as i32, y as i32);
+                placed
+                    .lock()
+                    .unwrap()
+                    .push((pos, Block::from_blockstate_str(block)));
+            });
+        }
+        {
+            let placed = placed.clone();
+            engine.register_fn(
+                "fill",
+                move |x1: i64, y1: i64, z1: i64, x2: i64, y2: i64, z2: i64, block: &str| {
+                    let block = Block::from_blockstate_str(block);
+                    let mut placed = placed.lock().unwrap();
+                    for x in x1.min(x2)..=x1.max(x2) {
+                        for y in y1.min(y2)..=y1.max(y2) {
+                            for z in z1.min(z2)..=z1.max(z2) {
+                                placed.push((ivec3(x as i32, z as i32, y as i32), block));
+                            }
+                        }
+                    }
+                },
+            );
+        }
+
+        let mut bounds = Map::new();
+        bounds.insert("min_x".into(), (area.min.x as i64).into());
+        bounds.insert("min_z".into(), (area.min.y as i64).into());
+        bounds.insert("max_x".into(), (area.max.x as i64).into());
+        bounds.insert("max_z".into(), (area.max.y as i64).into());
+        engine
+            .call_fn::<()>(&mut Scope::new(), &self.ast, "build", (bounds,))
+            .map_err(|err| anyhow!("{err}"))?;
+
+        let cursor = level.recording_cursor();
+        for (pos, block) in placed.lock().unwrap().drain(..) {
+            if area.contains(pos.truncate()) {
+                level.set(pos, block);
+            }
+        }
+        Ok(level.pop_recording(cursor).into())
+    }
+}
@@ -1,15 +1,32 @@
 #![allow(clippy::type_complexity)]
 #![allow(clippy::too_many_arguments)]
 
+pub mod apiary;
+pub mod blacksmith;
 pub mod building_plan;
+pub mod caravan;
+pub mod checkpoint;
 mod construction;
+pub mod erosion;
+pub mod garden;
+pub mod idle;
+mod job_board;
 mod logistics;
 pub mod lumberjack;
 mod main_loop;
+pub mod milling;
+pub mod passes;
 mod personal_name;
+pub mod population;
 pub mod quarry;
+pub mod schedule;
+pub mod settlement;
+pub mod skills;
+pub mod stats;
+pub mod vanilla;
 
-pub use main_loop::sim;
+pub use construction::Built;
+pub use main_loop::{sim, Generator, Rerun, DEFAULT_TICKS};
 
 use std::collections::VecDeque;
 
@@ -19,10 +36,14 @@ use crate::optimize::optimize;
 use crate::remove_foliage::remove_tree;
 use crate::*;
 use crate::{pathfind::pathfind, remove_foliage::remove_trees, replay::*};
+use blacksmith::{Blacksmith, ToolWear};
 use building_plan::*;
 use construction::*;
+use idle::{cut_fishing_holes, FishingHoles, Idle, Seats};
+use job_board::JobBoard;
 use logistics::*;
 use lumberjack::Lumberjack;
+use skills::{Skill, Skills};
 
 use bevy_derive::{Deref, DerefMut};
 use bevy_ecs::prelude::*;
@@ -34,6 +55,10 @@ pub struct Tick(pub i32);
 #[derive(Component)]
 pub struct CityCenter;
 
+/// Name of the settlement, on its [`CityCenter`]
+#[derive(Component)]
+pub struct TownName(pub String);
+
 #[derive(Component, Deref, DerefMut, PartialEq)]
 pub struct Pos(pub Vec3);
 
@@ -48,10 +73,41 @@ pub struct PrevPos(pub Vec3);
 
 #[derive(Component, Default)]
 pub struct Villager {
-    pub carry: Option<Stack>,
+    pub carry: Inventory,
     pub carry_id: Id,
 }
 
+/// Tool shown in the villager's hand while working
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HeldTool {
+    Axe,
+    Pickaxe,
+    Shovel,
+    FishingRod,
+}
+
+impl HeldTool {
+    /// What a builder would use to place this block
+    pub fn for_block(block: Block) -> Self {
+        match block {
+            _ if block.soil() => Self::Shovel,
+            Log(..) | Full(Wood(_)) | Slab(Wood(_), _) | Stair(Wood(_), ..) | Fence(Wood(_)) => {
+                Self::Axe
+            }
+            _ => Self::Pickaxe,
+        }
+    }
+
+    pub fn item(self) -> &'static str {
+        match self {
+            Self::Axe => "minecraft:iron_axe",
+            Self::Pickaxe => "minecraft:iron_pickaxe",
+            Self::Shovel => "minecraft:iron_shovel",
+            Self::FishingRod => "minecraft:fishing_rod",
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Name(pub String);
 
@@ -82,100 +138,6 @@ impl Tree {
     }
 }
 
-fn assign_work(
-    mut commands: Commands,
-    mut replay: ResMut<Replay>,
-    idle: Query<
-        (Entity, &Pos),
-        (
-            With<Villager>,
-            With<Jobless>,
-            Without<DeliverTask>,
-            Without<BuildTask>,
-        ),
-    >,
-    mut out_piles: Query<(Entity, &Pos, &mut OutPile)>,
-    mut in_piles: Query<(Entity, &Pos, &mut InPile)>,
-    mut construction_sites: Query<(Entity, &Pos, &mut ConstructionSite)>,
-) {
-    for (vill, vil_pos) in &idle {
-        // Construction
-        if let Some((building, pos, mut site)) = construction_sites
-            .iter_mut()
-            .filter(|(_, _, site)| site.has_materials & !site.has_builder)
-            .min_by_key(|(_, pos, _)| pos.distance_squared(vil_pos.0) as u32)
-        {
-            site.has_builder = true;
-            commands
-                .entity(vill)
-                .insert((MoveTask::new(pos.0.block()), BuildTask { building }));
-            continue;
-        }
-
-        // Transport
-        if let Some((_, task)) = out_piles
-            .iter_mut()
-            .filter_map(|(out_entity, out_pos, out_pile)| {
-                let mut best_score = f32::INFINITY;
-                let mut task = None;
-                for (good, &amount) in out_pile.available.iter() {
-                    if amount == 0. {
-                        continue;
-                    }
-                    for (in_entity, in_pos, in_pile) in &mut in_piles {
-                        if let Some(&requested) = in_pile.requested.get(good)
-                            && requested > 0.
-                        {
-                            if let Some(priority) = in_pile.priority {
-                                if priority != *good {
-                                    continue;
-                                }
-                            }
-                            let mut score = out_pos.distance_squared(in_pos.0);
-                            // Try to reduce the amount of trips
-                            if amount < requested {
-                                score *= 2.;
-                            }
-                            if score < best_score {
-                                best_score = score;
-                                task = Some((
-                                    PickupTask {
-                                        from: out_entity,
-                                        stack: Stack::new(
-                                            *good,
-                                            amount.min(requested).min(CARRY_CAPACITY),
-                                        ),
-                                        max_stack: requested.min(CARRY_CAPACITY),
-                                    },
-                                    DeliverTask { to: in_entity },
-                                ));
-                            }
-                        }
-                    }
-                }
-                task.map(|task| (vil_pos.distance_squared(out_pos.0), task))
-            })
-            // TODO: Also influence via best_score?
-            .min_by_key(|(d, _)| *d as i32)
-        {
-            out_piles
-                .get_mut(task.0.from)
-                .unwrap()
-                .2
-                .available
-                .remove(task.0.stack);
-            in_piles
-                .get_mut(task.1.to)
-                .unwrap()
-                .2
-                .requested
-                .remove(task.0.stack);
-            replay.dbg("assign carry");
-            commands.entity(vill).insert(task);
-        }
-    }
-}
-
 fn place(
     mut commands: Commands,
     mut replay: ResMut<Replay>,
@@ -184,6 +146,11 @@ fn place(
     for (entity, mut build) in &mut builders {
         if let Some(set) = build.0.pop_front() {
             replay.block(set.pos, set.block);
+            if set.block == Air {
+                replay.block_effect(set.pos, set.previous, true);
+            } else {
+                replay.block_effect(set.pos, set.block, false);
+            }
         } else {
             commands.entity(entity).remove::<PlaceTask>();
         }
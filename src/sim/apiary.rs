@@ -0,0 +1,165 @@
+use crate::{
+    remove_foliage::{remove_trees, RemovedNest},
+    *,
+};
+use nbt::CompoundTag;
+use sim::*;
+
+use super::lumberjack::Lumberjack;
+
+const APIARY_SIZE: i32 = 5;
+/// How far from the lumber camp the apiary may be
+const SEARCH_DISTANCE: i32 = 15;
+const BEES_PER_HIVE: usize = 3;
+/// Ticks a bee stays in the hive before it comes out
+const MIN_OCCUPATION_TICKS: i32 = 600;
+
+/// Fenced flower patch with beehives, next to a lumber camp
+#[derive(Component)]
+pub struct Apiary {
+    /// Spots for nests taken off chopped trees
+    free: Vec<(IVec3, HDir)>,
+}
+
+/// Nests the lumberjacks took off trees that still need a place in an apiary
+#[derive(Resource, Default)]
+pub struct RelocatedNests(pub Vec<RemovedNest>);
+
+/// Block entity of a hive with `bees` bees inside
+pub fn hive_nbt(bees: usize) -> CompoundTag {
+    let bees: Vec<CompoundTag> = (0..bees)
+        .map(|_| {
+            let mut entity = CompoundTag::new();
+            entity.insert_str("id", "minecraft:bee");
+            let mut bee = CompoundTag::new();
+            bee.insert("EntityData", entity);
+            bee.insert_i32("TicksInHive", rand_range(0..MIN_OCCUPATION_TICKS));
+            bee.insert_i32("MinOccupationTicks", MIN_OCCUPATION_TICKS);
+            bee
+        })
+        .collect();
+    let mut nbt = CompoundTag::new();
+    nbt.insert_str("id", "beehive");
+    nbt.insert_compound_tag_vec("Bees", bees);
+    nbt
+}
+
+/// Two beehives in the corners of a flower patch; the other two corners are left for nests.
+/// Returns the free corners together with the direction the hives there should face.
+pub fn make_apiary(level: &mut impl WorldView, area: Rect) -> (PlaceList, Vec<(IVec3, HDir)>) {
+    let cursor = level.recording_cursor();
+    let inner = area.shrink(1);
+    remove_trees(level, area.grow(1));
+    for column in area {
+        for z in level.height(column) + 1..level.height(column) + 4 {
+            level.set(column.extend(z), Air);
+        }
+    }
+    for column in area.border() {
+        level.set(level.ground(column) + IVec3::Z, Fence(Wood(Oak)));
+    }
+    let gate = ivec2(area.center().x, area.min.y);
+    level.set(
+        level.ground(gate) + IVec3::Z,
+        Block::from_name("oak_fence_gate", &[("facing", "north")]),
+    );
+
+    let flowers = [
+        SmallPlant::Dandelion,
+        SmallPlant::Poppy,
+        SmallPlant::Cornflower,
+        SmallPlant::Allium,
+        SmallPlant::OxeyeDaisy,
+    ];
+    let corners: Vec<IVec2> = inner.corners().collect();
+    for column in inner {
+        if corners.contains(&column) {
+            continue;
+        }
+        let ground = level.ground(column);
+        level.set(ground, Grass);
+        level.set(ground + IVec3::Z, SmallPlant(*flowers.choose()));
+    }
+
+    let mut free = Vec::new();
+    for (i, corner) in corners.into_iter().enumerate() {
+        // Facing the middle, so the bees fly out over the flowers
+        let facing = if corner.y < inner.center().y {
+            YPos
+        } else {
+            YNeg
+        };
+        let pos = level.ground(corner) + IVec3::Z;
+        if i % 2 == 0 {
+            let honey = rand_range(0..=5);
+            level.set(pos, Beehive(facing, honey));
+            level.set_tile_entity(pos, hive_nbt(BEES_PER_HIVE));
+        } else {
+            free.push((pos, facing));
+        }
+    }
+    (level.pop_recording(cursor).into(), free)
+}
+
+pub fn make_apiaries(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    mut replay: ResMut<Replay>,
+    new_lumberjacks: Query<&Lumberjack, Added<Built>>,
+) {
+    for lumberjack in &new_lumberjacks {
+        let Some(area) = level.free_spot_near(
+            lumberjack.area.center(),
+            IVec2::splat(APIARY_SIZE),
+            SEARCH_DISTANCE,
+        ) else {
+            continue;
+        };
+        if wateryness(&level, area) > 0. {
+            continue;
+        }
+        let (rec, free) = make_apiary(&mut *level, area);
+        for set in rec {
+            if let Some(nbt) = level.tile_entity(set.pos) {
+                replay.block_entity(set.pos, set.block, &nbt);
+            } else {
+                replay.block(set.pos, set.block);
+            }
+        }
+        level.set_blocked(area);
+        commands.spawn((Pos(level.ground(area.center()).as_vec3()), Apiary { free }));
+    }
+}
+
+/// Moves nests from chopped trees into apiaries with free spots
+pub fn relocate_nests(
+    mut level: ResMut<Level>,
+    mut replay: ResMut<Replay>,
+    mut nests: ResMut<RelocatedNests>,
+    mut apiaries: Query<&mut Apiary>,
+) {
+    while !nests.0.is_empty() {
+        let Some((pos, facing)) = apiaries.iter_mut().find_map(|mut apiary| apiary.free.pop())
+        else {
+            return;
+        };
+        let (nest, nbt) = nests.0.pop().unwrap();
+        let honey = match nest {
+            BeeNest(_, honey) => honey,
+            _ => 0,
+        };
+        let nest = BeeNest(facing, honey);
+        // Replayed here with its block entity instead of by tick_replay
+        let cursor = level.recording_cursor();
+        level(pos, nest);
+        let placed: Vec<SetBlock> = level.pop_recording(cursor).collect();
+        for set in placed {
+            if let Some(nbt) = &nbt {
+                level.set_tile_entity(set.pos, nbt.clone());
+                replay.block_entity(set.pos, set.block, nbt);
+            } else {
+                replay.block(set.pos, set.block);
+            }
+        }
+    }
+}
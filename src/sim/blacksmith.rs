@@ -0,0 +1,183 @@
+use crate::{house::shack, *};
+use sim::*;
+
+/// Materials for this many tools are kept in stock
+const STOCK: f32 = 5.;
+/// Wear at which tools need replacing. Workers with worn-out tools work slower.
+const WORN_OUT: f32 = 1.;
+
+#[derive(Component)]
+pub struct Blacksmith {
+    pub area: Rect,
+}
+
+#[derive(Component)]
+pub struct Smith {
+    workplace: Entity,
+    progress: i32,
+}
+
+/// Tools of a worker wear out through work and get replaced from the stock of the workplace
+#[derive(Component)]
+pub struct ToolWear {
+    workplace: Entity,
+    pub wear: f32,
+    ordered: bool,
+}
+
+impl ToolWear {
+    pub fn new(workplace: Entity) -> Self {
+        Self {
+            workplace,
+            wear: 0.,
+            ordered: false,
+        }
+    }
+
+    pub fn efficiency(&self) -> f32 {
+        if self.wear < WORN_OUT {
+            1.
+        } else {
+            0.5
+        }
+    }
+}
+
+pub fn make_smithy(level: &mut impl WorldView, area: Rect) -> PlaceList {
+    let floor = level.average_height(area.border()).round() as i32;
+    let mut rec = shack(level, area);
+
+    let cursor = level.recording_cursor();
+    let inner = area.shrink(1);
+    for x in [inner.min.x, inner.min.x + 1] {
+        level.set(ivec3(x, inner.min.y, floor + 1), Furnace(YPos));
+    }
+    level.set(inner.center().extend(floor + 1), Anvil(HAxis::X));
+    level.set(inner.max.extend(floor + 1), LavaCauldron);
+
+    // Chimney behind the furnaces, reaching above the roof
+    let chimney = ivec2(inner.min.x, area.min.y - 1);
+    let top = floor + 6 + area.size().min_element() / 2;
+    for z in level.height(chimney) + 1..=top {
+        level.set(chimney.extend(z), Full(Brick));
+    }
+    level.set(chimney.extend(top + 1), Air);
+    rec.extend(level.pop_recording(cursor));
+    rec
+}
+
+pub fn assign_worker(
+    mut commands: Commands,
+    available: Query<(Entity, &Pos), With<Jobless>>,
+    new: Query<(Entity, &Pos), (With<Blacksmith>, Added<Built>)>,
+) {
+    let mut assigned = Vec::new();
+    for (workplace, pos) in &new {
+        let Some((worker, _)) = available
+            .iter()
+            .filter(|(e, _)| !assigned.contains(e))
+            .min_by_key(|(_, p)| p.distance_squared(pos.0) as i32)
+        else {
+            return;
+        };
+        commands.entity(worker).remove::<Jobless>().insert(Smith {
+            workplace,
+            progress: 0,
+        });
+        assigned.push(worker);
+    }
+}
+
+pub fn setup_smithy(
+    mut commands: Commands,
+    mut new: Query<(Entity, &mut OutPile), (With<Blacksmith>, Added<Built>)>,
+) {
+    for (entity, mut out_pile) in &mut new {
+        // Leftover construction materials get turned into tools instead
+        out_pile.available = default();
+        let mut requested = Goods::default();
+        for input in &ECONOMY.recipe("tools").inputs {
+            requested.add(Stack::new(input.kind, input.amount * STOCK));
+        }
+        commands.entity(entity).insert(InPile {
+            requested,
+            priority: None,
+        });
+    }
+}
+
+pub fn work(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut smiths: Query<(Entity, &Pos, &mut Smith), (Without<MoveTask>, Without<DeliverTask>)>,
+    mut workplaces: Query<(&Pos, &mut Pile, &mut InPile, &mut OutPile), With<Blacksmith>>,
+) {
+    for (entity, pos, mut smith) in &mut smiths {
+        let Ok((workplace_pos, mut pile, mut in_pile, mut out_pile)) =
+            workplaces.get_mut(smith.workplace)
+        else {
+            continue;
+        };
+        if pos.distance(workplace_pos.0) > 3. {
+            commands.entity(entity).insert(MoveTask {
+                goal: workplace_pos.block(),
+                distance: 2,
+            });
+            continue;
+        }
+
+        let recipe = ECONOMY.recipe("tools");
+        if !recipe.inputs.iter().all(|input| pile.has(*input)) {
+            continue;
+        }
+        smith.progress += 1;
+        if smith.progress < recipe.ticks {
+            continue;
+        }
+        smith.progress = 0;
+        for input in &recipe.inputs {
+            pile.remove(*input);
+            in_pile.requested.add(*input);
+        }
+        pile.add(recipe.output);
+        out_pile.available.add(recipe.output);
+        replay.dust(workplace_pos.block());
+    }
+}
+
+/// Orders new tools to the workplace once they're worn, and swaps them in when they've arrived
+pub fn resupply_tools(
+    mut commands: Commands,
+    mut workers: Query<&mut ToolWear>,
+    mut workplaces: Query<(&mut Pile, Option<&mut InPile>, Option<&mut OutPile>)>,
+) {
+    for mut tools in &mut workers {
+        if tools.wear < WORN_OUT / 2. {
+            continue;
+        }
+        let Ok((mut pile, in_pile, out_pile)) = workplaces.get_mut(tools.workplace) else {
+            continue;
+        };
+        let new_tools = Stack::new(Good::Tools, 1.);
+        if pile.has(new_tools) {
+            pile.remove(new_tools);
+            if let Some(mut out_pile) = out_pile {
+                out_pile.available.remove(new_tools);
+            }
+            tools.wear = 0.;
+            tools.ordered = false;
+        } else if !tools.ordered {
+            tools.ordered = true;
+            if let Some(mut in_pile) = in_pile {
+                in_pile.requested.add(new_tools);
+            } else {
+                let mut requested = Goods::default();
+                requested.add(new_tools);
+                commands.entity(tools.workplace).insert(InPile {
+                    requested,
+                    priority: None,
+                });
+            }
+        }
+    }
+}
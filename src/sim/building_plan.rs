@@ -1,24 +1,78 @@
-use crate::*;
+#[cfg(feature = "scripting")]
+use crate::scripting::BuildingScripts;
+use crate::{
+    castle::{self, castle, MIN_SIZE},
+    cliff_dwelling::{self, cliff_dwelling, find_cliffs},
+    dzong::{self, dzong, find_peaks},
+    heraldry::TownCrest,
+    hydrology::Hydrology,
+    mill::{watermill, watermill_side, windmill},
+    parallel::ParallelPasses,
+    *,
+};
 use bevy_ecs::prelude::*;
 use sim::*;
 
-use super::{lumberjack::TreeIsNearLumberCamp, quarry::Quarry};
+use super::{
+    blacksmith::{make_smithy, Blacksmith},
+    garden::Garden,
+    idle::Bed,
+    lumberjack::TreeIsNearLumberCamp,
+    milling::Mill,
+    population::needs_housing,
+    quarry::Quarry,
+    vanilla::{demolish, Replaces},
+};
 
 #[derive(Component, Deref, DerefMut)]
-pub struct Planned(Rect);
+pub struct Planned(pub Rect);
 
 #[derive(Component)]
 pub struct House {
-    area: Rect,
+    pub area: Rect,
 }
 
 #[derive(Component)]
 pub struct ToBeBuild;
 
+/// Seat of a settlement that has grown large enough, see [`crate::castle`]
+#[derive(Component)]
+pub struct Castle {
+    /// Outline of the curtain wall
+    pub area: Rect,
+    pub crest: TownCrest,
+}
+
+/// Villagers per settlement before it gets a castle
+const CASTLE_POPULATION: usize = 40;
+
+/// House carved into a cliff, see [`crate::cliff_dwelling`]. Also has a [`House`] component,
+/// so it counts towards housing.
+#[derive(Component)]
+pub struct CliffDwelling {
+    pub foot: IVec2,
+    pub dir: HDir,
+}
+
+/// Monastery fortress on a mountain top overlooking a settlement, see [`crate::dzong`]
+#[derive(Component)]
+pub struct Dzong {
+    pub peak: IVec2,
+    pub village: IVec2,
+}
+
+/// Villagers needed before a dzong gets built
+const DZONG_POPULATION: usize = 30;
+
+/// Measured on the water surface, so flat water counts as even (piers can be built on it)
 pub fn unevenness(level: &Level, area: Rect) -> f32 {
-    let avg_height = level.average_height(area);
+    let avg_height = area
+        .into_iter()
+        .map(|pos| level.surface(pos).z as f32)
+        .sum::<f32>()
+        / area.total() as f32;
     area.into_iter()
-        .map(|pos| ((level.height)(pos) as f32 - avg_height).abs().powf(2.))
+        .map(|pos| (level.surface(pos).z as f32 - avg_height).abs().powf(2.))
         .sum::<f32>()
         / area.total() as f32
 }
@@ -30,6 +84,11 @@ pub fn wateryness(level: &Level, area: Rect) -> f32 {
         / area.total() as f32
 }
 
+/// Fraction of columns with lava, powder snow, ravines and such
+pub fn hazardousness(level: &Level, area: Rect) -> f32 {
+    area.into_iter().filter(|&pos| level.hazard(pos)).count() as f32 / area.total() as f32
+}
+
 pub fn choose_starting_area(level: &Level) -> Rect {
     optimize(
         Rect::new_centered(level.area().center(), IVec2::splat(44)),
@@ -40,7 +99,7 @@ pub fn choose_starting_area(level: &Level) -> Rect {
                 rand_range(-max_move..=max_move),
             ));
 
-            if !level.area().has_subrect(area) {
+            if !level.area().has_subrect(area) || !level.unblocked(area.shrink(10)) {
                 return None;
             }
             // TODO: Take biomes into account
@@ -49,8 +108,10 @@ pub fn choose_starting_area(level: &Level) -> Rect {
                 .as_vec2()
                 .distance(level.area().center().as_vec2())
                 / (level.area().size().as_vec2().min_element() - 40.);
-            let score =
-                wateryness(level, area) * 20. + unevenness(level, area) + distance.powf(2.) / 2.;
+            let score = wateryness(level, area) * 20.
+                + hazardousness(level, area) * 50.
+                + unevenness(level, area)
+                + distance.powf(2.) / 2.;
             Some((area, score))
         },
         300,
@@ -59,6 +120,55 @@ pub fn choose_starting_area(level: &Level) -> Rect {
     .shrink(10)
 }
 
+/// Starting areas for several settlements, spread out over the level.
+/// `areas` are starting areas that have already been decided on.
+pub fn choose_starting_areas(level: &Level, count: usize, mut areas: Vec<Rect>) -> Vec<Rect> {
+    if areas.is_empty() {
+        areas.push(choose_starting_area(level));
+    }
+    let spacing = level.area().size().min_element() as f32 / (count as f32 + 1.);
+    while areas.len() < count {
+        let start = Rect::new_centered(
+            ivec2(
+                rand_range(level.area().min.x + 22..=level.area().max.x - 22),
+                rand_range(level.area().min.y + 22..=level.area().max.y - 22),
+            ),
+            IVec2::splat(44),
+        );
+        let Some(area) = optimize(
+            start,
+            |mut area, temperature| {
+                let max_move = (100. * temperature) as i32;
+                area = area.offset(ivec2(
+                    rand_range(-max_move..=max_move),
+                    rand_range(-max_move..=max_move),
+                ));
+
+                if !level.area().has_subrect(area) || !level.unblocked(area.shrink(10)) {
+                    return None;
+                }
+                let crowding: f32 = areas
+                    .iter()
+                    .map(|other| {
+                        let distance = other.center().as_vec2().distance(area.center().as_vec2());
+                        (spacing / distance.max(1.)).powf(4.)
+                    })
+                    .sum();
+                let score = wateryness(level, area) * 20.
+                    + hazardousness(level, area) * 50.
+                    + unevenness(level, area)
+                    + crowding;
+                Some((area, score))
+            },
+            300,
+        ) else {
+            break;
+        };
+        areas.push(area.shrink(10));
+    }
+    areas
+}
+
 // Note this would require apply_defered after each placement
 // pub fn remove_outdated(
 //     mut commands: Commands,
@@ -78,18 +188,20 @@ pub fn choose_starting_area(level: &Level) -> Rect {
 //     }
 // }
 
+/// Plans a house once there are more villagers than the existing and planned houses can take
 pub fn plan_house(
     mut commands: Commands,
     level: Res<Level>,
     planned: Query<(), (With<House>, With<Planned>)>,
+    houses: Query<(), With<House>>,
+    villagers: Query<(), With<Villager>>,
     center: Query<&Pos, With<CityCenter>>,
 ) {
-    if planned.iter().len() > 0 {
+    if (planned.iter().len() > 0) | !needs_housing(villagers.iter().len(), houses.iter().len()) {
         return;
     }
 
-    // TODO: On a large map, allow for multiple town centers
-    let center = center.single().truncate();
+    let center = center.iter().collect::<Vec<_>>().choose().truncate();
     let Some(area) = optimize(
         Rect::new_centered(
             center.block(),
@@ -111,6 +223,7 @@ pub fn plan_house(
             let distance = (level.reachability)(area.center()) as f32;
             // TODO: try to minimize the amount of trees in the footprint
             let score = wateryness(&level, area) * 20.
+                + hazardousness(&level, area) * 50.
                 + unevenness(&level, area)
                 + (distance / 100.).powf(2.);
             Some((area, score))
@@ -127,6 +240,37 @@ pub fn plan_house(
     ));
 }
 
+/// Instead of building a house, settlers sometimes move into a cliff near a settlement
+pub fn plan_cliff_dwelling(
+    mut commands: Commands,
+    level: Res<Level>,
+    mut cliffs: Local<Option<Vec<(IVec2, HDir)>>>,
+    planned: Query<(), (With<House>, With<Planned>)>,
+    houses: Query<(), With<House>>,
+    villagers: Query<(), With<Villager>>,
+) {
+    if (planned.iter().len() > 0) | !needs_housing(villagers.iter().len(), houses.iter().len()) {
+        return;
+    }
+    let cliffs = cliffs.get_or_insert_with(|| find_cliffs(&level, level.area(), 10));
+
+    let Some(index) = cliffs.iter().position(|&(foot, dir)| {
+        level.unblocked(cliff_dwelling::footprint(foot, dir)) & ((level.reachability)(foot) < 150)
+    }) else {
+        return;
+    };
+    // Each cliff only gets one dwelling
+    let (foot, dir) = cliffs.remove(index);
+    let area = cliff_dwelling::footprint(foot, dir);
+
+    commands.spawn((
+        Pos(level.ground(foot).as_vec3()),
+        Planned(area),
+        House { area },
+        CliffDwelling { foot, dir },
+    ));
+}
+
 pub fn plan_lumberjack(
     mut commands: Commands,
     level: Res<Level>,
@@ -163,6 +307,7 @@ pub fn plan_lumberjack(
                 })
                 .sum::<f32>();
             let score = wateryness(&level, area) * 20.
+                + hazardousness(&level, area) * 50.
                 + unevenness(&level, area) * 1.
                 + center_distance / 200.
                 + tree_access * 5.;
@@ -186,6 +331,201 @@ pub fn plan_lumberjack(
     ));
 }
 
+pub fn plan_blacksmith(
+    mut commands: Commands,
+    level: Res<Level>,
+    planned: Query<(), (With<Blacksmith>, With<Planned>)>,
+    center: Query<&Pos, With<CityCenter>>,
+) {
+    if !planned.is_empty() {
+        return;
+    }
+
+    let center = center.iter().collect::<Vec<_>>().choose().truncate();
+    let Some(area) = optimize(
+        Rect::new_centered(center.block(), ivec2(rand_range(6..=8), rand_range(7..=9))),
+        |mut area, temperature| {
+            let max_move = (60. * temperature) as i32;
+            area = area.offset(ivec2(
+                rand_range(-max_move..=max_move),
+                rand_range(-max_move..=max_move),
+            ));
+            if 0.2 > rand() {
+                area = Rect::new_centered(area.center(), area.size().yx())
+            }
+
+            if !level.unblocked(area) {
+                return None;
+            }
+            let distance = (level.reachability)(area.center()) as f32;
+            let score = wateryness(&level, area) * 20.
+                + hazardousness(&level, area) * 50.
+                + unevenness(&level, area)
+                + (distance / 150.).powf(2.);
+            Some((area, score))
+        },
+        200,
+    ) else {
+        return;
+    };
+
+    commands.spawn((
+        Pos(level.ground(area.center()).as_vec3()),
+        Planned(area),
+        Blacksmith { area },
+    ));
+}
+
+/// Plans a castle for a settlement once it's populous enough, flying that settlement's crest
+pub fn plan_castle(
+    mut commands: Commands,
+    level: Res<Level>,
+    planned: Query<(), (With<Castle>, With<Planned>)>,
+    castles: Query<(), With<Castle>>,
+    villagers: Query<(), With<Villager>>,
+    center: Query<(&Pos, &TownCrest), With<CityCenter>>,
+) {
+    let centers = center.iter().len();
+    if !planned.is_empty()
+        | (castles.iter().len() >= centers)
+        | (villagers.iter().len() < CASTLE_POPULATION * (castles.iter().len() + 1))
+    {
+        return;
+    }
+
+    let (center, crest) = *center.iter().collect::<Vec<_>>().choose();
+    let Some(area) = optimize(
+        Rect::new_centered(
+            center.truncate().block(),
+            IVec2::splat(rand_range(MIN_SIZE..=MIN_SIZE + 6)),
+        ),
+        |mut area, temperature| {
+            let max_move = (80. * temperature) as i32;
+            area = area.offset(ivec2(
+                rand_range(-max_move..=max_move),
+                rand_range(-max_move..=max_move),
+            ));
+
+            let footprint = castle::footprint(area);
+            if !level.unblocked(footprint) {
+                return None;
+            }
+            let distance = (level.reachability)(area.center()) as f32;
+            let score = wateryness(&level, footprint) * 20.
+                + hazardousness(&level, footprint) * 50.
+                + unevenness(&level, area)
+                + (distance / 200.).powf(2.);
+            Some((area, score))
+        },
+        200,
+    ) else {
+        return;
+    };
+
+    commands.spawn((
+        Pos(level.ground(area.center()).as_vec3()),
+        Planned(castle::footprint(area)),
+        Castle {
+            area,
+            crest: crest.clone(),
+        },
+    ));
+}
+
+/// Plans a single dzong on the most prominent peak near a settlement
+pub fn plan_dzong(
+    mut commands: Commands,
+    level: Res<Level>,
+    mut peaks: Local<Option<Vec<IVec2>>>,
+    dzongs: Query<(), With<Dzong>>,
+    villagers: Query<(), With<Villager>>,
+    center: Query<&Pos, With<CityCenter>>,
+) {
+    if !dzongs.is_empty() | (villagers.iter().len() < DZONG_POPULATION) {
+        return;
+    }
+    let peaks = peaks.get_or_insert_with(|| find_peaks(&level, level.area(), 24, 15.));
+
+    // Peaks are sorted by prominence
+    let Some(peak) = peaks
+        .iter()
+        .copied()
+        .find(|&peak| level.unblocked(dzong::footprint(peak)) & ((level.reachability)(peak) < 300))
+    else {
+        return;
+    };
+    let Some(village) = center
+        .iter()
+        .map(|pos| pos.truncate().block())
+        .min_by_key(|village| village.distance_squared(peak))
+    else {
+        return;
+    };
+
+    commands.spawn((
+        Pos(level.ground(peak).as_vec3()),
+        Planned(dzong::footprint(peak)),
+        Dzong { peak, village },
+    ));
+}
+
+/// Plans a mill for each settlement once there are gardens whose harvest it can grind.
+/// Windmills go on hills, watermills next to rivers.
+pub fn plan_mill(
+    mut commands: Commands,
+    level: Res<Level>,
+    mut hydrology: Local<Option<Hydrology>>,
+    planned: Query<(), (With<Mill>, With<Planned>)>,
+    mills: Query<(), With<Mill>>,
+    gardens: Query<(), With<Garden>>,
+    center: Query<&Pos, With<CityCenter>>,
+) {
+    if !planned.is_empty() | gardens.is_empty() | (mills.iter().len() >= center.iter().len()) {
+        return;
+    }
+    let hydrology = hydrology.get_or_insert_with(|| Hydrology::new(&level));
+
+    let center = center.iter().collect::<Vec<_>>().choose().truncate();
+    let Some(area) = optimize(
+        Rect::new_centered(center.block(), IVec2::splat(5)),
+        |mut area, temperature| {
+            let max_move = (60. * temperature) as i32;
+            area = area.offset(ivec2(
+                rand_range(-max_move..=max_move),
+                rand_range(-max_move..=max_move),
+            ));
+
+            if !level.unblocked(area) {
+                return None;
+            }
+            let distance = (level.reachability)(area.center()) as f32;
+            let exposure = if watermill_side(&*level, hydrology, area).is_some() {
+                10.
+            } else {
+                level.average_height(area) - level.average_height(area.grow(8).border())
+            };
+            let score = wateryness(&level, area) * 20.
+                + hazardousness(&level, area) * 50.
+                + unevenness(&level, area)
+                + (distance / 150.).powf(2.)
+                - exposure;
+            Some((area, score))
+        },
+        200,
+    ) else {
+        return;
+    };
+
+    commands.spawn((
+        Pos(level.ground(area.center()).as_vec3()),
+        Planned(area),
+        Mill {
+            area,
+            water: watermill_side(&*level, hydrology, area),
+        },
+    ));
+}
+
 pub fn plan_quarry(
     mut commands: Commands,
     level: Res<Level>,
@@ -226,6 +566,7 @@ pub fn plan_quarry(
                 return None;
             }
             let score = wateryness(&level, quarry.area) * 20.
+                + hazardousness(&level, quarry.area) * 50.
                 + unevenness(&level, quarry.area) * 1.5
                 - quarried_height * 1.
                 + distance / 100.;
@@ -248,26 +589,37 @@ pub fn assign_builds(
     mut commands: Commands,
     mut level: ResMut<Level>,
     construction_sites: Query<(), With<ConstructionSite>>,
-    houses: Query<(), (With<House>, Without<Planned>)>,
     planned_houses: Query<(Entity, &Planned), With<House>>,
     lumberjacks: Query<(), (With<Lumberjack>, Without<Planned>)>,
     planned_lumberjacks: Query<(Entity, &Planned), With<Lumberjack>>,
     quarries: Query<(), (With<Quarry>, Without<Planned>)>,
     planned_quarries: Query<(Entity, &Planned), With<Quarry>>,
+    blacksmiths: Query<(), (With<Blacksmith>, Without<Planned>)>,
+    planned_blacksmiths: Query<(Entity, &Planned), With<Blacksmith>>,
+    planned_mills: Query<(Entity, &Planned), With<Mill>>,
+    planned_castles: Query<(Entity, &Planned), With<Castle>>,
+    planned_dzongs: Query<(Entity, &Planned), With<Dzong>>,
 ) {
     if construction_sites.iter().len() > 10 {
         return;
     }
     let mut plans = Vec::new();
-    if houses.iter().len() < 30 {
-        plans.extend(&planned_houses)
-    }
+    // Houses are only planned when they're needed
+    plans.extend(&planned_houses);
     if lumberjacks.iter().len() < 10 {
         plans.extend(&planned_lumberjacks)
     }
     if quarries.iter().len() < 10 {
         plans.extend(&planned_quarries)
     }
+    if blacksmiths.iter().len() < 3 {
+        plans.extend(&planned_blacksmiths)
+    }
+    // Mills are only planned when there's a settlement without one
+    plans.extend(&planned_mills);
+    // Castles are only planned when a settlement is large enough
+    plans.extend(&planned_castles);
+    plans.extend(&planned_dzongs);
     if let Some(&(selected, area)) = plans.try_choose() {
         level.set_blocked(area.0);
         commands
@@ -277,19 +629,103 @@ pub fn assign_builds(
     }
 }
 
+/// House on stilts, which gets connected to its neighbors by bridges
+#[derive(Component)]
+pub struct StiltHouse {
+    pub area: Rect,
+    pub floor: i32,
+}
+
+// Buildings also modify their surroundings (roof overhang, removed trees)
+const BUILD_MARGIN: i32 = 6;
+
 // TMP
 pub fn test_build_house(
     mut replay: ResMut<Replay>,
     mut commands: Commands,
     mut level: ResMut<Level>,
-    new: Query<(Entity, &House), With<ToBeBuild>>,
+    new: Query<(Entity, &House, Option<&Replaces>), (With<ToBeBuild>, Without<CliffDwelling>)>,
+    stilt_houses: Query<&StiltHouse, With<Built>>,
+    #[cfg(feature = "scripting")] scripts: Res<BuildingScripts>,
 ) {
-    for (entity, house) in &new {
+    let mut passes = ParallelPasses::new();
+    let mut new_stilt_houses = HashMap::default();
+    for (entity, house, replaces) in &new {
         replay.dbg(&format!("building house at {:?}", house.area.center()));
-        commands
-            .entity(entity)
+        let area = house.area;
+        let replaces = replaces.copied();
+        let fisher = wateryness(&level, area) > 0.5;
+        let snowy = matches!((level.biome)(area.center()), Biome::Snowy);
+        let swampy = !fisher
+            && matches!(
+                (level.biome)(area.center()),
+                Biome::Swamp | Biome::MangroveSwamp
+            );
+        if swampy {
+            let floor = house::stilt_floor(&*level, area);
+            new_stilt_houses.insert(entity, StiltHouse { area, floor });
+        }
+        #[cfg(feature = "scripting")]
+        let script = scripts.pick();
+        passes.add(area.grow(BUILD_MARGIN), move |region| {
+            let cursor = region.recording_cursor();
+            if let Some(Replaces(bounds)) = replaces {
+                demolish(region, bounds);
+            }
+            let mut rec: PlaceList = region.pop_recording(cursor).into();
+            if fisher {
+                rec.extend(house::fisher_hut(region, area));
+                return (entity, rec);
+            }
+            if swampy {
+                rec.extend(house::stilt_house(region, area));
+                return (entity, rec);
+            }
+            if snowy {
+                rec.extend(if 0.5 > rand() {
+                    house::igloo(region, area)
+                } else {
+                    house::spruce_hut(region, area)
+                });
+                return (entity, rec);
+            }
+            #[cfg(feature = "scripting")]
+            if let Some(script) = script {
+                match script.build(region, area) {
+                    Ok(built) => {
+                        rec.extend(built);
+                        return (entity, rec);
+                    }
+                    Err(err) => eprintln!("Building script {} failed: {err}", script.name),
+                }
+            }
+            rec.extend(house::house(region, area));
+            (entity, rec)
+        });
+    }
+    for (entity, mut rec) in passes.run(&mut level) {
+        if let Some(stilt_house) = new_stilt_houses.remove(&entity) {
+            for other in &stilt_houses {
+                if let Some(bridge) = house::rope_bridge(
+                    &mut *level,
+                    (stilt_house.area, stilt_house.floor),
+                    (other.area, other.floor),
+                ) {
+                    rec.extend(bridge);
+                }
+            }
+            commands.entity(entity).insert(stilt_house);
+        }
+        let mut entity = commands.entity(entity);
+        if let Some(pos) = house::find_bed(&rec) {
+            entity.insert(Bed {
+                pos,
+                occupied: false,
+            });
+        }
+        entity
             .remove::<ToBeBuild>()
-            .insert(ConstructionSite::new(house::house(&mut level, house.area)));
+            .insert(ConstructionSite::new(rec));
     }
 }
 
@@ -299,14 +735,108 @@ pub fn test_build_lumberjack(
     mut level: ResMut<Level>,
     new: Query<(Entity, &Lumberjack), With<ToBeBuild>>,
 ) {
+    let mut passes = ParallelPasses::new();
     for (entity, lumberjack) in &new {
+        let area = lumberjack.area;
+        passes.add(area.grow(BUILD_MARGIN), move |region| {
+            (entity, house::shack(region, area))
+        });
+    }
+    for (entity, rec) in passes.run(&mut level) {
         commands
             .entity(entity)
             .remove::<ToBeBuild>()
-            .insert(ConstructionSite::new(house::shack(
-                &mut level,
-                lumberjack.area,
-            )));
+            .insert(ConstructionSite::new(rec));
+    }
+}
+
+// TMP
+pub fn test_build_blacksmith(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    new: Query<(Entity, &Blacksmith), With<ToBeBuild>>,
+) {
+    let mut passes = ParallelPasses::new();
+    for (entity, blacksmith) in &new {
+        let area = blacksmith.area;
+        passes.add(area.grow(BUILD_MARGIN), move |region| {
+            (entity, make_smithy(region, area))
+        });
+    }
+    for (entity, rec) in passes.run(&mut level) {
+        commands
+            .entity(entity)
+            .remove::<ToBeBuild>()
+            .insert(ConstructionSite::new(rec));
+    }
+}
+
+// TMP
+pub fn test_build_mill(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    new: Query<(Entity, &Mill), With<ToBeBuild>>,
+) {
+    // Built directly, the sails reach further than the margin of a region
+    for (entity, mill) in &new {
+        let rec = match mill.water {
+            Some(water) => watermill(&mut *level, mill.area, water),
+            None => windmill(&mut *level, mill.area),
+        };
+        commands
+            .entity(entity)
+            .remove::<ToBeBuild>()
+            .insert(ConstructionSite::new(rec));
+    }
+}
+
+// TMP
+pub fn test_build_castle(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    new: Query<(Entity, &Castle), With<ToBeBuild>>,
+) {
+    // Built directly, the moat reaches further than the margin of a region
+    for (entity, castle_plan) in &new {
+        let Some(rec) = castle(&mut *level, castle_plan.area, &castle_plan.crest) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        commands
+            .entity(entity)
+            .remove::<ToBeBuild>()
+            .insert(ConstructionSite::new(rec));
+    }
+}
+
+// TMP
+pub fn test_build_dzong(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    new: Query<(Entity, &Dzong), With<ToBeBuild>>,
+) {
+    // Built directly, the path down to the village leaves the footprint
+    for (entity, plan) in &new {
+        let rec = dzong(&mut *level, plan.peak, plan.village);
+        commands
+            .entity(entity)
+            .remove::<ToBeBuild>()
+            .insert(ConstructionSite::new(rec));
+    }
+}
+
+// TMP
+pub fn test_build_cliff_dwelling(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    new: Query<(Entity, &CliffDwelling), With<ToBeBuild>>,
+) {
+    for (entity, dwelling) in &new {
+        let rec = cliff_dwelling(&mut *level, dwelling.foot, dwelling.dir);
+        commands
+            .entity(entity)
+            .remove::<ToBeBuild>()
+            .insert(ConstructionSite::new(rec));
     }
 }
 
@@ -321,8 +851,8 @@ pub fn test_build_quarry(
             let pos = level.ground(pos);
             level(pos, Wool(Black))
         }
+        level.set_blocked(quarry.probing_area());
         for pos in quarry.probing_area() {
-            (level.blocked)(pos, true);
             let pos = level.ground(pos);
             level(pos, Wool(Red))
         }
@@ -0,0 +1,238 @@
+use crate::{
+    hydrology::Hydrology,
+    nav_graph::NavGraph,
+    pathfind::{pathfind_with, Profile},
+    road::{carve_road, smooth_path, travel_height, Crossing},
+    *,
+};
+use sim::*;
+
+/// Ticks between caravans setting out
+const INTERVAL: i32 = 1500;
+const SPEED: f32 = 0.12;
+/// Distance between the members of a caravan
+const SPACING: f32 = 2.;
+pub const ROAD_WIDTH: i32 = 3;
+/// Matches the sampling of `smooth_path`
+const SAMPLES_PER_BLOCK: f32 = 2.;
+/// Chunk crossings of the coarse route per leg of the detailed search
+const CROSSINGS_PER_LEG: usize = 3;
+/// Searches per leg before giving up, the pathfinder stops after exploring a while
+const SEARCHES_PER_LEG: usize = 4;
+/// Rivers, lakes or bays a single road may cross
+const MAX_CROSSINGS: u32 = 3;
+
+/// Road connecting two settlements, along which caravans travel
+#[derive(Component)]
+pub struct Road {
+    /// Smoothed course of the road, with samples about half a block apart
+    path: Vec<Vec3>,
+}
+
+/// Trader leading pack animals along a road
+#[derive(Component)]
+pub struct Caravan {
+    road: Entity,
+    /// Travelling from the end of the road to its start
+    reverse: bool,
+    /// Distance travelled along the road
+    progress: f32,
+    animals: Vec<Id>,
+}
+
+/// Finds a path between the settlements that carts could take and carves a road along it.
+/// Where there is no dry route, the road crosses the water by ford, bridge or ferry.
+pub fn build_road(level: &mut Level, nav: &mut NavGraph, from: IVec3, to: IVec3) -> Option<Road> {
+    let path = find_path(level, nav, from, to, MAX_CROSSINGS)?;
+    let curve = smooth_path(&path);
+    let road = carve_road(level, &curve, ROAD_WIDTH);
+    level.set_blocked(road);
+    let path = curve
+        .iter()
+        .map(|sample| {
+            let column = sample.round().as_ivec2();
+            sample.extend(travel_height(level, column) as f32 + 1.)
+        })
+        .collect();
+    Some(Road { path })
+}
+
+/// Path for carts, planned on the coarse `nav` graph first, then searched in detail leg by
+/// leg. If water is in the way, the path leads over a crossing, which gets built.
+fn find_path(
+    level: &mut Level,
+    nav: &mut NavGraph,
+    from: IVec3,
+    to: IVec3,
+    crossings: u32,
+) -> Option<Vec<IVec3>> {
+    if let Some(path) = find_dry_path(level, nav, from, to) {
+        return Some(path);
+    }
+    if crossings == 0 {
+        return None;
+    }
+    let hydrology = Hydrology::new(level);
+    let crossing = Crossing::plan(level, &hydrology, from.truncate(), to.truncate())?;
+    // Both sides need to be reachable before building anything
+    let mut path = find_path(
+        level,
+        nav,
+        from,
+        level.ground(crossing.start) + IVec3::Z,
+        crossings - 1,
+    )?;
+    let rest = find_path(
+        level,
+        nav,
+        level.ground(crossing.end) + IVec3::Z,
+        to,
+        crossings - 1,
+    )?;
+    crossing.build(level, ROAD_WIDTH);
+    path.extend(
+        crossing
+            .water()
+            .map(|column| column.extend(travel_height(level, column) + 1)),
+    );
+    path.extend(rest);
+    Some(path)
+}
+
+fn find_dry_path(level: &Level, nav: &mut NavGraph, from: IVec3, to: IVec3) -> Option<Vec<IVec3>> {
+    let route = nav.route(level, from.truncate(), to.truncate())?;
+    let mut targets: Vec<IVec3> = route[..route.len() - 1]
+        .iter()
+        .skip(CROSSINGS_PER_LEG - 1)
+        .step_by(CROSSINGS_PER_LEG)
+        .map(|column| level.ground(*column) + IVec3::Z)
+        .collect();
+    targets.push(to);
+
+    let mut path = vec![from];
+    for target in targets {
+        let mut arrived = false;
+        for _ in 0..SEARCHES_PER_LEG {
+            let search = pathfind_with(level, *path.last().unwrap(), target, 3, Profile::Cart);
+            if search.path.len() <= 1 {
+                return None;
+            }
+            path.extend(search.path.iter().skip(1).map(|node| node.pos));
+            if search.success {
+                arrived = true;
+                break;
+            }
+        }
+        if !arrived {
+            return None;
+        }
+    }
+    Some(path)
+}
+
+impl Road {
+    /// Course of the road, ignoring the height
+    pub fn curve(&self) -> Vec<Vec2> {
+        self.path.iter().map(|sample| sample.truncate()).collect()
+    }
+
+    pub fn length(&self) -> f32 {
+        self.path
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum()
+    }
+
+    /// Position after walking `distance` blocks along the road, or None if past its end
+    fn point_along(&self, reverse: bool, distance: f32) -> Option<Vec3> {
+        let index = distance.max(0.) * SAMPLES_PER_BLOCK;
+        let steps = self.path.len() - 1;
+        if index as usize >= steps {
+            return None;
+        }
+        let node = |i: usize| self.path[if reverse { steps - i } else { i }];
+        Some(node(index as usize).lerp(node(index as usize + 1), index.fract()))
+    }
+}
+
+pub fn depart(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    tick: Res<Tick>,
+    roads: Query<(Entity, &Road)>,
+) {
+    if (tick.0 == 0) | (tick.0 % INTERVAL != 0) {
+        return;
+    }
+    let roads: Vec<_> = roads.iter().collect();
+    let Some(&(entity, road)) = roads.try_choose() else {
+        return;
+    };
+    let reverse = 0.5 > rand();
+    let start = road.point_along(reverse, 0.).unwrap();
+
+    let trader = Id::default();
+    replay.command(format!(
+        "summon wandering_trader {} {} {} {{{}, NoAI:1, Invulnerable:1}}",
+        start.x,
+        start.z,
+        start.y,
+        trader.snbt(),
+    ));
+    let animals: Vec<Id> = (0..rand_range(2..=3)).map(|_| Id::default()).collect();
+    for animal in &animals {
+        let variant: i32 = rand_range(0..4);
+        replay.command(format!(
+            "summon trader_llama {} {} {} {{{}, NoAI:1, Invulnerable:1, Variant:{}, Leash:{{{}}}}}",
+            start.x,
+            start.z,
+            start.y,
+            animal.snbt(),
+            variant,
+            trader.snbt(),
+        ));
+    }
+    commands.spawn((
+        trader,
+        Pos(start),
+        PrevPos(start),
+        Caravan {
+            road: entity,
+            reverse,
+            progress: 0.,
+            animals,
+        },
+    ));
+}
+
+pub fn travel(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    roads: Query<&Road>,
+    mut caravans: Query<(Entity, &Id, &mut Pos, &mut Caravan)>,
+) {
+    for (entity, id, mut pos, mut caravan) in &mut caravans {
+        let Ok(road) = roads.get(caravan.road) else {
+            continue;
+        };
+        caravan.progress += SPEED;
+        let Some(new_pos) = road.point_along(caravan.reverse, caravan.progress) else {
+            // Arrived
+            replay.command(format!("kill {id}"));
+            for animal in &caravan.animals {
+                replay.command(format!("kill {animal}"));
+            }
+            commands.entity(entity).despawn();
+            continue;
+        };
+        pos.0 = new_pos;
+        for (i, animal) in caravan.animals.iter().enumerate() {
+            let distance = caravan.progress - SPACING * (i + 1) as f32;
+            let animal_pos = road.point_along(caravan.reverse, distance).unwrap();
+            let facing = road
+                .point_along(caravan.reverse, distance + 1.)
+                .unwrap_or(new_pos);
+            replay.tp(*animal, animal_pos, facing);
+        }
+    }
+}
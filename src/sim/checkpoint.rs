@@ -0,0 +1,41 @@
+//! Checkpoints to resume long simulations from after a crash.
+//!
+//! The simulation is deterministic for a given seed, so instead of serializing the world, a
+//! resumed run simulates up to the checkpoint again. It skips the expensive part of that, the
+//! encoding and writing of replay chunks that are already in the save.
+
+use std::{fs::rename, path::Path};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Ticks between checkpoints
+pub const CHECKPOINT_INTERVAL: i32 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    /// What the RNG was seeded with at the start of the run
+    pub seed: u64,
+    pub settlements: usize,
+    /// Ticks simulated so far
+    pub tick: i32,
+    /// Identifies the replay in the save
+    pub invocation: u8,
+    /// Replay chunks handed off for writing so far. Whether a chunk actually got written
+    /// before the crash is checked when resuming.
+    pub replay_chunks: i32,
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Replaces the file atomically, so a crash while writing leaves the previous checkpoint
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        rename(temp, path)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,14 @@
-use crate::*;
+use crate::{
+    build_recorder::{order_by_support, BuildRecording},
+    *,
+};
 use bevy_ecs::prelude::*;
+use nbt::CompoundTag;
 use sim::*;
 
+/// How far above their feet builders can place blocks without climbing
+const REACH: i32 = 3;
+
 #[derive(Component)]
 pub struct BuildTask {
     pub building: Entity,
@@ -13,17 +20,30 @@ pub struct Built;
 #[derive(Component, Debug)]
 pub struct ConstructionSite {
     pub todo: PlaceList,
+    pub tile_entities: HashMap<IVec3, CompoundTag>,
     pub has_builder: bool,
     /// Whether it has the materials necessary for the next block
     pub has_materials: bool,
+    /// Temporary scaffolding and ladders the builder is standing on, bottom to top. Only
+    /// exists in the replay.
+    pub scaffolding: Vec<IVec3>,
 }
 
 impl ConstructionSite {
     pub fn new(blocks: PlaceList) -> Self {
         Self {
-            todo: blocks,
+            todo: order_by_support(blocks),
+            tile_entities: default(),
             has_builder: false,
             has_materials: false,
+            scaffolding: Vec::new(),
+        }
+    }
+
+    pub fn from_recording(recording: BuildRecording) -> Self {
+        Self {
+            tile_entities: recording.tile_entities,
+            ..Self::new(recording.blocks)
         }
     }
 }
@@ -63,26 +83,65 @@ pub fn new_construction_site(
 pub fn build(
     mut commands: Commands,
     mut replay: ResMut<Replay>,
-    mut builders: Query<(Entity, &BuildTask), (With<Villager>, Without<MoveTask>)>,
+    level: Res<Level>,
+    mut builders: Query<
+        (Entity, &mut Pos, &BuildTask, &mut Skills, Option<&HeldTool>),
+        (With<Villager>, Without<MoveTask>),
+    >,
     mut buildings: Query<(Entity, &mut ConstructionSite, &mut Pile)>,
 ) {
-    for (builder, build_task) in &mut builders {
+    for (builder, mut pos, build_task, mut skills, held) in &mut builders {
         let Ok((entity, mut building, mut pile)) = buildings.get_mut(build_task.building) else {
             continue;
         };
-        if let Some(set) = building.todo.get(0).copied() {
+        let next = building.todo.front().copied();
+        let feet = pos.block();
+        // Climb down before leaving or when the next block is far below
+        let too_low = next.is_some_and(|set| set.pos.z + REACH < feet.z);
+        let leaving = next
+            .is_none_or(|set| goods_for_block(set.block).is_some_and(|needed| !pile.has(needed)));
+        if (too_low | leaving)
+            && let Some(top) = building.scaffolding.pop()
+        {
+            replay.block(top, Air);
+            pos.0.z = top.z as f32;
+            continue;
+        }
+        if let Some(set) = next
+            && set.pos.z > feet.z + REACH
+            && (0..3).all(|i| level(feet + IVec3::Z * i) == Air)
+        {
+            replay.block(feet, climbing_block(&level, &building, feet));
+            building.scaffolding.push(feet);
+            pos.0.z = feet.z as f32 + 1.;
+            continue;
+        }
+        if let Some(set) = next {
+            if !skills.progress(Skill::Building) {
+                continue;
+            }
             if let Some(block) = pile.build(set.block) {
-                replay.block(set.pos, block);
+                if let Some(nbt) = building.tile_entities.get(&set.pos) {
+                    replay.block_entity(set.pos, block, nbt);
+                } else {
+                    replay.block(set.pos, block);
+                }
                 replay.dust(set.pos);
+                replay.block_effect(set.pos, block, false);
+                let tool = HeldTool::for_block(block);
+                if held != Some(&tool) {
+                    commands.entity(builder).insert(tool);
+                }
                 building.todo.pop_front();
+                skills.practice(Skill::Building);
             } else {
                 building.has_builder = false;
                 building.has_materials = false;
-                commands.entity(builder).remove::<BuildTask>();
+                commands.entity(builder).remove::<(BuildTask, HeldTool)>();
             }
         } else {
             replay.dbg("Building finished");
-            commands.entity(builder).remove::<BuildTask>();
+            commands.entity(builder).remove::<(BuildTask, HeldTool)>();
             commands
                 .entity(entity)
                 .remove::<(InPile, ConstructionSite)>()
@@ -96,6 +155,17 @@ pub fn build(
     }
 }
 
+/// Ladder if there's a finished wall to put it on, scaffolding otherwise
+fn climbing_block(level: &Level, site: &ConstructionSite, pos: IVec3) -> Block {
+    for dir in HDir::ALL {
+        let wall = pos + IVec3::from(dir);
+        if level(wall).solid() && !site.todo.iter().any(|set| set.pos == wall) {
+            return Ladder(dir.rotated(2));
+        }
+    }
+    Block::from_name("scaffolding", &[("bottom", "false"), ("distance", "0")])
+}
+
 pub fn check_construction_site_readiness(
     mut query: Query<(&mut ConstructionSite, &Pile, &mut InPile), Changed<Pile>>,
 ) {
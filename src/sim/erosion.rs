@@ -0,0 +1,153 @@
+//! Wear and tear left behind by construction and villagers walking around, so settlements
+//! don't look freshly pasted onto the terrain
+
+use crate::*;
+use sim::*;
+
+/// Width of the band around buildings where the ground got dug up and trodden on
+const FRINGE: i32 = 2;
+const GRAVEL_CHANCE: f32 = 0.12;
+const COARSE_DIRT_CHANCE: f32 = 0.25;
+/// Chance of a column in the fringe being a block higher or lower
+const BUMP_CHANCE: f32 = 0.1;
+/// How far out from a door the ground is worn down
+const DOORSTEP_LENGTH: i32 = 3;
+/// Villagers walking over a column this often wear it down to a path…
+const TRAMPLED: u32 = 40;
+/// …this often to coarse dirt
+const WORN: u32 = 15;
+/// Ticks between wearing down the ground where villagers walk
+const TRAMPLE_INTERVAL: i32 = 200;
+
+/// How many times villagers walked onto each column
+#[derive(Resource, Default)]
+pub struct Footfall(pub HashMap<IVec2, u32>);
+
+/// Needs to run before `tick_replay` updates `PrevPos`
+pub fn track_footfall(
+    mut footfall: ResMut<Footfall>,
+    moved: Query<(&Pos, &PrevPos), (With<Villager>, Without<InBoat>, Changed<Pos>)>,
+) {
+    for (pos, prev) in &moved {
+        let column = pos.block().truncate();
+        if column != prev.0.block().truncate() {
+            *footfall.0.entry(column).or_default() += 1;
+        }
+    }
+}
+
+/// Grass that villagers walk over a lot turns into coarse dirt and then into a path over the
+/// course of the simulation, forming trails where there are no roads. Planned and built
+/// areas are left alone.
+pub fn trample(tick: Res<Tick>, mut level: ResMut<Level>, footfall: Res<Footfall>) {
+    if tick.0 % TRAMPLE_INTERVAL != 0 {
+        return;
+    }
+    for (&column, &steps) in &footfall.0 {
+        let worn = if steps >= TRAMPLED {
+            Path
+        } else if steps >= WORN {
+            CoarseDirt
+        } else {
+            continue;
+        };
+        if level.unblocked(Rect {
+            min: column,
+            max: column,
+        }) {
+            wear(&mut level, column, worn);
+        }
+    }
+}
+
+/// Pass for [`sim::passes::Phase::Finish`] that roughens the ground around buildings and
+/// wears it down in front of doors
+pub fn erode(level: &mut Level, world: &mut World) {
+    let buildings: Vec<Rect> = world
+        .query_filtered::<&Planned, With<Built>>()
+        .iter(world)
+        .map(|planned| planned.0)
+        .collect();
+    let inside = |column: IVec2| buildings.iter().any(|area| area.contains(column));
+
+    for &area in &buildings {
+        for column in (1..=FRINGE).flat_map(|distance| area.grow(distance).border()) {
+            if level.area().contains(column) && !inside(column) {
+                roughen(level, column);
+            }
+        }
+        for (door, facing) in doors(level, area) {
+            for dir in [facing, facing.rotated(2)] {
+                for distance in 1..=DOORSTEP_LENGTH {
+                    let column = door.truncate() + IVec2::from(dir) * distance;
+                    if level.area().contains(column) && !inside(column) {
+                        wear(level, column, Path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Doors (their lower half) in the outline of the building
+fn doors(level: &Level, area: Rect) -> Vec<(IVec3, HDir)> {
+    let outline: Vec<IVec2> = area.border().collect();
+    let Some(bottom) = area
+        .grow(1)
+        .border()
+        .map(|column| level.height(column))
+        .min()
+    else {
+        return Vec::new();
+    };
+    let top = outline
+        .iter()
+        .map(|column| level.height(*column))
+        .max()
+        .unwrap_or(bottom);
+    outline
+        .into_iter()
+        .flat_map(|column| (bottom..=top).map(move |z| column.extend(z)))
+        .filter_map(|pos| match level(pos) {
+            Door(_, facing, meta) if !meta.contains(DoorMeta::TOP) => Some((pos, facing)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Replaces grass or dirt on the surface of the column, or coarse dirt with a path
+fn wear(level: &mut Level, column: IVec2, block: Block) {
+    let ground = level.ground(column);
+    if matches!(level(ground), Grass | Dirt) | ((level(ground) == CoarseDirt) & (block == Path)) {
+        level(ground, block);
+        if matches!(level(ground + IVec3::Z), SmallPlant(..) | GroundPlant(..)) {
+            level(ground + IVec3::Z, Air);
+        }
+    }
+}
+
+/// Scatters gravel and coarse dirt and makes the ground a little uneven
+fn roughen(level: &mut Level, column: IVec2) {
+    let ground = level.ground(column);
+    if !matches!(level(ground), Grass | Dirt) || level(ground + IVec3::Z) != Air {
+        return;
+    }
+    let roll = rand_f32(0., 1.);
+    if roll < GRAVEL_CHANCE {
+        level(ground, Gravel);
+    } else if roll < GRAVEL_CHANCE + COARSE_DIRT_CHANCE {
+        level(ground, CoarseDirt);
+    }
+    if rand_f32(0., 1.) < BUMP_CHANCE {
+        let top = level(ground);
+        if rand() {
+            level(ground, Dirt);
+            level(ground + IVec3::Z, top);
+            level.set_height(column, ground.z + 1);
+        } else if level(ground - IVec3::Z).dirtsoil() {
+            level(ground, Air);
+            level(ground - IVec3::Z, top);
+            level.set_height(column, ground.z - 1);
+        }
+    }
+}
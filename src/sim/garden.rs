@@ -0,0 +1,326 @@
+use crate::{armor_stand::scarecrow, terraform::flood, *};
+use sim::*;
+
+use super::milling::{harvest_factor, Mill, Miller};
+
+const GARDEN_SIZE: i32 = 5;
+const TERRACES_SIZE: i32 = 9;
+/// Height difference between neighboring terraces
+const TERRACE_STEP: i32 = 2;
+/// Unevenness above which terraces are built instead of a garden
+const STEEP: f32 = 1.5;
+/// How far from the house the garden may be
+const SEARCH_DISTANCE: i32 = 8;
+/// Ticks between growth stages of the crops
+const STAGE_TICKS: i32 = 200;
+/// Vegetables per crop block and harvest
+const YIELD: f32 = 0.25;
+/// Ticks between meals of a household
+const MEAL_TICKS: i32 = 2400;
+/// Vegetables a household keeps in stock
+const HOUSEHOLD_FOOD: f32 = 3.;
+
+/// Fenced plot next to a house with vegetable patches, a flower bed and a composter
+#[derive(Component)]
+pub struct Garden {
+    crop: Crop,
+    crops: Vec<IVec3>,
+    age: u8,
+    composter: IVec3,
+    compost: u8,
+}
+
+impl Garden {
+    fn new(crop: Crop, crops: Vec<IVec3>, composter: IVec3) -> Self {
+        Self {
+            crop,
+            crops,
+            age: 0,
+            composter,
+            compost: 0,
+        }
+    }
+
+    fn crop_block(&self) -> Block {
+        if self.age == self.crop.max_age() {
+            GroundPlant(Crop(self.crop))
+        } else {
+            GrowingCrop(self.crop, self.age)
+        }
+    }
+}
+
+/// House that gets its vegetables delivered
+#[derive(Component)]
+pub struct Household;
+
+/// The crops are freshly planted
+pub fn make_garden(level: &mut impl WorldView, area: Rect) -> (PlaceList, Garden) {
+    let cursor = level.recording_cursor();
+    remove_foliage::remove_trees(level, area.grow(1));
+    for column in area {
+        for z in level.height(column) + 1..level.height(column) + 4 {
+            level.set(column.extend(z), Air);
+        }
+    }
+    for column in area.border() {
+        level.set(level.ground(column) + IVec3::Z, Fence(Wood(Oak)));
+    }
+    for corner in area.corners() {
+        let plant = *[
+            SmallPlant::RedTulip,
+            SmallPlant::Poppy,
+            SmallPlant::BlueOrchid,
+            SmallPlant::Fern,
+        ]
+        .choose();
+        level.set(level.ground(corner) + IVec3::Z * 2, FlowerPot(Some(plant)));
+    }
+    let gate = ivec2(area.center().x, area.min.y);
+    level.set(
+        level.ground(gate) + IVec3::Z,
+        Block::from_name("oak_fence_gate", &[("facing", "north")]),
+    );
+
+    // Flower bed along the back, vegetables in front of it
+    let inner = area.shrink(1);
+    let crop = *[Crop::Carrot, Crop::Potato, Crop::Beetroot].choose();
+    let mut crops = Vec::new();
+    for column in inner {
+        let ground = level.ground(column);
+        if column.y == inner.max.y {
+            level.set(ground, Grass);
+            level.set(
+                ground + IVec3::Z,
+                SmallPlant(
+                    *[
+                        SmallPlant::Dandelion,
+                        SmallPlant::Cornflower,
+                        SmallPlant::OxeyeDaisy,
+                        SmallPlant::PinkTulip,
+                    ]
+                    .choose(),
+                ),
+            );
+        } else if column != inner.min {
+            level.set(ground, Farmland);
+            level.set(ground + IVec3::Z, GrowingCrop(crop, 0));
+            crops.push(ground + IVec3::Z);
+        }
+    }
+    let composter = level.ground(inner.min) + IVec3::Z;
+    level.set(composter, Composter(0));
+    (
+        level.pop_recording(cursor).into(),
+        Garden::new(crop, crops, composter),
+    )
+}
+
+/// Paddies stepped along the contour lines of a slope. Each terrace is leveled and flooded in
+/// the middle, with freshly planted crops on farmland along the water's edge.
+pub fn make_terraces(level: &mut impl WorldView, area: Rect) -> (PlaceList, Garden) {
+    let cursor = level.recording_cursor();
+    remove_foliage::remove_trees(level, area.grow(1));
+    let base = area
+        .into_iter()
+        .map(|column| level.height(column))
+        .min()
+        .unwrap();
+    let floors: HashMap<IVec2, i32> = area
+        .into_iter()
+        .map(|column| {
+            let height = level.height(column);
+            (column, base + (height - base) / TERRACE_STEP * TERRACE_STEP)
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+    let mut middles = Vec::new();
+    for column in area {
+        let floor = floors[&column];
+        for z in (floor + 1..=level.height(column) + 3).rev() {
+            level.set(column.extend(z), Air);
+        }
+        level.set_height(column, floor);
+        // The water is held in by the edges of its terrace
+        let edge = NEIGHBORS_2D
+            .iter()
+            .any(|off| floors.get(&(column + *off)) != Some(&floor));
+        if edge {
+            edges.push(column);
+        } else {
+            middles.push(column);
+        }
+    }
+
+    for &column in &middles {
+        level.set(column.extend(floors[&column]), Air);
+    }
+    for &column in &middles {
+        let pos = column.extend(floors[&column]);
+        if level.get(pos) != Air {
+            continue;
+        }
+        level.set(pos, Water);
+        level.set_water_level(column, Some(pos.z));
+        if flood(level, pos, (TERRACES_SIZE * TERRACES_SIZE) as usize).is_none() {
+            // Leaks, so it gets planted instead
+            level.set(pos, Air);
+            level.set_water_level(column, None);
+            edges.push(column);
+        }
+    }
+
+    let crop = *[Crop::Wheat, Crop::Carrot, Crop::Potato].choose();
+    let composter = edges[0].extend(floors[&edges[0]] + 1);
+    let mut crops = Vec::new();
+    for &column in &edges[1..] {
+        let floor = floors[&column];
+        level.set(column.extend(floor), Farmland);
+        level.set(column.extend(floor + 1), GrowingCrop(crop, 0));
+        crops.push(column.extend(floor + 1));
+    }
+    level.set(composter - IVec3::Z, Dirt);
+    level.set(composter, Composter(0));
+    (
+        level.pop_recording(cursor).into(),
+        Garden::new(crop, crops, composter),
+    )
+}
+
+pub fn make_gardens(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    mut replay: ResMut<Replay>,
+    new_houses: Query<(Entity, &House), Added<Built>>,
+) {
+    for (entity, house) in &new_houses {
+        commands.entity(entity).insert((
+            Household,
+            InPile {
+                requested: {
+                    let mut requested = Goods::default();
+                    requested.add(Stack::new(Good::Vegetables, HOUSEHOLD_FOOD));
+                    requested
+                },
+                priority: None,
+            },
+        ));
+
+        let search_distance = SEARCH_DISTANCE + house.area.size().max_element() / 2;
+        // Farms on slopes are terraced
+        let terraces = level
+            .free_spot_near(
+                house.area.center(),
+                IVec2::splat(TERRACES_SIZE),
+                search_distance,
+            )
+            .filter(|&area| unevenness(&level, area) > STEEP);
+        let Some(area) = terraces.or_else(|| {
+            level.free_spot_near(
+                house.area.center(),
+                IVec2::splat(GARDEN_SIZE),
+                search_distance,
+            )
+        }) else {
+            continue;
+        };
+        if wateryness(&level, area) > 0. {
+            continue;
+        }
+        let (rec, garden) = if terraces.is_some() {
+            make_terraces(&mut *level, area)
+        } else {
+            make_garden(&mut *level, area)
+        };
+        for set in rec {
+            replay.block(set.pos, set.block);
+        }
+        // Scarecrow in the back corner of the flower bed
+        if terraces.is_none() {
+            let post = level.ground(area.max - IVec2::ONE) + IVec3::Z;
+            let stand = scarecrow(&mut level, post);
+            replay.block(post, level(post));
+            stand.summon(&mut replay);
+        }
+        level.set_blocked(area);
+        commands.spawn((
+            Pos(level.ground(area.center()).as_vec3() + Vec3::Z),
+            garden,
+            Pile::default(),
+            OutPile::default(),
+        ));
+    }
+}
+
+/// Crops grow stage by stage and get harvested and replanted once fully grown, which the
+/// replay picks up from the level. The harvest goes to the households, with part of it going
+/// into the composter, which turns into soil once it's full. Gardens near a mill yield more.
+pub fn grow(
+    tick: Res<Tick>,
+    mut level: ResMut<Level>,
+    mut replay: ResMut<Replay>,
+    mut gardens: Query<(&Pos, &mut Garden, &mut Pile, &mut OutPile)>,
+    millers: Query<&Miller>,
+    mills: Query<&Pos, With<Mill>>,
+) {
+    if tick.0 % STAGE_TICKS != 0 {
+        return;
+    }
+    for (pos, mut garden, mut pile, mut out_pile) in &mut gardens {
+        if garden.age < garden.crop.max_age() {
+            garden.age += 1;
+            let block = garden.crop_block();
+            for &pos in &garden.crops {
+                level(pos, block);
+            }
+            continue;
+        }
+
+        let harvested = garden.crop_block();
+        garden.age = 0;
+        let planted = garden.crop_block();
+        for &pos in &garden.crops {
+            replay.block_effect(pos, harvested, true);
+            level(pos, planted);
+        }
+        let vegetables = Stack::new(
+            Good::Vegetables,
+            garden.crops.len() as f32 * YIELD * harvest_factor(&millers, &mills, pos.0),
+        );
+        pile.add(vegetables);
+        out_pile.available.add(vegetables);
+
+        garden.compost += 1;
+        if garden.compost == 8 {
+            garden.compost = 0;
+            let soil = Stack::new(Good::Soil, 1.);
+            pile.add(soil);
+            out_pile.available.add(soil);
+        }
+        level(garden.composter, Composter(garden.compost));
+    }
+}
+
+/// Households eat their vegetables and order new ones
+pub fn eat(
+    tick: Res<Tick>,
+    mut households: Query<(&mut Pile, &mut InPile, Option<&mut OutPile>), With<Household>>,
+) {
+    for (mut pile, mut in_pile, out_pile) in &mut households {
+        // Food isn't passed on to other households
+        if let Some(mut out_pile) = out_pile {
+            out_pile
+                .available
+                .remove(Stack::new(Good::Vegetables, f32::INFINITY));
+        }
+        if tick.0 % MEAL_TICKS != 0 {
+            continue;
+        }
+        let meal = Stack::new(Good::Vegetables, 1.);
+        if pile.has(meal) {
+            pile.remove(meal);
+            in_pile.requested.add(meal);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::*;
+use sim::*;
+
+use super::schedule::GAME_TICKS_PER_TICK;
+
+/// Length of a Minecraft day in game ticks. The replay sets the time to sunrise when it starts.
+const DAY_LENGTH: i32 = 24000;
+/// Part of the day villagers spend in bed
+const NIGHT: std::ops::Range<i32> = 12500..23500;
+/// Chance per tick that a villager without work starts doing something
+const IDLE_CHANCE: f32 = 0.02;
+const SIT_DURATION: i32 = 300;
+const CHAT_DURATION: i32 = 200;
+const FISH_DURATION: i32 = 600;
+/// How far from a settlement center holes get cut into frozen lakes
+const FISHING_RANGE: i32 = 48;
+const HOLES_PER_SETTLEMENT: usize = 3;
+/// How far villagers walk for a seat
+const SEAT_RANGE: f32 = 40.;
+
+/// Benches and other stairs to sit on that aren't taken
+#[derive(Resource, Default)]
+pub struct Seats(pub Vec<IVec3>);
+
+/// Spots on the ice next to fishing holes that aren't taken
+#[derive(Resource, Default)]
+pub struct FishingHoles(pub Vec<IVec3>);
+
+/// Bed in a house; the position of the head part
+#[derive(Component)]
+pub struct Bed {
+    pub pos: IVec3,
+    pub occupied: bool,
+}
+
+#[derive(Component)]
+pub struct Idle {
+    activity: Activity,
+    /// Tick the activity ends, set once the villager has arrived
+    until: Option<i32>,
+}
+
+enum Activity {
+    Sit { seat: IVec3, seat_id: Id },
+    Sleep { bed: Entity },
+    Chat { partner: Entity },
+    Fish { spot: IVec3 },
+}
+
+pub fn is_night(tick: i32) -> bool {
+    NIGHT.contains(&time_of_day(tick))
+}
+
+/// In game ticks since sunrise
+fn time_of_day(tick: i32) -> i32 {
+    tick * GAME_TICKS_PER_TICK % DAY_LENGTH
+}
+
+/// Cuts holes into frozen lakes near the settlements. Returns the spots on the ice next to the
+/// holes for [`FishingHoles`].
+pub fn cut_fishing_holes(level: &mut Level, centers: &[IVec3]) -> Vec<IVec3> {
+    let mut spots = Vec::new();
+    for center in centers {
+        let area = Rect::new_centered(center.truncate(), IVec2::splat(FISHING_RANGE * 2))
+            .overlap(level.area().shrink(1));
+        let mut candidates: Vec<(IVec3, IVec3)> = area
+            .into_iter()
+            .filter_map(|column| {
+                let hole = column.extend((level.water)(column)?);
+                if !level(hole).frozen_water() {
+                    return None;
+                }
+                // Somewhere to stand that's frozen as well
+                let spot = NEIGHBORS_2D
+                    .iter()
+                    .map(|&off| hole + off.extend(0))
+                    .find(|&pos| level(pos).frozen_water() & (level(pos + IVec3::Z) == Air))?;
+                Some((hole, spot + IVec3::Z))
+            })
+            .collect();
+        let mut cut: Vec<IVec3> = Vec::new();
+        while (cut.len() < HOLES_PER_SETTLEMENT) & !candidates.is_empty() {
+            let (hole, spot) = candidates.swap_remove(rand_range(0..candidates.len()));
+            if cut
+                .iter()
+                .any(|other| other.truncate().distance_squared(hole.truncate()) < 8 * 8)
+            {
+                continue;
+            }
+            level(hole, Water);
+            cut.push(hole);
+            spots.push(spot);
+        }
+    }
+    spots
+}
+
+/// Sends villagers without work to bed, to a bench, fishing or to a chat with another villager
+pub fn start_idle(
+    mut commands: Commands,
+    tick: Res<Tick>,
+    mut seats: ResMut<Seats>,
+    mut holes: ResMut<FishingHoles>,
+    idle: Query<
+        (Entity, &Pos),
+        (
+            With<Villager>,
+            With<Jobless>,
+            Without<Idle>,
+            Without<MoveTask>,
+            Without<PickupTask>,
+            Without<DeliverTask>,
+            Without<BuildTask>,
+        ),
+    >,
+    mut beds: Query<(Entity, &mut Bed), With<Built>>,
+    centers: Query<&Pos, With<CityCenter>>,
+) {
+    let mut chatty: Option<Entity> = None;
+    for (vill, pos) in &idle {
+        let night = is_night(tick.0);
+        if !night && 1. - IDLE_CHANCE < rand() {
+            continue;
+        }
+
+        if night {
+            let Some((bed_entity, mut bed)) = beds
+                .iter_mut()
+                .filter(|(_, bed)| !bed.occupied)
+                .min_by_key(|(_, bed)| bed.pos.as_vec3().distance_squared(pos.0) as i32)
+            else {
+                continue;
+            };
+            bed.occupied = true;
+            commands.entity(vill).insert((
+                MoveTask {
+                    goal: bed.pos,
+                    distance: 2,
+                },
+                Idle {
+                    activity: Activity::Sleep { bed: bed_entity },
+                    until: None,
+                },
+            ));
+        } else if 0.5 > rand()
+            && let Some(i) = (0..seats.0.len())
+                .filter(|&i| seats.0[i].as_vec3().distance(pos.0) < SEAT_RANGE)
+                .min_by_key(|&i| seats.0[i].as_vec3().distance_squared(pos.0) as i32)
+        {
+            let seat = seats.0.swap_remove(i);
+            commands.entity(vill).insert((
+                MoveTask {
+                    goal: seat,
+                    distance: 1,
+                },
+                Idle {
+                    activity: Activity::Sit {
+                        seat,
+                        seat_id: Id::default(),
+                    },
+                    until: None,
+                },
+            ));
+        } else if 0.3 > rand()
+            && let Some(i) = (0..holes.0.len())
+                .filter(|&i| holes.0[i].as_vec3().distance(pos.0) < SEAT_RANGE)
+                .min_by_key(|&i| holes.0[i].as_vec3().distance_squared(pos.0) as i32)
+        {
+            let spot = holes.0.swap_remove(i);
+            commands.entity(vill).insert((
+                MoveTask {
+                    goal: spot,
+                    distance: 0,
+                },
+                Idle {
+                    activity: Activity::Fish { spot },
+                    until: None,
+                },
+            ));
+        } else if let Some(partner) = chatty.take() {
+            let Some(plaza) = centers
+                .iter()
+                .min_by_key(|center| center.distance_squared(pos.0) as i32)
+            else {
+                continue;
+            };
+            let plaza = plaza.block();
+            for (vill, partner, offset) in [(vill, partner, IVec3::X), (partner, vill, -IVec3::X)] {
+                commands.entity(vill).insert((
+                    MoveTask {
+                        goal: plaza + offset * 2 + IVec3::Z,
+                        distance: 1,
+                    },
+                    Idle {
+                        activity: Activity::Chat { partner },
+                        until: None,
+                    },
+                ));
+            }
+        } else {
+            chatty = Some(vill);
+        }
+    }
+}
+
+/// Starts the activity once the villager has arrived and ends it when the time is up
+pub fn idle(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    tick: Res<Tick>,
+    mut seats: ResMut<Seats>,
+    mut holes: ResMut<FishingHoles>,
+    mut villagers: Query<(Entity, &Id, &mut Idle), Without<MoveTask>>,
+    ids: Query<&Id>,
+    mut beds: Query<&mut Bed>,
+) {
+    for (vill, id, mut idle) in &mut villagers {
+        let Some(until) = idle.until else {
+            let duration = match &idle.activity {
+                Activity::Sit { seat, seat_id } => {
+                    replay.command(format!(
+                        "summon armor_stand {}.5 {} {}.5 {{{}, Invisible:1, Marker:1, NoGravity:1}}",
+                        seat.x,
+                        seat.z as f32 + 0.3,
+                        seat.y,
+                        seat_id.snbt()
+                    ));
+                    replay.command(format!("ride {id} mount {seat_id}"));
+                    SIT_DURATION
+                }
+                Activity::Sleep { bed } => {
+                    let bed = beds.get(*bed).unwrap().pos;
+                    replay.command(format!(
+                        "data merge entity {id} {{SleepingX:{},SleepingY:{},SleepingZ:{}}}",
+                        bed.x, bed.z, bed.y
+                    ));
+                    // Until morning
+                    (NIGHT.end - time_of_day(tick.0)) / GAME_TICKS_PER_TICK
+                }
+                Activity::Chat { partner } => {
+                    if let Ok(partner) = ids.get(*partner) {
+                        replay.command(format!(
+                            "execute as {id} at @s run tp @s ~ ~ ~ facing entity {partner} eyes"
+                        ));
+                    }
+                    CHAT_DURATION
+                }
+                Activity::Fish { .. } => {
+                    commands.entity(vill).insert(HeldTool::FishingRod);
+                    FISH_DURATION
+                }
+            };
+            idle.until = Some(tick.0 + duration);
+            continue;
+        };
+        if tick.0 < until {
+            continue;
+        }
+        match idle.activity {
+            Activity::Sit { seat, seat_id } => {
+                replay.command(format!("ride {id} dismount"));
+                replay.command(format!("kill {seat_id}"));
+                seats.0.push(seat);
+            }
+            Activity::Sleep { bed } => {
+                replay.command(format!("data remove entity {id} SleepingX"));
+                beds.get_mut(bed).unwrap().occupied = false;
+            }
+            Activity::Chat { .. } => {
+                replay.command(format!(
+                    "execute at {id} run particle happy_villager ~ ~2 ~ 0.3 0.3 0.3 0 3"
+                ));
+            }
+            Activity::Fish { spot } => {
+                commands.entity(vill).remove::<HeldTool>();
+                holes.0.push(spot);
+            }
+        }
+        commands.entity(vill).remove::<Idle>();
+    }
+}
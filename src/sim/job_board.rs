@@ -0,0 +1,210 @@
+use super::*;
+
+/// Priority gained per tick a job stays open, so none of them wait forever
+const AGING: f32 = 0.002;
+/// Priority lost per block between the villager and the job
+const DISTANCE_COST: f32 = 0.02;
+/// Extra priority for deliveries of the good a pile is waiting for most
+const URGENT: f32 = 1.;
+
+/// Work that villagers without a profession can take on
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Job {
+    Build { site: Entity },
+    Deliver { from: Entity, to: Entity },
+}
+
+impl Job {
+    fn base_priority(self) -> f32 {
+        match self {
+            Job::Build { .. } => 2.,
+            Job::Deliver { .. } => 1.,
+        }
+    }
+}
+
+struct Posting {
+    pos: Vec3,
+    priority: f32,
+    /// Tick the job was first posted
+    since: i32,
+    /// Still posted this tick
+    current: bool,
+}
+
+/// Open jobs, posted anew each tick by [`post_jobs`]. Jobs that have waited longer get
+/// preferred, so that e.g. deliveries don't starve while there are many sites to build on.
+#[derive(Resource, Default)]
+pub struct JobBoard {
+    postings: HashMap<Job, Posting>,
+}
+
+impl JobBoard {
+    /// Posts the job, or keeps it up if it was already posted
+    pub fn post(&mut self, job: Job, pos: Vec3, priority: f32, tick: i32) {
+        let posting = self.postings.entry(job).or_insert(Posting {
+            pos,
+            priority,
+            since: tick,
+            current: true,
+        });
+        posting.pos = pos;
+        posting.priority = priority;
+        posting.current = true;
+    }
+
+    /// Takes down the jobs that weren't posted again since the last call
+    fn take_down_stale(&mut self) {
+        self.postings.retain(|_, posting| posting.current);
+        for posting in self.postings.values_mut() {
+            posting.current = false;
+        }
+    }
+
+    /// Most pressing job for a villager at `pos`, weighing priority, waiting time and distance
+    pub fn best_for(&self, pos: Vec3, tick: i32) -> Option<Job> {
+        self.postings
+            .iter()
+            .map(|(&job, posting)| {
+                let score = posting.priority + (tick - posting.since) as f32 * AGING
+                    - posting.pos.distance(pos) * DISTANCE_COST;
+                (job, score)
+            })
+            .max_by(|(a, a_score), (b, b_score)| {
+                a_score
+                    .total_cmp(b_score)
+                    .then(job_key(*b).cmp(&job_key(*a)))
+            })
+            .map(|(job, _)| job)
+    }
+
+    pub fn take(&mut self, job: Job) {
+        self.postings.remove(&job);
+    }
+}
+
+/// Tie breaker, so the choice doesn't depend on the order of the map
+fn job_key(job: Job) -> (u8, Entity, Entity) {
+    match job {
+        Job::Build { site } => (0, site, site),
+        Job::Deliver { from, to } => (1, from, to),
+    }
+}
+
+/// Posts construction sites ready to be built on and, for each pile requesting goods, a
+/// delivery from the pile that can supply the most for the shortest walk
+pub fn post_jobs(
+    tick: Res<Tick>,
+    mut board: ResMut<JobBoard>,
+    out_piles: Query<(Entity, &Pos, &OutPile)>,
+    in_piles: Query<(Entity, &Pos, &InPile)>,
+    construction_sites: Query<(Entity, &Pos, &ConstructionSite)>,
+) {
+    for (site, pos, construction) in &construction_sites {
+        if construction.has_materials & !construction.has_builder {
+            let job = Job::Build { site };
+            board.post(job, pos.0, job.base_priority(), tick.0);
+        }
+    }
+
+    let empty = Inventory::default();
+    for (to, in_pos, in_pile) in &in_piles {
+        let Some((from, out_pos, _)) = out_piles
+            .iter()
+            .filter_map(|(from, out_pos, out_pile)| {
+                let load: f32 = empty
+                    .plan_pickup(&out_pile.available, &in_pile.requested, in_pile.priority)
+                    .iter()
+                    .map(|stack| stack.weight())
+                    .sum();
+                (load > 0.).then(|| (from, out_pos, out_pos.distance_squared(in_pos.0) / load))
+            })
+            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+        else {
+            continue;
+        };
+        let job = Job::Deliver { from, to };
+        let urgency = if in_pile.priority.is_some() {
+            URGENT
+        } else {
+            0.
+        };
+        board.post(job, out_pos.0, job.base_priority() + urgency, tick.0);
+    }
+    board.take_down_stale();
+}
+
+/// Gives each idle villager the most pressing job on the board
+pub fn assign_jobs(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    tick: Res<Tick>,
+    mut board: ResMut<JobBoard>,
+    idle: Query<
+        (Entity, &Pos, &Villager),
+        (
+            With<Jobless>,
+            Without<DeliverTask>,
+            Without<BuildTask>,
+            Without<Idle>,
+        ),
+    >,
+    mut out_piles: Query<&mut OutPile>,
+    mut in_piles: Query<&mut InPile>,
+    mut construction_sites: Query<(&Pos, &mut ConstructionSite)>,
+) {
+    for (vill, vil_pos, villager) in &idle {
+        // Reservations made for earlier villagers can leave a job with nothing to do
+        while let Some(job) = board.best_for(vil_pos.0, tick.0) {
+            board.take(job);
+            match job {
+                Job::Build { site } => {
+                    let Ok((pos, mut construction)) = construction_sites.get_mut(site) else {
+                        continue;
+                    };
+                    if construction.has_builder {
+                        continue;
+                    }
+                    construction.has_builder = true;
+                    commands
+                        .entity(vill)
+                        .insert((MoveTask::new(pos.0.block()), BuildTask { building: site }));
+                    break;
+                }
+                Job::Deliver { from, to } => {
+                    let (Ok(mut out_pile), Ok(mut in_pile)) =
+                        (out_piles.get_mut(from), in_piles.get_mut(to))
+                    else {
+                        continue;
+                    };
+                    let stacks = villager.carry.plan_pickup(
+                        &out_pile.available,
+                        &in_pile.requested,
+                        in_pile.priority,
+                    );
+                    if stacks.is_empty() {
+                        continue;
+                    }
+                    let max_amounts = stacks
+                        .iter()
+                        .map(|stack| in_pile.requested[&stack.kind].min(stack.kind.stack_size()))
+                        .collect();
+                    for &stack in &stacks {
+                        out_pile.available.remove(stack);
+                        in_pile.requested.remove(stack);
+                    }
+                    replay.dbg("assign carry");
+                    commands.entity(vill).insert((
+                        PickupTask {
+                            from,
+                            stacks,
+                            max_amounts,
+                        },
+                        DeliverTask { to },
+                    ));
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -30,8 +30,10 @@ pub struct MovePath {
 #[derive(Component)]
 pub struct PickupTask {
     pub from: Entity,
-    pub stack: Stack,
-    pub max_stack: f32,
+    /// Planned with [`Inventory::plan_pickup`]
+    pub stacks: Vec<Stack>,
+    /// Per stack, how much may be taken if more became available in the meantime
+    pub max_amounts: Vec<f32>,
 }
 
 #[derive(Component)]
@@ -82,17 +84,20 @@ pub fn pickup(
                 },
                 PickupReady,
             ));
-        } else if villager.carry.is_none() {
+        } else {
             let (mut pile, mut out_pile) = out_piles.get_mut(task.from).unwrap();
-            // If more goods have been deposited since the task was set, take them too
-            let missing = task.max_stack - task.stack.amount;
-            let extra = out_pile.available.remove_up_to(Stack {
-                kind: task.stack.kind,
-                amount: missing,
-            });
-            task.stack.amount += extra.amount;
-            pile.remove(task.stack);
-            villager.carry = Some(task.stack);
+            let task = &mut *task;
+            for (stack, &max) in task.stacks.iter_mut().zip(&task.max_amounts) {
+                // If more goods have been deposited since the task was set, take them too
+                let missing = (max - stack.amount).min(villager.carry.room_for(stack.kind));
+                let extra = out_pile.available.remove_up_to(Stack {
+                    kind: stack.kind,
+                    amount: missing.max(0.),
+                });
+                stack.amount += extra.amount;
+                pile.remove(*stack);
+                villager.carry.add(*stack);
+            }
             commands
                 .entity(entity)
                 .remove::<(PickupTask, PickupReady)>();
@@ -110,10 +115,10 @@ pub fn deliver(
     >,
 ) {
     for (entity, mut villager, task, deliver_ready) in &mut deliver {
-        let Some(stack) = villager.carry else {
+        if villager.carry.is_empty() {
             commands.entity(entity).remove::<DeliverTask>();
             return;
-        };
+        }
         if !deliver_ready {
             commands.entity(entity).insert((
                 MoveTask {
@@ -123,18 +128,19 @@ pub fn deliver(
                 DeliverReady,
             ));
         } else {
-            let (mut pile, in_pile, out_pile) = piles.get_mut(task.to).unwrap();
-            pile.add(stack);
+            let (mut pile, mut in_pile, mut out_pile) = piles.get_mut(task.to).unwrap();
+            for stack in villager.carry.take_all() {
+                pile.add(stack);
 
-            if let Some(mut in_pile) = in_pile {
-                if in_pile.priority == Some(stack.kind) {
+                if let Some(in_pile) = &mut in_pile
+                    && in_pile.priority == Some(stack.kind)
+                {
                     in_pile.priority = None
                 }
+                if let Some(out_pile) = &mut out_pile {
+                    out_pile.available.add(stack)
+                };
             }
-            if let Some(mut out_pile) = out_pile {
-                out_pile.available.add(stack)
-            };
-            villager.carry = None;
             commands
                 .entity(entity)
                 .remove::<(DeliverTask, DeliverReady)>();
@@ -142,11 +148,32 @@ pub fn deliver(
     }
 }
 
-// TODO: Smooth this out
+/// Blocks per tick
+#[derive(Resource)]
+pub struct WalkSpeed {
+    pub walk: f32,
+    pub boat: f32,
+    pub climb: f32,
+}
+
+impl Default for WalkSpeed {
+    fn default() -> Self {
+        Self {
+            walk: 0.16,
+            boat: 0.2,
+            climb: 0.09,
+        }
+    }
+}
+
+/// How close walking villagers get to each other before stepping aside
+const PERSONAL_SPACE: f32 = 0.7;
+
 pub fn walk(
     mut commands: Commands,
     mut replay: ResMut<Replay>,
     level: Res<Level>,
+    speeds: Res<WalkSpeed>,
     mut query: Query<
         (
             Entity,
@@ -159,20 +186,22 @@ pub fn walk(
         With<Villager>,
     >,
 ) {
+    // Positions at the start of the tick, so the order villagers are processed in doesn't matter
+    let positions: Vec<(Entity, Vec3)> = query
+        .iter()
+        .map(|(entity, _, pos, ..)| (entity, pos.0))
+        .collect();
     for (entity, id, mut pos, goal, in_boat, path) in &mut query {
         if let Some(mut path) = path {
-            const WALK_PER_TICK: f32 = 0.16;
-            const BOATING_PER_TICK: f32 = 0.2;
-            const CLIMB_PER_TICK: f32 = 0.09;
             let mut next_node = *path.steps.front().unwrap();
             let diff = (next_node.pos.as_vec3() - pos.0).truncate();
             if path.vertical {
                 // Climbing
                 if if next_node.pos.z as f32 > pos.0.z {
-                    pos.0.z += CLIMB_PER_TICK;
+                    pos.0.z += speeds.climb;
                     pos.0.z > next_node.pos.z as f32
                 } else {
-                    pos.0.z -= CLIMB_PER_TICK;
+                    pos.0.z -= speeds.climb;
                     pos.0.z < next_node.pos.z as f32
                 } {
                     path.steps.pop_front();
@@ -185,7 +214,7 @@ pub fn walk(
             } else {
                 let speed;
                 if next_node.boat {
-                    speed = BOATING_PER_TICK;
+                    speed = speeds.boat;
                     if in_boat.is_none() {
                         let boat_id = Id::default();
                         commands.entity(entity).insert(InBoat(boat_id));
@@ -201,7 +230,7 @@ pub fn walk(
                         replay.command(format!("ride {id} mount {boat_id}"));
                     }
                 } else {
-                    speed = WALK_PER_TICK;
+                    speed = speeds.walk;
                     if let Some(boat_id) = in_boat {
                         commands.entity(entity).remove::<InBoat>();
                         replay.command(format!("kill {}", boat_id.0));
@@ -221,6 +250,7 @@ pub fn walk(
                     let diff = (next_node.pos.as_vec3() - pos.0).truncate();
                     pos.0 += (diff.normalize_or_zero() * speed).extend(0.);
                     if !next_node.boat {
+                        step_aside(&positions, entity, &mut pos);
                         set_walk_height(&level, &mut pos);
                     }
                 }
@@ -235,6 +265,24 @@ pub fn walk(
     }
 }
 
+/// Keep villagers on the same road from walking inside one another by moving away from those
+/// that are too close, as long as that doesn't take them off the block they're on
+fn step_aside(positions: &[(Entity, Vec3)], entity: Entity, pos: &mut Pos) {
+    let mut offset = Vec2::ZERO;
+    for &(other, other_pos) in positions {
+        let away = (pos.0 - other_pos).truncate();
+        let distance = away.length();
+        if (other == entity) | (distance >= PERSONAL_SPACE) | ((pos.0.z - other_pos.z).abs() > 1.) {
+            continue;
+        }
+        offset += away.try_normalize().unwrap_or(Vec2::X) * (PERSONAL_SPACE - distance) / 2.;
+    }
+    let stepped = pos.0 + offset.clamp_length_max(0.1).extend(0.);
+    if stepped.block() == pos.block() {
+        pos.0 = stepped;
+    }
+}
+
 fn set_walk_height(level: &Level, pos: &mut Vec3) {
     let size = 0.35;
     let mut height = 0f32;
@@ -249,11 +297,28 @@ fn set_walk_height(level: &Level, pos: &mut Vec3) {
         height = height.max(
             block_pos.z as f32
                 - match level(block_pos - ivec3(0, 0, 1)) {
-                    Slab(_, Bottom) => 0.5,
-                    // TODO: Stairs
+                    // Stairs are taken in two half steps
+                    Slab(_, Bottom) | Stair(_, _, Bottom, _) => 0.5,
                     _ => 0.,
                 },
         );
     }
     pos.z = height;
 }
+
+/// Perishable goods slowly rot away while stored
+pub fn decay_goods(mut piles: Query<(&mut Pile, Option<&mut OutPile>)>) {
+    for (mut pile, out_pile) in &mut piles {
+        if pile.keys().all(|good| good.info().decay == 0.) {
+            continue;
+        }
+        for (good, amount) in pile.iter_mut() {
+            *amount *= 1. - good.info().decay;
+        }
+        if let Some(mut out_pile) = out_pile {
+            for (good, available) in out_pile.available.iter_mut() {
+                *available = available.min(pile.get(good).copied().unwrap_or(0.));
+            }
+        }
+    }
+}
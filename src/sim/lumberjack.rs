@@ -1,6 +1,12 @@
-use crate::*;
+use crate::{build_recorder::order_by_support, loot::scatter_loot, *};
 use sim::*;
 
+use super::apiary::RelocatedNests;
+
+/// Stray items around the lumber pile
+const LOOT: [&str; 3] = ["oak_log", "stick", "stick"];
+const LOOT_COUNT: usize = 5;
+
 #[derive(Component)]
 pub struct Lumberjack {
     pub area: Rect,
@@ -72,13 +78,13 @@ pub fn assign_worker(
         else {
             return;
         };
-        commands
-            .entity(worker)
-            .remove::<Jobless>()
-            .insert(Lumberworker {
+        commands.entity(worker).remove::<Jobless>().insert((
+            Lumberworker {
                 workplace,
                 ready_to_work: true,
-            });
+            },
+            ToolWear::new(workplace),
+        ));
     }
 }
 
@@ -106,7 +112,7 @@ pub fn work(
             commands.entity(entity).insert(ChopTask::new(tree));
             tree_meta.to_be_chopped = true;
             lumberworker.ready_to_work = false;
-        } else if let Some(stack) = villager.carry {
+        } else if let Some(stack) = villager.carry.get(Good::Wood) {
             // Drop off lumber
             // TODO: This allows overly full piles due to multiple simultaneous deliveries. Fix this by introducing storage piles?
             if let Some((to, _, _, _)) = piles
@@ -132,13 +138,20 @@ pub fn work(
 pub fn chop(
     mut commands: Commands,
     mut level: ResMut<Level>,
+    mut nests: ResMut<RelocatedNests>,
     mut lumberjacks: Query<
-        (Entity, &mut Villager, &mut ChopTask),
+        (
+            Entity,
+            &mut Villager,
+            &mut ChopTask,
+            &mut Skills,
+            Option<&mut ToolWear>,
+        ),
         (Without<MoveTask>, Without<PlaceTask>),
     >,
     trees: Query<(&Pos, &Tree)>,
 ) {
-    for (jack, mut vill, mut task) in &mut lumberjacks {
+    for (jack, mut vill, mut task, mut skills, tools) in &mut lumberjacks {
         match task.stage {
             ChopStage::Goto => {
                 let (target, _tree) = trees.get(task.tree).unwrap();
@@ -149,10 +162,14 @@ pub fn chop(
                 task.stage = ChopStage::Chop;
             }
             ChopStage::Chop => {
+                if !skills.progress(Skill::Chopping) {
+                    continue;
+                }
                 let (target, _tree) = trees.get(task.tree).unwrap();
                 let cursor = level.recording_cursor();
-                remove_tree(&mut level, target.block());
-                let place = PlaceTask(level.pop_recording(cursor).collect());
+                // Bee nests get moved to an apiary instead of being destroyed
+                nests.0.extend(remove_tree(&mut *level, target.block()));
+                let place = PlaceTask(order_by_support(level.pop_recording(cursor).collect()));
                 let mut amount = 0.;
                 for set in &place.0 {
                     amount += match set.previous {
@@ -162,13 +179,19 @@ pub fn chop(
                         _ => 0.,
                     }
                 }
-                vill.carry = Some(Stack::new(Good::Wood, amount));
+                if let Some(mut tools) = tools {
+                    amount *= tools.efficiency();
+                    tools.wear += 0.1;
+                }
+                amount *= skills.yield_factor(Skill::Chopping);
+                skills.practice(Skill::Chopping);
+                vill.carry.add(Stack::new(Good::Wood, amount));
                 commands.entity(task.tree).despawn();
-                commands.entity(jack).insert(place);
+                commands.entity(jack).insert((place, HeldTool::Axe));
                 task.stage = ChopStage::Finish;
             }
             ChopStage::Finish => {
-                commands.entity(jack).remove::<ChopTask>();
+                commands.entity(jack).remove::<(ChopTask, HeldTool)>();
             }
         }
     }
@@ -177,11 +200,16 @@ pub fn chop(
 pub fn make_lumber_piles(
     mut commands: Commands,
     mut level: ResMut<Level>,
+    mut replay: ResMut<Replay>,
     center: Query<&Pos, With<CityCenter>>,
     new_lumberjacks: Query<&Pos, (With<Lumberjack>, Added<Built>)>,
 ) {
     for lumberjack in &new_lumberjacks {
-        let center = center.single().truncate();
+        let center = center
+            .iter()
+            .min_by_key(|center| center.distance_squared(lumberjack.0) as i32)
+            .unwrap()
+            .truncate();
 
         let params = LumberPile {
             axis: if 0.5 > rand() { HAxis::X } else { HAxis::Y },
@@ -231,6 +259,9 @@ pub fn make_lumber_piles(
 
         let z = level.average_height(area(pos, params).border()) + 1.;
         level.set_blocked(area(pos, params));
+        for item in scatter_loot(&level, area(pos, params), &LOOT, LOOT_COUNT) {
+            item.summon(&mut replay);
+        }
         commands.spawn((
             Pos(pos.as_vec2().extend(z)),
             params,
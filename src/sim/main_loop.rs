@@ -1,18 +1,422 @@
-use bevy_ecs::schedule::ExecutorKind;
+use std::path::Path;
 
-use crate::{pathfind::reachability_2d_from, remove_foliage::find_trees};
+use itertools::Itertools;
 
-use super::*;
+use crate::{
+    armor_stand::statue,
+    debug_image::{Color as MapColor, MapImage},
+    heraldry::TownCrest,
+    lsystem::garden_paths,
+    map::{map_chest, spawn_point, write_map, Marker},
+    nav_graph::NavGraph,
+    pathfind::reachability_2d_from,
+    player_structures::block_player_structures,
+    quest::{Goal, Quest},
+    remove_foliage::find_trees,
+    ruins::ruins,
+    street_furniture::{decorate_road, wells_at_intersections},
+    tour::TourStop,
+};
+
+use super::{
+    checkpoint::{Checkpoint, CHECKPOINT_INTERVAL},
+    passes::{Passes, Phase},
+    quarry::Quarry,
+    schedule::{Sim, StopCondition},
+    settlement::Blueprint,
+    stats::Stats,
+    *,
+};
+
+/// Ruins per 100×100 columns
+const RUIN_DENSITY: f32 = 0.5;
+/// Street furniture per 100 blocks of road
+const FURNITURE_DENSITY: f32 = 4.;
+/// Where dry runs put the map of the result
+pub const DRY_RUN_MAP: &str = "dry_run.png";
+/// Logs to bring to each storehouse for its quest
+const QUEST_WOOD: u32 = 32;
+/// Houses per settlement that the guided tour visits, on top of the workplaces
+const TOUR_HOUSES: usize = 2;
+/// Length of the simulation unless set otherwise
+pub const DEFAULT_TICKS: usize = 30000;
+/// How often block changes get written to the change log when streaming
+const FLUSH_INTERVAL: usize = 100;
+/// Largest volume a single `fillbiome` may change
+const FILLBIOME_LIMIT: i32 = 32768;
+/// Blocks below the lowest and above the highest ground of a town that get the town biome
+const TOWN_BIOME_DEPTH: i32 = 16;
+const TOWN_BIOME_HEIGHT: i32 = 48;
+
+/// Simulates `settlements` villages growing at once, connected by roads.
+/// With `use_vanilla_villages`, existing villages get upgraded and extended instead of starting
+/// from scratch.
+/// With `activation_range`, villagers are only animated near players.
+/// A summary gets printed at the end, and written to `stats_json` if given.
+pub fn sim(
+    level: Level,
+    settlements: usize,
+    use_vanilla_villages: bool,
+    activation_range: Option<u32>,
+    stats_json: Option<&Path>,
+) {
+    Generator::new(settlements)
+        .use_vanilla_villages(use_vanilla_villages)
+        .activation_range(activation_range)
+        .stats_json(stats_json)
+        .run(level)
+}
+
+/// What to do if the save was already generated on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rerun {
+    /// Leave the areas changed before alone and only build around them
+    #[default]
+    Extend,
+    /// Generate as if the save was untouched
+    Overwrite,
+}
+
+/// Settings of the simulation, see [`sim`], plus custom passes
+pub struct Generator<'a> {
+    settlements: usize,
+    use_vanilla_villages: bool,
+    activation_range: Option<u32>,
+    stats_json: Option<&'a Path>,
+    stream_changes: bool,
+    dry_run: bool,
+    headless: bool,
+    stop: StopCondition,
+    quests: bool,
+    #[cfg(feature = "preview")]
+    preview: Option<u16>,
+    /// Where to write checkpoints, and the seed of the run
+    checkpoint: Option<(&'a Path, u64)>,
+    resume: Option<Checkpoint>,
+    level_settings: LevelSettings,
+    /// Margin of the world border around the generated area
+    world_border: Option<i32>,
+    spawn_at_plaza: bool,
+    rerun: Rerun,
+    extend: Option<Blueprint>,
+    town_biome: Option<String>,
+    passes: Passes,
+}
+
+impl<'a> Generator<'a> {
+    /// The ground around buildings gets worn down (see [`erosion::erode`]), farmland
+    /// irrigated (see [`irrigation::irrigate`]) and cellars dug (see
+    /// [`catacombs::dig_cellars`]) at the end, before any passes added later
+    pub fn new(settlements: usize) -> Self {
+        let mut passes = Passes::default();
+        passes.add(Phase::Finish, erosion::erode);
+        passes.add(Phase::Finish, irrigation::irrigate);
+        passes.add(Phase::Finish, catacombs::dig_cellars);
+        Self {
+            settlements,
+            use_vanilla_villages: false,
+            activation_range: None,
+            stats_json: None,
+            stream_changes: false,
+            dry_run: false,
+            headless: false,
+            stop: StopCondition::Ticks(DEFAULT_TICKS),
+            quests: false,
+            #[cfg(feature = "preview")]
+            preview: None,
+            checkpoint: None,
+            resume: None,
+            level_settings: default(),
+            world_border: None,
+            spawn_at_plaza: false,
+            rerun: default(),
+            extend: None,
+            town_biome: None,
+            passes,
+        }
+    }
+
+    pub fn use_vanilla_villages(mut self, use_vanilla_villages: bool) -> Self {
+        self.use_vanilla_villages = use_vanilla_villages;
+        self
+    }
+
+    pub fn activation_range(mut self, activation_range: Option<u32>) -> Self {
+        self.activation_range = activation_range;
+        self
+    }
+
+    pub fn stats_json(mut self, stats_json: Option<&'a Path>) -> Self {
+        self.stats_json = stats_json;
+        self
+    }
+
+    /// Writes block changes to an append-only log in the save folder as the simulation goes
+    /// instead of keeping them in memory (see [`Level::apply_change_log`]). The replay is
+    /// always written out in chunks as it's recorded.
+    pub fn stream_changes(mut self, stream_changes: bool) -> Self {
+        self.stream_changes = stream_changes;
+        self
+    }
+
+    /// Runs the whole generation, but instead of writing anything to the save renders a map of
+    /// the result to [`DRY_RUN_MAP`]
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Skips recording the replay and saves the finished settlements to the level directly,
+    /// for when only the result matters
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+
+    /// How long to simulate, [`DEFAULT_TICKS`] unless set
+    pub fn ticks(self, ticks: usize) -> Self {
+        self.stop_when(StopCondition::Ticks(ticks))
+    }
+
+    /// Simulates until the condition is met instead of for a fixed number of ticks
+    pub fn stop_when(mut self, stop: StopCondition) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Adds quests to the replay datapack, with a storehouse and a bell on each plaza for them
+    pub fn quests(mut self, quests: bool) -> Self {
+        self.quests = quests;
+        self
+    }
+
+    /// Streams the simulation to a viewer in the browser at the given port, see
+    /// [`crate::preview`]
+    #[cfg(feature = "preview")]
+    pub fn preview(mut self, port: Option<u16>) -> Self {
+        self.preview = port;
+        self
+    }
+
+    /// Regularly writes a [`Checkpoint`] to `path` to resume from after a crash. `seed` is what
+    /// the RNG was seeded with, resuming needs the same one.
+    pub fn checkpoint(mut self, path: Option<&'a Path>, seed: u64) -> Self {
+        self.checkpoint = path.map(|path| (path, seed));
+        self
+    }
+
+    /// Continues a crashed run, see [`checkpoint`](super::checkpoint). The RNG needs to be
+    /// seeded with [`Checkpoint::seed`] and the other settings need to be the same as before.
+    pub fn resume(mut self, checkpoint: Option<Checkpoint>) -> Self {
+        if let Some(checkpoint) = &checkpoint {
+            self.settlements = checkpoint.settlements;
+        }
+        self.resume = checkpoint;
+        self
+    }
+
+    /// Puts the world border around the generated area, leaving `margin` blocks on each side
+    pub fn world_border(mut self, margin: Option<i32>) -> Self {
+        self.world_border = margin;
+        self
+    }
+
+    pub fn game_mode(mut self, mode: Option<GameMode>) -> Self {
+        self.level_settings.game_mode = mode;
+        self
+    }
+
+    /// Moves the world spawn to the plaza of the first settlement
+    pub fn spawn_at_plaza(mut self, spawn_at_plaza: bool) -> Self {
+        self.spawn_at_plaza = spawn_at_plaza;
+        self
+    }
+
+    /// Sets a game rule in level.dat, in addition to the ones the replay needs
+    pub fn game_rule(mut self, rule: &str, value: &str) -> Self {
+        self.level_settings
+            .game_rules
+            .push((rule.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// How to treat areas changed by earlier runs on the same save, [`Rerun::Extend`] unless
+    /// set
+    pub fn rerun(mut self, rerun: Rerun) -> Self {
+        self.rerun = rerun;
+        self
+    }
+
+    /// Keeps growing the settlements of an earlier run instead of founding new ones, see
+    /// [`settlement`](super::settlement). Overrides the number of settlements.
+    pub fn extend(mut self, blueprint: Option<Blueprint>) -> Self {
+        if let Some(blueprint) = &blueprint {
+            self.settlements = blueprint.towns.len();
+        }
+        self.extend = blueprint;
+        self
+    }
+
+    /// Gives the towns and their buildings a uniform biome such as `minecraft:meadow`, so that
+    /// grass colors, weather and mob spawning don't change within a town
+    pub fn town_biome(mut self, biome: Option<String>) -> Self {
+        self.town_biome = biome;
+        self
+    }
+
+    /// Runs `pass` at the given phase. Passes of the same phase run in the order they were added.
+    pub fn add_pass(
+        mut self,
+        phase: Phase,
+        pass: impl FnMut(&mut Level, &mut World) + 'static,
+    ) -> Self {
+        self.passes.add(phase, pass);
+        self
+    }
+
+    pub fn run(self, level: Level) {
+        run(level, self)
+    }
+}
+
+fn run(mut level: Level, generator: Generator) {
+    let Generator {
+        settlements,
+        use_vanilla_villages,
+        activation_range,
+        stats_json,
+        stream_changes,
+        dry_run,
+        headless,
+        stop,
+        quests,
+        #[cfg(feature = "preview")]
+        preview,
+        checkpoint,
+        resume,
+        mut level_settings,
+        world_border,
+        spawn_at_plaza,
+        rerun,
+        extend,
+        town_biome,
+        mut passes,
+    } = generator;
+    // Headless runs have no replay chunks to skip when resuming
+    let checkpoint = checkpoint.filter(|_| !dry_run & !headless);
+    let stream_changes = stream_changes & !dry_run;
+    if stream_changes {
+        // Left over from an earlier run into the same save
+        let _ = std::fs::remove_file(level.change_log_path());
+    }
 
-pub fn sim(mut level: Level) {
     let mut replay = Replay::new(&level);
+    replay.set_activation_range(activation_range);
+    replay.set_mode(if dry_run {
+        ReplayMode::DryRun
+    } else if headless {
+        ReplayMode::Headless
+    } else {
+        ReplayMode::Record
+    });
+    #[cfg(feature = "preview")]
+    if let Some(port) = preview {
+        match crate::preview::Preview::start(port) {
+            Ok(preview) => replay.set_preview(preview),
+            Err(err) => eprintln!("Failed to start preview: {err}"),
+        }
+    }
+    let mut resume_tick = 0;
+    if let Some(checkpoint) = &resume {
+        if replay.resume(checkpoint) {
+            resume_tick = checkpoint.tick;
+            println!("Resuming, simulating up to tick {resume_tick} again");
+        } else {
+            eprintln!("Checkpoint belongs to another run, starting over");
+        }
+    }
+    // Villagers go to bed by the clock, so start the replay at sunrise
+    replay.command("time set 0".into());
 
     let mut world = World::new();
     world.init_resource::<Tick>();
+    world.init_resource::<Stats>();
+    world.init_resource::<WalkSpeed>();
+    world.init_resource::<Seats>();
+    world.init_resource::<FishingHoles>();
+    world.init_resource::<apiary::RelocatedNests>();
+    world.init_resource::<erosion::Footfall>();
+    world.init_resource::<JobBoard>();
+    #[cfg(feature = "scripting")]
+    world.insert_resource(crate::scripting::BuildingScripts::load("plugins"));
 
-    let city_center = choose_starting_area(&level);
-    let city_center_pos = level.ground(city_center.center());
-    println!("center: {city_center_pos:?}");
+    block_player_structures(&mut level);
+    let previous: Vec<Rect> = level
+        .previous_generations()
+        .into_iter()
+        .filter(|area| area.overlapps(level.area()))
+        .collect();
+    // When extending, the buildings of the earlier run get blocked individually instead
+    if !previous.is_empty() && extend.is_none() {
+        match rerun {
+            Rerun::Extend => {
+                println!("Extending around {} earlier runs", previous.len());
+                for area in previous {
+                    level.set_blocked(area.overlap(level.area()));
+                }
+            }
+            Rerun::Overwrite => println!("Overwriting {} earlier runs", previous.len()),
+        }
+    }
+
+    let villages: Vec<StructureStart> = if use_vanilla_villages {
+        vanilla::find_villages(&level)
+            .into_iter()
+            .take(settlements)
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for village in &villages {
+        vanilla::integrate_village(&mut world, &mut level, village);
+    }
+
+    let starting_areas = match &extend {
+        Some(blueprint) => blueprint.starting_areas(),
+        None => choose_starting_areas(
+            &level,
+            settlements,
+            villages.iter().map(vanilla::starting_area).collect(),
+        ),
+    };
+    let city_centers: Vec<IVec3> = starting_areas
+        .iter()
+        .map(|area| level.surface(area.center()))
+        .collect();
+    println!("centers: {city_centers:?}");
+
+    // Ruins are already there at the start of the replay
+    if extend.is_none() {
+        for set in ruins(&mut level, &starting_areas, RUIN_DENSITY) {
+            replay.block(set.pos, set.block);
+        }
+    }
+
+    let cursor = level.recording_cursor();
+    world.resource_mut::<FishingHoles>().0 = cut_fishing_holes(&mut level, &city_centers);
+    for set in level.pop_recording(cursor) {
+        replay.block(set.pos, set.block);
+    }
+
+    // Ornamental paths on the plazas
+    let cursor = level.recording_cursor();
+    for area in starting_areas.iter().filter(|_| extend.is_none()) {
+        garden_paths(&mut level, *area, rand());
+    }
+    for set in level.pop_recording(cursor) {
+        replay.block(set.pos, set.block);
+    }
 
     let starting_resources = {
         let mut stock = Goods::default();
@@ -21,92 +425,448 @@ pub fn sim(mut level: Level) {
         stock.add(Stack::new(Good::Soil, 99999999.));
         stock
     };
-    level.set_blocked(city_center);
-    world.spawn((
-        Pos(city_center_pos.as_vec3()),
-        CityCenter,
-        OutPile {
-            available: starting_resources.clone(),
-        },
-        Pile::new(starting_resources),
-    ));
+    let mut crests: Vec<TownCrest> = Vec::new();
+    for _ in &city_centers {
+        crests.push(TownCrest::unique(&crests));
+    }
+    let mut center_entities = Vec::new();
+    for ((&city_center, &city_center_pos), crest) in
+        starting_areas.iter().zip(&city_centers).zip(&crests)
+    {
+        level.set_blocked(city_center);
+        let entity = world.spawn((
+            Pos(city_center_pos.as_vec3()),
+            CityCenter,
+            crest.clone(),
+            OutPile {
+                available: starting_resources.clone(),
+            },
+            Pile::new(starting_resources.clone()),
+        ));
+        center_entities.push(entity.id());
+
+        for pos in city_center {
+            let pos = level.ground(pos);
+            level(pos, Wool(Magenta))
+        }
+    }
+
+    // Connect each settlement to the closest one founded before it
+    let mut roads = Vec::new();
+    let mut nav = NavGraph::default();
+    for (i, &center) in city_centers.iter().enumerate().skip(1) {
+        let (j, &other) = city_centers[..i]
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, other)| other.distance_squared(center))
+            .unwrap();
+        if let Some(road) =
+            caravan::build_road(&mut level, &mut nav, center + IVec3::Z, other + IVec3::Z)
+        {
+            roads.push((i, j, road));
+        } else {
+            eprintln!("Failed to find road from {center:?} to {other:?}");
+        }
+    }
+
+    // Signposts have tile entities, so the furniture gets replayed here instead of by tick_replay
+    let names: Vec<String> = match &extend {
+        Some(blueprint) => blueprint
+            .towns
+            .iter()
+            .map(|town| town.name.clone())
+            .collect(),
+        None => city_centers
+            .iter()
+            .map(|_| make_name::make_town_name())
+            .collect(),
+    };
+    for (&entity, name) in center_entities.iter().zip(&names) {
+        world.entity_mut(entity).insert(TownName(name.clone()));
+    }
+    if let Some(blueprint) = &extend {
+        blueprint.restore(&mut world, &mut level);
+    }
+    let cursor = level.recording_cursor();
+    for (from, to, road) in &roads {
+        decorate_road(
+            &mut level,
+            &road.curve(),
+            caravan::ROAD_WIDTH,
+            FURNITURE_DENSITY,
+            [&names[*from], &names[*to]],
+        );
+    }
+    let curves: Vec<Vec<Vec2>> = roads.iter().map(|(_, _, road)| road.curve()).collect();
+    wells_at_intersections(&mut level, &curves);
+    let furniture: Vec<SetBlock> = level.pop_recording(cursor).collect();
+    world.resource_mut::<Seats>().0 = furniture
+        .iter()
+        .filter(|set| matches!(set.block, Stair(..)))
+        .map(|set| set.pos)
+        .collect();
+    for set in furniture {
+        if let Some(nbt) = level.tile_entity(set.pos) {
+            replay.block_entity(set.pos, set.block, &nbt);
+        } else {
+            replay.block(set.pos, set.block);
+        }
+    }
+    for (_, _, road) in roads {
+        world.spawn(road);
+    }
+
+    // Newcomers arrive by a road from the edge of the map
+    let edge = population::edge_near(&level, city_centers[0].truncate());
+    if let Some(road) = caravan::build_road(&mut level, &mut nav, edge, city_centers[0] + IVec3::Z)
+    {
+        world.spawn(road);
+    } else {
+        eprintln!("Failed to find road from the edge of the map at {edge:?}");
+    }
+    world.insert_resource(population::Entrance(edge.as_vec3()));
 
-    for pos in city_center {
-        let pos = level.ground(pos);
-        level(pos, Wool(Magenta))
+    // Banners with the town crests mark the settlements on maps
+    let markers: Vec<Marker> = city_centers
+        .iter()
+        .zip(&names)
+        .zip(&crests)
+        .map(|((center, name), crest)| Marker {
+            pos: *center + IVec3::Z,
+            name: name.clone(),
+            color: crest.base,
+            patterns: crest.patterns.clone(),
+        })
+        .collect();
+    for marker in &markers {
+        let (block, nbt) = marker.banner();
+        level(marker.pos, block);
+        level.set_tile_entity(marker.pos, nbt.clone());
+        replay.block_entity(marker.pos, block, &nbt);
     }
 
-    level.reachability = reachability_2d_from(&level, city_center.center());
+    // Statue in a corner of each plaza, looking across it
+    for area in &starting_areas {
+        let pedestal = level.ground(area.max) + IVec3::Z;
+        let stand = statue(&mut level, pedestal, YNeg);
+        replay.block(pedestal, level(pedestal));
+        stand.summon(&mut replay);
+    }
+
+    if quests {
+        add_quests(&mut level, &mut replay, &starting_areas, &names);
+    }
+
+    level.reachability = starting_areas
+        .iter()
+        .map(|area| reachability_2d_from(&level, area.center()))
+        .reduce(|mut reachability, other| {
+            for (a, b) in reachability.data.iter_mut().zip(other.data) {
+                *a = (*a).min(b);
+            }
+            reachability
+        })
+        .unwrap();
 
     // Find trees
     for (pos, species) in find_trees(&level, level.area()) {
         world.spawn((Pos(pos.as_vec3()), Tree::new(species)));
     }
 
-    let mut sched = Schedule::default();
-    sched.set_executor_kind(ExecutorKind::SingleThreaded);
-    sched.add_systems(
-        (
-            grow_trees,
-            assign_work,
-            (
-                place,
-                lumberjack::work,
-                lumberjack::chop,
-                walk,
-                build,
-                pickup,
-                deliver,
-                check_construction_site_readiness,
-            ),
-            (
-                lumberjack::assign_worker,
-                lumberjack::make_lumber_piles,
-                lumberjack::update_lumber_pile_visuals,
-            ),
-            (
-                quarry::assign_worker,
-                quarry::make_stone_piles,
-                quarry::update_stone_pile_visuals,
-            ),
-            (plan_house, plan_lumberjack, plan_quarry),
-            assign_builds,
-            new_construction_site,
-            (test_build_house, test_build_lumberjack, test_build_quarry),
-            personal_name::name,
-            tick_replay,
-            // remove_outdated,
-            |mut tick: ResMut<Tick>| tick.0 += 1,
-            |world: &mut World| world.clear_trackers(),
-        )
-            .chain(),
-    );
+    let mut sim = Sim::new(stop);
 
     replay.command(format!(
         "tp @p {} {} {}",
-        city_center_pos.x,
-        city_center_pos.z + 30,
-        city_center_pos.y
+        city_centers[0].x,
+        city_centers[0].z + 30,
+        city_centers[0].y
     ));
     world.insert_resource(replay);
     world.insert_resource(level);
-    for tick in 0..30000 {
-        sched.run(&mut world);
-
-        if tick < 40 {
-            world.spawn((
-                Id::default(),
-                Villager::default(),
-                Jobless,
-                Pos(city_center_pos.as_vec3() + Vec3::Z),
-                PrevPos(default()),
+    passes.run(Phase::Setup, &mut world);
+    while !sim.finished(&mut world) {
+        let tick = world.resource::<Tick>().0 as usize;
+        sim.tick(&mut world);
+        passes.run(Phase::Tick, &mut world);
+        if stream_changes && tick % FLUSH_INTERVAL == 0 {
+            flush_changes(&mut world.resource_mut::<Level>());
+        }
+        let ticks = tick as i32 + 1;
+        if let Some((path, seed)) = checkpoint
+            && ticks > resume_tick
+            && ticks % CHECKPOINT_INTERVAL == 0
+        {
+            let replay = world.resource::<Replay>();
+            let written = Checkpoint {
+                seed,
+                settlements,
+                tick: ticks,
+                invocation: replay.invocation(),
+                replay_chunks: replay.chunks(),
+            }
+            .write(path);
+            if let Err(err) = written {
+                eprintln!("Failed to write checkpoint: {err}");
+            }
+        }
+
+        // Extended settlements already have their villagers
+        if extend.is_none() && tick < 40 * city_centers.len() {
+            let city_center_pos = city_centers[tick % city_centers.len()];
+            world.spawn(population::new_villager(
+                city_center_pos.as_vec3() + Vec3::Z,
             ));
         }
     }
 
-    let level = world.remove_resource::<Level>().unwrap();
+    passes.run(Phase::Finish, &mut world);
+    if stream_changes {
+        flush_changes(&mut world.resource_mut::<Level>());
+    }
+    let mut level = world.remove_resource::<Level>().unwrap();
     // level.debug_save();
-    let replay = world.remove_resource::<Replay>().unwrap();
-    rayon::spawn(move || level.save_metadata().unwrap());
+    let mut replay = world.remove_resource::<Replay>().unwrap();
+    for stop in tour_stops(&mut world, &level, &city_centers, &names) {
+        replay.tour_stop(stop);
+    }
+
+    if dry_run {
+        let mut map = MapImage::new(level.area());
+        map.heightmap(&level);
+        map.water(&level);
+        for column in level.area() {
+            if level.is_blocked(column) {
+                map.set(column, MapColor::Building);
+            }
+        }
+        map.save(DRY_RUN_MAP);
+        println!("Dry run, map written to {DRY_RUN_MAP}");
+    } else {
+        // Maps of the region and of each settlement, in a chest at spawn
+        let mut maps = Vec::new();
+        match write_map(&level, level.area(), &markers) {
+            Ok(id) => maps.push((id, "Map of the region".to_owned())),
+            Err(err) => eprintln!("Failed to write map: {err}"),
+        }
+        for (area, marker) in starting_areas.iter().zip(&markers) {
+            match write_map(&level, area.grow(40), std::slice::from_ref(marker)) {
+                Ok(id) => maps.push((id, format!("Map of {}", marker.name))),
+                Err(err) => eprintln!("Failed to write map: {err}"),
+            }
+        }
+        let chest_pos = match spawn_point(&level) {
+            Ok(spawn) if !spawn_at_plaza && level.area().contains(spawn.truncate()) => {
+                level.ground(spawn.truncate()) + IVec3::Z
+            }
+            _ => city_centers[0] + IVec3::new(1, 0, 1),
+        };
+        let (chest, nbt) = map_chest(&maps);
+        if headless {
+            // The tour needs the functions of the replay datapack, so there's no lectern
+            level(chest_pos, chest);
+            level.set_tile_entity(chest_pos, nbt);
+        } else {
+            replay.block_entity(chest_pos, chest, &nbt);
+            let (lectern, nbt) = replay.tour_lectern();
+            replay.block_entity(chest_pos + IVec3::X, lectern, &nbt);
+        }
+    }
+
+    skills::bestow_titles(&mut world, &mut replay);
+    let blueprint = Blueprint::collect(&mut world, &starting_areas);
+    if let Some(biome) = &town_biome {
+        for footprint in blueprint.footprints() {
+            set_town_biome(&mut level, &mut replay, footprint.grow(4), biome);
+        }
+    }
+    if !dry_run {
+        let path = level.path.join(settlement::BLUEPRINT);
+        if let Err(err) = blueprint.write(&path) {
+            eprintln!("Failed to write settlement blueprint: {err}");
+        }
+    }
+    let stats = stats::finish(&mut world, &replay);
+    stats.print();
+    if let Some(path) = stats_json
+        && let Err(err) = stats.write_json(path)
+    {
+        eprintln!("Failed to write stats: {err}");
+    }
+
+    if !level.load_warnings.is_empty() {
+        eprintln!("{} warnings while loading:", level.load_warnings.len());
+        for warning in &level.load_warnings {
+            eprintln!("  {warning}");
+        }
+    }
+
+    if headless & !dry_run {
+        level.save_chunks();
+    }
+    if !dry_run {
+        if let Some(margin) = world_border {
+            let area = level.area();
+            let size = area.size().max_element() + 2 * margin;
+            level_settings.world_border = Some((area.center(), size));
+        }
+        if spawn_at_plaza {
+            level_settings.spawn = Some(city_centers[0] + IVec3::Z);
+        }
+        // Not in the background, the process could exit while level.dat is half written
+        level.save_metadata(&level_settings).unwrap();
+    }
     replay.finish();
+    // Finished, so there's nothing to resume
+    if let Some((path, _)) = checkpoint {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Sets the biome in the level for saving and also with `fillbiome`s for the replay. The
+/// replay only changes the layers around the ground, that's what matters for the looks and
+/// spawning.
+fn set_town_biome(level: &mut Level, replay: &mut Replay, footprint: Rect, biome: &str) {
+    let footprint = footprint.overlap(level.area());
+    level.set_custom_biome(footprint, biome);
+    let (bottom, top) = footprint
+        .into_iter()
+        .map(|column| level.height(column))
+        .minmax()
+        .into_option()
+        .unwrap();
+    let (bottom, top) = (bottom - TOWN_BIOME_DEPTH, top + TOWN_BIOME_HEIGHT);
+    let layers = FILLBIOME_LIMIT / (16 * 16);
+    for x in (footprint.min.x..=footprint.max.x).step_by(16) {
+        for y in (footprint.min.y..=footprint.max.y).step_by(16) {
+            for z in (bottom..=top).step_by(layers as usize) {
+                let max = ivec3(x + 15, y + 15, z + layers - 1).min(footprint.max.extend(top));
+                replay.command(format!(
+                    "fillbiome {x} {z} {y} {} {} {} {biome}",
+                    max.x, max.z, max.y
+                ));
+            }
+        }
+    }
+}
+
+/// Per settlement, a storehouse to fill and a bell to ring in the other corners of the plaza,
+/// plus visiting all settlements if there are several
+fn add_quests(level: &mut Level, replay: &mut Replay, starting_areas: &[Rect], names: &[String]) {
+    for (area, name) in starting_areas.iter().zip(names) {
+        let storehouse = level.ground(ivec2(area.min.x, area.max.y)) + IVec3::Z;
+        let barrel = Block::from_name("barrel", &[("facing", "up")]);
+        level(storehouse, barrel);
+        let mut nbt = nbt::CompoundTag::new();
+        nbt.insert_str("id", "minecraft:barrel");
+        nbt.insert_str(
+            "CustomName",
+            format!("{{\"text\":\"Storehouse of {name}\"}}"),
+        );
+        level.set_tile_entity(storehouse, nbt.clone());
+        replay.block_entity(storehouse, barrel, &nbt);
+        replay.quest(Quest {
+            title: format!("Firewood for {name}"),
+            description: format!(
+                "Winter is coming. Bring {QUEST_WOOD} oak logs to the storehouse on the plaza of {name}."
+            ),
+            flavor: format!("The hearths of {name} will burn through the winter."),
+            goal: Goal::Deliver {
+                item: "oak_log",
+                amount: QUEST_WOOD,
+                pos: storehouse,
+            },
+        });
+
+        let bell = level.ground(ivec2(area.max.x, area.min.y)) + IVec3::Z;
+        level(bell, Bell(YPos, BellAttachment::Floor));
+        replay.block(bell, Bell(YPos, BellAttachment::Floor));
+        replay.quest(Quest {
+            title: format!("The bell of {name}"),
+            description: format!(
+                "Ring the bell on the plaza of {name} to call its people together."
+            ),
+            flavor: format!("The toll echoes through the streets of {name}."),
+            goal: Goal::RingBell(bell),
+        });
+    }
+
+    if starting_areas.len() > 1 {
+        replay.quest(Quest {
+            title: "Wanderer".into(),
+            description: format!("Visit the plazas of all {} settlements.", names.len()),
+            flavor: "Every settlement of the region has seen you pass through.".into(),
+            goal: Goal::Visit(
+                starting_areas
+                    .iter()
+                    .map(|area| level.ground(area.center()) + IVec3::Z)
+                    .collect(),
+            ),
+        });
+    }
+}
+
+/// Each settlement's center, followed by its workplaces and a few of its houses
+fn tour_stops(
+    world: &mut World,
+    level: &Level,
+    city_centers: &[IVec3],
+    names: &[String],
+) -> Vec<TourStop> {
+    let mut buildings = world.query_filtered::<(
+        &Pos,
+        Option<&House>,
+        Option<&Lumberjack>,
+        Option<&Quarry>,
+        Option<&Blacksmith>,
+    ), With<Built>>();
+    let mut per_town: Vec<Vec<(Vec3, &str)>> = vec![Vec::new(); city_centers.len()];
+    for (pos, house, lumberjack, quarry, blacksmith) in buildings.iter(world) {
+        let kind = match (house, lumberjack, quarry, blacksmith) {
+            (Some(_), ..) => "House",
+            (_, Some(_), ..) => "Lumber camp",
+            (_, _, Some(_), _) => "Quarry",
+            (.., Some(_)) => "Smithy",
+            _ => continue,
+        };
+        let Some(town) = (0..city_centers.len())
+            .min_by_key(|&i| city_centers[i].as_vec3().distance_squared(pos.0) as i32)
+        else {
+            break;
+        };
+        per_town[town].push((pos.0, kind));
+    }
+
+    let mut stops = Vec::new();
+    for ((center, name), mut buildings) in city_centers.iter().zip(names).zip(per_town) {
+        stops.push(TourStop::overlooking(
+            level.area(),
+            center.as_vec3() + vec3(0.5, 0.5, 1.),
+            name.clone(),
+            "Town center".into(),
+        ));
+        // Workplaces first
+        buildings.sort_by_key(|(_, kind)| *kind == "House");
+        let mut houses = 0;
+        for (pos, kind) in buildings {
+            if kind == "House" {
+                if houses == TOUR_HOUSES {
+                    continue;
+                }
+                houses += 1;
+            }
+            stops.push(TourStop::overlooking(
+                level.area(),
+                pos,
+                kind.into(),
+                name.clone(),
+            ));
+        }
+    }
+    stops
+}
+
+fn flush_changes(level: &mut Level) {
+    if let Err(err) = level.flush_recording() {
+        eprintln!("Failed to write change log: {err}");
+    }
 }
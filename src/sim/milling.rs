@@ -0,0 +1,74 @@
+use crate::*;
+use sim::*;
+
+/// Gardens within this distance of a mill that has a miller get a bigger harvest
+const MILL_RANGE: f32 = 64.;
+const MILL_BOOST: f32 = 1.5;
+
+/// Windmill, or watermill if there's a river next to it, see [`crate::mill`]
+#[derive(Component)]
+pub struct Mill {
+    pub area: Rect,
+    /// Side the water wheel is on
+    pub water: Option<HDir>,
+}
+
+#[derive(Component)]
+pub struct Miller {
+    workplace: Entity,
+}
+
+pub fn assign_worker(
+    mut commands: Commands,
+    available: Query<(Entity, &Pos), With<Jobless>>,
+    new: Query<(Entity, &Pos), (With<Mill>, Added<Built>)>,
+) {
+    let mut assigned = Vec::new();
+    for (workplace, pos) in &new {
+        let Some((worker, _)) = available
+            .iter()
+            .filter(|(e, _)| !assigned.contains(e))
+            .min_by_key(|(_, p)| p.distance_squared(pos.0) as i32)
+        else {
+            return;
+        };
+        commands
+            .entity(worker)
+            .remove::<Jobless>()
+            .insert(Miller { workplace });
+        assigned.push(worker);
+    }
+}
+
+/// Millers stay at their mill
+pub fn work(
+    mut commands: Commands,
+    millers: Query<(Entity, &Pos, &Miller), (Without<MoveTask>, Without<DeliverTask>)>,
+    mills: Query<&Pos, With<Mill>>,
+) {
+    for (entity, pos, miller) in &millers {
+        let Ok(mill_pos) = mills.get(miller.workplace) else {
+            continue;
+        };
+        if pos.distance(mill_pos.0) > 3. {
+            commands.entity(entity).insert(MoveTask {
+                goal: mill_pos.block(),
+                distance: 2,
+            });
+        }
+    }
+}
+
+/// Factor for the harvest of a garden at `pos`
+pub fn harvest_factor(millers: &Query<&Miller>, mills: &Query<&Pos, With<Mill>>, pos: Vec3) -> f32 {
+    let milled = millers.iter().any(|miller| {
+        mills
+            .get(miller.workplace)
+            .is_ok_and(|mill| mill.distance(pos) < MILL_RANGE)
+    });
+    if milled {
+        MILL_BOOST
+    } else {
+        1.
+    }
+}
@@ -0,0 +1,49 @@
+//! Hooks for downstream crates to run their own generation steps, e.g. for building types
+//! of their own, without having to fork the simulation.
+
+use crate::*;
+use sim::*;
+
+/// Points during the generation at which custom passes run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    /// Once the settlements have been founded and connected by roads, before the first tick
+    Setup,
+    /// At the end of every simulated tick
+    Tick,
+    /// After the last tick, before the maps get drawn and the level is saved
+    Finish,
+}
+
+/// Custom pass. The level gets handed out separately, the world otherwise contains all of the
+/// simulation's entities and resources, including the `Replay`.
+pub type Pass = Box<dyn FnMut(&mut Level, &mut World)>;
+
+#[derive(Default)]
+pub struct Passes(Vec<(Phase, Pass)>);
+
+impl Passes {
+    pub fn add(&mut self, phase: Phase, pass: impl FnMut(&mut Level, &mut World) + 'static) {
+        self.0.push((phase, Box::new(pass)));
+    }
+
+    /// Runs the passes for this phase in the order they were added. Blocks they set get
+    /// replayed right away; blocked columns are respected by the simulation from then on.
+    pub(super) fn run(&mut self, phase: Phase, world: &mut World) {
+        for (_, pass) in self.0.iter_mut().filter(|(p, _)| *p == phase) {
+            world.resource_scope(|world, mut level: Mut<Level>| {
+                let cursor = level.recording_cursor();
+                pass(&mut level, world);
+                let placed: Vec<SetBlock> = level.pop_recording(cursor).collect();
+                let mut replay = world.resource_mut::<Replay>();
+                for set in placed {
+                    if let Some(nbt) = level.tile_entity(set.pos) {
+                        replay.block_entity(set.pos, set.block, &nbt);
+                    } else {
+                        replay.block(set.pos, set.block);
+                    }
+                }
+            });
+        }
+    }
+}
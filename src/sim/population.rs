@@ -0,0 +1,86 @@
+//! Newcomers settle where there is food to spare, and the settlements grow houses for them
+
+use crate::*;
+use sim::*;
+
+use super::garden::Garden;
+
+/// Ticks between checks whether someone new arrives
+const ARRIVAL_INTERVAL: i32 = 600;
+/// Vegetables that need to be left over for a newcomer to arrive. They eat them on arrival.
+const ARRIVAL_FOOD: f32 = 2.;
+/// Villagers per house
+pub const RESIDENTS_PER_HOUSE: usize = 2;
+
+/// Where newcomers enter the map: the end of the road from its edge
+#[derive(Resource)]
+pub struct Entrance(pub Vec3);
+
+pub fn new_villager(pos: Vec3) -> impl Bundle {
+    (
+        Id::default(),
+        Villager::default(),
+        Skills::default(),
+        Jobless,
+        Pos(pos),
+        PrevPos(default()),
+    )
+}
+
+/// Point on the edge of the map closest to `center`, shifted inwards a bit so that there's
+/// room for a road
+pub fn edge_near(level: &Level, center: IVec2) -> IVec3 {
+    let column = level
+        .area()
+        .shrink(2)
+        .border()
+        .min_by_key(|column| column.distance_squared(center))
+        .unwrap();
+    level.ground(column) + IVec3::Z
+}
+
+/// Food left over in the gardens draws new villagers, who come in by road. Each eats some of
+/// the surplus, so growth stays bounded by the harvests.
+pub fn arrive(
+    mut commands: Commands,
+    tick: Res<Tick>,
+    entrance: Res<Entrance>,
+    mut stats: ResMut<stats::Stats>,
+    mut gardens: Query<(&mut Pile, &mut OutPile), With<Garden>>,
+) {
+    if tick.0 % ARRIVAL_INTERVAL != 0 {
+        return;
+    }
+    let surplus: f32 = gardens
+        .iter()
+        .map(|(_, out_pile)| {
+            out_pile
+                .available
+                .get(&Good::Vegetables)
+                .copied()
+                .unwrap_or(0.)
+        })
+        .sum();
+    if surplus < ARRIVAL_FOOD {
+        return;
+    }
+
+    let mut hunger = ARRIVAL_FOOD;
+    for (mut pile, mut out_pile) in &mut gardens {
+        let eaten = out_pile
+            .available
+            .remove_up_to(Stack::new(Good::Vegetables, hunger));
+        pile.remove(eaten);
+        hunger -= eaten.amount;
+        if hunger <= 0. {
+            break;
+        }
+    }
+    commands.spawn(new_villager(entrance.0));
+    stats.arrivals += 1;
+}
+
+/// Whether there are more villagers than the houses, including planned ones, have room for
+pub fn needs_housing(villagers: usize, houses: usize) -> bool {
+    villagers > houses * RESIDENTS_PER_HOUSE
+}
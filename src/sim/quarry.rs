@@ -1,6 +1,13 @@
-use crate::*;
+use crate::{loot::scatter_loot, *};
 use sim::*;
 
+/// Stone broken per work cycle with fresh tools
+const STONE_PER_CYCLE: f32 = 4.;
+const CYCLE_TICKS: i32 = 60;
+/// Stray items around the stone pile
+const LOOT: [&str; 3] = ["cobblestone", "cobblestone", "flint"];
+const LOOT_COUNT: usize = 5;
+
 #[derive(Component, Eq, PartialEq, Copy, Clone)]
 pub struct Quarry {
     pub area: Rect,
@@ -21,7 +28,7 @@ impl Quarry {
 #[derive(Component)]
 pub struct Mason {
     workplace: Entity,
-    ready_to_work: bool,
+    progress: i32,
 }
 
 #[derive(Component)]
@@ -32,7 +39,7 @@ pub struct StonePile {
 pub fn assign_worker(
     mut commands: Commands,
     available: Query<(Entity, &Pos), With<Jobless>>,
-    new: Query<(Entity, &Pos), (With<Lumberjack>, Added<Built>)>,
+    new: Query<(Entity, &Pos), (With<Quarry>, Added<Built>)>,
 ) {
     let assigned = Vec::new();
     for (workplace, pos) in &new {
@@ -43,10 +50,66 @@ pub fn assign_worker(
         else {
             return;
         };
-        commands.entity(worker).remove::<Jobless>().insert(Mason {
-            workplace,
-            ready_to_work: true,
-        });
+        commands.entity(worker).remove::<Jobless>().insert((
+            Mason {
+                workplace,
+                progress: 0,
+            },
+            ToolWear::new(workplace),
+        ));
+    }
+}
+
+pub fn work(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut masons: Query<
+        (
+            Entity,
+            &Pos,
+            &mut Villager,
+            &mut Mason,
+            Option<&mut ToolWear>,
+        ),
+        (Without<MoveTask>, Without<DeliverTask>),
+    >,
+    quarries: Query<&Pos, With<Quarry>>,
+    piles: Query<(Entity, &Pos), With<StonePile>>,
+) {
+    for (entity, pos, mut vill, mut mason, tools) in &mut masons {
+        let Ok(quarry_pos) = quarries.get(mason.workplace) else {
+            continue;
+        };
+        if vill.carry.get(Good::Stone).is_some() {
+            // Drop off the stone
+            if let Some((to, _)) = piles
+                .iter()
+                .min_by_key(|(_, pile_pos)| pile_pos.distance(quarry_pos.0) as i32)
+            {
+                commands.entity(entity).insert(DeliverTask { to });
+            }
+            continue;
+        }
+        if pos.distance(quarry_pos.0) > 3. {
+            commands.entity(entity).insert(MoveTask {
+                goal: quarry_pos.block(),
+                distance: 2,
+            });
+            continue;
+        }
+
+        mason.progress += 1;
+        if mason.progress < CYCLE_TICKS {
+            continue;
+        }
+        mason.progress = 0;
+        let mut amount = STONE_PER_CYCLE;
+        if let Some(mut tools) = tools {
+            amount *= tools.efficiency();
+            tools.wear += 0.1;
+        }
+        vill.carry.add(Stack::new(Good::Stone, amount));
+        replay.dust(quarry_pos.block());
     }
 }
 
@@ -76,6 +139,7 @@ pub fn make_quarry(level: &mut Level, quarry: Quarry) -> PlaceList {
 pub fn make_stone_piles(
     mut commands: Commands,
     mut level: ResMut<Level>,
+    mut replay: ResMut<Replay>,
     new_quarries: Query<&Pos, (With<Quarry>, Added<Built>)>,
 ) {
     for quarry in &new_quarries {
@@ -113,6 +177,9 @@ pub fn make_stone_piles(
 
         let z = level.average_height(area.border()) as i32 + 1;
         level.set_blocked(area);
+        for item in scatter_loot(&level, area, &LOOT, LOOT_COUNT) {
+            item.summon(&mut replay);
+        }
         commands.spawn((
             Pos(area.center_vec2().extend(z as f32)),
             StonePile {
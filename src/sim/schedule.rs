@@ -0,0 +1,212 @@
+//! The systems of the simulation and the order they run in each tick
+
+use bevy_ecs::schedule::{ExecutorKind, IntoSystemConfigs, IntoSystemSetConfigs, SystemSet};
+
+use super::*;
+
+/// Each tick of the simulation gets played back as this many game ticks, so durations in
+/// ticks (such as the length of a day) are the same as in Minecraft
+pub const GAME_TICKS_PER_TICK: i32 = 1;
+/// Upper limit for runs that stop on a condition other than the tick count, in case it's
+/// never met
+pub const MAX_TICKS: usize = 300000;
+
+/// Parts of a tick, run in this order
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Handing out work to villagers
+    Assign,
+    /// Villagers and workplaces doing their work
+    Work,
+    /// Planning new buildings and setting up construction sites for them
+    Plan,
+    /// Things happening on their own: caravans, names, spoiling goods
+    Upkeep,
+    /// Gathering stats and passing changes on to the replay
+    Record,
+    /// Moving on to the next tick
+    Advance,
+}
+
+/// When the simulation ends
+#[derive(Copy, Clone, Debug)]
+pub enum StopCondition {
+    Ticks(usize),
+    /// Finished buildings, including workplaces
+    Buildings(usize),
+    Population(usize),
+}
+
+/// Schedule of the simulation. Runs single-threaded, so that the result only depends on the
+/// seed.
+pub struct Sim {
+    schedule: Schedule,
+    stop: StopCondition,
+}
+
+impl Sim {
+    pub fn new(stop: StopCondition) -> Self {
+        let mut schedule = Schedule::default();
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        schedule.configure_sets(
+            (
+                Stage::Assign,
+                Stage::Work,
+                Stage::Plan,
+                Stage::Upkeep,
+                Stage::Record,
+                Stage::Advance,
+            )
+                .chain(),
+        );
+        schedule.add_systems(
+            (
+                grow_trees,
+                (job_board::post_jobs, job_board::assign_jobs).chain(),
+                idle::start_idle,
+            )
+                .chain()
+                .in_set(Stage::Assign),
+        );
+        schedule.add_systems(
+            (
+                (
+                    place,
+                    lumberjack::work,
+                    lumberjack::chop,
+                    walk,
+                    build,
+                    pickup,
+                    deliver,
+                    check_construction_site_readiness,
+                    idle::idle,
+                ),
+                (
+                    lumberjack::assign_worker,
+                    lumberjack::make_lumber_piles,
+                    lumberjack::update_lumber_pile_visuals,
+                    apiary::make_apiaries,
+                    apiary::relocate_nests,
+                ),
+                (
+                    garden::make_gardens,
+                    garden::grow,
+                    garden::eat,
+                    population::arrive,
+                ),
+                (
+                    quarry::assign_worker,
+                    quarry::work,
+                    quarry::make_stone_piles,
+                    quarry::update_stone_pile_visuals,
+                ),
+                (
+                    blacksmith::assign_worker,
+                    blacksmith::setup_smithy,
+                    blacksmith::work,
+                    blacksmith::resupply_tools,
+                ),
+                (milling::assign_worker, milling::work),
+            )
+                .chain()
+                .in_set(Stage::Work),
+        );
+        schedule.add_systems(
+            (
+                (
+                    plan_house,
+                    plan_cliff_dwelling,
+                    plan_lumberjack,
+                    plan_quarry,
+                    plan_blacksmith,
+                    plan_mill,
+                    plan_castle,
+                    plan_dzong,
+                ),
+                assign_builds,
+                new_construction_site,
+                (
+                    test_build_house,
+                    test_build_lumberjack,
+                    test_build_blacksmith,
+                    test_build_quarry,
+                    test_build_mill,
+                    test_build_castle,
+                    test_build_dzong,
+                    test_build_cliff_dwelling,
+                ),
+            )
+                .chain()
+                .in_set(Stage::Plan),
+        );
+        schedule.add_systems(
+            (
+                (caravan::depart, caravan::travel),
+                personal_name::name,
+                decay_goods,
+            )
+                .chain()
+                .in_set(Stage::Upkeep),
+        );
+        schedule.add_systems(
+            (
+                (
+                    stats::count_actions,
+                    stats::track_walking,
+                    erosion::track_footfall,
+                ),
+                erosion::trample,
+                tick_replay,
+            )
+                .chain()
+                .in_set(Stage::Record),
+        );
+        schedule.add_systems(
+            (
+                // remove_outdated,
+                |mut tick: ResMut<Tick>| tick.0 += 1,
+                |world: &mut World| world.clear_trackers(),
+            )
+                .chain()
+                .in_set(Stage::Advance),
+        );
+        Self { schedule, stop }
+    }
+
+    /// Adds systems to run during `stage`
+    pub fn add_systems<M>(
+        &mut self,
+        stage: Stage,
+        systems: impl IntoSystemConfigs<M>,
+    ) -> &mut Self {
+        self.schedule.add_systems(systems.in_set(stage));
+        self
+    }
+
+    /// Runs a single tick
+    pub fn tick(&mut self, world: &mut World) {
+        self.schedule.run(world);
+    }
+
+    pub fn finished(&self, world: &mut World) -> bool {
+        let ticks = world.resource::<Tick>().0 as usize;
+        match self.stop {
+            StopCondition::Ticks(stop) => ticks >= stop,
+            _ if ticks >= MAX_TICKS => true,
+            StopCondition::Buildings(stop) => {
+                world
+                    .query_filtered::<(), With<Built>>()
+                    .iter(world)
+                    .count()
+                    >= stop
+            }
+            StopCondition::Population(stop) => {
+                world
+                    .query_filtered::<(), With<Villager>>()
+                    .iter(world)
+                    .count()
+                    >= stop
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Blueprint of the finished settlements, written into the save at the end of a run. A later
+//! run can load it to keep growing the same towns instead of founding new ones: the buildings
+//! stay as they are, and the towns start out with the villagers and goods they ended with.
+
+use std::{fs::rename, path::Path};
+
+use anyhow::Result;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use milling::Mill;
+use population::new_villager;
+use quarry::Quarry;
+
+/// Name of the blueprint in the save directory
+pub const BLUEPRINT: &str = "mcgen-settlement.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Blueprint {
+    pub towns: Vec<Town>,
+    /// Finished buildings by kind, with their area as `[min x, min y, max x, max y]`
+    pub buildings: Vec<(String, [i32; 4])>,
+    pub population: usize,
+}
+
+/// Building from an earlier run
+#[derive(Component)]
+pub struct Restored {
+    pub kind: String,
+    pub area: Rect,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Town {
+    pub name: String,
+    /// Starting area, as `[min x, min y, max x, max y]`
+    pub area: [i32; 4],
+    /// Goods at the town center
+    pub stock: Vec<(Good, f32)>,
+}
+
+impl Blueprint {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        rename(temp, path)?;
+        Ok(())
+    }
+
+    /// Gathers the state of the settlements. `areas` are the starting areas of the towns.
+    pub fn collect(world: &mut World, areas: &[Rect]) -> Self {
+        let towns = world
+            .query_filtered::<(&TownName, &Pile), With<CityCenter>>()
+            .iter(world)
+            .zip(areas)
+            .map(|((name, pile), area)| Town {
+                name: name.0.clone(),
+                area: to_array(*area),
+                stock: pile.iter().map(|(&good, &amount)| (good, amount)).collect(),
+            })
+            .collect();
+        let mut buildings = Vec::new();
+        let mut query = world.query_filtered::<(
+            Option<&House>,
+            Option<&Lumberjack>,
+            Option<&Quarry>,
+            Option<&Blacksmith>,
+            Option<&Castle>,
+            Option<&Mill>,
+        ), With<Built>>();
+        for (house, lumberjack, quarry, blacksmith, castle, mill) in query.iter(world) {
+            let (kind, area) = match (house, lumberjack, quarry, blacksmith, castle, mill) {
+                (Some(house), ..) => ("house", house.area),
+                (_, Some(lumberjack), ..) => ("lumberjack", lumberjack.area),
+                (_, _, Some(quarry), ..) => ("quarry", quarry.area),
+                (_, _, _, Some(blacksmith), ..) => ("blacksmith", blacksmith.area),
+                // Including the moat
+                (_, _, _, _, Some(castle), _) => ("castle", castle::footprint(castle.area)),
+                (.., Some(mill)) => ("mill", mill.area),
+                _ => continue,
+            };
+            buildings.push((kind.to_owned(), to_array(area)));
+        }
+        for restored in world.query::<&Restored>().iter(world) {
+            buildings.push((restored.kind.clone(), to_array(restored.area)));
+        }
+        let population = world
+            .query_filtered::<(), With<Villager>>()
+            .iter(world)
+            .count();
+        Self {
+            towns,
+            buildings,
+            population,
+        }
+    }
+
+    pub fn starting_areas(&self) -> Vec<Rect> {
+        self.towns.iter().map(|town| to_rect(town.area)).collect()
+    }
+
+    /// Per town, the bounding box of its starting area and the buildings closest to it
+    pub fn footprints(&self) -> Vec<Rect> {
+        let mut footprints = self.starting_areas();
+        if footprints.is_empty() {
+            return footprints;
+        }
+        for (_, area) in &self.buildings {
+            let area = to_rect(*area);
+            let town = footprints
+                .iter()
+                .position_min_by_key(|footprint| footprint.center().distance_squared(area.center()))
+                .unwrap();
+            let footprint = &mut footprints[town];
+            footprint.min = footprint.min.min(area.min);
+            footprint.max = footprint.max.max(area.max);
+        }
+        footprints
+    }
+
+    /// Blocks the buildings and brings back the villagers and the goods of the towns. Houses
+    /// count towards the available housing. Needs the town centers to be spawned already.
+    pub fn restore(&self, world: &mut World, level: &mut Level) {
+        for (kind, area) in &self.buildings {
+            let area = to_rect(*area);
+            level.set_blocked(area);
+            let mut building = world.spawn(Restored {
+                kind: kind.clone(),
+                area,
+            });
+            // Not marked as built, that would set them up again
+            if kind == "house" {
+                building.insert((Pos(level.ground(area.center()).as_vec3()), House { area }));
+            }
+        }
+        let centers: Vec<Vec3> = world
+            .query_filtered::<(&Pos, &mut OutPile, &mut Pile), With<CityCenter>>()
+            .iter_mut(world)
+            .zip(&self.towns)
+            .map(|((pos, mut out, mut pile), town)| {
+                let mut stock = Goods::default();
+                for &(good, amount) in &town.stock {
+                    stock.add(Stack::new(good, amount));
+                }
+                out.available = stock.clone();
+                pile.goods = stock;
+                pos.0
+            })
+            .collect();
+        if centers.is_empty() {
+            return;
+        }
+        for i in 0..self.population {
+            world.spawn(new_villager(centers[i % centers.len()] + Vec3::Z));
+        }
+    }
+}
+
+fn to_array(area: Rect) -> [i32; 4] {
+    [area.min.x, area.min.y, area.max.x, area.max.y]
+}
+
+fn to_rect([min_x, min_y, max_x, max_y]: [i32; 4]) -> Rect {
+    Rect {
+        min: ivec2(min_x, min_y),
+        max: ivec2(max_x, max_y),
+    }
+}
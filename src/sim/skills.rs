@@ -0,0 +1,103 @@
+//! Villagers get better at what they do often: faster and, for chopping, with more yield
+
+use crate::*;
+use sim::*;
+
+/// Highest skill level, reached by masters of their trade
+const MAX_LEVEL: u32 = 5;
+/// Chance per tick of an unskilled villager getting on with their task, rising to 1 at
+/// [`MAX_LEVEL`]
+const UNSKILLED_SPEED: f32 = 0.5;
+/// Extra yield per skill level
+const YIELD_PER_LEVEL: f32 = 0.1;
+/// Level from which a villager is known by their trade
+const TITLE_LEVEL: u32 = 3;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Skill {
+    Chopping,
+    Building,
+}
+
+impl Skill {
+    pub const ALL: [Self; 2] = [Self::Chopping, Self::Building];
+
+    /// Tasks needed for the first level. Each level after that takes that many more than the
+    /// one before.
+    fn first_level(self) -> u32 {
+        match self {
+            Self::Chopping => 3,
+            Self::Building => 150,
+        }
+    }
+
+    fn trade(self) -> &'static str {
+        match self {
+            Self::Chopping => "Woodcutter",
+            Self::Building => "Builder",
+        }
+    }
+}
+
+/// Tasks performed so far, by skill
+#[derive(Component, Default, Debug)]
+pub struct Skills {
+    tasks: [u32; Skill::ALL.len()],
+}
+
+impl Skills {
+    pub fn practice(&mut self, skill: Skill) {
+        self.tasks[skill as usize] += 1;
+    }
+
+    pub fn level(&self, skill: Skill) -> u32 {
+        let tasks = self.tasks[skill as usize] / skill.first_level();
+        // Level n takes n * (n + 1) / 2 times the tasks of the first one
+        (0..MAX_LEVEL)
+            .take_while(|level| (level + 1) * (level + 2) / 2 <= tasks)
+            .count() as u32
+    }
+
+    /// Chance of getting on with a task of the skill in a given tick
+    pub fn speed(&self, skill: Skill) -> f32 {
+        UNSKILLED_SPEED + (1. - UNSKILLED_SPEED) * self.level(skill) as f32 / MAX_LEVEL as f32
+    }
+
+    /// Whether to get on with a task of the skill this tick
+    pub fn progress(&self, skill: Skill) -> bool {
+        rand_f32(0., 1.) < self.speed(skill)
+    }
+
+    pub fn yield_factor(&self, skill: Skill) -> f32 {
+        1. + self.level(skill) as f32 * YIELD_PER_LEVEL
+    }
+
+    /// E.g. "Master Builder" for the skill with the highest level, if high enough
+    pub fn title(&self) -> Option<String> {
+        let skill = Skill::ALL
+            .into_iter()
+            .max_by_key(|skill| self.level(*skill))?;
+        match self.level(skill) {
+            MAX_LEVEL => Some(format!("Master {}", skill.trade())),
+            level if level >= TITLE_LEVEL => Some(skill.trade().into()),
+            _ => None,
+        }
+    }
+}
+
+/// Adds their titles to the names of skilled villagers and lists them in the stats. Runs
+/// after the simulation, so that the villagers in the replay end up with their titles.
+pub fn bestow_titles(world: &mut World, replay: &mut Replay) {
+    let mut villagers = world.query::<(&Id, &mut Name, &Skills)>();
+    let mut titled = Vec::new();
+    for (id, mut name, skills) in villagers.iter_mut(world) {
+        if let Some(title) = skills.title() {
+            name.0 = format!("{} the {title}", name.0);
+            replay.name(*id, &name.0);
+            titled.push(name.0.clone());
+        }
+    }
+    if let Some(mut stats) = world.get_resource_mut::<stats::Stats>() {
+        stats.titles = titled;
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::*;
+use milling::Mill;
+use quarry::Quarry;
+use sim::*;
+
+/// Summary of a generation run, for tuning and writeups
+#[derive(Resource, Default, Serialize)]
+pub struct Stats {
+    /// Placed blocks by name, including those placed before the simulation started
+    pub blocks: BTreeMap<String, u64>,
+    /// Finished buildings by kind
+    pub buildings: BTreeMap<&'static str, u32>,
+    /// Completed walks, pickups, deliveries and builds
+    pub villager_actions: u64,
+    /// Total distance walked by villagers
+    pub distance_walked: f32,
+    /// Length of each road between settlements
+    pub road_lengths: Vec<f32>,
+    /// Villagers known for their skills, with their titles
+    pub titles: Vec<String>,
+    /// Villagers that arrived from outside during the simulation
+    pub arrivals: u32,
+    pub ticks: i32,
+}
+
+impl Stats {
+    pub fn print(&self) {
+        println!("=== Statistics ===");
+        println!("Ticks: {}", self.ticks);
+        println!("Villager actions: {}", self.villager_actions);
+        println!("Arrivals: {}", self.arrivals);
+        println!("Distance walked: {:.0}", self.distance_walked);
+        println!(
+            "Roads: {} with a total length of {:.0}",
+            self.road_lengths.len(),
+            self.road_lengths.iter().sum::<f32>()
+        );
+        if !self.titles.is_empty() {
+            println!("Skilled villagers: {}", self.titles.join(", "));
+        }
+        println!("Buildings:");
+        for (kind, count) in &self.buildings {
+            println!("  {kind}: {count}");
+        }
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+        println!(
+            "Blocks placed: {}",
+            blocks.iter().map(|(_, count)| **count).sum::<u64>()
+        );
+        for (block, count) in blocks.iter().take(20) {
+            println!("  {block}: {count}");
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub fn count_actions(
+    mut stats: ResMut<Stats>,
+    mut walks: RemovedComponents<MoveTask>,
+    mut pickups: RemovedComponents<PickupTask>,
+    mut deliveries: RemovedComponents<DeliverTask>,
+    mut builds: RemovedComponents<BuildTask>,
+) {
+    stats.villager_actions += (walks.read().count()
+        + pickups.read().count()
+        + deliveries.read().count()
+        + builds.read().count()) as u64;
+}
+
+/// Needs to run before `tick_replay` updates `PrevPos`
+pub fn track_walking(
+    mut stats: ResMut<Stats>,
+    moved: Query<(&Pos, &PrevPos), (With<Villager>, Changed<Pos>)>,
+) {
+    for (pos, prev) in &moved {
+        stats.distance_walked += pos.distance(prev.0);
+    }
+}
+
+/// Fills in what's only known at the end of the run
+pub fn finish(world: &mut World, replay: &Replay) -> Stats {
+    let mut stats = world.remove_resource::<Stats>().unwrap_or_default();
+    stats.ticks = world.resource::<Tick>().0;
+
+    for (block, count) in replay.placed_blocks() {
+        let name = block.blockstate(&UNKNOWN_BLOCKS.read().unwrap()).0;
+        *stats.blocks.entry(name.into_owned()).or_default() += count;
+    }
+
+    let mut buildings = world.query_filtered::<(
+        Option<&House>,
+        Option<&Lumberjack>,
+        Option<&Quarry>,
+        Option<&Blacksmith>,
+        Option<&Castle>,
+        Option<&Mill>,
+    ), With<Built>>();
+    for (house, lumberjack, quarry, blacksmith, castle, mill) in buildings.iter(world) {
+        let kind = match (house, lumberjack, quarry, blacksmith, castle, mill) {
+            (Some(_), ..) => "house",
+            (_, Some(_), ..) => "lumberjack",
+            (_, _, Some(_), ..) => "quarry",
+            (_, _, _, Some(_), ..) => "blacksmith",
+            (_, _, _, _, Some(_), _) => "castle",
+            (.., Some(_)) => "mill",
+            _ => "other",
+        };
+        *stats.buildings.entry(kind).or_default() += 1;
+    }
+
+    let mut roads = world.query::<&caravan::Road>();
+    stats.road_lengths = roads.iter(world).map(|road| road.length()).collect();
+    stats
+}
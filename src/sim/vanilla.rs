@@ -0,0 +1,53 @@
+use crate::*;
+use sim::*;
+
+/// Smaller vanilla houses are kept as they are
+const MIN_UPGRADE_SIZE: i32 = 7;
+
+/// The planned house replaces the vanilla building in this volume
+#[derive(Component, Clone, Copy)]
+pub struct Replaces(pub Cuboid);
+
+/// Vanilla villages in the settlement area
+pub fn find_villages(level: &Level) -> Vec<&StructureStart> {
+    level
+        .structures
+        .iter()
+        .filter(|start| start.id.starts_with("minecraft:village_"))
+        .filter(|start| level.area().has_subrect(start.area()))
+        .collect()
+}
+
+/// Area around the meeting point of the village
+pub fn starting_area(village: &StructureStart) -> Rect {
+    Rect::new_centered(village.pieces[0].bounds.d2().center(), IVec2::splat(24))
+}
+
+/// Keeps new buildings from being placed on top of the village and plans to replace its larger
+/// houses with better ones
+pub fn integrate_village(world: &mut World, level: &mut Level, village: &StructureStart) {
+    for piece in &village.pieces {
+        let area = piece.bounds.d2();
+        level.set_blocked(area);
+        if !piece.element.contains("/houses/") || (area.size().min_element() < MIN_UPGRADE_SIZE) {
+            continue;
+        }
+        world.spawn((
+            Pos(level.ground(area.center()).as_vec3()),
+            Planned(area),
+            House { area },
+            Replaces(piece.bounds),
+        ));
+    }
+}
+
+/// Removes the vanilla building so a new one can be built in its place
+pub fn demolish(level: &mut impl WorldView, bounds: Cuboid) {
+    let floor = bounds.min.z;
+    for column in bounds.d2() {
+        for z in (floor + 1..=bounds.max.z).rev() {
+            level.set(column.extend(z), Air);
+        }
+        level.set_height(column, floor);
+    }
+}
@@ -0,0 +1,238 @@
+use std::f32::consts::TAU;
+
+use nbt::CompoundTag;
+
+use crate::*;
+
+/// Road samples are about half a block apart, see `road::smooth_path`
+const SAMPLES_PER_BLOCK: f32 = 2.;
+/// Wells are placed at most this often
+const WELL_SPACING: f32 = 40.;
+
+#[derive(Clone, Copy)]
+enum Furniture {
+    Lamp,
+    Bench,
+    Planter,
+}
+
+/// Lines the road with lamp posts, benches and flower planters, `density` of them per 100
+/// blocks, and puts signposts pointing to the `destinations` (start and end of the road)
+/// at both ends.
+pub fn decorate_road(
+    level: &mut Level,
+    curve: &[Vec2],
+    width: i32,
+    density: f32,
+    destinations: [&str; 2],
+) {
+    if curve.len() < 2 || density <= 0. {
+        return;
+    }
+    let offset = width as f32 / 2. + 1.;
+    let interval = ((100. / density) * SAMPLES_PER_BLOCK).max(1.) as usize;
+    let mut side = 1.;
+    for (i, index) in (interval / 2..curve.len() - 1)
+        .step_by(interval)
+        .enumerate()
+    {
+        side *= -1.;
+        let Some((column, towards_road)) = roadside(level, curve, index, offset * side) else {
+            continue;
+        };
+        let furniture = match i % 4 {
+            0 | 2 => Furniture::Lamp,
+            1 => Furniture::Bench,
+            _ => Furniture::Planter,
+        };
+        let pos = level.ground(column) + IVec3::Z;
+        match furniture {
+            Furniture::Lamp => lamp_post(level, pos),
+            Furniture::Bench => bench(level, pos, towards_road),
+            Furniture::Planter => planter(level, pos),
+        }
+    }
+
+    // Signposts at both ends, pointing to the other one
+    let end = curve.len() - 1;
+    for (index, destination) in [
+        ((6. * SAMPLES_PER_BLOCK) as usize, destinations[1]),
+        (
+            end.saturating_sub((6. * SAMPLES_PER_BLOCK) as usize),
+            destinations[0],
+        ),
+    ] {
+        if let Some((column, towards_road)) = roadside(level, curve, index.min(end - 1), offset) {
+            let pos = level.ground(column) + IVec3::Z;
+            signpost(level, pos, towards_road, destination);
+        }
+    }
+}
+
+/// Free column next to the road at the given sample and the direction from it towards the road
+fn roadside(level: &Level, curve: &[Vec2], index: usize, offset: f32) -> Option<(IVec2, Vec2)> {
+    let along = (curve[index + 1] - curve[index]).normalize_or_zero();
+    let across = along.perp() * offset;
+    let column = (curve[index] + across).round().as_ivec2();
+    if !level.area().contains(column) {
+        return None;
+    }
+    let ground = level.ground(column);
+    (level.water_level(column).is_none()
+        && level(ground).solid()
+        && (level(ground) != Path)
+        && (level(ground + IVec3::Z) == Air))
+        .then_some((column, -across.normalize_or_zero()))
+}
+
+fn facing(dir: Vec2) -> HDir {
+    if dir.x.abs() > dir.y.abs() {
+        if dir.x > 0. {
+            XPos
+        } else {
+            XNeg
+        }
+    } else if dir.y > 0. {
+        YPos
+    } else {
+        YNeg
+    }
+}
+
+fn lamp_post(level: &mut Level, pos: IVec3) {
+    for z in 0..3 {
+        level(pos + IVec3::Z * z, Fence(Wood(Spruce)));
+    }
+    level(pos + IVec3::Z * 3, Block::from_name("lantern", &[]));
+}
+
+/// Stair to sit on with signs as armrests
+fn bench(level: &mut Level, pos: IVec3, towards_road: Vec2) {
+    let front = facing(towards_road);
+    // Stairs face towards their back
+    level(
+        pos,
+        Stair(Wood(Oak), front.rotated(2), Bottom, StairShape::Straight),
+    );
+    for side in [front.rotated(1), front.rotated(3)] {
+        let armrest = pos.add(side);
+        if level(armrest) == Air {
+            level(
+                armrest,
+                Block::from_name("oak_wall_sign", &[("facing", side.to_str())]),
+            );
+        }
+    }
+}
+
+fn planter(level: &mut Level, pos: IVec3) {
+    level(pos, Dirt);
+    let flower = *[
+        SmallPlant::Poppy,
+        SmallPlant::Dandelion,
+        SmallPlant::Cornflower,
+        SmallPlant::Allium,
+        SmallPlant::OxeyeDaisy,
+    ]
+    .choose();
+    level(pos + IVec3::Z, SmallPlant(flower));
+    for side in HDir::ALL {
+        let edge = pos.add(side);
+        if level(edge) == Air {
+            level(edge, Trapdoor(Spruce, side, DoorMeta::OPEN));
+        }
+    }
+}
+
+fn signpost(level: &mut Level, pos: IVec3, towards_road: Vec2, text: &str) {
+    level(pos, Fence(Wood(Spruce)));
+    // Sign rotation goes clockwise in sixteenths of a turn, starting facing south
+    let rotation = ((f32::atan2(-towards_road.x, towards_road.y) / TAU * 16.).round() as i32)
+        .rem_euclid(16)
+        .to_string();
+    let sign = pos + IVec3::Z;
+    level(
+        sign,
+        Block::from_name("spruce_sign", &[("rotation", rotation.as_str())]),
+    );
+    let mut front_text = CompoundTag::new();
+    front_text.insert_str_vec(
+        "messages",
+        [
+            "\"\"".to_owned(),
+            format!("\"{text}\""),
+            "\"\"".to_owned(),
+            "\"\"".to_owned(),
+        ],
+    );
+    let mut nbt = CompoundTag::new();
+    nbt.insert_str("id", "minecraft:sign");
+    nbt.insert_compound_tag("front_text", front_text);
+    nbt.insert_i8("is_waxed", 1);
+    level.set_tile_entity(sign, nbt);
+}
+
+/// Places wells where roads meet
+pub fn wells_at_intersections(level: &mut Level, roads: &[Vec<Vec2>]) {
+    let mut usage: HashMap<IVec2, usize> = HashMap::default();
+    for road in roads {
+        let columns: HashSet<IVec2> = road
+            .iter()
+            .map(|sample| sample.round().as_ivec2())
+            .collect();
+        for column in columns {
+            *usage.entry(column).or_default() += 1;
+        }
+    }
+    let mut intersections: Vec<IVec2> = usage
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(column, _)| column)
+        .collect();
+    intersections.sort_by_key(|column| (column.x, column.y));
+
+    let mut wells: Vec<IVec2> = Vec::new();
+    for intersection in intersections {
+        if wells
+            .iter()
+            .any(|well| well.as_vec2().distance(intersection.as_vec2()) < WELL_SPACING)
+        {
+            continue;
+        }
+        let site = (3..=6)
+            .flat_map(|distance| {
+                Rect::new_centered(intersection, IVec2::splat(distance * 2 + 1)).border()
+            })
+            .map(|center| Rect::new_centered(center, IVec2::splat(3)))
+            .find(|site| {
+                level.unblocked(*site)
+                    && site.into_iter().all(|column| {
+                        level.water_level(column).is_none()
+                            && (level.height)(column) == (level.height)(site.center())
+                    })
+            });
+        if let Some(site) = site {
+            well(level, site);
+            wells.push(intersection);
+        }
+    }
+}
+
+fn well(level: &mut Level, site: Rect) {
+    let ground = (level.height)(site.center());
+    level(site.center().extend(ground), Water);
+    level(site.center().extend(ground - 1), Full(Cobble));
+    for column in site.border() {
+        level(column.extend(ground), Full(Cobble));
+        level(column.extend(ground + 1), Full(Cobble));
+    }
+    for corner in site.corners() {
+        for z in ground + 2..=ground + 3 {
+            level(corner.extend(z), Fence(Wood(Spruce)));
+        }
+    }
+    for column in site {
+        level(column.extend(ground + 4), Slab(Wood(Spruce), Bottom));
+    }
+    level.set_blocked(site);
+}
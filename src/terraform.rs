@@ -1,7 +1,6 @@
-use std::usize;
-
-use crate::*;
+use crate::{remove_foliage::remove_trees, *};
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum WallCrest {
     None,
     Full,
@@ -9,70 +8,77 @@ pub enum WallCrest {
     Wall,
 }
 
-pub fn make_retaining_wall(level: &mut Level, area: &Polygon, height: i32, crest: WallCrest) {
+pub fn make_retaining_wall(
+    level: &mut (impl WorldView + ?Sized),
+    area: &Polygon,
+    height: i32,
+    crest: WallCrest,
+) {
     let material = Cobble;
     // Placement order matters for replay -> build wall first
     let crest = match crest {
         WallCrest::None => Air,
-        WallCrest::Full => FullBlock(material),
-        WallCrest::Fence => Block::Fence(Wood(level.biome(area.0[0]).default_tree_species())),
+        WallCrest::Full => Full(material),
+        WallCrest::Fence => Fence(Wood(level.biome(area.0[0]).default_tree_species())),
         WallCrest::Wall => Fence(material),
     };
 
     for column in area.border(LineStyle::ThickWobbly) {
-        let mut y = level.height(column);
+        let mut z = level.height(column);
         // Check if wall is neccessary
-        if y > height || (y == height && !side_exposted(level, column.at(y))) {
+        if z > height || (z == height && !side_exposed(level, column.extend(z))) {
             // Todo: also skip this column if the only exposed side is within the polygon
             continue;
         }
 
         // Build wall
-        while matches!(level[column.at(y)], Soil(_)) {
-            y -= 1;
-        }
-        for y in y..=height {
-            level[column.at(y)] = FullBlock(material)
+        while level.get(column.extend(z)).soil() {
+            z -= 1;
         }
-        let above = &mut level[column.at(height + 1)];
-        if matches!((crest, &above), (Air, GroundPlant(_))) {
-            *above = Air
-        } else {
-            *above = crest
+        for z in z..=height {
+            level.set(column.extend(z), Full(material))
         }
+        level.set(column.extend(height + 1), crest);
 
-        *level.height_mut(column) = height;
+        level.set_height(column, height);
     }
 
     // Then fill
     // TODO: bottom to top
     for column in area.iter() {
         if level.height(column) < height {
-            for y in level.height(column)..=height {
-                level[column.at(y)] = Soil(get_filling_soil(level, column))
+            let soil = filling_soil(level, column);
+            for z in level.height(column)..=height {
+                level.set(column.extend(z), soil)
             }
-            *level.height_mut(column) = height;
+            level.set_height(column, height);
         }
     }
 }
 
-fn get_filling_soil(level: &Level, column: Vec2) -> Soil {
-    if let Soil(soil) = level[column.at(level.height(column))] {
-        soil
+fn filling_soil(level: &(impl WorldView + ?Sized), column: IVec2) -> Block {
+    let top = level.get(level.ground(column));
+    if top.soil() {
+        top
     } else {
         level.biome(column).default_topsoil()
     }
 }
 
-pub fn make_foundation_sloped(level: &mut Level, mut area: Rect, height: i32, material: Material) {
+pub fn make_foundation_sloped(
+    level: &mut (impl WorldView + ?Sized),
+    mut area: Rect,
+    height: i32,
+    material: BlockMaterial,
+) {
     // TODO: proper placement order
 
-    remove_foliage::trees(level, area.into_iter(), false);
+    remove_trees(level, area);
     for column in area {
-        level[column.at(height)] = FullBlock(material);
+        level.set(column.extend(height), Full(material));
     }
 
-    let mut y = height - 1;
+    let mut z = height - 1;
     let mut width_increased_last_layer = false;
     let mut outmost_is_wall = false;
     let mut block_placed_this_layer = true;
@@ -80,24 +86,26 @@ pub fn make_foundation_sloped(level: &mut Level, mut area: Rect, height: i32, ma
     while block_placed_this_layer {
         block_placed_this_layer = false;
         for column in area.shrink(1) {
-            level[column.at(y)] |= FullBlock(material);
+            level.modify(column.extend(z), &mut |b| b | Full(material));
         }
         for column in area.border() {
-            if !level[column.at(y)].solid() || side_exposted(level, column.at(y)) {
+            let pos = column.extend(z);
+            if !level.get(pos).solid() || side_exposed(level, pos) {
                 block_placed_this_layer = true;
-                level[column.at(y)] = FullBlock(material);
+                level.set(pos, Full(material));
             }
         }
         if outmost_is_wall {
             for column in area.grow(1).border() {
-                if !level[column.at(y)].solid() {
+                let pos = column.extend(z);
+                if !level.get(pos).solid() {
                     block_placed_this_layer = true;
-                    level[column.at(y)] = Fence(material);
+                    level.set(pos, Fence(material));
                 }
             }
         }
 
-        y -= 1;
+        z -= 1;
 
         if !width_increased_last_layer {
             if outmost_is_wall {
@@ -109,74 +117,82 @@ pub fn make_foundation_sloped(level: &mut Level, mut area: Rect, height: i32, ma
     }
 }
 
-pub fn make_foundation_straight(level: &mut Level, area: Rect, height: i32, material: Material) {
+pub fn make_foundation_straight(
+    level: &mut (impl WorldView + ?Sized),
+    area: Rect,
+    height: i32,
+    material: BlockMaterial,
+) {
     for column in area {
-        level[column.at(height)] = FullBlock(material);
-        let mut y = height - 1;
+        level.set(column.extend(height), Full(material));
+        let mut z = height - 1;
         let ground_height = level.height(column);
-        while (y > ground_height) | soil_exposted(level, column.at(y)) {
-            level[column.at(y)] = FullBlock(material);
-            y -= 1;
+        while (z > ground_height) | soil_exposed(level, column.extend(z)) {
+            level.set(column.extend(z), Full(material));
+            z -= 1;
         }
-        for y in (height + 1)..=ground_height {
-            level[column.at(y)] = Air;
+        for z in (height + 1)..=ground_height {
+            level.set(column.extend(z), Air);
         }
     }
 
     make_support(
         level,
-        ((area.min.0 + 1)..area.max.0).map(|x| Vec2(x, area.min.1)),
+        ((area.min.x + 1)..area.max.x).map(|x| ivec2(x, area.min.y)),
         height,
-        ZPos,
+        YPos,
         material,
     );
     make_support(
         level,
-        ((area.min.0 + 1)..area.max.0).map(|x| Vec2(x, area.max.1)),
+        ((area.min.x + 1)..area.max.x).map(|x| ivec2(x, area.max.y)),
         height,
-        ZNeg,
+        YNeg,
         material,
     );
     make_support(
         level,
-        ((area.min.1 + 1)..area.max.1).map(|z| Vec2(area.min.0, z)),
+        ((area.min.y + 1)..area.max.y).map(|y| ivec2(area.min.x, y)),
         height,
-        XVec3,
+        XPos,
         material,
     );
     make_support(
         level,
-        ((area.min.1 + 1)..area.max.1).map(|z| Vec2(area.max.0, z)),
+        ((area.min.y + 1)..area.max.y).map(|y| ivec2(area.max.x, y)),
         height,
         XNeg,
         material,
     );
 
     fn make_support(
-        level: &mut Level,
-        columns: impl Iterator<Item = Vec2>,
-        y: i32,
+        level: &mut (impl WorldView + ?Sized),
+        columns: impl Iterator<Item = IVec2>,
+        z: i32,
         facing: HDir,
-        material: Material,
+        material: BlockMaterial,
     ) {
         let support_chance = 0.7;
         let min_height = 3;
         let max_height = 6;
         let mut just_placed = false;
         for column in columns {
-            let column = column - Vec2::from(facing);
-            let mut ground_distance = y.saturating_sub(level.height(column));
-            while soil_exposted(level, column.at(y - ground_distance - 1)) {
+            let column = column - IVec2::from(facing);
+            let mut ground_distance = (z - level.height(column)).max(0);
+            while soil_exposed(level, column.extend(z - ground_distance - 1)) {
                 ground_distance += 1;
             }
             just_placed = if (ground_distance >= min_height)
                 & (ground_distance <= max_height)
                 & !just_placed
-                & rand(support_chance)
+                & (support_chance > rand())
             {
-                level[column.at(y)] = Stair(material, facing, Flipped(false));
-                for y in y - ground_distance..y {
-                    level[column.at(y)] = FullBlock(material);
+                level.set(
+                    column.extend(z),
+                    Stair(material, facing, Bottom, StairShape::Straight),
+                );
+                for z in z - ground_distance..z {
+                    level.set(column.extend(z), Full(material));
                 }
                 true
             } else {
@@ -186,74 +202,33 @@ pub fn make_foundation_straight(level: &mut Level, area: Rect, height: i32, mate
     }
 }
 
-pub fn soil_exposted(level: &Level, pos: Vec3) -> bool {
-    matches!(level[pos], Soil(..)) & side_exposted(level, pos)
+pub fn soil_exposed(level: &(impl WorldView + ?Sized), pos: IVec3) -> bool {
+    level.get(pos).soil() & side_exposed(level, pos)
 }
 
-pub fn side_exposted(level: &Level, pos: Vec3) -> bool {
-    !(level[pos + Vec2(0, 1)].solid()
-        && level[pos + Vec2(0, -1)].solid()
-        && level[pos + Vec2(1, 0)].solid()
-        && level[pos + Vec2(-1, 0)].solid())
+pub fn side_exposed(level: &(impl WorldView + ?Sized), pos: IVec3) -> bool {
+    !NEIGHBORS_2D
+        .iter()
+        .all(|off| level.get(pos.add(*off)).solid())
 }
 
-pub fn average_height(level: &Level, area: impl Iterator<Item = Vec2>) -> u8 {
-    let mut sum = 0.0;
-    let mut count = 0;
-    for column in area {
-        sum += level.height(column) as f32;
-        count += 1;
-    }
-    (sum / count as f32) as u8
-}
-
-pub fn slope(level: &Level, column: Vec2) -> Vec2 {
+pub fn slope(level: &(impl WorldView + ?Sized), column: IVec2) -> IVec2 {
     let mut neighbors = [0; 9];
     for dx in -1..=1 {
-        for dz in -1..=1 {
-            neighbors[(4 + dx + 3 * dz) as usize] = level.height(column + Vec2(dx, dz));
+        for dy in -1..=1 {
+            neighbors[(4 + dx + 3 * dy) as usize] = level.height(column + ivec2(dx, dy));
         }
     }
     // Sobel kernel
     let slope_x = (neighbors[2] + 2 * neighbors[5] + neighbors[8])
         - (neighbors[0] + 2 * neighbors[3] + neighbors[6]);
-    let slope_z = (neighbors[6] + 2 * neighbors[7] + neighbors[8])
+    let slope_y = (neighbors[6] + 2 * neighbors[7] + neighbors[8])
         - (neighbors[0] + 2 * neighbors[1] + neighbors[2]);
-    Vec2(slope_x, slope_z)
+    ivec2(slope_x, slope_y)
 }
 
-/*
-/// Neighborborhood_size specifies a square. Results aren't fully acurate, but that's ok
-pub fn find_local_maxima(level: &Level, area: Rect, neighborhood_size: u8) -> Vec<Vec3> {
-    // Divide area into cells
-    let cell_size = neighborhood_size as i32 / 3;
-    let cell_count = area.size() / cell_size;
-    // Actually searched area is rounded down to integer number of cells
-    let area = {
-        let min = area.min + (area.size() % cell_size) / 2;
-        Rect {
-            min,
-            max: min + cell_count * cell_size,
-        }
-    };
-    for z in (area.min.1..area.max.1).step_by(cell_size as usize) {
-        for x in (area.min.0..area.max.0).step_by(cell_size as usize) {
-            Rect {
-                min: Vec2(x, z),
-                max: Vec2(x + cell_size, z + cell_size),
-            }
-            .iter()
-        }
-    }
-    // find highest in each cell
-    // return highest in cell when n higher in surrounding cells
-
-    todo!()
-}
-*/
-
 // TODO: add average
-// TODO: move into World, cache
+// TODO: move into Level, cache
 pub fn max_chunk_heights(level: &Level) -> HashMap<ChunkIndex, i32> {
     level
         .chunks()
@@ -263,10 +238,60 @@ pub fn max_chunk_heights(level: &Level) -> HashMap<ChunkIndex, i32> {
                 chunk
                     .area()
                     .into_iter()
-                    .map(|column| level.height(column))
+                    .map(|column| (level.height)(column))
                     .max()
                     .unwrap(),
             )
         })
         .collect()
 }
+
+/// Fills the non-solid space connected to the water block at `source` (e.g. a dug channel or
+/// moat) with water up to the level of the source. Water is only placed where it's held in by
+/// solid blocks, so if the space can't hold water (or would need more than `max_volume` blocks),
+/// nothing is placed.
+/// Returns the number of placed water blocks.
+pub fn flood(
+    level: &mut (impl WorldView + ?Sized),
+    source: IVec3,
+    max_volume: usize,
+) -> Option<usize> {
+    let area = level.area();
+    let fillable = |block: Block| !block.solid() && block != Water;
+
+    let mut filled = HashSet::default();
+    let mut queue = vec![source];
+    while let Some(pos) = queue.pop() {
+        // Never flow upwards
+        for next in HDir::ALL
+            .map(|dir| pos.add(dir))
+            .into_iter()
+            .chain([pos - IVec3::Z])
+        {
+            if filled.contains(&next) || !fillable(level.get(next)) {
+                continue;
+            }
+            // Would flow out of the world
+            if !area.contains(next.truncate()) {
+                return None;
+            }
+            filled.insert(next);
+            if filled.len() > max_volume {
+                return None;
+            }
+            queue.push(next);
+        }
+    }
+
+    let mut filled: Vec<_> = filled.into_iter().collect();
+    // Bottom to top for replay
+    filled.sort_by_key(|pos| (pos.z, pos.x, pos.y));
+    for &pos in &filled {
+        level.set(pos, Water);
+        let column = pos.truncate();
+        if !matches!(level.water_level(column), Some(water) if water >= pos.z) {
+            level.set_water_level(column, Some(pos.z));
+        }
+    }
+    Some(filled.len())
+}
@@ -21,8 +21,7 @@ pub fn house(level: &mut Level, outer: Cuboid) {
     level.fill_at(outer.d2(), outer.max.z, Full(MudBrick));
 
     let door_pos = ivec2(rand_range(inner.min.x..=inner.max.x), outer.min.y);
-    level(door_pos, inner.min.z, Door(Oak, YPos, DoorMeta::empty()));
-    level(door_pos, inner.min.z + 1, Door(Oak, YPos, DoorMeta::TOP));
+    place_door(level, door_pos.extend(inner.min.z), YPos, Oak);
 
     let mut roof_access = false;
     if 0.7 > rand() {
@@ -87,22 +86,22 @@ pub fn house(level: &mut Level, outer: Cuboid) {
 fn crenel(width: i32, i: i32) -> Block {
     if width % 2 == 0 {
         if i % 2 == 0 {
-            Stair(MudBrick, XPos, Bottom)
+            Stair(MudBrick, XPos, Bottom, StairShape::Straight)
         } else {
-            Stair(MudBrick, XNeg, Bottom)
+            Stair(MudBrick, XNeg, Bottom, StairShape::Straight)
         }
     } else {
         if (width / 2) % 3 == 2 {
             if i == 0 {
-                return Stair(MudBrick, XPos, Bottom);
+                return Stair(MudBrick, XPos, Bottom, StairShape::Straight);
             } else if i == width - 1 {
-                return Stair(MudBrick, XNeg, Bottom);
+                return Stair(MudBrick, XNeg, Bottom, StairShape::Straight);
             }
         }
         [
-            Stair(MudBrick, XPos, Bottom),
+            Stair(MudBrick, XPos, Bottom, StairShape::Straight),
             Slab(MudBrick, Bottom),
-            Stair(MudBrick, XNeg, Bottom),
+            Stair(MudBrick, XNeg, Bottom, StairShape::Straight),
         ][(width + i) as usize % 3]
     }
 }
@@ -0,0 +1,128 @@
+use std::{fmt::Write, fs::write, path::Path};
+
+use anyhow::Result;
+use nbt::CompoundTag;
+use serde_json::json;
+
+use crate::*;
+
+/// How far above and to the side of the sight the viewpoint is
+const VIEW_HEIGHT: f32 = 6.;
+const VIEW_DISTANCE: f32 = 9.;
+
+/// Place the guided tour teleports the player to, with a title introducing it
+pub struct TourStop {
+    pub viewpoint: Vec3,
+    pub look_at: Vec3,
+    pub title: String,
+    pub subtitle: String,
+}
+
+impl TourStop {
+    /// Viewpoint diagonally above `sight`, as long as it stays within `area`
+    pub fn overlooking(area: Rect, sight: Vec3, title: String, subtitle: String) -> Self {
+        let offset = vec2(1., 1.).normalize() * VIEW_DISTANCE;
+        let viewpoint = [offset, -offset, offset.perp(), -offset.perp()]
+            .into_iter()
+            .map(|offset| sight.truncate() + offset)
+            .find(|column| area.contains(column.as_ivec2()))
+            .unwrap_or(sight.truncate());
+        Self {
+            viewpoint: viewpoint.extend(sight.z + VIEW_HEIGHT),
+            look_at: sight,
+            title,
+            subtitle,
+        }
+    }
+}
+
+/// Writes the functions of the tour into `function_path`: `tour` dispatches on the
+/// `<namespace>_tour` trigger, which the lectern book and the stops' chat links set to the
+/// number of the stop to go to.
+pub fn write_functions(function_path: &Path, namespace: &str, stops: &[TourStop]) -> Result<()> {
+    let mut dispatch = String::new();
+    for (i, stop) in stops.iter().enumerate() {
+        let number = i + 1;
+        writeln!(
+            dispatch,
+            "execute if score @s {namespace}_tour matches {number} run function {namespace}:tour_{number}"
+        )?;
+        let mut function = format!(
+            "tp @s {} {} {} facing {} {} {}\n",
+            stop.viewpoint.x,
+            stop.viewpoint.z,
+            stop.viewpoint.y,
+            stop.look_at.x,
+            stop.look_at.z,
+            stop.look_at.y
+        );
+        // Titles can contain quotes, so the text components are built as JSON
+        writeln!(
+            function,
+            "title @s subtitle {}",
+            json!({"text": stop.subtitle})
+        )?;
+        writeln!(function, "title @s title {}", json!({"text": stop.title}))?;
+        if number < stops.len() {
+            writeln!(
+                function,
+                r#"tellraw @s {{"text":"[Next stop]","color":"gold","clickEvent":{{"action":"run_command","value":"/trigger {namespace}_tour set {}"}}}}"#,
+                number + 1
+            )?;
+        } else {
+            writeln!(function, r#"tellraw @s {{"text":"End of the tour"}}"#)?;
+        }
+        write(
+            function_path.join(format!("tour_{number}.mcfunction")),
+            function,
+        )?;
+    }
+    writeln!(dispatch, "scoreboard players set @s {namespace}_tour 0")?;
+    write(function_path.join("tour.mcfunction"), dispatch)?;
+    Ok(())
+}
+
+/// Lectern holding a book that lists the stops, each one clickable to start the tour there
+pub fn lectern(namespace: &str, stops: &[TourStop]) -> (Block, CompoundTag) {
+    let block = Block::from_name("lectern", &[("facing", "north"), ("has_book", "true")]);
+    let entries: Vec<String> = stops
+        .iter()
+        .enumerate()
+        .map(|(i, stop)| {
+            json!({
+                "text": format!("{}. {}\n", i + 1, stop.title),
+                "clickEvent": {
+                    "action": "run_command",
+                    "value": format!("/trigger {namespace}_tour set {}", i + 1),
+                },
+            })
+            .to_string()
+        })
+        .collect();
+    // Pages fit about a dozen lines
+    let pages = entries.chunks(12).enumerate().map(|(page, entries)| {
+        let heading = (page == 0).then(|| r#"{"text":"Guided tour\n\n","bold":true}"#.to_owned());
+        format!(
+            "[{}]",
+            heading
+                .into_iter()
+                .chain(entries.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    });
+
+    let mut tag = CompoundTag::new();
+    tag.insert_str("title", "Guided tour");
+    tag.insert_str("author", "mc-gen");
+    tag.insert_str_vec("pages", pages);
+    let mut book = CompoundTag::new();
+    book.insert_str("id", "minecraft:written_book");
+    book.insert_i8("Count", 1);
+    book.insert_compound_tag("tag", tag);
+    let mut nbt = CompoundTag::new();
+    nbt.insert_str("id", "minecraft:lectern");
+    nbt.insert_compound_tag("Book", book);
+    nbt.insert_i32("Page", 0);
+    (block, nbt)
+}
@@ -0,0 +1,163 @@
+use crate::*;
+
+/// Makes whatever is built in `area` look ruined: Holes get punched into walls, walls crumble
+/// from the top with the rubble falling down next to them, stone turns mossy and cracked and
+/// plants grow over the remains.
+/// `severity` goes from 0 (untouched) to 1 (barely anything left standing).
+pub fn ruin(level: &mut impl WorldView, area: Cuboid, severity: f32) {
+    let area = Cuboid {
+        min: area.min.max(level.area().min.extend(i32::MIN)),
+        max: area.max.min(level.area().max.extend(i32::MAX)),
+    };
+    punch_holes(level, area, severity);
+    crumble(level, area, severity);
+    weather(level, area, severity);
+    overgrow(level, area, severity);
+}
+
+fn punch_holes(level: &mut impl WorldView, area: Cuboid, severity: f32) {
+    let size = area.size();
+    let holes = (size.x * size.y) as f32 / 32. * severity;
+    for _ in 0..holes.round() as i32 {
+        let center = ivec3(
+            rand_range(area.min.x..=area.max.x),
+            rand_range(area.min.y..=area.max.y),
+            rand_range(area.min.z..=area.max.z),
+        );
+        if !level.get(center).artificial() {
+            continue;
+        }
+        let radius = rand_f32(0.8, 1.5 + severity);
+        for pos in Cuboid::around(center, radius.ceil() as i32) {
+            if (pos.as_vec3().distance(center.as_vec3()) <= radius)
+                & pos.cmpgt(area.min).all()
+                & pos.cmple(area.max).all()
+                & level.get(pos).artificial()
+            {
+                level.set(pos, Air);
+            }
+        }
+    }
+}
+
+/// Walls break away more towards the top, some of it landing next to them
+fn crumble(level: &mut impl WorldView, area: Cuboid, severity: f32) {
+    let height = area.size().z as f32;
+    let footprint = area.d2();
+    for column in footprint {
+        for z in (area.min.z + 1..=area.max.z).rev() {
+            let pos = column.extend(z);
+            let block = level.get(pos);
+            if !block.artificial() {
+                continue;
+            }
+            let relative_height = (z - area.min.z) as f32 / height;
+            if severity * (0.1 + relative_height) < rand() {
+                continue;
+            }
+            level.set(pos, Air);
+
+            let Some(rubble) = rubble(block) else {
+                continue;
+            };
+            if 0.5 > rand() {
+                continue;
+            }
+            let landing = column + *NEIGHBORS_2D.choose() * rand_range(1..=2);
+            if !footprint.contains(landing) {
+                continue;
+            }
+            let mut landing = landing.extend(z);
+            while (landing.z > area.min.z) & !level.get(landing - IVec3::Z).solid() {
+                landing.z -= 1;
+            }
+            if !level.get(landing).solid() {
+                level.set(landing, rubble);
+            }
+        }
+    }
+}
+
+fn rubble(block: Block) -> Option<Block> {
+    match block {
+        Full(material) if 0.5 > rand() => Some(Full(material)),
+        Full(material) | Stair(material, ..) | Slab(material, ..) => Some(Slab(material, Bottom)),
+        _ => None,
+    }
+}
+
+/// Stone gets mossy and cracked
+fn weather(level: &mut impl WorldView, area: Cuboid, severity: f32) {
+    let cracked = Block::from_name("cracked_stone_bricks", &[]);
+    for pos in area {
+        let mossy = |material| match material {
+            Cobble => Some(MossyCobble),
+            StoneBrick => Some(MossyStonebrick),
+            _ => None,
+        };
+        let weathered = match level.get(pos) {
+            Full(StoneBrick) if 0.3 > rand() => Some(cracked),
+            Full(material) => mossy(material).map(Full),
+            Stair(material, dir, half, shape) => {
+                mossy(material).map(|m| Stair(m, dir, half, shape))
+            }
+            Slab(material, half) => mossy(material).map(|m| Slab(m, half)),
+            Fence(material) => mossy(material).map(Fence),
+            _ => None,
+        };
+        if let Some(weathered) = weathered
+            && severity > rand()
+        {
+            level.set(pos, weathered);
+        }
+    }
+}
+
+/// Vines on the walls, bushes on top of the remains and tall grass around them
+fn overgrow(level: &mut impl WorldView, area: Cuboid, severity: f32) {
+    for column in area.d2() {
+        for dir in HDir::ALL {
+            let wall = column + dir;
+            if severity * 0.15 < rand() {
+                continue;
+            }
+            // Vines start at the top of the wall
+            let Some(start) = (area.min.z + 1..=area.max.z).rev().find(|z| {
+                level.get(wall.extend(*z)).artificial() & (level.get(column.extend(*z)) == Air)
+            }) else {
+                continue;
+            };
+            let vine = Block::from_name("vine", &[(dir.to_str(), "true")]);
+            for z in (start - rand_range(0..4)..=start).rev() {
+                if (level.get(column.extend(z)) != Air) | !level.get(wall.extend(z)).artificial() {
+                    break;
+                }
+                level.set(column.extend(z), vine);
+            }
+        }
+
+        let Some(top) = (area.min.z..=area.max.z + 1)
+            .rev()
+            .find(|z| level.get(column.extend(*z)).solid())
+        else {
+            continue;
+        };
+        let top = column.extend(top);
+        let above = top + IVec3::Z;
+        if level.get(above) != Air {
+            continue;
+        }
+        if level.get(top).artificial() {
+            if severity * 0.4 > rand() {
+                level.set(above, Leaves(Oak, None));
+            }
+        } else if level.get(top).soil() && severity * 0.5 > rand() {
+            if 0.5 > rand() {
+                level.set(above, SmallPlant(SmallPlant::Grass));
+            } else if level.get(above + IVec3::Z) == Air {
+                level.set(above, TallPlant(TallPlant::Grass, Bottom));
+                level.set(above + IVec3::Z, TallPlant(TallPlant::Grass, Top));
+            }
+        }
+    }
+}
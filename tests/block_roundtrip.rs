@@ -0,0 +1,173 @@
+use enum_iterator::{all, Sequence};
+use mc_gen::*;
+use proptest::{prelude::*, sample::select};
+
+fn variant<T: Sequence + Clone + std::fmt::Debug + 'static>() -> impl Strategy<Value = T> {
+    select(all::<T>().collect::<Vec<_>>())
+}
+
+fn door_meta() -> impl Strategy<Value = DoorMeta> {
+    (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(|(top, open, powered)| {
+        let mut meta = DoorMeta::empty();
+        meta.set(DoorMeta::TOP, top);
+        meta.set(DoorMeta::OPEN, open);
+        meta.set(DoorMeta::POWERED, powered);
+        meta
+    })
+}
+
+fn block() -> impl Strategy<Value = Block> {
+    prop_oneof![
+        select(vec![
+            Air,
+            Water,
+            Lava,
+            Dirt,
+            Grass,
+            Sand,
+            Gravel,
+            Farmland,
+            Path,
+            Podzol,
+            CoarseDirt,
+            SoulSand,
+            PackedMud,
+            MushroomStem,
+            MangroveRoots,
+            MuddyMangroveRoots,
+            SmoothQuartz,
+            SnowLayer,
+            Glowstone,
+            Hay,
+            Barrel,
+            Barrier,
+            Bedrock,
+            CraftingTable,
+            LavaCauldron,
+        ]),
+        variant().prop_map(Full),
+        (variant(), variant()).prop_map(|(material, half)| Slab(material, half)),
+        (variant(), variant(), variant(), variant())
+            .prop_map(|(material, dir, half, shape)| Stair(material, dir, half, shape)),
+        variant().prop_map(Fence),
+        variant().prop_map(Ladder),
+        (variant(), variant()).prop_map(|(species, log_type)| Log(species, log_type)),
+        (variant(), prop::option::of(1..=7i8))
+            .prop_map(|(species, distance)| Leaves(species, distance)),
+        variant().prop_map(SmallPlant),
+        // Tall seagrass gets loaded as water
+        (
+            variant().prop_filter("tall seagrass", |plant| *plant != TallPlant::Seagrass),
+            variant()
+        )
+            .prop_map(|(plant, half)| TallPlant(plant, half)),
+        variant().prop_map(GroundPlant),
+        variant().prop_map(Wool),
+        prop::option::of(variant()).prop_map(Terracotta),
+        prop::option::of(variant()).prop_map(GlassPane),
+        (variant(), variant()).prop_map(|(dir, color)| WallBanner(dir, color)),
+        (variant(), 0..16u8).prop_map(|(color, rotation)| Banner(color, rotation)),
+        (0..=3u8).prop_map(|water| Cauldron { water }),
+        (variant(), variant(), door_meta())
+            .prop_map(|(species, dir, meta)| Trapdoor(species, dir, meta)),
+        // Only doors have a hinge
+        (variant(), variant(), door_meta(), any::<bool>()).prop_map(
+            |(species, dir, mut meta, hinge_right)| {
+                meta.set(DoorMeta::HINGE_RIGHT, hinge_right);
+                Door(species, dir, meta)
+            }
+        ),
+        (variant(), variant()).prop_map(|(dir, attachment)| Bell(dir, attachment)),
+        (variant(), 1..=4u8).prop_map(|(dir, delay)| Repeater(dir, delay)),
+        variant().prop_map(Rail),
+        variant().prop_map(Stonecutter),
+        variant().prop_map(Anvil),
+        variant().prop_map(Furnace),
+        (variant(), variant(), variant()).prop_map(|(color, dir, part)| Bed(color, dir, part)),
+    ]
+}
+
+/// Random sections with up to `max_distinct` different blocks (plus air)
+fn section(max_distinct: usize) -> impl Strategy<Value = Vec<Block>> {
+    (1..=max_distinct).prop_flat_map(|distinct| {
+        prop::collection::vec(0..=distinct, 16 * 16 * 16)
+            .prop_map(|indices| indices.into_iter().map(test_block).collect())
+    })
+}
+
+/// Distinct blocks that aren't known, so that there can be arbitrarily many
+fn test_block(index: usize) -> Block {
+    if index == 0 {
+        Air
+    } else {
+        Block::from_name("mcgen_test", &[("index", &index.to_string())])
+    }
+}
+
+fn section_from(blocks: &[Block]) -> Section {
+    let mut section = Section::default();
+    for (i, block) in blocks.iter().enumerate() {
+        section.set(i, *block);
+    }
+    section
+}
+
+fn assert_roundtrip(blocks: &[Block]) {
+    let (palette, data) = section_from(blocks).pack();
+    let bits_per_index = palette.len().next_power_of_two().ilog2().max(4) as usize;
+    // Minecraft refuses to load sections with superfluous longs
+    assert_eq!(data.len(), 4096usize.div_ceil(64 / bits_per_index));
+    let unpacked = Section::unpack(palette, &data).unwrap();
+    assert!(unpacked.iter().eq(blocks.iter().copied()));
+}
+
+proptest! {
+    #[test]
+    fn block_roundtrip(block in block()) {
+        let nbt = block.to_nbt(&UNKNOWN_BLOCKS.read().unwrap());
+        prop_assert_eq!(Block::from_nbt(&nbt), block);
+    }
+
+    #[test]
+    fn section_roundtrip(blocks in section(300)) {
+        assert_roundtrip(&blocks);
+    }
+
+}
+
+proptest! {
+    // Large sections are slow to build
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn large_palette_roundtrip(blocks in section(4096)) {
+        assert_roundtrip(&blocks);
+    }
+}
+
+#[test]
+fn all_known_roundtrip() {
+    let unknown = UnknownBlocks::default();
+    for block in Block::all_known() {
+        assert_eq!(Block::from_nbt(&block.to_nbt(&unknown)), block);
+    }
+}
+
+/// Every block different, which needs a palette of 4097 entries including air
+#[test]
+fn full_palette_roundtrip() {
+    let blocks: Vec<Block> = (1..=4096).map(test_block).collect();
+    assert_roundtrip(&blocks);
+}
+
+#[test]
+fn connected_fence_loads_as_fence() {
+    let unknown = UnknownBlocks::default();
+    let mut sides = FenceSides::default();
+    sides.set(XPos, WallSide::Tall);
+    sides.set(YNeg, WallSide::Low);
+    for material in [Wood(Oak), Cobble] {
+        let nbt = ConnectedFence(material, sides).to_nbt(&unknown);
+        assert_eq!(Block::from_nbt(&nbt), Fence(material));
+    }
+}